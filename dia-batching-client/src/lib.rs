@@ -0,0 +1,262 @@
+//! Typed client of the batching server API, the one Rust definition of its wire format on the
+//! client side. Requests are signed like the offchain worker signs them if a key is given.
+
+use futures::{SinkExt, Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::error::Error;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+pub use reqwest::Url;
+
+pub const TIMESTAMP_HEADER: &str = "X-Dia-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Dia-Signature";
+
+/// Asset as requested from `/currencies`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Currency {
+	pub blockchain: String,
+	pub symbol: String,
+}
+
+impl Currency {
+	pub fn new(blockchain: &str, symbol: &str) -> Self {
+		Currency { blockchain: blockchain.into(), symbol: symbol.into() }
+	}
+}
+
+/// Price of an asset with 12 decimals, with the blockchain and symbol cased like requested
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinInfo {
+	pub symbol: String,
+	pub name: String,
+	pub blockchain: String,
+	pub supply: u128,
+	pub last_update_timestamp: u64,
+	pub price: u128,
+	/// Source the price was fetched from, empty for coin infos the server read from the chain
+	#[serde(default)]
+	pub source: String,
+	/// Unix timestamp the price was fetched from the `source` at, 0 if unknown
+	#[serde(default)]
+	pub fetched_at: u64,
+}
+
+/// Coin info with the earliest unix timestamp its asset is fetched again at and its time-weighted
+/// average prices of the last hour and day
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledCoinInfo {
+	pub coin_info: CoinInfo,
+	pub next_update_at: Option<u64>,
+	pub twap_1h: Option<u128>,
+	pub twap_24h: Option<u128>,
+}
+
+/// Open, highest, lowest and last price with 12 decimals served during a bucket of `/history`
+/// starting at the unix timestamp `start`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Candle {
+	pub start: u64,
+	pub open: u128,
+	pub high: u128,
+	pub low: u128,
+	pub close: u128,
+	pub updates: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ClientMessage {
+	Subscribe { assets: Vec<String> },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerMessage {
+	Prices { prices: Vec<CoinInfo> },
+	Pong,
+	Error { message: String },
+}
+
+/// Hex encoded HMAC-SHA256 of `"{timestamp}\n{path}"`, the signature the server checks with
+/// `--hmac-key`
+pub fn sign(key: &[u8], timestamp: u64, path: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+	mac.update(format!("{}\n{}", timestamp, path).as_bytes());
+	hex::encode(mac.finalize().into_bytes())
+}
+
+fn asset(currency: &Currency) -> String {
+	format!("{}:{}", currency.blockchain, currency.symbol)
+}
+
+/// Decodes a message of `/ws` into the pushed prices, `None` for messages without prices
+fn prices(text: &str) -> Option<Result<Vec<CoinInfo>, Box<dyn Error + Send + Sync>>> {
+	match serde_json::from_str(text) {
+		Ok(ServerMessage::Prices { prices }) => Some(Ok(prices)),
+		Ok(ServerMessage::Pong) => None,
+		Ok(ServerMessage::Error { message }) => Some(Err(message.into())),
+		Err(err) => Some(Err(err.into())),
+	}
+}
+
+pub struct BatchingClient {
+	client: reqwest::Client,
+	base_url: Url,
+	hmac_key: Option<Vec<u8>>,
+}
+
+impl BatchingClient {
+	/// Client of the server at `base_url`, e.g. `http://localhost:8070`
+	pub fn new(base_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+		Ok(Self { client: reqwest::Client::new(), base_url: base_url.parse()?, hmac_key: None })
+	}
+
+	pub fn with_hmac_key(self, key: Vec<u8>) -> Self {
+		Self { hmac_key: Some(key), ..self }
+	}
+
+	/// Timestamp and signature headers of a request to `url`, none without a key
+	fn signature_headers(&self, url: &Url) -> Vec<(&'static str, String)> {
+		let key = match &self.hmac_key {
+			Some(key) => key,
+			None => return vec![],
+		};
+		let path = match url.query() {
+			Some(query) => format!("{}?{}", url.path(), query),
+			None => url.path().to_string(),
+		};
+		let timestamp = chrono::Utc::now().timestamp().unsigned_abs();
+		vec![
+			(TIMESTAMP_HEADER, timestamp.to_string()),
+			(SIGNATURE_HEADER, sign(key, timestamp, &path)),
+		]
+	}
+
+	async fn get<T>(&self, url: Url) -> Result<T, Box<dyn Error + Send + Sync>>
+	where
+		T: for<'de> Deserialize<'de>,
+	{
+		let mut request = self.client.get(url.clone());
+		for (name, value) in self.signature_headers(&url) {
+			request = request.header(name, value);
+		}
+		Ok(request.send().await?.error_for_status()?.json().await?)
+	}
+
+	/// Coin infos of the currencies the server knows, ordered by blockchain and symbol
+	pub async fn get_currencies(
+		&self,
+		currencies: &[Currency],
+	) -> Result<Vec<CoinInfo>, Box<dyn Error + Send + Sync>> {
+		let url = self.base_url.join("/currencies")?;
+		let mut request = self.client.post(url.clone()).json(currencies);
+		for (name, value) in self.signature_headers(&url) {
+			request = request.header(name, value);
+		}
+		Ok(request.send().await?.error_for_status()?.json().await?)
+	}
+
+	/// Coin infos of the currencies the server knows with their next update and time-weighted
+	/// average prices
+	pub async fn get_scheduled_currencies(
+		&self,
+		currencies: &[Currency],
+	) -> Result<Vec<ScheduledCoinInfo>, Box<dyn Error + Send + Sync>> {
+		let mut url = self.base_url.join("/currencies")?;
+		let assets: Vec<String> = currencies.iter().map(asset).collect();
+		url.query_pairs_mut()
+			.append_pair("assets", &assets.join(","))
+			.append_pair("envelope", "true");
+		self.get(url).await
+	}
+
+	/// Candles of the prices the server served for the currency from `from` up to but excluding
+	/// `to` in buckets of `interval` seconds, leaving out buckets without an update
+	pub async fn get_history(
+		&self,
+		currency: &Currency,
+		from: u64,
+		to: u64,
+		interval: u64,
+	) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+		let mut url = self.base_url.join("/history")?;
+		url.query_pairs_mut()
+			.append_pair("asset", &asset(currency))
+			.append_pair("from", &from.to_string())
+			.append_pair("to", &to.to_string())
+			.append_pair("interval", &interval.to_string());
+		self.get(url).await
+	}
+
+	/// Subscribes to the prices of the currencies on `/ws`. The stream yields their current coin
+	/// infos first and then the coin infos of every update changing them, until the connection
+	/// closes.
+	pub async fn stream_prices(
+		&self,
+		currencies: &[Currency],
+	) -> Result<
+		impl Stream<Item = Result<Vec<CoinInfo>, Box<dyn Error + Send + Sync>>>,
+		Box<dyn Error + Send + Sync>,
+	> {
+		let mut url = self.base_url.join("/ws")?;
+		let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+		url.set_scheme(scheme)
+			.map_err(|_| format!("Can't connect a WebSocket to {}", url))?;
+		let mut request = url.as_str().into_client_request()?;
+		for (name, value) in self.signature_headers(&url) {
+			request.headers_mut().insert(name, value.parse()?);
+		}
+		let (mut socket, _) = tokio_tungstenite::connect_async(request).await?;
+		let subscribe = ClientMessage::Subscribe { assets: currencies.iter().map(asset).collect() };
+		socket.send(Message::Text(serde_json::to_string(&subscribe)?)).await?;
+		Ok(socket.filter_map(|message| async move {
+			match message {
+				Ok(Message::Text(text)) => prices(&text),
+				Ok(_) => None,
+				Err(err) => Some(Err(err.into())),
+			}
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sign_matches_server() {
+		// Same vector is checked by the server and the pallet
+		assert_eq!(
+			sign(b"secret", 1700000000, "/currencies/"),
+			"12cdbc46add9bc26f32f3ddf831f673da6960abc2cbdbceccf6ec59e0d830c21"
+		);
+	}
+
+	#[test]
+	fn test_decode_pushed_prices() {
+		let pushed = prices(
+			r#"{"type":"prices","prices":[{"symbol":"DOT","name":"Polkadot","blockchain":"Polkadot","supply":0,"lastUpdateTimestamp":1700474693,"price":5000000000000}]}"#,
+		);
+
+		assert_eq!(
+			pushed.unwrap().unwrap(),
+			vec![CoinInfo {
+				symbol: "DOT".into(),
+				name: "Polkadot".into(),
+				blockchain: "Polkadot".into(),
+				supply: 0,
+				last_update_timestamp: 1700474693,
+				price: 5_000_000_000_000,
+				..Default::default()
+			}]
+		);
+		assert!(prices(r#"{"type":"pong"}"#).is_none());
+		assert!(prices(r#"{"type":"error","message":"Invalid asset 'DOT'"}"#).unwrap().is_err());
+	}
+}
@@ -0,0 +1,147 @@
+use crate::request_signing::{SignatureError, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorUnauthorized};
+use actix_web::web::Bytes;
+use actix_web::{web, FromRequest, HttpRequest};
+use chrono::Utc;
+use futures::future::LocalBoxFuture;
+use hmac::{Hmac, Mac};
+use log::warn;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secret operators sign their requests to the `/admin` endpoints with, separate from the key of
+/// the offchain workers. The signature is the hex encoded HMAC-SHA256 of
+/// `"{timestamp}\n{method}\n{path}\n{hex encoded SHA-256 of the body}"`, so a captured request
+/// can't be sent again with another body.
+#[derive(Clone)]
+pub struct AdminKey {
+	key: Vec<u8>,
+	max_skew_in_seconds: u64,
+}
+
+impl AdminKey {
+	pub fn new(key: Vec<u8>, max_skew_in_seconds: u64) -> Self {
+		Self { key, max_skew_in_seconds }
+	}
+
+	fn mac(&self, timestamp: &str, method: &str, path: &str, body: &[u8]) -> HmacSha256 {
+		let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+		let digest = hex::encode(Sha256::digest(body));
+		mac.update(format!("{}\n{}\n{}\n{}", timestamp, method, path, digest).as_bytes());
+		mac
+	}
+
+	/// Returns the values of the timestamp and signature headers for a request with `body`
+	pub fn sign(&self, timestamp: u64, method: &str, path: &str, body: &[u8]) -> (String, String) {
+		let timestamp = timestamp.to_string();
+		let signature =
+			hex::encode(self.mac(&timestamp, method, path, body).finalize().into_bytes());
+		(timestamp, signature)
+	}
+
+	pub fn verify(
+		&self,
+		timestamp: &str,
+		signature: &str,
+		method: &str,
+		path: &str,
+		body: &[u8],
+		now: u64,
+	) -> Result<(), SignatureError> {
+		let seconds = timestamp.parse::<u64>().map_err(|_| SignatureError::InvalidTimestamp)?;
+		if seconds.max(now) - seconds.min(now) > self.max_skew_in_seconds {
+			return Err(SignatureError::TimestampOutOfRange)
+		}
+		let signature = hex::decode(signature).map_err(|_| SignatureError::InvalidSignature)?;
+		self.mac(timestamp, method, path, body)
+			.verify_slice(&signature)
+			.map_err(|_| SignatureError::InvalidSignature)
+	}
+
+	pub fn verify_request(
+		&self,
+		req: &HttpRequest,
+		body: &[u8],
+		now: u64,
+	) -> Result<(), SignatureError> {
+		let header = |name: &'static str| {
+			req.headers()
+				.get(name)
+				.and_then(|value| value.to_str().ok())
+				.ok_or(SignatureError::MissingHeader(name))
+		};
+		let path = match req.uri().query() {
+			Some(query) => format!("{}?{}", req.path(), query),
+			None => req.path().to_string(),
+		};
+		let (timestamp, signature) = (header(TIMESTAMP_HEADER)?, header(SIGNATURE_HEADER)?);
+		self.verify(timestamp, signature, req.method().as_str(), &path, body, now)
+	}
+}
+
+/// Body of a request to an `/admin` endpoint, extracted once its signature was verified with the
+/// `AdminKey` of the app. Without an admin key every request is rejected with 403.
+pub struct AdminRequest {
+	body: Bytes,
+}
+
+impl AdminRequest {
+	pub fn json<T: DeserializeOwned>(&self) -> Result<T, actix_web::Error> {
+		serde_json::from_slice(&self.body).map_err(ErrorBadRequest)
+	}
+}
+
+impl FromRequest for AdminRequest {
+	type Error = actix_web::Error;
+	type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+	type Config = ();
+
+	fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+		let req = req.clone();
+		let body = Bytes::from_request(&req, payload);
+		Box::pin(async move {
+			let admin_key = req.app_data::<web::Data<AdminKey>>().ok_or_else(|| {
+				ErrorForbidden("Admin endpoints are disabled without --admin-key")
+			})?;
+			let body = body.await?;
+			let now = Utc::now().timestamp().unsigned_abs();
+			admin_key.verify_request(&req, &body, now).map_err(|err| {
+				warn!("Rejected request to {}: {}", req.path(), err);
+				ErrorUnauthorized(err.to_string())
+			})?;
+			Ok(AdminRequest { body })
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_verify() {
+		let admin_key = AdminKey::new(b"admin".to_vec(), 300);
+		let body: &[u8] = br#"{"directives":"info"}"#;
+		let (timestamp, signature) = admin_key.sign(1700000000, "POST", "/admin/loglevel", body);
+		let verify = |path: &str, body: &[u8], now: u64| {
+			admin_key.verify(&timestamp, &signature, "POST", path, body, now)
+		};
+
+		assert_eq!(verify("/admin/loglevel", body, 1700000100), Ok(()));
+		assert_eq!(
+			verify("/admin/loglevel", br#"{"directives":"trace"}"#, 1700000100),
+			Err(SignatureError::InvalidSignature)
+		);
+		assert_eq!(
+			verify("/admin/currencies", body, 1700000100),
+			Err(SignatureError::InvalidSignature)
+		);
+		assert_eq!(
+			verify("/admin/loglevel", body, 1700000301),
+			Err(SignatureError::TimestampOutOfRange)
+		);
+	}
+}
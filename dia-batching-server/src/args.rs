@@ -1,3 +1,19 @@
+use crate::coinmarketcap::CoinMarketCapIds;
+use crate::cors::{CorsMethods, CorsOrigins};
+use crate::deviation::DeviationLimits;
+use crate::exchanges::{BlockchainSources, ExchangeSources, FallbackSources, SourceTimeouts};
+use crate::hysteresis::PublicationThresholds;
+use crate::rate_limit::RouteLimits;
+use crate::reconcile::ChainUrls;
+use crate::report::SourceCosts;
+use crate::schedule::PublicationSchedules;
+use crate::smoothing::SmoothingAlphas;
+use crate::spread::PriceSpreads;
+use crate::supply::SupplySources;
+use crate::timestamps::{TimestampSource, TimestampSources};
+use crate::AssetSpecifier;
+use rust_decimal::Decimal;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 fn parse_currency_vec(src: &str) -> SupportedCurrencies {
@@ -8,27 +24,560 @@ fn parse_currency_vec(src: &str) -> SupportedCurrencies {
 	SupportedCurrencies(vec)
 }
 
+/// Currencies supported by default, which the subcommands default to as well
+const DEFAULT_CURRENCIES: &str = "Polkadot:DOT,Kusama:KSM,Stellar:XLM,FIAT:USD-USD,FIAT:MXN-USD,\
+	FIAT:BRL-USD,Amplitude:AMPE,Pendulum:KPEN,Bifrost:vASTR";
+
 // We need the extra struct to be able to parse the currencies to a Vec
 #[derive(Debug)]
 pub struct SupportedCurrencies(pub Vec<String>);
 
+/// Every option of the server taking a value can also be set through the environment variable
+/// named next to it in `--help`, e.g. `DIA_SUPPORTED_CURRENCIES`, and flags without a value
+/// through the variable named in their description. Command line flags take precedence over the
+/// environment. Options of the subcommands only read the variables `--help` names.
 #[derive(Debug, StructOpt)]
 #[structopt(name = "dia-batching-server", about = "An server for batching requests to the Dia API")]
 pub struct DiaApiArgs {
 	/// Iteration duration after one batch of requests
-	#[structopt(short, long, default_value = "60")]
+	#[structopt(short, long, env = "DIA_ITERATION_TIMEOUT_IN_SECONDS", default_value = "60")]
 	pub iteration_timeout_in_seconds: u64,
 
 	/// Timeout after one request
-	#[structopt(short, long, default_value = "100")]
+	#[structopt(short, long, env = "DIA_REQUEST_TIMEOUT_IN_MILLISECONDS", default_value = "100")]
 	pub request_timeout_in_milliseconds: u64,
 
+	/// Port the HTTP server listens on
+	#[structopt(short, long, env = "DIA_PORT", default_value = "8070")]
+	pub port: u16,
+
+	/// Seconds without a finished update iteration or a published price of a source after which
+	/// `/health` answers with 503
+	#[structopt(long, env = "DIA_MAX_UPDATE_AGE_IN_SECONDS", default_value = "600")]
+	pub max_update_age_in_seconds: u64,
+
+	/// Seconds after their last update from which prices are left out of `/currencies`
+	/// responses, so the offchain worker doesn't submit them. Prices are served whatever their
+	/// age if not set.
+	#[structopt(long, env = "DIA_MAX_PRICE_AGE_IN_SECONDS")]
+	pub max_price_age_in_seconds: Option<u64>,
+
+	/// Seconds of prices kept per asset for the time-weighted averages `/currencies` serves with
+	/// `envelope=true`, a day at most are averaged
+	#[structopt(long, env = "DIA_PRICE_HISTORY_IN_SECONDS", default_value = "86400")]
+	pub price_history_in_seconds: u64,
+
+	/// Basis points the price of an asset has to move by since its last change to be served again
+	/// to requests with `changedSince`
+	#[structopt(long, env = "DIA_CHANGE_EPSILON_IN_BPS", default_value = "0")]
+	pub change_epsilon_in_bps: u128,
+
+	/// Directory of the append-only logs of every price served per asset, which `/history` reads.
+	/// Prices aren't logged if not set.
+	#[structopt(long, env = "DIA_PRICE_LOG_DIR")]
+	pub price_log_dir: Option<PathBuf>,
+
+	/// RPC url of a node of the chain the prices are fed to. The coin infos on chain are read back
+	/// to track the latency of every asset from its quotation to its publication and to the chain
+	/// at `/debug/latency`.
+	#[structopt(long, env = "DIA_LATENCY_RPC_URL")]
+	pub latency_rpc_url: Option<String>,
+
+	/// Interval in which the coin infos are read back from `--latency-rpc-url`, the resolution of
+	/// the latencies to the chain
+	#[structopt(long, env = "DIA_LATENCY_POLL_INTERVAL_IN_SECONDS", default_value = "6")]
+	pub latency_poll_interval_in_seconds: u64,
+
+	/// Maximum time the server takes to handle a `/currencies` request before answering with 503
+	#[structopt(long, env = "DIA_MAX_HANDLING_TIME_IN_MILLISECONDS", default_value = "1000")]
+	pub max_handling_time_in_milliseconds: u64,
+
+	/// Seconds the server waits for requests in flight to finish when it is shut down
+	#[structopt(long, env = "DIA_SHUTDOWN_TIMEOUT_IN_SECONDS", default_value = "30")]
+	pub shutdown_timeout_in_seconds: u64,
+
 	/// Currencies to support
 	/// Each currency needs to have the format <blockchain>:<symbol>
-	/// Fiat currencies need to have the format FIAT:<from>-<to>
+	/// Fiat currencies need to have the format FIAT:<from>-<to>, pairs not against USD like
+	/// FIAT:EUR-BRL are crossed from the USD quotations of both currencies
 	#[structopt(short, long,
+      env = "DIA_SUPPORTED_CURRENCIES",
+      parse(from_str = parse_currency_vec),
+      default_value = DEFAULT_CURRENCIES
+    )]
+	pub supported_currencies: SupportedCurrencies,
+
+	/// Number of errors kept per asset for `/debug/errors`
+	#[structopt(long, env = "DIA_ERRORS_PER_ASSET", default_value = "32")]
+	pub errors_per_asset: usize,
+
+	/// Maximum estimated memory usage of the error log in bytes. Once exceeded, the errors of the
+	/// least recently queried assets are dropped first. Unbounded if not set.
+	#[structopt(long, env = "DIA_MAX_ERROR_LOG_BYTES")]
+	pub max_error_log_bytes: Option<usize>,
+
+	/// Timestamp published for assets without an entry in `--timestamp-sources`: the time of the
+	/// upstream quotation (`upstream`), the time it was fetched (`server`) or the later of both
+	/// (`max`)
+	#[structopt(long, env = "DIA_DEFAULT_TIMESTAMP_SOURCE", default_value = "upstream")]
+	pub default_timestamp_source: TimestampSource,
+
+	/// Timestamp sources of individual assets
+	/// Each entry needs to have the format <blockchain>:<symbol>=<upstream|server|max>
+	#[structopt(long, env = "DIA_TIMESTAMP_SOURCES", default_value = "")]
+	pub timestamp_sources: TimestampSources,
+
+	/// RPC url of a chain running the dia-oracle pallet, e.g. `http://localhost:9933`. If set, the
+	/// supported currencies are read from the pallet's `SupportedCurrencies` storage and kept in
+	/// sync with it, `--supported-currencies` is ignored.
+	#[structopt(long, env = "DIA_CHAIN_RPC_URL")]
+	pub chain_rpc_url: Option<String>,
+
+	/// Name of the dia-oracle pallet in the chain's runtime
+	#[structopt(long, env = "DIA_CHAIN_PALLET_NAME", default_value = "DiaOracleModule")]
+	pub chain_pallet_name: String,
+
+	/// RPC url of a chain running the dia-oracle pallet whose coin infos are served at startup
+	/// until the first update iteration finished, so a restart doesn't change the prices before
+	/// the next regular update
+	#[structopt(long, env = "DIA_SEED_RPC_URL")]
+	pub seed_rpc_url: Option<String>,
+
+	/// Time the coin infos of the seed chain may take to be read, the server starts without
+	/// prices after it
+	#[structopt(long, env = "DIA_SEED_TIMEOUT_IN_SECONDS", default_value = "10")]
+	pub seed_timeout_in_seconds: u64,
+
+	/// Interval in which the supported currencies are read from the chain again
+	#[structopt(long, env = "DIA_CHAIN_SYNC_INTERVAL_IN_SECONDS", default_value = "60")]
+	pub chain_sync_interval_in_seconds: u64,
+
+	/// Daily UTC windows individual assets are fetched in, `<blockchain>:<symbol>=<windows>,...`
+	/// with windows like `16:00-16:15`, several joined by `+`. Outside of them the published price
+	/// is carried forward without querying the source.
+	#[structopt(long, env = "DIA_PUBLICATION_SCHEDULES", default_value = "")]
+	pub publication_schedules: PublicationSchedules,
+
+	/// Smallest price changes published for individual assets, `<blockchain>:<symbol>=<amount>`
+	/// with USD amounts like `Stellar:USDC=0.000001`. Smaller changes keep the published price.
+	#[structopt(long, env = "DIA_PUBLICATION_THRESHOLDS", default_value = "")]
+	pub publication_thresholds: PublicationThresholds,
+
+	/// Seconds after which a price kept by its publication threshold is replaced anyway, which
+	/// should be less than `--max-price-age-in-seconds`
+	#[structopt(
+		long,
+		env = "DIA_PUBLICATION_THRESHOLD_HEARTBEAT_IN_SECONDS",
+		default_value = "3600"
+	)]
+	pub publication_threshold_heartbeat_in_seconds: u64,
+
+	/// Largest price changes of individual assets in percent that are published right away,
+	/// `<blockchain>:<symbol>=<percent>,...` like `Bitcoin:BTC=10`. Larger changes are held back.
+	#[structopt(long, env = "DIA_DEVIATION_LIMITS", default_value = "")]
+	pub deviation_limits: DeviationLimits,
+
+	/// Spreads applied to the quoted prices of individual assets before they are published,
+	/// `<blockchain>:<symbol>=<basis points>,...` like `FIAT:BRL-USD=-5` to publish 5 bps less
+	#[structopt(long, env = "DIA_PRICE_SPREADS", default_value = "")]
+	pub price_spreads: PriceSpreads,
+
+	/// Weights of the latest price in the exponential moving averages published instead of the
+	/// quoted prices of individual assets, `<blockchain>:<symbol>=<alpha>,...` with alphas in
+	/// `(0, 1]` like `Bitcoin:BTC=0.3`. The quoted prices are served at `/debug/smoothing`.
+	#[structopt(long, env = "DIA_SMOOTHING_ALPHAS", default_value = "")]
+	pub smoothing_alphas: SmoothingAlphas,
+
+	/// Number of iterations a price exceeding its deviation limit is held back, after which it is
+	/// published as the new level of the asset
+	#[structopt(long, env = "DIA_DEVIATION_HOLD_CYCLES", default_value = "3")]
+	pub deviation_hold_cycles: u32,
+
+	/// Consecutive prices of a source the deviation guard rejects for an asset after which the
+	/// source is quarantined, i.e. not asked for the asset while it has other sources. Sources are
+	/// only quarantined through `POST /admin/quarantine` if not set.
+	#[structopt(long, env = "DIA_QUARANTINE_AFTER_REJECTIONS")]
+	pub quarantine_after_rejections: Option<u32>,
+
+	/// Consecutive shadow quotations of a quarantined source within the deviation limit of the
+	/// asset after which the source is routed to again
+	#[structopt(long, env = "DIA_QUARANTINE_PROBATION_SAMPLES", default_value = "5")]
+	pub quarantine_probation_samples: u32,
+
+	/// Interval in which quarantined sources are asked for a shadow quotation
+	#[structopt(long, env = "DIA_QUARANTINE_PROBE_INTERVAL_IN_SECONDS", default_value = "300")]
+	pub quarantine_probe_interval_in_seconds: u64,
+
+	/// Substrate chains whose `Balances::TotalIssuance` is served as the supply of individual
+	/// assets, `<blockchain>:<symbol>=<rpc url>,...`, e.g. `Amplitude:AMPE=http://localhost:9933`
+	#[structopt(long, env = "DIA_SUPPLY_SOURCES", default_value = "")]
+	pub supply_sources: SupplySources,
+
+	/// Interval in which the total issuance of the supply sources is read again
+	#[structopt(long, env = "DIA_SUPPLY_REFRESH_INTERVAL_IN_SECONDS", default_value = "3600")]
+	pub supply_refresh_interval_in_seconds: u64,
+
+	/// Exchanges quoting individual assets instead of DIA, `<blockchain>:<symbol>=<exchange>,...`
+	/// with `coinbase`, `bitstamp` or `kraken`, all regulated venues
+	#[structopt(long, env = "DIA_EXCHANGE_SOURCES", default_value = "")]
+	pub exchange_sources: ExchangeSources,
+
+	/// Sources individual assets fall back to in order if their source fails or quotes a zero
+	/// price, `<blockchain>:<symbol>=<sources>,...` with `dia`, `coinmarketcap` or an exchange,
+	/// several joined by `+`, e.g. `Amplitude:AMPE=coinbase+bitstamp`
+	#[structopt(long, env = "DIA_FALLBACK_SOURCES", default_value = "")]
+	pub fallback_sources: FallbackSources,
+
+	/// Sources of all assets of individual blockchains, `<blockchain>=<sources>,...` with `dia`,
+	/// `coinmarketcap` or an exchange joined by `+`, the first quoting the assets and the others
+	/// as fallbacks, e.g. `FIAT=dia+coinbase`. `--exchange-sources` and `--fallback-sources`
+	/// override them per asset.
+	#[structopt(long, env = "DIA_BLOCKCHAIN_SOURCES", default_value = "")]
+	pub blockchain_sources: BlockchainSources,
+
+	/// API key of the CoinMarketCap plan the `coinmarketcap` source is requested with
+	#[structopt(long, env = "DIA_COINMARKETCAP_API_KEY", hide_env_values = true)]
+	pub coinmarketcap_api_key: Option<String>,
+
+	/// CoinMarketCap IDs of assets quoted by `coinmarketcap`, `<blockchain>:<symbol>=<id>,...`,
+	/// e.g. `Bitcoin:BTC=1`. Assets without an ID are requested by symbol, which takes the highest
+	/// ranked coin with the symbol.
+	#[structopt(long, env = "DIA_COINMARKETCAP_IDS", default_value = "")]
+	pub coinmarketcap_ids: CoinMarketCapIds,
+
+	/// Time a source has to answer a request before it counts as failed
+	#[structopt(long, env = "DIA_SOURCE_TIMEOUT_IN_MILLISECONDS", default_value = "5000")]
+	pub source_timeout_in_milliseconds: u64,
+
+	/// Timeouts of individual sources overriding `--source-timeout-in-milliseconds`,
+	/// `<source>=<milliseconds>,...` with `dia`, `coinmarketcap` or an exchange, e.g.
+	/// `dia=2000,coinbase=1000`
+	#[structopt(long, env = "DIA_SOURCE_TIMEOUTS", default_value = "")]
+	pub source_timeouts: SourceTimeouts,
+
+	/// Number of times a failed or timed out request to a source is sent again. Client errors
+	/// other than rate limits are not retried.
+	#[structopt(long, env = "DIA_SOURCE_RETRIES", default_value = "2")]
+	pub source_retries: u32,
+
+	/// Wait before the first retry of a request to a source, doubled with every further retry and
+	/// each extended by up to the same again as jitter
+	#[structopt(long, env = "DIA_SOURCE_RETRY_BACKOFF_IN_MILLISECONDS", default_value = "200")]
+	pub source_retry_backoff_in_milliseconds: u64,
+
+	/// Only warn instead of refusing to start if supported currencies only differ in case. Also
+	/// set by `DIA_ALLOW_ASSET_CONFLICTS=true`.
+	#[structopt(long)]
+	pub allow_asset_conflicts: bool,
+
+	/// Refuse to start if one of the sources the supported currencies are quoted by doesn't
+	/// answer the startup probe. Also set by `DIA_FAIL_FAST=true`.
+	#[structopt(long)]
+	pub fail_fast: bool,
+
+	/// Fetch all prices once, print them as JSON to stdout and exit instead of serving them.
+	/// Exits with an error if any asset couldn't be resolved. Also set by `DIA_ONE_SHOT=true`.
+	#[structopt(long)]
+	pub one_shot: bool,
+
+	/// Interval in which the server requests all supported currencies from its own `/currencies`
+	/// endpoint and checks that the pallet offchain worker could parse the response.
+	/// Disabled if not set.
+	#[structopt(long, env = "DIA_SELF_TEST_INTERVAL_IN_SECONDS")]
+	pub self_test_interval_in_seconds: Option<u64>,
+
+	/// Interval in which the published prices of fiat pairs crossed via USD, like `FIAT:USD-BRL`,
+	/// are compared with the pair quoted directly. Disabled if not set.
+	#[structopt(long, env = "DIA_CROSS_CHECK_INTERVAL_IN_SECONDS")]
+	pub cross_check_interval_in_seconds: Option<u64>,
+
+	/// Deviation in basis points from the directly quoted pair above which a crossed price is
+	/// reported
+	#[structopt(long, env = "DIA_CROSS_CHECK_TOLERANCE_IN_BPS", default_value = "50")]
+	pub cross_check_tolerance_in_bps: Decimal,
+
+	/// File the prices and fetch errors are written to in every state interval and on shutdown,
+	/// and restored from at startup, so a restarted server serves the last known prices right away
+	#[structopt(long, env = "DIA_STATE_FILE", parse(from_os_str))]
+	pub state_file: Option<PathBuf>,
+
+	/// Interval in which the state file is written
+	#[structopt(long, env = "DIA_STATE_INTERVAL_IN_SECONDS", default_value = "60")]
+	pub state_interval_in_seconds: u64,
+
+	/// File the feed report is appended to as a JSON line every report interval
+	#[structopt(long, env = "DIA_REPORT_FILE", parse(from_os_str))]
+	pub report_file: Option<PathBuf>,
+
+	/// Url the feed report is posted to as JSON every report interval
+	#[structopt(long, env = "DIA_REPORT_WEBHOOK")]
+	pub report_webhook: Option<String>,
+
+	/// Interval of the feed report with update attempts, uptime and staleness incidents per asset
+	/// and the requests sent to each source. Only reported if a report file or webhook is set.
+	#[structopt(long, env = "DIA_REPORT_INTERVAL_IN_SECONDS", default_value = "86400")]
+	pub report_interval_in_seconds: u64,
+
+	/// Estimated cost of a request to each source for the spend in the feed report and at
+	/// `/debug/costs`, e.g. `diadata.org=0.0001,YahooFinance=0.00005`
+	#[structopt(long, env = "DIA_SOURCE_COSTS", default_value = "")]
+	pub source_costs: SourceCosts,
+
+	/// Shared secret the dia-oracle offchain workers sign their requests with. If set, requests to
+	/// `/currencies` without a valid signature are rejected.
+	#[structopt(long, env = "DIA_HMAC_KEY", hide_env_values = true)]
+	pub hmac_key: Option<String>,
+
+	/// Maximum difference between the timestamp of a signed request and the server time
+	#[structopt(long, env = "DIA_HMAC_MAX_SKEW_IN_SECONDS", default_value = "300")]
+	pub hmac_max_skew_in_seconds: u64,
+
+	/// Secret requests to the `/admin` endpoints have to be signed with, which has to differ from
+	/// `--hmac-key`. The signature covers the method, path and body of the request. The `/admin`
+	/// endpoints answer 403 if not set.
+	#[structopt(long, env = "DIA_ADMIN_KEY", hide_env_values = true)]
+	pub admin_key: Option<String>,
+
+	/// Hex encoded 32 byte seed of an Ed25519 key the bodies of `/currencies` responses are signed
+	/// with. The signature and public key are sent in the `X-Dia-Response-Signature` and
+	/// `X-Dia-Public-Key` headers.
+	#[structopt(long, env = "DIA_RESPONSE_SIGNING_KEY", hide_env_values = true)]
+	pub response_signing_key: Option<String>,
+
+	/// File with the hex encoded seed of the response signing key, instead of
+	/// `--response-signing-key`
+	#[structopt(long, env = "DIA_RESPONSE_SIGNING_KEY_FILE")]
+	pub response_signing_key_file: Option<PathBuf>,
+
+	/// Per-IP token bucket rate limits of the routes starting with a path prefix, e.g.
+	/// `/currencies=10:50,/=1`. Each entry allows `<requests per second>[:<burst>]`, the longest
+	/// matching prefix applies and requests over the limit are answered with 429.
+	#[structopt(long, env = "DIA_RATE_LIMITS", default_value = "")]
+	pub rate_limits: RouteLimits,
+
+	/// Comma separated API keys of clients that aren't rate limited when sending one of them in
+	/// the `X-Api-Key` header. Requests with another key are rejected.
+	#[structopt(long, env = "DIA_API_KEYS", hide_env_values = true)]
+	pub api_keys: Option<String>,
+
+	/// Rate limits clients by the address in the `Forwarded` or `X-Forwarded-For` header instead
+	/// of the peer address. Only set it behind a proxy that overwrites these headers. Also set by
+	/// `DIA_RATE_LIMIT_FORWARDED_FOR=true`.
+	#[structopt(long)]
+	pub rate_limit_forwarded_for: bool,
+
+	/// Comma separated origins of the browser frontends allowed to request the server, e.g.
+	/// `https://portal.pendulumchain.org,http://localhost:3000`, or `*` for any origin. CORS
+	/// requests aren't answered if not set.
+	#[structopt(long, env = "DIA_CORS_ORIGINS", default_value = "")]
+	pub cors_origins: CorsOrigins,
+
+	/// Comma separated methods the allowed origins may request the server with
+	#[structopt(long, env = "DIA_CORS_METHODS", default_value = "GET,POST")]
+	pub cors_methods: CorsMethods,
+
+	/// PEM file with the certificate chain the server is served with over HTTPS instead of HTTP.
+	/// The certificate and key are read again on SIGHUP.
+	#[structopt(long, env = "DIA_TLS_CERT", parse(from_os_str), requires = "tls-key")]
+	pub tls_cert: Option<PathBuf>,
+
+	/// PEM file with the PKCS#8 or RSA private key of `--tls-cert`
+	#[structopt(long, env = "DIA_TLS_KEY", parse(from_os_str), requires = "tls-cert")]
+	pub tls_key: Option<PathBuf>,
+
+	#[structopt(subcommand)]
+	pub command: Option<Command>,
+}
+
+impl DiaApiArgs {
+	/// Parses the command line, with the flags without a value also set by their environment
+	/// variables, which structopt only reads for options taking a value
+	pub fn from_args_and_env() -> Self {
+		let mut args = Self::from_args();
+		args.allow_asset_conflicts |= env_flag("DIA_ALLOW_ASSET_CONFLICTS");
+		args.fail_fast |= env_flag("DIA_FAIL_FAST");
+		args.one_shot |= env_flag("DIA_ONE_SHOT");
+		args.rate_limit_forwarded_for |= env_flag("DIA_RATE_LIMIT_FORWARDED_FOR");
+		args
+	}
+}
+
+/// Whether the environment variable `name` is `true` or `1`
+fn env_flag(name: &str) -> bool {
+	std::env::var(name).map_or(false, |value| matches!(value.trim(), "true" | "1"))
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+	/// Recomputes the publication hash of the coin infos on chain at a block and looks it up in
+	/// the server log
+	Verify(VerifyArgs),
+
+	/// Sends `POST /currencies` requests to a running instance at a fixed rate and reports latency
+	/// percentiles and the error rate
+	#[structopt(name = "loadtest")]
+	LoadTest(LoadTestArgs),
+
+	/// Reads the on-chain coin info of an asset at a range of blocks and prints it as CSV
+	History(HistoryArgs),
+
+	/// Polls a running instance and prints the prices of assets whenever they change
+	Watch(WatchArgs),
+
+	/// Compares the supported currencies of several chains with the configured currencies and
+	/// prints the differences of each chain
+	Reconcile(ReconcileArgs),
+
+	/// Only fetches prices and writes them to the snapshot file, without serving them
+	Fetch(SnapshotArgs),
+
+	/// Only serves the prices read from the snapshot file another process writes
+	Serve(SnapshotArgs),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SnapshotArgs {
+	/// File the fetching process writes the coin infos and errors to and the serving process
+	/// reads them from
+	#[structopt(long, env = "DIA_SNAPSHOT_FILE", parse(from_os_str))]
+	pub snapshot_file: PathBuf,
+
+	/// Interval at which the snapshot is written or checked for changes
+	#[structopt(long, env = "DIA_SNAPSHOT_INTERVAL_IN_SECONDS", default_value = "5")]
+	pub snapshot_interval_in_seconds: u64,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct VerifyArgs {
+	/// RPC url of a chain running the dia-oracle pallet
+	#[structopt(long, env = "DIA_CHAIN_RPC_URL")]
+	pub rpc_url: String,
+
+	/// Name of the dia-oracle pallet in the chain's runtime
+	#[structopt(long, env = "DIA_CHAIN_PALLET_NAME", default_value = "DiaOracleModule")]
+	pub pallet_name: String,
+
+	/// Number of the block to read the coin infos at
+	#[structopt(long)]
+	pub block: u64,
+
+	/// Server log to look the publication hash up in
+	#[structopt(long, parse(from_os_str))]
+	pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct HistoryArgs {
+	/// RPC url of a chain running the dia-oracle pallet, an archive node for blocks older than
+	/// the pruning window
+	#[structopt(long, env = "DIA_CHAIN_RPC_URL")]
+	pub rpc_url: String,
+
+	/// Name of the dia-oracle pallet in the chain's runtime
+	#[structopt(long, env = "DIA_CHAIN_PALLET_NAME", default_value = "DiaOracleModule")]
+	pub pallet_name: String,
+
+	/// Asset to read the price history of, `<blockchain>:<symbol>`
+	#[structopt(long)]
+	pub asset: AssetSpecifier,
+
+	/// First block to read the coin info at
+	#[structopt(long)]
+	pub from_block: u64,
+
+	/// Last block to read the coin info at
+	#[structopt(long)]
+	pub to_block: u64,
+
+	/// Number of blocks between two reads
+	#[structopt(long, default_value = "1")]
+	pub step: u64,
+
+	/// File the CSV is written to instead of stdout
+	#[structopt(long, parse(from_os_str))]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ReconcileArgs {
+	/// Chains running the dia-oracle pallet, `<name>=<rpc url>,...`, e.g.
+	/// `pendulum=https://rpc-pendulum.prd.pendulumchain.tech,amplitude=https://rpc-amplitude.pendulumchain.tech`
+	#[structopt(long)]
+	pub chains: ChainUrls,
+
+	/// Name of the dia-oracle pallet in the runtimes of the chains
+	#[structopt(long, env = "DIA_CHAIN_PALLET_NAME", default_value = "DiaOracleModule")]
+	pub pallet_name: String,
+
+	/// Currencies the server is configured with, in the format of `--supported-currencies`
+	#[structopt(long,
+      env = "DIA_SUPPORTED_CURRENCIES",
       parse(from_str = parse_currency_vec),
-      default_value = "Polkadot:DOT,Kusama:KSM,Stellar:XLM,FIAT:USD-USD,FIAT:MXN-USD,FIAT:BRL-USD,Amplitude:AMPE"
+      default_value = DEFAULT_CURRENCIES
     )]
 	pub supported_currencies: SupportedCurrencies,
 }
+
+#[derive(Debug, StructOpt)]
+pub struct LoadTestArgs {
+	/// Url of the `/currencies` endpoint of the instance to test
+	#[structopt(long)]
+	pub target: String,
+
+	/// Requests sent per second
+	#[structopt(long, default_value = "100")]
+	pub rps: u64,
+
+	/// Number of assets requested in each request
+	#[structopt(long, default_value = "10")]
+	pub assets: usize,
+
+	/// Duration of the test
+	#[structopt(long, default_value = "10")]
+	pub duration_in_seconds: u64,
+
+	/// Currencies requested first in each request, the rest is filled with unknown assets
+	#[structopt(long,
+      parse(from_str = parse_currency_vec),
+      default_value = DEFAULT_CURRENCIES
+    )]
+	pub currencies: SupportedCurrencies,
+
+	/// Timeout of a single request, slower requests count as errors
+	#[structopt(long, default_value = "5000")]
+	pub request_timeout_in_milliseconds: u64,
+
+	/// Shared secret to sign the requests with if the instance requires it
+	#[structopt(long, env = "DIA_HMAC_KEY", hide_env_values = true)]
+	pub hmac_key: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct WatchArgs {
+	/// Url of the `/currencies` endpoint of the instance to watch
+	#[structopt(long, default_value = "http://localhost:8070/currencies")]
+	pub target: String,
+
+	/// Assets to watch
+	#[structopt(long,
+      parse(from_str = parse_currency_vec),
+      default_value = DEFAULT_CURRENCIES
+    )]
+	pub assets: SupportedCurrencies,
+
+	/// Interval at which the instance is polled
+	#[structopt(long, default_value = "1000")]
+	pub interval_in_milliseconds: u64,
+
+	/// Timeout of a single request
+	#[structopt(long, default_value = "5000")]
+	pub request_timeout_in_milliseconds: u64,
+
+	/// Shared secret to sign the requests with if the instance requires it
+	#[structopt(long, env = "DIA_HMAC_KEY", hide_env_values = true)]
+	pub hmac_key: Option<String>,
+
+	/// Prints the changes without ANSI colors
+	#[structopt(long)]
+	pub no_color: bool,
+}
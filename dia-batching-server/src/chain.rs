@@ -0,0 +1,419 @@
+use crate::storage::{CoinInfo, CoinInfoStorage};
+use crate::{AssetSpecifier, SupportedAssets};
+use blake2::{digest::consts::U16, Blake2b, Digest};
+use codec::{Decode, Encode};
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::hash::Hasher;
+use std::sync::Arc;
+use twox_hash::XxHash64;
+
+/// Number of keys requested per `state_getKeysPaged` call
+const KEYS_PAGE_SIZE: u32 = 1000;
+
+/// Length of a `Blake2_128Concat` hashed key without the encoded key itself
+const BLAKE2_128_CONCAT_PREFIX_LEN: usize = 16;
+
+pub fn twox_128(data: &[u8]) -> [u8; 16] {
+	let mut out = [0u8; 16];
+	for (seed, chunk) in out.chunks_mut(8).enumerate() {
+		let mut hasher = XxHash64::with_seed(seed as u64);
+		hasher.write(data);
+		chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+	}
+	out
+}
+
+/// Storage key prefix of a storage item (or map) of a pallet
+pub fn storage_prefix(pallet: &str, storage: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(storage.as_bytes())].concat()
+}
+
+/// Key of `asset_id` in a `Blake2_128Concat` map with the given storage `prefix`
+pub fn blake2_128_concat_key(prefix: &[u8], asset_id: &OnChainAssetId) -> Vec<u8> {
+	let encoded = asset_id.encode();
+	[prefix, &Blake2b::<U16>::digest(&encoded)[..], &encoded[..]].concat()
+}
+
+/// `AssetId` and `CoinInfo` of the dia-oracle pallet as they are SCALE encoded on chain
+pub use dia_common::{AssetId as OnChainAssetId, CoinInfo as OnChainCoinInfo};
+
+/// Decodes the asset id of a `Blake2_128Concat` map key with the given storage `prefix`
+pub fn asset_id_from_map_key(prefix: &[u8], key: &[u8]) -> Option<OnChainAssetId> {
+	let mut encoded = key.get(prefix.len() + BLAKE2_128_CONCAT_PREFIX_LEN..)?;
+	OnChainAssetId::decode(&mut encoded).ok()
+}
+
+pub fn to_asset_specifier(asset_id: &OnChainAssetId) -> Option<AssetSpecifier> {
+	Some(AssetSpecifier {
+		blockchain: String::from_utf8(asset_id.blockchain.clone()).ok()?,
+		symbol: String::from_utf8(asset_id.symbol.clone()).ok()?,
+	})
+}
+
+impl From<&CoinInfo> for OnChainCoinInfo {
+	fn from(coin_info: &CoinInfo) -> Self {
+		Self {
+			symbol: coin_info.symbol.as_bytes().to_vec(),
+			name: coin_info.name.as_bytes().to_vec(),
+			blockchain: coin_info.blockchain.as_bytes().to_vec(),
+			supply: coin_info.supply,
+			last_update_timestamp: coin_info.last_update_timestamp,
+			price: coin_info.price,
+		}
+	}
+}
+
+impl TryFrom<&OnChainCoinInfo> for CoinInfo {
+	type Error = std::str::Utf8Error;
+
+	fn try_from(coin_info: &OnChainCoinInfo) -> Result<Self, Self::Error> {
+		Ok(Self {
+			symbol: std::str::from_utf8(&coin_info.symbol)?.into(),
+			name: std::str::from_utf8(&coin_info.name)?.into(),
+			blockchain: std::str::from_utf8(&coin_info.blockchain)?.into(),
+			supply: coin_info.supply,
+			last_update_timestamp: coin_info.last_update_timestamp,
+			price: coin_info.price,
+			source: Default::default(),
+			fetched_at: 0,
+		})
+	}
+}
+
+#[derive(Deserialize, Debug)]
+struct StorageChangeSet {
+	changes: Vec<(String, Option<String>)>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+	code: i64,
+	message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse<T> {
+	result: Option<T>,
+	error: Option<RpcError>,
+}
+
+/// Minimal JSON-RPC client for reading the state of a chain running the dia-oracle pallet
+#[derive(Clone)]
+pub struct ChainClient {
+	client: reqwest::Client,
+	url: String,
+	pallet_name: String,
+}
+
+impl ChainClient {
+	pub fn new(url: String, pallet_name: String) -> Self {
+		Self { client: reqwest::Client::new(), url, pallet_name }
+	}
+
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+
+	pub async fn rpc<T: DeserializeOwned>(
+		&self,
+		method: &str,
+		params: serde_json::Value,
+	) -> Result<T, Box<dyn Error + Send + Sync>> {
+		let request =
+			serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+		let response: RpcResponse<T> =
+			self.client.post(&self.url).json(&request).send().await?.json().await?;
+		match (response.result, response.error) {
+			(_, Some(RpcError { code, message })) =>
+				Err(format!("{} failed with {}: {}", method, code, message).into()),
+			(Some(result), None) => Ok(result),
+			(None, None) => Err(format!("{} returned no result", method).into()),
+		}
+	}
+
+	/// Returns all storage keys starting with `prefix`
+	pub async fn storage_keys(
+		&self,
+		prefix: &[u8],
+		at: Option<&str>,
+	) -> Result<Vec<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+		let prefix_hex = format!("0x{}", hex::encode(prefix));
+		let mut keys = Vec::new();
+		let mut start_key: Option<String> = None;
+		loop {
+			let page: Vec<String> = self
+				.rpc(
+					"state_getKeysPaged",
+					serde_json::json!([prefix_hex, KEYS_PAGE_SIZE, start_key, at]),
+				)
+				.await?;
+			let page_len = page.len();
+			start_key = page.last().cloned();
+			for key in page {
+				keys.push(decode_hex(&key)?);
+			}
+			if page_len < KEYS_PAGE_SIZE as usize {
+				return Ok(keys)
+			}
+		}
+	}
+
+	/// Returns the values stored under `keys`, `None` for keys without a value
+	pub async fn storage_values(
+		&self,
+		keys: &[Vec<u8>],
+		at: Option<&str>,
+	) -> Result<Vec<Option<Vec<u8>>>, Box<dyn Error + Send + Sync>> {
+		let keys_hex: Vec<String> =
+			keys.iter().map(|key| format!("0x{}", hex::encode(key))).collect();
+		let change_sets: Vec<StorageChangeSet> =
+			self.rpc("state_queryStorageAt", serde_json::json!([keys_hex, at])).await?;
+		let mut values: std::collections::HashMap<String, Option<String>> =
+			change_sets.into_iter().flat_map(|change_set| change_set.changes).collect();
+		keys_hex
+			.iter()
+			.map(|key| values.remove(key).flatten().map(|value| decode_hex(&value)).transpose())
+			.collect()
+	}
+
+	/// Returns the hash of block `number`
+	pub async fn block_hash(&self, number: u64) -> Result<String, Box<dyn Error + Send + Sync>> {
+		let hash: Option<String> =
+			self.rpc("chain_getBlockHash", serde_json::json!([number])).await?;
+		hash.ok_or_else(|| format!("Block {} not found", number).into())
+	}
+
+	/// Returns all `CoinInfosMap` entries of the dia-oracle pallet at block `at`
+	pub async fn coin_infos(
+		&self,
+		at: Option<&str>,
+	) -> Result<Vec<OnChainCoinInfo>, Box<dyn Error + Send + Sync>> {
+		let prefix = storage_prefix(&self.pallet_name, "CoinInfosMap");
+		let keys = self.storage_keys(&prefix, at).await?;
+		let values = self.storage_values(&keys, at).await?;
+		values
+			.into_iter()
+			.flatten()
+			.map(|value| {
+				OnChainCoinInfo::decode(&mut &value[..])
+					.map_err(|err| format!("Undecodable coin info: {}", err).into())
+			})
+			.collect()
+	}
+
+	/// Returns the `CoinInfosMap` entry of `asset_id` at block `at`, `None` if there is none. Needs
+	/// an archive node for blocks older than the pruning window of the node.
+	pub async fn coin_info(
+		&self,
+		asset_id: &OnChainAssetId,
+		at: Option<&str>,
+	) -> Result<Option<OnChainCoinInfo>, Box<dyn Error + Send + Sync>> {
+		let key =
+			blake2_128_concat_key(&storage_prefix(&self.pallet_name, "CoinInfosMap"), asset_id);
+		let value: Option<String> = self
+			.rpc("state_getStorage", serde_json::json!([format!("0x{}", hex::encode(key)), at]))
+			.await?;
+		value
+			.map(|value| {
+				OnChainCoinInfo::decode(&mut &decode_hex(&value)?[..])
+					.map_err(|err| format!("Undecodable coin info: {}", err).into())
+			})
+			.transpose()
+	}
+
+	/// Returns `Balances::TotalIssuance` at block `at` in the smallest unit of the native token
+	pub async fn total_issuance(
+		&self,
+		at: Option<&str>,
+	) -> Result<u128, Box<dyn Error + Send + Sync>> {
+		let key = storage_prefix("Balances", "TotalIssuance");
+		let value: Option<String> = self
+			.rpc("state_getStorage", serde_json::json!([format!("0x{}", hex::encode(key)), at]))
+			.await?;
+		let value = value.ok_or("Balances::TotalIssuance is not set")?;
+		u128::decode(&mut &decode_hex(&value)?[..])
+			.map_err(|err| format!("Undecodable total issuance: {}", err).into())
+	}
+
+	/// Returns the number of decimals of the native token from the chain's `system_properties`
+	pub async fn token_decimals(&self) -> Result<u32, Box<dyn Error + Send + Sync>> {
+		let properties: serde_json::Value =
+			self.rpc("system_properties", serde_json::json!([])).await?;
+		token_decimals(&properties).ok_or_else(|| "No tokenDecimals in system_properties".into())
+	}
+
+	/// Returns the `SupportedCurrencies` of the dia-oracle pallet
+	pub async fn supported_currencies(
+		&self,
+	) -> Result<HashSet<AssetSpecifier>, Box<dyn Error + Send + Sync>> {
+		let prefix = storage_prefix(&self.pallet_name, "SupportedCurrencies");
+		let keys = self.storage_keys(&prefix, None).await?;
+		Ok(keys
+			.iter()
+			.filter_map(|key| {
+				let asset = asset_id_from_map_key(&prefix, key)
+					.and_then(|asset_id| to_asset_specifier(&asset_id));
+				if asset.is_none() {
+					warn!("Skipping undecodable supported currency key 0x{}", hex::encode(key));
+				}
+				asset
+			})
+			.collect())
+	}
+}
+
+/// `tokenDecimals` of `system_properties`, the first entry on chains with several tokens
+fn token_decimals(properties: &serde_json::Value) -> Option<u32> {
+	let decimals = match &properties["tokenDecimals"] {
+		serde_json::Value::Array(decimals) => decimals.first()?,
+		decimals => decimals,
+	};
+	decimals.as_u64().map(|decimals| decimals as u32)
+}
+
+pub fn decode_hex(value: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+	Ok(hex::decode(value.trim_start_matches("0x"))?)
+}
+
+/// Replaces the supported currencies with the `SupportedCurrencies` on chain in every `interval`
+pub fn run_supported_currencies_sync_loop(
+	client: ChainClient,
+	supported_currencies: Arc<SupportedAssets>,
+	interval: std::time::Duration,
+) {
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			match client.supported_currencies().await {
+				Ok(currencies) =>
+					if supported_currencies.load().as_deref() != Some(&currencies) {
+						info!("Supported currencies changed on chain: {} assets", currencies.len());
+						supported_currencies.store(Some(Arc::new(currencies)));
+					},
+				Err(err) => error!("Failed to read supported currencies from chain: {}", err),
+			}
+		}
+	});
+}
+
+/// Fills the empty `storage` with the coin infos on chain, of the `supported` currencies if they
+/// are known, so a restarted server serves what the chain last saw until the first update
+/// iteration finished. Gives up after `timeout`, the server then starts without prices. Returns
+/// the number of coin infos seeded, none if `storage` was filled meanwhile.
+pub async fn seed_storage(
+	client: &ChainClient,
+	storage: &CoinInfoStorage,
+	supported: Option<&HashSet<AssetSpecifier>>,
+	timeout: std::time::Duration,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+	let coin_infos = tokio::time::timeout(timeout, client.coin_infos(None))
+		.await
+		.map_err(|_| format!("No coin infos within {}s", timeout.as_secs()))??;
+	let coin_infos: Vec<CoinInfo> = coin_infos
+		.iter()
+		.filter_map(|coin_info| {
+			let coin_info = CoinInfo::try_from(coin_info).ok()?;
+			let asset = AssetSpecifier {
+				blockchain: coin_info.blockchain.to_string(),
+				symbol: coin_info.symbol.to_string(),
+			};
+			supported
+				.map_or(true, |supported| supported.contains(&asset))
+				.then(|| coin_info)
+		})
+		.collect();
+	if storage.len() != 0 {
+		return Ok(0)
+	}
+	let seeded = coin_infos.len();
+	storage.replace_currencies_by_symbols(coin_infos);
+	Ok(seeded)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_twox_128() {
+		assert_eq!(hex::encode(twox_128(b"System")), "26aa394eea5630e07c48ae0c9558cef7");
+		assert_eq!(
+			hex::encode(storage_prefix("Balances", "TotalIssuance")),
+			"c2261276cc9d1f8598ea4b6a74b15c2f57c875e4cff74148e4628f264b974c80"
+		);
+	}
+
+	#[test]
+	fn test_coin_info_from_chain() {
+		let on_chain = OnChainCoinInfo {
+			symbol: b"MXN-USD".to_vec(),
+			name: b"MXN-USD".to_vec(),
+			blockchain: b"FIAT".to_vec(),
+			supply: 1,
+			last_update_timestamp: 1700474693,
+			price: 53712327000,
+		};
+
+		let coin_info = CoinInfo::try_from(&on_chain).unwrap();
+		assert_eq!(OnChainCoinInfo::from(&coin_info), on_chain);
+		assert!(CoinInfo::try_from(&OnChainCoinInfo { symbol: vec![0xff], ..on_chain }).is_err());
+	}
+
+	#[test]
+	fn test_token_decimals() {
+		assert_eq!(token_decimals(&serde_json::json!({ "tokenDecimals": 12 })), Some(12));
+		assert_eq!(token_decimals(&serde_json::json!({ "tokenDecimals": [10, 12] })), Some(10));
+		assert_eq!(token_decimals(&serde_json::json!({ "ss58Format": 57 })), None);
+	}
+
+	#[test]
+	fn test_asset_id_from_map_key() {
+		let prefix = storage_prefix("DiaOracleModule", "SupportedCurrencies");
+		let asset_id = OnChainAssetId { blockchain: b"FIAT".to_vec(), symbol: b"MXN-USD".to_vec() };
+		let key = [&prefix[..], &[0u8; 16][..], &asset_id.encode()[..]].concat();
+
+		assert_eq!(asset_id_from_map_key(&prefix, &key), Some(asset_id.clone()));
+		assert_eq!(asset_id_from_map_key(&prefix, &prefix), None);
+		assert_eq!(
+			to_asset_specifier(&asset_id),
+			Some(AssetSpecifier { blockchain: "FIAT".into(), symbol: "MXN-USD".into() })
+		);
+	}
+
+	#[test]
+	fn test_blake2_128_concat_key() {
+		let prefix = storage_prefix("DiaOracleModule", "CoinInfosMap");
+		let asset_id = OnChainAssetId { blockchain: b"FIAT".to_vec(), symbol: b"MXN-USD".to_vec() };
+		let key = blake2_128_concat_key(&prefix, &asset_id);
+
+		assert_eq!(hex::encode(&key[prefix.len()..][..16]), "a63493f28afb9e8b4858b5b680c44ea3");
+		assert_eq!(asset_id_from_map_key(&prefix, &key), Some(asset_id));
+	}
+
+	#[test]
+	fn test_coin_info_json_matches_pallet() {
+		let coin_info = CoinInfo {
+			symbol: "MXN-USD".into(),
+			name: "MXN-USD".into(),
+			blockchain: "FIAT".into(),
+			supply: 0,
+			last_update_timestamp: 1700474693,
+			price: 53712327000,
+			source: "YahooFinance".into(),
+			fetched_at: 1700474700,
+		};
+
+		let json = serde_json::to_string(&coin_info).unwrap();
+
+		assert_eq!(
+			serde_json::from_str::<OnChainCoinInfo>(&json).unwrap(),
+			OnChainCoinInfo::from(&coin_info)
+		);
+	}
+}
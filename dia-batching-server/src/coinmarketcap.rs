@@ -0,0 +1,237 @@
+use crate::dia::{Asset, Quotation};
+use crate::{parse_asset_map, AssetSpecifier};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+/// ### CoinMarketCap Latest Quotes
+///
+/// `GET : https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest?id=:id`
+///
+/// Requests carry the API key in the `X-CMC_PRO_API_KEY` header. Assets without a CoinMarketCap ID
+/// are requested with `?symbol=:symbol`, which lists every coin with the symbol, highest ranked
+/// first.
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"status": { "error_code": 0, "error_message": null },
+/// 	"data": {
+/// 		"1": {
+/// 			"id": 1,
+/// 			"name": "Bitcoin",
+/// 			"symbol": "BTC",
+/// 			"quote": {
+/// 				"USD": {
+/// 					"price": 37329.98,
+/// 					"volume_24h": 15213434534.21,
+/// 					"last_updated": "2023-11-20T10:04:00.000Z"
+/// 				}
+/// 			}
+/// 		}
+/// 	}
+/// }
+/// ```
+const QUOTES_ENDPOINT: &str = "https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest";
+
+const API_KEY_HEADER: &str = "X-CMC_PRO_API_KEY";
+
+pub const SOURCE: &str = "CoinMarketCap";
+
+#[derive(Deserialize, Debug)]
+struct QuotesResponse {
+	status: Status,
+	#[serde(default)]
+	data: HashMap<String, Listing>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Status {
+	error_code: i64,
+	error_message: Option<String>,
+}
+
+/// A coin requested by ID, or all coins with a requested symbol
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Listing {
+	Coin(Coin),
+	Coins(Vec<Coin>),
+}
+
+#[derive(Deserialize, Debug)]
+struct Coin {
+	name: String,
+	quote: HashMap<String, UsdQuote>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UsdQuote {
+	price: Option<Decimal>,
+	volume_24h: Option<Decimal>,
+	last_updated: DateTime<Utc>,
+}
+
+/// ID of a coin on CoinMarketCap, which unlike its symbol is unique
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinMarketCapId(pub u64);
+
+impl FromStr for CoinMarketCapId {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.trim()
+			.parse()
+			.map(CoinMarketCapId)
+			.map_err(|_| format!("Invalid CoinMarketCap ID '{}'", s))
+	}
+}
+
+/// CoinMarketCap IDs of individual assets, parsed from `<blockchain>:<symbol>=<id>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoinMarketCapIds(pub HashMap<AssetSpecifier, CoinMarketCapId>);
+
+impl FromStr for CoinMarketCapIds {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(CoinMarketCapIds)
+	}
+}
+
+/// Quotes crypto assets against USD with the API key of a CoinMarketCap plan, an alternative to
+/// DIA and the exchanges for crypto assets
+#[derive(Debug, Clone)]
+pub struct CoinMarketCap {
+	api_key: String,
+	ids: CoinMarketCapIds,
+	client: reqwest::Client,
+}
+
+impl CoinMarketCap {
+	pub fn new(api_key: String, ids: CoinMarketCapIds) -> Self {
+		Self { api_key, ids, client: reqwest::Client::new() }
+	}
+
+	fn id(&self, asset: &Asset) -> Option<CoinMarketCapId> {
+		let asset =
+			AssetSpecifier { blockchain: asset.blockchain.clone(), symbol: asset.symbol.clone() };
+		self.ids.0.get(&asset).copied()
+	}
+
+	pub async fn get_quotation(
+		&self,
+		asset: &Asset,
+	) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+		let query = match self.id(asset) {
+			Some(CoinMarketCapId(id)) => ("id", id.to_string()),
+			None => ("symbol", asset.symbol.to_uppercase()),
+		};
+		let response: QuotesResponse = self
+			.client
+			.get(QUOTES_ENDPOINT)
+			.query(&[query])
+			.header(API_KEY_HEADER, &self.api_key)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+		quotation(asset, response)
+	}
+}
+
+/// Quotation of the only coin requested, the highest ranked one if requested by symbol
+fn quotation(
+	asset: &Asset,
+	response: QuotesResponse,
+) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+	if response.status.error_code != 0 {
+		return Err(format!(
+			"CoinMarketCap failed: {}",
+			response.status.error_message.unwrap_or_default()
+		)
+		.into())
+	}
+	let mut coin = response
+		.data
+		.into_values()
+		.next()
+		.and_then(|listing| match listing {
+			Listing::Coin(coin) => Some(coin),
+			Listing::Coins(coins) => coins.into_iter().next(),
+		})
+		.ok_or_else(|| format!("CoinMarketCap doesn't list {}", asset.symbol))?;
+	let quote = coin
+		.quote
+		.remove("USD")
+		.ok_or_else(|| format!("CoinMarketCap doesn't quote {} in USD", asset.symbol))?;
+	let price = quote
+		.price
+		.ok_or_else(|| format!("CoinMarketCap has no price of {}", asset.symbol))?;
+	Ok(Quotation {
+		symbol: asset.symbol.clone(),
+		name: coin.name,
+		address: None,
+		blockchain: Some(asset.blockchain.clone()),
+		price,
+		price_yesterday: Default::default(),
+		volume_yesterday: quote.volume_24h.unwrap_or_default(),
+		time: quote.last_updated,
+		source: SOURCE.to_string(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn btc() -> Asset {
+		Asset {
+			symbol: "BTC".into(),
+			name: "BTC".into(),
+			address: "".into(),
+			decimals: 0,
+			blockchain: "Bitcoin".into(),
+		}
+	}
+
+	#[test]
+	fn test_quotation() {
+		let by_id: QuotesResponse = serde_json::from_str(
+			r#"{"status":{"error_code":0,"error_message":null},"data":{"1":{"id":1,"name":"Bitcoin","symbol":"BTC","quote":{"USD":{"price":37329.98,"volume_24h":1500.5,"last_updated":"2023-11-20T10:04:00.000Z"}}}}}"#,
+		)
+		.unwrap();
+		let by_id = quotation(&btc(), by_id).unwrap();
+
+		assert_eq!(by_id.price, Decimal::new(3732998, 2));
+		assert_eq!(by_id.volume_yesterday, Decimal::new(15005, 1));
+		assert_eq!(by_id.name, "Bitcoin");
+		assert_eq!(by_id.time.timestamp(), 1700474640);
+		assert_eq!(by_id.source, "CoinMarketCap");
+
+		let by_symbol: QuotesResponse = serde_json::from_str(
+			r#"{"status":{"error_code":0,"error_message":null},"data":{"BTC":[{"id":1,"name":"Bitcoin","symbol":"BTC","quote":{"USD":{"price":37329.98,"volume_24h":null,"last_updated":"2023-11-20T10:04:00.000Z"}}},{"id":2,"name":"Other","symbol":"BTC","quote":{}}]}}"#,
+		)
+		.unwrap();
+		assert_eq!(quotation(&btc(), by_symbol).unwrap().name, "Bitcoin");
+
+		let unknown: QuotesResponse = serde_json::from_str(
+			r#"{"status":{"error_code":400,"error_message":"Invalid value for \"id\""}}"#,
+		)
+		.unwrap();
+		assert!(quotation(&btc(), unknown).is_err());
+	}
+
+	#[test]
+	fn test_parse_ids() {
+		let ids: CoinMarketCapIds = "Bitcoin:BTC=1, Polkadot:DOT=6636".parse().unwrap();
+		let cmc = CoinMarketCap::new("key".into(), ids);
+
+		assert_eq!(cmc.id(&btc()), Some(CoinMarketCapId(1)));
+		assert!("Bitcoin:BTC=one".parse::<CoinMarketCapIds>().is_err());
+	}
+}
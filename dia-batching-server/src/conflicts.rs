@@ -0,0 +1,196 @@
+use crate::AssetSpecifier;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+fn display(assets: &[&AssetSpecifier]) -> String {
+	assets
+		.iter()
+		.map(|asset| format!("{}:{}", asset.blockchain, asset.symbol))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Groups of at least two `assets` that only differ in case. They are fetched from the same
+/// upstream quotation, because the sources match blockchains and symbols case-insensitively.
+pub fn normalization_conflicts(assets: &HashSet<AssetSpecifier>) -> Vec<String> {
+	let mut by_normalized: BTreeMap<(String, String), Vec<&AssetSpecifier>> = BTreeMap::new();
+	for asset in assets {
+		by_normalized
+			.entry((asset.blockchain.to_uppercase(), asset.symbol.to_uppercase()))
+			.or_default()
+			.push(asset);
+	}
+	by_normalized
+		.into_values()
+		.filter(|group| group.len() > 1)
+		.map(|mut group| {
+			group.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+			display(&group)
+		})
+		.collect()
+}
+
+/// Groups of `assets` on different blockchains sharing a symbol, which consumers keying prices by
+/// symbol alone can mix up
+pub fn shared_symbols(assets: &HashSet<AssetSpecifier>) -> Vec<String> {
+	let mut by_symbol: BTreeMap<String, Vec<&AssetSpecifier>> = BTreeMap::new();
+	for asset in assets {
+		by_symbol.entry(asset.symbol.to_uppercase()).or_default().push(asset);
+	}
+	by_symbol
+		.into_values()
+		.filter(|group| {
+			group
+				.iter()
+				.map(|asset| asset.blockchain.to_uppercase())
+				.collect::<HashSet<_>>()
+				.len() > 1
+		})
+		.map(|mut group| {
+			group.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+			display(&group)
+		})
+		.collect()
+}
+
+/// Blockchains other than the asset's own that assets of the same symbol are labelled with
+fn other_blockchains<'a>(
+	asset: &AssetSpecifier,
+	assets: impl IntoIterator<Item = &'a AssetSpecifier>,
+) -> Vec<String> {
+	assets
+		.into_iter()
+		.filter(|other| {
+			other.symbol.eq_ignore_ascii_case(&asset.symbol) &&
+				!other.blockchain.eq_ignore_ascii_case(&asset.blockchain)
+		})
+		.map(|other| other.blockchain.clone())
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.collect()
+}
+
+fn contains(assets: &HashSet<AssetSpecifier>, asset: &AssetSpecifier) -> bool {
+	assets.iter().any(|other| {
+		other.blockchain.eq_ignore_ascii_case(&asset.blockchain) &&
+			other.symbol.eq_ignore_ascii_case(&asset.symbol)
+	})
+}
+
+/// Assets with per-asset options that aren't supported while a supported currency of the same
+/// symbol is, so the options are likely meant for it but labelled with another blockchain, e.g.
+/// `Bifrost:BNC` configured and `BifrostPolkadot:BNC` supported
+pub fn configured_label_mismatches<'a>(
+	supported: &HashSet<AssetSpecifier>,
+	configured: impl IntoIterator<Item = &'a AssetSpecifier>,
+) -> Vec<String> {
+	let mut mismatches: Vec<String> = configured
+		.into_iter()
+		.filter(|asset| !contains(supported, asset))
+		.filter_map(|asset| {
+			let blockchains = other_blockchains(asset, supported);
+			(!blockchains.is_empty()).then(|| {
+				format!(
+					"{}:{} (supported on {})",
+					asset.blockchain,
+					asset.symbol,
+					blockchains.join(", ")
+				)
+			})
+		})
+		.collect();
+	mismatches.sort();
+	mismatches.dedup();
+	mismatches
+}
+
+/// A supported currency no source quotes on its blockchain while assets of the same symbol are
+/// quoted on others
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockchainMismatch {
+	pub asset: AssetSpecifier,
+	pub quoted_on: Vec<String>,
+}
+
+impl Display for BlockchainMismatch {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}:{} isn't quoted on {}, only on {}",
+			self.asset.blockchain,
+			self.asset.symbol,
+			self.asset.blockchain,
+			self.quoted_on.join(", ")
+		)
+	}
+}
+
+impl Error for BlockchainMismatch {}
+
+/// Supported currencies the `quotable` assets only share the symbol with, ordered by blockchain
+/// and then symbol
+pub fn blockchain_mismatches(
+	supported: &HashSet<AssetSpecifier>,
+	quotable: &HashSet<AssetSpecifier>,
+) -> Vec<BlockchainMismatch> {
+	let mut mismatches: Vec<BlockchainMismatch> = supported
+		.iter()
+		.filter(|asset| !contains(quotable, asset))
+		.filter_map(|asset| {
+			let quoted_on = other_blockchains(asset, quotable);
+			(!quoted_on.is_empty()).then(|| BlockchainMismatch { asset: asset.clone(), quoted_on })
+		})
+		.collect();
+	mismatches.sort_by(|a, b| {
+		(&a.asset.blockchain, &a.asset.symbol).cmp(&(&b.asset.blockchain, &b.asset.symbol))
+	});
+	mismatches
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assets(assets: &[(&str, &str)]) -> HashSet<AssetSpecifier> {
+		assets
+			.iter()
+			.map(|(blockchain, symbol)| AssetSpecifier {
+				blockchain: blockchain.to_string(),
+				symbol: symbol.to_string(),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_normalization_conflicts() {
+		let assets = assets(&[
+			("Bifrost", "vDOT"),
+			("BIFROST", "VDOT"),
+			("Polkadot", "DOT"),
+			("Ethereum", "USDC"),
+			("Stellar", "USDC"),
+		]);
+
+		assert_eq!(normalization_conflicts(&assets), vec!["BIFROST:VDOT, Bifrost:vDOT"]);
+		assert_eq!(shared_symbols(&assets), vec!["Ethereum:USDC, Stellar:USDC"]);
+	}
+
+	#[test]
+	fn test_label_mismatches() {
+		let supported = assets(&[("BifrostPolkadot", "BNC"), ("Polkadot", "DOT")]);
+		let configured = assets(&[("Bifrost", "BNC"), ("polkadot", "DOT"), ("Kusama", "KSM")]);
+		let quotable = assets(&[("Bifrost", "BNC"), ("Kusama", "BNC"), ("Polkadot", "DOT")]);
+
+		assert_eq!(
+			configured_label_mismatches(&supported, &configured),
+			vec!["Bifrost:BNC (supported on BifrostPolkadot)"]
+		);
+		let mismatches = blockchain_mismatches(&supported, &quotable);
+		assert_eq!(mismatches.len(), 1);
+		assert_eq!(
+			mismatches[0].to_string(),
+			"BifrostPolkadot:BNC isn't quoted on BifrostPolkadot, only on Bifrost, Kusama"
+		);
+	}
+}
@@ -0,0 +1,107 @@
+use actix_cors::Cors;
+use actix_web::http::Method;
+use dia_common::signing::{PUBLIC_KEY_HEADER, RESPONSE_SIGNATURE_HEADER};
+use std::str::FromStr;
+
+/// Origins of the browser frontends allowed to request the server, parsed from comma separated
+/// origins like `https://portal.pendulumchain.org` or `*` for any origin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsOrigins {
+	Any,
+	Listed(Vec<String>),
+}
+
+impl CorsOrigins {
+	/// Whether CORS requests are answered at all, which they aren't without any origin
+	pub fn is_enabled(&self) -> bool {
+		!matches!(self, CorsOrigins::Listed(origins) if origins.is_empty())
+	}
+}
+
+impl FromStr for CorsOrigins {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let origins: Vec<&str> =
+			s.split(',').map(str::trim).filter(|origin| !origin.is_empty()).collect();
+		if origins.contains(&"*") {
+			return Ok(CorsOrigins::Any)
+		}
+		origins
+			.into_iter()
+			.map(|origin| {
+				let host = origin
+					.strip_prefix("https://")
+					.or_else(|| origin.strip_prefix("http://"))
+					.unwrap_or_default();
+				if host.is_empty() || host.contains('/') {
+					return Err(format!(
+						"Invalid origin '{}', expected <scheme>://<host>[:<port>] or *",
+						origin
+					))
+				}
+				Ok(origin.to_string())
+			})
+			.collect::<Result<_, _>>()
+			.map(CorsOrigins::Listed)
+	}
+}
+
+/// Methods allowed in CORS requests, parsed from comma separated methods like `GET,POST`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsMethods(pub Vec<Method>);
+
+impl FromStr for CorsMethods {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.map(str::trim)
+			.filter(|method| !method.is_empty())
+			.map(|method| {
+				Method::from_str(&method.to_uppercase())
+					.map_err(|_| format!("Invalid method '{}'", method))
+			})
+			.collect::<Result<_, _>>()
+			.map(CorsMethods)
+	}
+}
+
+/// CORS middleware answering the preflight requests of the allowed origins, which exposes the
+/// response signature headers to them
+pub fn cors(origins: &CorsOrigins, methods: &CorsMethods) -> Cors {
+	let cors = match origins {
+		CorsOrigins::Any => Cors::default().allow_any_origin(),
+		CorsOrigins::Listed(origins) =>
+			origins.iter().fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)),
+	};
+	cors.allowed_methods(methods.0.iter().map(Method::as_str))
+		.allow_any_header()
+		.expose_headers(vec![RESPONSE_SIGNATURE_HEADER, PUBLIC_KEY_HEADER])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_cors_origins() {
+		assert_eq!(
+			"https://portal.pendulumchain.org, http://localhost:3000".parse::<CorsOrigins>(),
+			Ok(CorsOrigins::Listed(vec![
+				"https://portal.pendulumchain.org".to_string(),
+				"http://localhost:3000".to_string()
+			]))
+		);
+		assert_eq!("*".parse::<CorsOrigins>(), Ok(CorsOrigins::Any));
+		assert!(!"".parse::<CorsOrigins>().unwrap().is_enabled());
+		assert!("portal.pendulumchain.org".parse::<CorsOrigins>().is_err());
+		assert!("https://portal.pendulumchain.org/".parse::<CorsOrigins>().is_err());
+
+		assert_eq!(
+			"get, POST".parse::<CorsMethods>(),
+			Ok(CorsMethods(vec![Method::GET, Method::POST]))
+		);
+		assert!("GET,G ET".parse::<CorsMethods>().is_err());
+	}
+}
@@ -0,0 +1,124 @@
+use crate::dia::Dia;
+use crate::storage::{AssetError, AssetErrorLog, CoinInfoStorage};
+use crate::{AssetSpecifier, SupportedAssets};
+use chrono::Utc;
+use log::{debug, info, warn};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+
+/// Deviation in basis points of a published price with 12 decimals from a directly quoted price,
+/// `None` if the direct price is zero
+pub fn deviation_in_bps(published: u128, direct: Decimal) -> Option<Decimal> {
+	if direct.is_zero() {
+		return None
+	}
+	let published = Decimal::try_from_i128_with_scale(i128::try_from(published).ok()?, 12).ok()?;
+	Some(((published - direct) / direct).abs() * Decimal::from(10_000))
+}
+
+/// Fiat pairs of the supported currencies the server crosses via USD, e.g. `FIAT:USD-BRL` which is
+/// the inverse of `FIAT:BRL-USD`
+fn crossed_pairs(supported_currencies: &SupportedAssets) -> Vec<AssetSpecifier> {
+	supported_currencies
+		.load()
+		.iter()
+		.flat_map(|assets| assets.iter())
+		.filter(|asset| asset.blockchain.eq_ignore_ascii_case("FIAT"))
+		.filter(|asset| {
+			let symbol = asset.symbol.to_uppercase();
+			matches!(symbol.split_once('-'), Some((_, quote)) if quote != "USD")
+		})
+		.cloned()
+		.collect()
+}
+
+/// Compares the published price of every crossed fiat pair in every `interval` with the pair as
+/// the foreign quotation endpoint quotes it directly, if it does. A deviation of more than
+/// `tolerance_in_bps` points to a bug in crossing or mapping the pair, it is logged and recorded
+/// with kind `cross` in the error log of the pair.
+pub fn run_cross_check_loop(
+	storage: Arc<CoinInfoStorage>,
+	errors: Arc<AssetErrorLog>,
+	supported_currencies: Arc<SupportedAssets>,
+	tolerance_in_bps: Decimal,
+	interval: std::time::Duration,
+) {
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			for asset in crossed_pairs(&supported_currencies) {
+				let published = match storage.get_currency(&asset.blockchain, &asset.symbol) {
+					Some(published) => published,
+					None => continue,
+				};
+				let direct = match Dia::get_foreign_quotation(&asset.symbol).await {
+					Ok(direct) => direct,
+					Err(err) => {
+						debug!("No direct quotation of {} to cross check: {}", asset.symbol, err);
+						continue
+					},
+				};
+				match deviation_in_bps(published.price, direct.price) {
+					Some(deviation) if deviation > tolerance_in_bps => {
+						let message = format!(
+							"Crossed price deviates {} bps from the direct quotation {}",
+							deviation.round_dp(2),
+							direct.price
+						);
+						warn!("{}:{}: {}", asset.blockchain, asset.symbol, message);
+						errors.record(
+							&asset.blockchain,
+							&asset.symbol,
+							AssetError {
+								timestamp: Utc::now().timestamp().unsigned_abs(),
+								source: direct.source.into(),
+								kind: "cross".into(),
+								message,
+							},
+						);
+					},
+					Some(deviation) => info!(
+						"{}:{} is within {} bps of its direct quotation",
+						asset.blockchain,
+						asset.symbol,
+						deviation.round_dp(2)
+					),
+					None => debug!("Direct quotation of {} is zero", asset.symbol),
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_decimal_macros::dec;
+
+	#[test]
+	fn test_deviation_in_bps() {
+		// USD-BRL crossed as the inverse of 0.2 BRL-USD
+		let crossed = 5_000_000_000_000;
+
+		assert_eq!(deviation_in_bps(crossed, dec!(5)), Some(dec!(0)));
+		assert_eq!(deviation_in_bps(crossed, dec!(5.05)).map(|d| d.round()), Some(dec!(99)));
+		assert_eq!(deviation_in_bps(crossed, dec!(4)), Some(dec!(2500)));
+		assert_eq!(deviation_in_bps(crossed, dec!(0)), None);
+	}
+
+	#[test]
+	fn test_crossed_pairs() {
+		let supported = SupportedAssets::from_pointee(
+			["FIAT:USD-BRL", "FIAT:BRL-USD", "fiat:eur-mxn", "Polkadot:DOT", "FIAT:USD"]
+				.iter()
+				.map(|asset| asset.parse().unwrap())
+				.collect(),
+		);
+		let mut crossed: Vec<String> =
+			crossed_pairs(&supported).into_iter().map(|asset| asset.symbol).collect();
+		crossed.sort();
+
+		assert_eq!(crossed, vec!["USD-BRL".to_string(), "eur-mxn".to_string()]);
+	}
+}
@@ -0,0 +1,172 @@
+use crate::{
+	dia::Asset, parse_asset_map, quarantine::Quarantine, storage::CoinInfo, AssetSpecifier,
+};
+use chrono::Utc;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use std::{
+	collections::HashMap,
+	error::Error,
+	fmt::{Display, Formatter},
+	str::FromStr,
+	sync::{Arc, Mutex},
+};
+
+/// Largest change of the price of an asset in percent that is published right away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviationLimit(pub Decimal);
+
+impl FromStr for DeviationLimit {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match Decimal::from_str(s.trim()) {
+			Ok(limit) if limit.is_sign_positive() => Ok(DeviationLimit(limit)),
+			_ => Err(format!("Invalid deviation limit '{}', expected a positive percentage", s)),
+		}
+	}
+}
+
+/// Deviation limits of individual assets, parsed from `<blockchain>:<symbol>=<percent>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviationLimits(pub HashMap<AssetSpecifier, DeviationLimit>);
+
+impl FromStr for DeviationLimits {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(DeviationLimits)
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeviationError {
+	pub previous: u128,
+	pub price: u128,
+	pub deviation: Decimal,
+	pub held: u32,
+	pub hold_cycles: u32,
+}
+
+impl Display for DeviationError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Price {} deviates {}% from the published price {}, held back {} of {} iterations",
+			self.price,
+			self.deviation.round_dp(2),
+			self.previous,
+			self.held,
+			self.hold_cycles
+		)
+	}
+}
+
+impl Error for DeviationError {}
+
+/// Change from `previous` to `price` in percent of `previous`, `Decimal::MAX` if it doesn't fit
+pub fn deviation(previous: u128, price: u128) -> Decimal {
+	Decimal::from_u128(price.abs_diff(previous))
+		.zip(Decimal::from_u128(previous))
+		.and_then(|(change, previous)| change.checked_div(previous))
+		.and_then(|share| share.checked_mul(Decimal::from(100)))
+		.unwrap_or(Decimal::MAX)
+}
+
+/// Holds back prices deviating more than the limit of their asset from the published price, so a
+/// flash crash or a bad quotation of a source doesn't reach the chain. A price still deviating
+/// after `hold_cycles` consecutive iterations is taken as the new level of the asset.
+#[derive(Debug, Default)]
+pub struct DeviationGuard {
+	limits: DeviationLimits,
+	hold_cycles: u32,
+	held: Mutex<HashMap<AssetSpecifier, u32>>,
+	quarantine: Option<Arc<Quarantine>>,
+}
+
+impl DeviationGuard {
+	pub fn new(limits: DeviationLimits, hold_cycles: u32) -> Self {
+		Self { limits, hold_cycles, held: Default::default(), quarantine: None }
+	}
+
+	/// Counts the prices the guard rejects towards quarantining their source for the asset
+	pub fn with_quarantine(self, quarantine: Arc<Quarantine>) -> Self {
+		Self { quarantine: Some(quarantine), ..self }
+	}
+
+	/// Reports whether a price of `asset` quoted by `source` was rejected to the quarantine
+	pub fn report(&self, asset: &Asset, source: &str, rejected: bool) {
+		if let Some(quarantine) = &self.quarantine {
+			quarantine.record(asset, source, rejected, Utc::now().timestamp().unsigned_abs());
+		}
+	}
+
+	/// Fails if `coin_info` is to be held back in favour of the published `previous` coin info
+	pub fn check(
+		&self,
+		previous: Option<&CoinInfo>,
+		coin_info: &CoinInfo,
+	) -> Result<(), DeviationError> {
+		let asset = AssetSpecifier {
+			blockchain: coin_info.blockchain.to_string(),
+			symbol: coin_info.symbol.to_string(),
+		};
+		let (limit, previous) = match (self.limits.0.get(&asset), previous) {
+			(Some(limit), Some(previous)) if previous.price != 0 => (limit, previous),
+			_ => return Ok(()),
+		};
+		let mut held = self.held.lock().expect("poisoned lock");
+		let deviation = deviation(previous.price, coin_info.price);
+		let count = held.entry(asset).or_default();
+		if deviation <= limit.0 || *count >= self.hold_cycles {
+			*count = 0;
+			return Ok(())
+		}
+		*count += 1;
+		Err(DeviationError {
+			previous: previous.price,
+			price: coin_info.price,
+			deviation,
+			held: *count,
+			hold_cycles: self.hold_cycles,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_decimal_macros::dec;
+
+	fn coin_info(price: u128) -> CoinInfo {
+		CoinInfo {
+			symbol: "BTC".into(),
+			name: "Bitcoin".into(),
+			blockchain: "Bitcoin".into(),
+			price,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_deviation_guard() {
+		let guard = DeviationGuard::new("Bitcoin:BTC=10".parse().unwrap(), 2);
+		let published = coin_info(100);
+
+		assert_eq!(guard.check(Some(&published), &coin_info(109)), Ok(()));
+		assert_eq!(guard.check(None, &coin_info(50)), Ok(()));
+		let err = guard.check(Some(&published), &coin_info(50)).unwrap_err();
+		assert_eq!((err.deviation, err.held), (dec!(50), 1));
+		assert_eq!(guard.check(Some(&published), &coin_info(50)).unwrap_err().held, 2);
+		// Still deviating after two held iterations, the price is taken as the new level
+		assert_eq!(guard.check(Some(&published), &coin_info(50)), Ok(()));
+		assert_eq!(guard.check(Some(&published), &coin_info(50)).unwrap_err().held, 1);
+		// A price within the limit resets the count
+		assert_eq!(guard.check(Some(&published), &coin_info(95)), Ok(()));
+		assert_eq!(guard.check(Some(&published), &coin_info(150)).unwrap_err().held, 1);
+
+		let mut eth = coin_info(50);
+		eth.blockchain = "Ethereum".into();
+		assert_eq!(guard.check(Some(&published), &eth), Ok(()));
+		assert!("Bitcoin:BTC=-5".parse::<DeviationLimits>().is_err());
+	}
+}
@@ -1,12 +1,13 @@
 use async_trait::async_trait;
 use chrono::prelude::*;
 use chrono::DateTime;
+#[cfg(any(feature = "custom-ampe", feature = "custom-kpen"))]
 use graphql_client::{GraphQLQuery, Response};
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use std::error;
-use std::error::Error;
 use std::string::ToString;
+use std::sync::Arc;
 
 const QUOTABLE_ASSETS_ENDPOINT: &str = "https://api.diadata.org/v1/quotedAssets";
 /// ### Quotable Assets
@@ -119,6 +120,14 @@ impl Default for Quotation {
 }
 
 impl Quotation {
+	/// Whether the quotation is older than its source declares quotations to be at most, see
+	/// `max_quote_age`
+	pub fn is_delayed(&self, now: DateTime<Utc>) -> bool {
+		max_quote_age(&self.source).map_or(false, |max_age| {
+			now.signed_duration_since(self.time) > chrono::Duration::seconds(max_age as i64)
+		})
+	}
+
 	pub fn get_default_fiat_usd_quotation() -> Self {
 		Self {
 			symbol: "USD-USD".to_string(),
@@ -143,11 +152,41 @@ pub trait DiaApi {
 		&self,
 		_: &QuotedAsset,
 	) -> Result<Quotation, Box<dyn error::Error + Sync + Send>>;
+
+	/// Name of the upstream `get_quotation` queries for the given asset
+	fn quotation_source(&self, asset: &Asset) -> &'static str {
+		quotation_source(asset)
+	}
+}
+
+/// Lets the update loop and other tasks share an api
+#[async_trait]
+impl<T> DiaApi for Arc<T>
+where
+	T: DiaApi + Send + Sync,
+{
+	async fn get_quotable_assets(
+		&self,
+	) -> Result<Vec<QuotedAsset>, Box<dyn error::Error + Send + Sync>> {
+		self.as_ref().get_quotable_assets().await
+	}
+
+	async fn get_quotation(
+		&self,
+		quoted_asset: &QuotedAsset,
+	) -> Result<Quotation, Box<dyn error::Error + Sync + Send>> {
+		self.as_ref().get_quotation(quoted_asset).await
+	}
+
+	fn quotation_source(&self, asset: &Asset) -> &'static str {
+		self.as_ref().quotation_source(asset)
+	}
 }
 pub struct Dia;
 
 // The paths are relative to the directory where your `Cargo.toml` is located.
 // Both json and the GraphQL schema language are supported as sources for the schema
+#[cfg(feature = "custom-ampe")]
 #[derive(GraphQLQuery)]
 #[graphql(
 	schema_path = "resources/ampe_schema.graphql",
@@ -156,6 +195,7 @@ pub struct Dia;
 )]
 pub struct AmpePriceView;
 
+#[cfg(feature = "custom-ampe")]
 impl AmpePriceView {
 	const SYMBOL: &'static str = "AMPE";
 	const BLOCKCHAIN: &'static str = "Amplitude";
@@ -176,7 +216,7 @@ impl AmpePriceView {
 	/// }
 	/// ```
 	/// Returns the value of `eth_price`, which is the price of AMPE.
-	async fn get_price() -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+	async fn get_price() -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
 		let request_body = AmpePriceView::build_query(ampe_price_view::Variables {});
 
 		let client = reqwest::Client::new();
@@ -186,20 +226,276 @@ impl AmpePriceView {
 		let response_data = response_body.data.ok_or("No price found for AMPE")?;
 		let price = response_data.bundle_by_id.eth_price;
 
+		Ok(squid_quotation(Self::SYMBOL, Self::BLOCKCHAIN, Self::URL, price))
+	}
+}
+
+#[cfg(feature = "custom-kpen")]
+#[derive(GraphQLQuery)]
+#[graphql(
+	schema_path = "resources/kpen_schema.graphql",
+	query_path = "resources/kpen_query.graphql",
+	response_derives = "Debug"
+)]
+pub struct KpenPriceView;
+
+#[cfg(feature = "custom-kpen")]
+impl KpenPriceView {
+	const SYMBOL: &'static str = "KPEN";
+	const BLOCKCHAIN: &'static str = "Pendulum";
+	const URL: &'static str = "https://squid.subsquid.io/pendulum-squid/graphql";
+
+	/// Returns the `eth_price` of the bundle of the Pendulum squid, which is the price of KPEN,
+	/// like `AmpePriceView::get_price` does for the Amplitude squid
+	async fn get_price() -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+		let request_body = KpenPriceView::build_query(kpen_price_view::Variables {});
+
+		let client = reqwest::Client::new();
+		let response = client.post(Self::URL).json(&request_body).send().await?;
+		let response_body: Response<kpen_price_view::ResponseData> = response.json().await?;
+
+		let response_data = response_body.data.ok_or("No price found for KPEN")?;
+		let price = response_data.bundle_by_id.eth_price;
+
+		Ok(squid_quotation(Self::SYMBOL, Self::BLOCKCHAIN, Self::URL, price))
+	}
+}
+
+/// ### Binance 24h Ticker
+///
+/// `GET : https://api.binance.com/api/v3/ticker/24hr?symbol=USDTVES`
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"symbol": "USDTVES",
+/// 	"lastPrice": "36.52000000",
+/// 	"volume": "184203.10000000",
+/// 	"closeTime": 1700474693000,
+/// 	...
+/// }
+/// ```
+#[cfg(feature = "custom-ves")]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker {
+	last_price: Decimal,
+	volume: Decimal,
+	close_time: i64,
+}
+
+/// Parallel rate of the Venezuelan bolívar, which is what VES trades at while the official rate
+/// YahooFinance quotes is irrelevant in practice. Inverted from Binance's USDT/VES pair, taking
+/// USDT at par with USD.
+#[cfg(feature = "custom-ves")]
+pub struct VesParallelPriceView;
+
+#[cfg(feature = "custom-ves")]
+impl VesParallelPriceView {
+	const CURRENCY: &'static str = "VES";
+	const SYMBOL: &'static str = "VES-USD";
+	const SOURCE: &'static str = "Binance";
+	const URL: &'static str = "https://api.binance.com/api/v3/ticker/24hr?symbol=USDTVES";
+
+	async fn get_price() -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+		let ticker: BinanceTicker =
+			reqwest::get(Self::URL).await?.error_for_status()?.json().await?;
+		let price = Decimal::new(1, 0)
+			.checked_div(ticker.last_price)
+			.ok_or("Binance quoted a zero USDT/VES price")?;
+		let time = Utc
+			.timestamp_millis_opt(ticker.close_time)
+			.single()
+			.ok_or_else(|| format!("Invalid Binance close time '{}'", ticker.close_time))?;
+
+		Ok(Quotation {
+			symbol: Self::SYMBOL.to_string(),
+			name: Self::SYMBOL.to_string(),
+			address: None,
+			blockchain: None,
+			price,
+			price_yesterday: Default::default(),
+			// The volume is traded in USDT
+			volume_yesterday: ticker.volume,
+			time,
+			source: Self::SOURCE.to_string(),
+		})
+	}
+}
+
+/// Price of a coin as CoinGecko's simple price endpoint returns it, keyed by the CoinGecko id:
+/// ```ignore
+/// {
+/// 	"bifrost-voucher-astr": {
+/// 		"usd": 0.0712,
+/// 		"usd_24h_vol": 10431.27,
+/// 		"last_updated_at": 1700474693
+/// 	}
+/// }
+/// ```
+#[cfg(feature = "custom-vastr")]
+#[derive(Deserialize, Debug)]
+struct CoinGeckoPrice {
+	usd: Decimal,
+	#[serde(default)]
+	usd_24h_vol: Decimal,
+	last_updated_at: i64,
+}
+
+/// Price of Bifrost's voucher ASTR, which neither DIA nor the exchange sources list
+#[cfg(feature = "custom-vastr")]
+pub struct VastrPriceView;
+
+#[cfg(feature = "custom-vastr")]
+impl VastrPriceView {
+	const SYMBOL: &'static str = "vASTR";
+	const BLOCKCHAIN: &'static str = "Bifrost";
+	const COINGECKO_ID: &'static str = "bifrost-voucher-astr";
+	const SOURCE: &'static str = "CoinGecko";
+	const URL: &'static str = "https://api.coingecko.com/api/v3/simple/price?ids=bifrost-voucher-astr&vs_currencies=usd&include_24hr_vol=true&include_last_updated_at=true";
+
+	async fn get_price() -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+		let mut prices: std::collections::HashMap<String, CoinGeckoPrice> =
+			reqwest::get(Self::URL).await?.error_for_status()?.json().await?;
+		let price = prices.remove(Self::COINGECKO_ID).ok_or("No price found for vASTR")?;
+		let time = Utc
+			.timestamp_opt(price.last_updated_at, 0)
+			.single()
+			.ok_or_else(|| format!("Invalid CoinGecko update time '{}'", price.last_updated_at))?;
+
 		Ok(Quotation {
 			symbol: Self::SYMBOL.to_string(),
 			name: Self::BLOCKCHAIN.to_string(),
 			address: None,
 			blockchain: Some(Self::BLOCKCHAIN.to_string()),
-			price,
+			price: price.usd,
 			price_yesterday: Default::default(),
-			volume_yesterday: Default::default(),
-			time: Utc::now(),
-			source: Self::URL.to_string(),
+			volume_yesterday: price.usd_24h_vol,
+			time,
+			source: Self::SOURCE.to_string(),
 		})
 	}
 }
 
+/// Quotation of a price read from the squid at `url`, as recent as the response
+#[cfg(any(feature = "custom-ampe", feature = "custom-kpen"))]
+fn squid_quotation(symbol: &str, blockchain: &str, url: &str, price: Decimal) -> Quotation {
+	Quotation {
+		symbol: symbol.to_string(),
+		name: blockchain.to_string(),
+		address: None,
+		blockchain: Some(blockchain.to_string()),
+		price,
+		price_yesterday: Default::default(),
+		volume_yesterday: Default::default(),
+		time: Utc::now(),
+		source: url.to_string(),
+	}
+}
+
+/// Symbol of a fiat pair as the foreign quotation endpoint expects it, `{base}` and `{quote}` are
+/// replaced by the parts of our `{base}-{quote}` symbol
+const FOREIGN_QUOTATION_SYMBOL_TEMPLATE: &str = "{base}-{quote}";
+
+/// Builds the foreign quotation symbol of a fiat asset like "MXN-USD" from
+/// `FOREIGN_QUOTATION_SYMBOL_TEMPLATE`, symbols that aren't a pair are passed as they are
+pub fn foreign_quotation_symbol(symbol: &str) -> String {
+	let symbol = symbol.to_uppercase();
+	match symbol.split_once('-') {
+		Some((base, quote)) => FOREIGN_QUOTATION_SYMBOL_TEMPLATE
+			.replace("{base}", base)
+			.replace("{quote}", quote),
+		None => symbol,
+	}
+}
+
+/// Quotation of a `{base}-{quote}` fiat pair crossed from the USD quotations of both currencies,
+/// as old as the older of them
+pub fn cross_quotation(
+	base: &str,
+	quote: &str,
+	base_usd: &Quotation,
+	quote_usd: &Quotation,
+) -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+	let symbol = format!("{}-{}", base, quote);
+	let price = base_usd
+		.price
+		.checked_div(quote_usd.price)
+		.ok_or_else(|| format!("Can't cross {} from a zero {}-USD price", symbol, quote))?;
+	let price_yesterday = base_usd
+		.price_yesterday
+		.checked_div(quote_usd.price_yesterday)
+		.unwrap_or_default();
+	Ok(Quotation {
+		symbol: symbol.clone(),
+		name: symbol,
+		address: None,
+		blockchain: None,
+		price,
+		price_yesterday,
+		volume_yesterday: Decimal::new(0, 0),
+		time: base_usd.time.min(quote_usd.time),
+		source: "YahooFinance".to_string(),
+	})
+}
+
+/// Age in seconds up to which quotations of a source, by `Quotation::source`, are current. Exchange
+/// tickers quote the last trade, CoinMarketCap refreshes every minute, CoinGecko and DIA aggregate
+/// every few minutes, YahooFinance doesn't quote over weekends and holidays and exchangerate.host
+/// quotes the reference rates of working days. Quotations of other sources are never delayed.
+pub fn max_quote_age(source: &str) -> Option<u64> {
+	match source {
+		"Coinbase" | "Bitstamp" | "Kraken" | "Binance" | "CoinMarketCap" | "CoinGecko" =>
+			Some(15 * 60),
+		"diadata.org" => Some(60 * 60),
+		"YahooFinance" | "exchangerate.host" => Some(4 * 24 * 60 * 60),
+		_ => None,
+	}
+}
+
+/// Returns the name of the upstream `Dia::get_quotation` queries for the given asset
+pub fn quotation_source(asset: &Asset) -> &'static str {
+	match asset.blockchain.to_uppercase().as_str() {
+		#[cfg(feature = "custom-ves")]
+		"FIAT" if asset.symbol.to_uppercase() == VesParallelPriceView::SYMBOL =>
+			VesParallelPriceView::SOURCE,
+		"FIAT" => "YahooFinance",
+		#[cfg(feature = "custom-ampe")]
+		"AMPLITUDE" if asset.symbol.to_uppercase() == AmpePriceView::SYMBOL => AmpePriceView::URL,
+		#[cfg(feature = "custom-kpen")]
+		"PENDULUM" if asset.symbol.to_uppercase() == KpenPriceView::SYMBOL => KpenPriceView::URL,
+		#[cfg(feature = "custom-vastr")]
+		"BIFROST" if asset.symbol.eq_ignore_ascii_case(VastrPriceView::SYMBOL) => VastrPriceView::SOURCE,
+		_ => "diadata.org",
+	}
+}
+
+impl Dia {
+	/// Quotation of a fiat currency against USD
+	async fn get_fiat_usd_quotation(
+		currency: &str,
+	) -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+		if currency == "USD" {
+			return Ok(Quotation::get_default_fiat_usd_quotation())
+		}
+		#[cfg(feature = "custom-ves")]
+		if currency == VesParallelPriceView::CURRENCY {
+			return VesParallelPriceView::get_price().await
+		}
+		Self::get_foreign_quotation(&format!("{}-USD", currency)).await
+	}
+
+	/// Quotation of a fiat pair like "USD-BRL" as the foreign quotation endpoint quotes it, without
+	/// crossing it via USD
+	pub async fn get_foreign_quotation(
+		symbol: &str,
+	) -> Result<Quotation, Box<dyn error::Error + Send + Sync>> {
+		let fiat_symbol = foreign_quotation_symbol(symbol);
+		let r = reqwest::get(&format!("{}/{}", FOREIGN_QUOTATION_ENDPOINT, fiat_symbol)).await?;
+		Ok(r.json().await?)
+	}
+}
+
 #[async_trait]
 impl DiaApi for Dia {
 	async fn get_quotation(
@@ -210,17 +506,33 @@ impl DiaApi for Dia {
 
 		let r = match asset.blockchain.to_uppercase().as_str() {
 			"FIAT" => {
-				if asset.symbol.to_uppercase() == "USD-USD" {
-					return Ok(Quotation::get_default_fiat_usd_quotation());
-				} else {
-					// The fiat symbol should be of form `{base}-{target}` (e.g. "MXN-USD") for the API to work
-					let fiat_symbol = asset.symbol.to_uppercase();
-					reqwest::get(&format!("{}/{}", FOREIGN_QUOTATION_ENDPOINT, fiat_symbol)).await?
+				let symbol = asset.symbol.to_uppercase();
+				match symbol.split_once('-') {
+					_ if symbol == "USD-USD" =>
+						return Ok(Quotation::get_default_fiat_usd_quotation()),
+					// Only pairs against USD are quoted, others are crossed via USD
+					Some((base, quote)) if quote != "USD" => {
+						let base_usd = Self::get_fiat_usd_quotation(base).await?;
+						let quote_usd = Self::get_fiat_usd_quotation(quote).await?;
+						return cross_quotation(base, quote, &base_usd, &quote_usd)
+					},
+					// Goes through the custom views of currencies like VES
+					Some((base, _)) => return Self::get_fiat_usd_quotation(base).await,
+					None => return Self::get_foreign_quotation(&asset.symbol).await,
 				}
 			},
+			#[cfg(feature = "custom-ampe")]
 			"AMPLITUDE" if asset.symbol.to_uppercase() == AmpePriceView::SYMBOL => {
 				return AmpePriceView::get_price().await
 			},
+			#[cfg(feature = "custom-kpen")]
+			"PENDULUM" if asset.symbol.to_uppercase() == KpenPriceView::SYMBOL => {
+				return KpenPriceView::get_price().await
+			},
+			#[cfg(feature = "custom-vastr")]
+			"BIFROST" if asset.symbol.eq_ignore_ascii_case(VastrPriceView::SYMBOL) => {
+				return VastrPriceView::get_price().await
+			},
 			_ => {
 				reqwest::get(&format!(
 					"{}/{}/{}",
@@ -251,9 +563,20 @@ impl DiaApi for Dia {
 
 #[cfg(test)]
 mod tests {
-	use crate::dia::{AmpePriceView, Asset, Dia, DiaApi, QuotedAsset};
+	#[cfg(feature = "custom-ampe")]
+	use crate::dia::AmpePriceView;
+	#[cfg(feature = "custom-kpen")]
+	use crate::dia::KpenPriceView;
+	#[cfg(feature = "custom-vastr")]
+	use crate::dia::VastrPriceView;
+	#[cfg(feature = "custom-ves")]
+	use crate::dia::VesParallelPriceView;
+	use crate::dia::{
+		cross_quotation, foreign_quotation_symbol, Asset, Dia, DiaApi, Quotation, QuotedAsset,
+	};
 	use rust_decimal::Decimal;
 
+	#[cfg(feature = "custom-ampe")]
 	#[tokio::test]
 	async fn test_ampe_price() {
 		let quoted_asset = QuotedAsset {
@@ -273,6 +596,67 @@ mod tests {
 		assert!(price.price < Decimal::new(1, 0));
 	}
 
+	#[cfg(feature = "custom-kpen")]
+	#[tokio::test]
+	async fn test_kpen_price() {
+		let quoted_asset = QuotedAsset {
+			asset: Asset {
+				symbol: KpenPriceView::SYMBOL.to_string(),
+				name: "".to_string(),
+				address: "".to_string(),
+				decimals: 0,
+				blockchain: KpenPriceView::BLOCKCHAIN.to_string(),
+			},
+			volume: 0.0,
+		};
+		let price = Dia.get_quotation(&quoted_asset).await.expect("should return a quotation");
+
+		assert_eq!(price.symbol, quoted_asset.asset.symbol);
+		assert_eq!(price.blockchain.expect("should return kpen"), quoted_asset.asset.blockchain);
+		assert!(price.price > Decimal::new(0, 0));
+	}
+
+	#[cfg(feature = "custom-ves")]
+	#[tokio::test]
+	async fn test_ves_parallel_price() {
+		let quoted_asset = QuotedAsset {
+			asset: Asset {
+				symbol: VesParallelPriceView::SYMBOL.to_string(),
+				name: "".to_string(),
+				address: "".to_string(),
+				decimals: 0,
+				blockchain: "FIAT".to_string(),
+			},
+			volume: 0.0,
+		};
+		let price = Dia.get_quotation(&quoted_asset).await.expect("should return a quotation");
+
+		assert_eq!(price.symbol, VesParallelPriceView::SYMBOL);
+		assert_eq!(price.source, "Binance");
+		assert!(price.price > Decimal::new(0, 0) && price.price < Decimal::new(1, 0));
+	}
+
+	#[cfg(feature = "custom-vastr")]
+	#[tokio::test]
+	async fn test_vastr_price() {
+		let quoted_asset = QuotedAsset {
+			asset: Asset {
+				symbol: VastrPriceView::SYMBOL.to_string(),
+				name: "".to_string(),
+				address: "".to_string(),
+				decimals: 0,
+				blockchain: VastrPriceView::BLOCKCHAIN.to_string(),
+			},
+			volume: 0.0,
+		};
+		let price = Dia.get_quotation(&quoted_asset).await.expect("should return a quotation");
+
+		assert_eq!(price.symbol, quoted_asset.asset.symbol);
+		assert_eq!(price.blockchain.expect("should return vastr"), quoted_asset.asset.blockchain);
+		assert_eq!(price.source, "CoinGecko");
+		assert!(price.price > Decimal::new(0, 0));
+	}
+
 	#[tokio::test]
 	async fn test_fiat_price() {
 		let quoted_asset = QuotedAsset {
@@ -290,4 +674,43 @@ mod tests {
 		assert_eq!(price.symbol, quoted_asset.asset.symbol);
 		assert_eq!(price.price, Decimal::new(1, 0));
 	}
+
+	#[test]
+	fn test_delayed_quotation() {
+		let now = chrono::Utc::now();
+		let quotation = |source: &str, age: i64| Quotation {
+			source: source.to_string(),
+			time: now - chrono::Duration::seconds(age),
+			..Default::default()
+		};
+
+		assert!(!quotation("Coinbase", 60).is_delayed(now));
+		assert!(quotation("Coinbase", 16 * 60).is_delayed(now));
+		assert!(!quotation("YahooFinance", 2 * 24 * 60 * 60).is_delayed(now));
+		assert!(!quotation("https://squid.subsquid.io/amplitude-squid/graphql", 1 << 30)
+			.is_delayed(now));
+	}
+
+	#[test]
+	fn test_cross_quotation() {
+		let eur_usd = Quotation { price: Decimal::new(108, 2), ..Default::default() };
+		let brl_usd = Quotation { price: Decimal::new(20, 2), ..Default::default() };
+
+		let eur_brl = cross_quotation("EUR", "BRL", &eur_usd, &brl_usd).unwrap();
+		assert_eq!(eur_brl.symbol, "EUR-BRL");
+		assert_eq!(eur_brl.price, Decimal::new(54, 1));
+		assert_eq!(eur_brl.time, eur_usd.time.min(brl_usd.time));
+
+		let usd_brl =
+			cross_quotation("USD", "BRL", &Quotation::get_default_fiat_usd_quotation(), &brl_usd)
+				.unwrap();
+		assert_eq!(usd_brl.price, Decimal::new(5, 0));
+		assert!(cross_quotation("EUR", "XYZ", &eur_usd, &Quotation::default()).is_err());
+	}
+
+	#[test]
+	fn test_foreign_quotation_symbol() {
+		assert_eq!(foreign_quotation_symbol("mxn-usd"), "MXN-USD");
+		assert_eq!(foreign_quotation_symbol("USD"), "USD");
+	}
 }
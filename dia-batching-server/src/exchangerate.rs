@@ -0,0 +1,114 @@
+use crate::dia::{Asset, Quotation};
+use chrono::{NaiveDate, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// ### exchangerate.host Latest Rates
+///
+/// `GET : https://api.exchangerate.host/latest?base=:base&symbols=:quote`
+///
+/// Daily reference rates of the European Central Bank, published on working days.
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"success": true,
+/// 	"base": "MXN",
+/// 	"date": "2023-11-20",
+/// 	"rates": { "USD": 0.058252 }
+/// }
+/// ```
+const LATEST_ENDPOINT: &str = "https://api.exchangerate.host/latest";
+
+pub const SOURCE: &str = "exchangerate.host";
+
+#[derive(Deserialize, Debug)]
+struct LatestRates {
+	#[serde(default)]
+	success: bool,
+	date: Option<NaiveDate>,
+	#[serde(default)]
+	rates: HashMap<String, Decimal>,
+}
+
+/// Quotation of a `{base}-{quote}` fiat pair from the reference rates of the day, a fallback for
+/// fiat pairs whose source fails
+pub async fn get_quotation(asset: &Asset) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+	let symbol = asset.symbol.to_uppercase();
+	let (base, quote) = symbol
+		.split_once('-')
+		.ok_or_else(|| format!("{} isn't a fiat pair like MXN-USD", asset.symbol))?;
+	let rates: LatestRates = reqwest::Client::new()
+		.get(LATEST_ENDPOINT)
+		.query(&[("base", base), ("symbols", quote)])
+		.send()
+		.await?
+		.error_for_status()?
+		.json()
+		.await?;
+	quotation(asset, quote, rates)
+}
+
+fn quotation(
+	asset: &Asset,
+	quote: &str,
+	rates: LatestRates,
+) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+	if !rates.success {
+		return Err(format!("{} failed to quote {}", SOURCE, asset.symbol).into())
+	}
+	let price = *rates
+		.rates
+		.get(quote)
+		.ok_or_else(|| format!("{} doesn't quote {}", SOURCE, asset.symbol))?;
+	let time = rates
+		.date
+		.and_then(|date| date.and_hms_opt(0, 0, 0))
+		.map(|midnight| Utc.from_utc_datetime(&midnight))
+		.ok_or_else(|| format!("{} quoted {} without a date", SOURCE, asset.symbol))?;
+	Ok(Quotation {
+		symbol: asset.symbol.clone(),
+		name: asset.symbol.clone(),
+		address: None,
+		blockchain: Some(asset.blockchain.clone()),
+		price,
+		price_yesterday: Default::default(),
+		volume_yesterday: Default::default(),
+		time,
+		source: SOURCE.to_string(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mxn_usd() -> Asset {
+		Asset {
+			symbol: "MXN-USD".into(),
+			name: "".into(),
+			address: "".into(),
+			decimals: 0,
+			blockchain: "FIAT".into(),
+		}
+	}
+
+	#[test]
+	fn test_quotation() {
+		let rates: LatestRates = serde_json::from_str(
+			r#"{"success":true,"base":"MXN","date":"2023-11-20","rates":{"USD":0.058252}}"#,
+		)
+		.unwrap();
+		let mxn = quotation(&mxn_usd(), "USD", rates).unwrap();
+
+		assert_eq!(mxn.price, Decimal::new(58252, 6));
+		assert_eq!(mxn.time.timestamp(), 1700438400);
+		assert_eq!(mxn.source, "exchangerate.host");
+
+		let failed: LatestRates =
+			serde_json::from_str(r#"{"success":false,"error":{"code":202}}"#).unwrap();
+		assert!(quotation(&mxn_usd(), "USD", failed).is_err());
+	}
+}
@@ -0,0 +1,957 @@
+use crate::coinmarketcap::{self, CoinMarketCap};
+use crate::dia::{quotation_source, Asset, DiaApi, Quotation, QuotedAsset};
+use crate::exchangerate;
+use crate::quarantine::Quarantine;
+use crate::{parse_asset_map, AssetSpecifier};
+use async_trait::async_trait;
+#[cfg(feature = "bitstamp")]
+use chrono::TimeZone;
+#[cfg(any(feature = "coinbase", feature = "bitstamp", feature = "kraken"))]
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+#[cfg(any(feature = "coinbase", feature = "bitstamp", feature = "kraken"))]
+use rust_decimal::Decimal;
+#[cfg(any(feature = "coinbase", feature = "bitstamp", feature = "kraken"))]
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// ### Coinbase Exchange Ticker
+///
+/// `GET : https://api.exchange.coinbase.com/products/:base-USD/ticker`
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"trade_id": 578597550,
+/// 	"price": "37329.98",
+/// 	"size": "0.00090436",
+/// 	"time": "2023-11-20T10:04:52.396752Z",
+/// 	"bid": "37329.97",
+/// 	"ask": "37329.98",
+/// 	"volume": "8807.16655896"
+/// }
+/// ```
+#[cfg(feature = "coinbase")]
+const COINBASE_TICKER_ENDPOINT: &str = "https://api.exchange.coinbase.com/products";
+
+#[cfg(feature = "coinbase")]
+#[derive(Deserialize, Debug)]
+struct CoinbaseTicker {
+	price: Decimal,
+	volume: Decimal,
+	time: DateTime<Utc>,
+}
+
+/// ### Bitstamp Ticker
+///
+/// `GET : https://www.bitstamp.net/api/v2/ticker/:base+usd/`
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"timestamp": "1700474693",
+/// 	"open": "37380",
+/// 	"high": "37516",
+/// 	"low": "37205",
+/// 	"last": "37338",
+/// 	"volume": "300.35092290",
+/// 	"vwap": "37357"
+/// }
+/// ```
+#[cfg(feature = "bitstamp")]
+const BITSTAMP_TICKER_ENDPOINT: &str = "https://www.bitstamp.net/api/v2/ticker";
+
+#[cfg(feature = "bitstamp")]
+#[derive(Deserialize, Debug)]
+struct BitstampTicker {
+	last: Decimal,
+	volume: Decimal,
+	timestamp: String,
+}
+
+/// ### Kraken Ticker
+///
+/// `GET : https://api.kraken.com/0/public/Ticker?pair=:baseUSD`
+///
+/// Response:
+/// ```ignore
+/// {
+/// 	"error": [],
+/// 	"result": {
+/// 		"XXBTZUSD": {
+/// 			"a": ["37338.10000", "1", "1.000"],
+/// 			"b": ["37338.00000", "2", "2.000"],
+/// 			"c": ["37338.10000", "0.00107766"],
+/// 			"v": ["1163.12451180", "3049.67585374"],
+/// 			"p": ["37302.54402", "37294.47521"]
+/// 		}
+/// 	}
+/// }
+/// ```
+#[cfg(feature = "kraken")]
+const KRAKEN_TICKER_ENDPOINT: &str = "https://api.kraken.com/0/public/Ticker";
+
+#[cfg(feature = "kraken")]
+#[derive(Deserialize, Debug)]
+struct KrakenResponse {
+	error: Vec<String>,
+	#[serde(default)]
+	result: HashMap<String, KrakenTicker>,
+}
+
+/// Last trade price and lot volume in `c`, today's and the last 24 hours' volume in `v`
+#[cfg(feature = "kraken")]
+#[derive(Deserialize, Debug)]
+struct KrakenTicker {
+	c: (Decimal, Decimal),
+	v: (Decimal, Decimal),
+}
+
+/// Kraken names bitcoin XBT
+#[cfg(feature = "kraken")]
+fn kraken_base(symbol: &str) -> String {
+	match symbol.to_uppercase().as_str() {
+		"BTC" => "XBT".to_string(),
+		base => base.to_string(),
+	}
+}
+
+/// Regulated spot venues quoting crypto assets against USD, an alternative to DIA for
+/// deployments that need prices from regulated venues only. Each exchange is compiled in with the
+/// cargo feature of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Exchange {
+	#[cfg(feature = "coinbase")]
+	Coinbase,
+	#[cfg(feature = "bitstamp")]
+	Bitstamp,
+	#[cfg(feature = "kraken")]
+	Kraken,
+}
+
+impl FromStr for Exchange {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let name = s.trim().to_lowercase();
+		Exchange::ENABLED
+			.iter()
+			.copied()
+			.find(|exchange| exchange.name().to_lowercase() == name)
+			.ok_or_else(|| {
+				let enabled: Vec<String> = Exchange::ENABLED
+					.iter()
+					.map(|exchange| exchange.name().to_lowercase())
+					.collect();
+				format!(
+					"Invalid exchange '{}', expected one of the exchanges compiled in: {}",
+					name,
+					enabled.join(", ")
+				)
+			})
+	}
+}
+
+impl Exchange {
+	/// Exchanges enabled by cargo features
+	pub const ENABLED: &'static [Exchange] = &[
+		#[cfg(feature = "coinbase")]
+		Exchange::Coinbase,
+		#[cfg(feature = "bitstamp")]
+		Exchange::Bitstamp,
+		#[cfg(feature = "kraken")]
+		Exchange::Kraken,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			#[cfg(feature = "coinbase")]
+			Exchange::Coinbase => "Coinbase",
+			#[cfg(feature = "bitstamp")]
+			Exchange::Bitstamp => "Bitstamp",
+			#[cfg(feature = "kraken")]
+			Exchange::Kraken => "Kraken",
+		}
+	}
+
+	#[cfg_attr(
+		not(any(feature = "coinbase", feature = "bitstamp", feature = "kraken")),
+		allow(unused_variables)
+	)]
+	pub async fn get_quotation(
+		&self,
+		asset: &Asset,
+	) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+		match *self {
+			#[cfg(feature = "coinbase")]
+			Exchange::Coinbase => {
+				let base = asset.symbol.to_uppercase();
+				let url = format!("{}/{}-USD/ticker", COINBASE_TICKER_ENDPOINT, base);
+				// Coinbase rejects requests without a user agent
+				let ticker: CoinbaseTicker = reqwest::Client::new()
+					.get(&url)
+					.header(reqwest::header::USER_AGENT, "dia-batching-server")
+					.send()
+					.await?
+					.error_for_status()?
+					.json()
+					.await?;
+				Ok(self.quotation(asset, ticker.price, ticker.volume, ticker.time))
+			},
+			#[cfg(feature = "bitstamp")]
+			Exchange::Bitstamp => {
+				let base = asset.symbol.to_lowercase();
+				let url = format!("{}/{}usd/", BITSTAMP_TICKER_ENDPOINT, base);
+				let ticker: BitstampTicker =
+					reqwest::get(&url).await?.error_for_status()?.json().await?;
+				let time = Utc
+					.timestamp_opt(ticker.timestamp.parse()?, 0)
+					.single()
+					.ok_or_else(|| format!("Invalid Bitstamp timestamp '{}'", ticker.timestamp))?;
+				Ok(self.quotation(asset, ticker.last, ticker.volume, time))
+			},
+			#[cfg(feature = "kraken")]
+			Exchange::Kraken => {
+				let url =
+					format!("{}?pair={}USD", KRAKEN_TICKER_ENDPOINT, kraken_base(&asset.symbol));
+				let response: KrakenResponse =
+					reqwest::get(&url).await?.error_for_status()?.json().await?;
+				if !response.error.is_empty() {
+					return Err(format!("Kraken failed: {}", response.error.join(", ")).into())
+				}
+				// The ticker is keyed by Kraken's name of the pair, the only one requested
+				let ticker = response
+					.result
+					.into_values()
+					.next()
+					.ok_or_else(|| format!("Kraken doesn't list {}", asset.symbol))?;
+				// The ticker carries no time, it is as recent as the response
+				Ok(self.quotation(asset, ticker.c.0, ticker.v.1, Utc::now()))
+			},
+		}
+	}
+
+	/// Builds the quotation of `asset` from a ticker. `volume` is the 24h volume in units of the
+	/// asset, which is converted to USD like DIA reports it.
+	#[cfg(any(feature = "coinbase", feature = "bitstamp", feature = "kraken"))]
+	fn quotation(
+		&self,
+		asset: &Asset,
+		price: Decimal,
+		volume: Decimal,
+		time: DateTime<Utc>,
+	) -> Quotation {
+		Quotation {
+			symbol: asset.symbol.clone(),
+			name: asset.name.clone(),
+			address: None,
+			blockchain: Some(asset.blockchain.clone()),
+			price,
+			price_yesterday: Default::default(),
+			volume_yesterday: volume * price,
+			time,
+			source: self.name().to_string(),
+		}
+	}
+}
+
+/// Exchanges individual assets are quoted by, parsed from `<blockchain>:<symbol>=<exchange>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExchangeSources(pub HashMap<AssetSpecifier, Exchange>);
+
+impl FromStr for ExchangeSources {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(ExchangeSources)
+	}
+}
+
+/// Source quoting an asset: `dia` for the `api` of the router, which quotes assets without an
+/// exchange, `coinmarketcap`, `exchangerate` for fiat pairs or one of the exchanges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+	Dia,
+	CoinMarketCap,
+	ExchangeRate,
+	Exchange(Exchange),
+}
+
+impl FromStr for Source {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.trim().eq_ignore_ascii_case("dia") {
+			return Ok(Source::Dia)
+		}
+		if s.trim().eq_ignore_ascii_case("coinmarketcap") {
+			return Ok(Source::CoinMarketCap)
+		}
+		if s.trim().eq_ignore_ascii_case("exchangerate") {
+			return Ok(Source::ExchangeRate)
+		}
+		s.parse().map(Source::Exchange)
+	}
+}
+
+/// Sources of one asset tried in order, joined by `+`, e.g. `coinbase+bitstamp`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackChain(pub Vec<Source>);
+
+impl FromStr for FallbackChain {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split('+').map(str::parse).collect::<Result<_, _>>().map(FallbackChain)
+	}
+}
+
+/// Sources individual assets fall back to if their source fails or quotes a zero price, parsed
+/// from `<blockchain>:<symbol>=<sources>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FallbackSources(pub HashMap<AssetSpecifier, FallbackChain>);
+
+impl FromStr for FallbackSources {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(FallbackSources)
+	}
+}
+
+/// Sources of all assets of individual blockchains, the first quoting them and the others as their
+/// fallbacks, parsed from `<blockchain>=<sources>,...`, e.g. `FIAT=dia+coinbase`. The exchange and
+/// the fallback sources of an asset take precedence over the sources of its blockchain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockchainSources(pub HashMap<String, FallbackChain>);
+
+impl FromStr for BlockchainSources {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.filter(|entry| !entry.trim().is_empty())
+			.map(|entry| {
+				let (blockchain, sources) = entry.split_once('=').ok_or_else(|| {
+					format!("Invalid entry '{}', expected <blockchain>=<sources>", entry)
+				})?;
+				Ok((blockchain.trim().to_string(), sources.parse()?))
+			})
+			.collect::<Result<_, _>>()
+			.map(BlockchainSources)
+	}
+}
+
+/// Timeouts of requests to individual sources in milliseconds, parsed from
+/// `<source>=<milliseconds>,...` with `dia` or an exchange
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceTimeouts(pub HashMap<Source, u64>);
+
+impl FromStr for SourceTimeouts {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.filter(|entry| !entry.trim().is_empty())
+			.map(|entry| {
+				let (source, timeout) = entry.split_once('=').ok_or_else(|| {
+					format!("Invalid entry '{}', expected <source>=<milliseconds>", entry)
+				})?;
+				let timeout = timeout
+					.trim()
+					.parse()
+					.map_err(|_| format!("Invalid timeout '{}' of source {}", timeout, source))?;
+				Ok((source.parse()?, timeout))
+			})
+			.collect::<Result<_, _>>()
+			.map(SourceTimeouts)
+	}
+}
+
+/// A source didn't answer within its timeout
+#[derive(Debug)]
+pub struct SourceTimeout(pub Duration);
+
+impl Display for SourceTimeout {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "No response within {}ms", self.0.as_millis())
+	}
+}
+
+impl Error for SourceTimeout {}
+
+/// Longest wait before a retry, not counting the jitter
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Retries of failed requests to a source. The first retry waits `backoff`, every further retry
+/// twice as long as the one before, each plus up to the same again as jitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	pub retries: u32,
+	pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self { retries: 0, backoff: Duration::from_millis(0) }
+	}
+}
+
+impl RetryPolicy {
+	/// Delay before the `retry`th retry, counted from 1, with `jitter` between 0 and 1
+	fn delay(&self, retry: u32, jitter: f64) -> Duration {
+		let delay = self
+			.backoff
+			.checked_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+			.map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+		delay + delay.mul_f64(jitter.clamp(0.0, 1.0))
+	}
+}
+
+/// Whether a failed request may succeed if it's sent again. Client errors other than rate limits
+/// are answered the same way every time.
+fn is_retryable(err: &(dyn Error + Send + Sync + 'static)) -> bool {
+	match err.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+		Some(status) =>
+			!status.is_client_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+		None => true,
+	}
+}
+
+/// Jitter between 0 and 1 taken from the sub-second part of the current time
+fn jitter() -> f64 {
+	f64::from(chrono::Utc::now().timestamp_subsec_nanos() % 1_000_000) / 1_000_000.0
+}
+
+/// Quotes the assets configured in `ExchangeSources` by their exchange, the assets of blockchains
+/// configured in `BlockchainSources` by the sources of their blockchain and all others by `api`
+pub struct ExchangeRouter<T> {
+	api: T,
+	exchanges: ExchangeSources,
+	fallbacks: FallbackSources,
+	blockchains: BlockchainSources,
+	quarantine: Option<Arc<Quarantine>>,
+	coinmarketcap: Option<CoinMarketCap>,
+	timeouts: SourceTimeouts,
+	default_timeout: Option<Duration>,
+	retries: RetryPolicy,
+}
+
+impl<T> ExchangeRouter<T> {
+	pub fn new(api: T, exchanges: ExchangeSources) -> Self {
+		Self {
+			api,
+			exchanges,
+			fallbacks: FallbackSources::default(),
+			blockchains: BlockchainSources::default(),
+			quarantine: None,
+			coinmarketcap: None,
+			timeouts: SourceTimeouts::default(),
+			default_timeout: None,
+			retries: RetryPolicy::default(),
+		}
+	}
+
+	pub fn with_fallback_sources(self, fallbacks: FallbackSources) -> Self {
+		Self { fallbacks, ..self }
+	}
+
+	pub fn with_blockchain_sources(self, blockchains: BlockchainSources) -> Self {
+		Self { blockchains, ..self }
+	}
+
+	/// Skips the sources quarantined for an asset while it has others
+	pub fn with_quarantine(self, quarantine: Arc<Quarantine>) -> Self {
+		Self { quarantine: Some(quarantine), ..self }
+	}
+
+	/// Quotes the assets routed to `coinmarketcap` with the client, which fail without it
+	pub fn with_coinmarketcap(self, coinmarketcap: CoinMarketCap) -> Self {
+		Self { coinmarketcap: Some(coinmarketcap), ..self }
+	}
+
+	/// Gives every request to a source at most its timeout, `default_timeout` for sources without
+	/// one
+	pub fn with_source_timeouts(self, default_timeout: Duration, timeouts: SourceTimeouts) -> Self {
+		Self { default_timeout: Some(default_timeout), timeouts, ..self }
+	}
+
+	pub fn with_retry_policy(self, retries: RetryPolicy) -> Self {
+		Self { retries, ..self }
+	}
+
+	fn timeout(&self, source: Source) -> Option<Duration> {
+		self.timeouts
+			.0
+			.get(&source)
+			.copied()
+			.map(Duration::from_millis)
+			.or(self.default_timeout)
+	}
+
+	/// Sends `request` to `source` until it succeeds, fails with an error that isn't retryable or
+	/// all retries are used up
+	async fn with_retries<R, F, Fut>(
+		&self,
+		source: Source,
+		request: F,
+	) -> Result<R, Box<dyn Error + Send + Sync>>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<R, Box<dyn Error + Send + Sync>>>,
+	{
+		let timeout = self.timeout(source);
+		let mut retry = 0;
+		loop {
+			let result = match timeout {
+				Some(timeout) => tokio::time::timeout(timeout, request())
+					.await
+					.unwrap_or_else(|_| Err(SourceTimeout(timeout).into())),
+				None => request().await,
+			};
+			match result {
+				Err(err) if retry < self.retries.retries && is_retryable(err.as_ref()) => {
+					retry += 1;
+					let delay = self.retries.delay(retry, jitter());
+					debug!("Retrying {:?} in {}ms after: {}", source, delay.as_millis(), err);
+					tokio::time::delay_for(delay).await;
+				},
+				result => return result,
+			}
+		}
+	}
+
+	fn exchange(&self, asset: &Asset) -> Option<Exchange> {
+		let asset =
+			AssetSpecifier { blockchain: asset.blockchain.clone(), symbol: asset.symbol.clone() };
+		self.exchanges.0.get(&asset).copied()
+	}
+
+	/// The sources configured for the blockchain of the asset
+	fn blockchain_sources(&self, asset: &Asset) -> &[Source] {
+		self.blockchains
+			.0
+			.get(&asset.blockchain)
+			.map(|chain| chain.0.as_slice())
+			.unwrap_or_default()
+	}
+
+	/// The exchange of the asset, else the first source of its blockchain, else `api`
+	fn primary(&self, asset: &Asset) -> Source {
+		match self.exchange(asset) {
+			Some(exchange) => Source::Exchange(exchange),
+			None => self.blockchain_sources(asset).first().copied().unwrap_or(Source::Dia),
+		}
+	}
+
+	/// The source of the asset followed by its fallbacks, each once. Without fallback sources of
+	/// its own an asset falls back to the sources of its blockchain. Fiat pairs fall back to
+	/// `exchangerate` last, so they are priced while their sources are down.
+	fn sources(&self, asset: &Asset) -> Vec<Source> {
+		let mut sources = vec![self.primary(asset)];
+		let specifier =
+			AssetSpecifier { blockchain: asset.blockchain.clone(), symbol: asset.symbol.clone() };
+		let fallbacks = match self.fallbacks.0.get(&specifier) {
+			Some(chain) => chain.0.as_slice(),
+			None => self.blockchain_sources(asset),
+		};
+		for source in fallbacks {
+			if !sources.contains(source) {
+				sources.push(*source);
+			}
+		}
+		let is_fiat = asset.blockchain.eq_ignore_ascii_case("FIAT");
+		if is_fiat && !sources.contains(&Source::ExchangeRate) {
+			sources.push(Source::ExchangeRate);
+		}
+		sources
+	}
+
+	/// The sources of the asset without the ones quarantined for it, all of them if every one is
+	fn routed_sources(&self, asset: &Asset) -> Vec<Source> {
+		let sources = self.sources(asset);
+		let quarantine = match &self.quarantine {
+			Some(quarantine) => quarantine,
+			None => return sources,
+		};
+		let routed: Vec<Source> = sources
+			.iter()
+			.copied()
+			.filter(|source| {
+				let name = self.source_name(*source, asset);
+				!quarantine.is_quarantined(&asset.blockchain, &asset.symbol, name)
+			})
+			.collect();
+		if routed.is_empty() {
+			warn!(
+				"All sources of {}:{} are quarantined, asking them anyway",
+				asset.blockchain, asset.symbol
+			);
+			return sources
+		}
+		routed
+	}
+
+	fn source_name(&self, source: Source, asset: &Asset) -> &'static str {
+		match source {
+			Source::Dia => quotation_source(asset),
+			Source::CoinMarketCap => coinmarketcap::SOURCE,
+			Source::ExchangeRate => exchangerate::SOURCE,
+			Source::Exchange(exchange) => exchange.name(),
+		}
+	}
+}
+
+impl<T> ExchangeRouter<T>
+where
+	T: DiaApi + Send + Sync,
+{
+	async fn get_quotation_from(
+		&self,
+		source: Source,
+		quoted_asset: &QuotedAsset,
+	) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+		match source {
+			Source::Dia => self.with_retries(source, || self.api.get_quotation(quoted_asset)).await,
+			Source::CoinMarketCap => {
+				let coinmarketcap = self.coinmarketcap.as_ref().ok_or(
+					"CoinMarketCap is a source without an API key, give --coinmarketcap-api-key",
+				)?;
+				self.with_retries(source, || coinmarketcap.get_quotation(&quoted_asset.asset))
+					.await
+			},
+			Source::ExchangeRate =>
+				self.with_retries(source, || exchangerate::get_quotation(&quoted_asset.asset))
+					.await,
+			Source::Exchange(exchange) =>
+				self.with_retries(source, || exchange.get_quotation(&quoted_asset.asset)).await,
+		}
+	}
+
+	/// Quotation of the source of the asset named `name`, whether it is quarantined or not
+	pub async fn get_quotation_by(
+		&self,
+		name: &str,
+		quoted_asset: &QuotedAsset,
+	) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+		let asset = &quoted_asset.asset;
+		let source = self
+			.sources(asset)
+			.into_iter()
+			.find(|source| self.source_name(*source, asset).eq_ignore_ascii_case(name))
+			.ok_or_else(|| {
+				format!("{} isn't a source of {}:{}", name, asset.blockchain, asset.symbol)
+			})?;
+		self.get_quotation_from(source, quoted_asset).await
+	}
+}
+
+#[async_trait]
+impl<T> DiaApi for ExchangeRouter<T>
+where
+	T: DiaApi + Send + Sync,
+{
+	/// Assets quotable by `api` plus the assets routed to an exchange `api` doesn't know
+	async fn get_quotable_assets(&self) -> Result<Vec<QuotedAsset>, Box<dyn Error + Send + Sync>> {
+		let mut assets = self.with_retries(Source::Dia, || self.api.get_quotable_assets()).await?;
+		let known: HashSet<AssetSpecifier> = assets
+			.iter()
+			.map(|QuotedAsset { asset, .. }| AssetSpecifier {
+				blockchain: asset.blockchain.clone(),
+				symbol: asset.symbol.clone(),
+			})
+			.collect();
+		let mut missing: Vec<&AssetSpecifier> =
+			self.exchanges.0.keys().filter(|asset| !known.contains(*asset)).collect();
+		missing.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		assets.extend(missing.into_iter().map(|asset| QuotedAsset {
+			asset: Asset {
+				symbol: asset.symbol.clone(),
+				name: asset.symbol.clone(),
+				address: "".to_string(),
+				decimals: 0,
+				blockchain: asset.blockchain.clone(),
+			},
+			volume: 0.0,
+		}));
+		Ok(assets)
+	}
+
+	/// Quotation of the first source of the asset that answers with a non-zero price that isn't
+	/// delayed. If all sources are delayed or fail, the first delayed quotation is taken, and
+	/// without one the last source as it answers.
+	async fn get_quotation(
+		&self,
+		quoted_asset: &QuotedAsset,
+	) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+		let asset = &quoted_asset.asset;
+		let mut sources = self.routed_sources(asset).into_iter().peekable();
+		let mut delayed: Option<Quotation> = None;
+		loop {
+			let source = sources.next().expect("there is always the configured source");
+			let result = self.get_quotation_from(source, quoted_asset).await;
+			let next = match sources.peek() {
+				Some(next) => *next,
+				None =>
+					return match (result, delayed) {
+						(Ok(quotation), _)
+							if !quotation.price.is_zero() &&
+								!quotation.is_delayed(chrono::Utc::now()) =>
+							Ok(quotation),
+						(_, Some(delayed)) => Ok(delayed),
+						(result, None) => result,
+					},
+			};
+			let reason = match result {
+				Ok(quotation) if quotation.price.is_zero() => "quoted a zero price".to_string(),
+				Ok(quotation) if quotation.is_delayed(chrono::Utc::now()) => {
+					let reason = format!("quoted a delayed price of {}", quotation.time);
+					delayed.get_or_insert(quotation);
+					reason
+				},
+				Ok(quotation) => return Ok(quotation),
+				Err(err) => format!("failed: {}", err),
+			};
+			warn!(
+				"Falling back to {} for {}:{}, {} {}",
+				self.source_name(next, asset),
+				asset.blockchain,
+				asset.symbol,
+				self.source_name(source, asset),
+				reason
+			);
+		}
+	}
+
+	/// The configured source of the asset, fallbacks are not reflected
+	fn quotation_source(&self, asset: &Asset) -> &'static str {
+		self.source_name(self.primary(asset), asset)
+	}
+}
+
+#[cfg(all(test, feature = "coinbase", feature = "bitstamp"))]
+mod tests {
+	use super::*;
+
+	fn asset(blockchain: &str, symbol: &str) -> Asset {
+		Asset {
+			symbol: symbol.into(),
+			name: symbol.into(),
+			address: "".into(),
+			decimals: 0,
+			blockchain: blockchain.into(),
+		}
+	}
+
+	struct MockDia;
+
+	#[async_trait]
+	impl DiaApi for MockDia {
+		async fn get_quotable_assets(
+			&self,
+		) -> Result<Vec<QuotedAsset>, Box<dyn Error + Send + Sync>> {
+			Ok(vec![QuotedAsset { asset: asset("Bitcoin", "BTC"), volume: 1.0 }])
+		}
+
+		async fn get_quotation(
+			&self,
+			_: &QuotedAsset,
+		) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+			Ok(Quotation::default())
+		}
+	}
+
+	#[test]
+	fn test_parse_ticker() {
+		let coinbase: CoinbaseTicker = serde_json::from_str(
+			r#"{"trade_id":1,"price":"37329.98","size":"0.1","time":"2023-11-20T10:04:52.396752Z","volume":"2"}"#,
+		)
+		.unwrap();
+		let quotation = Exchange::Coinbase.quotation(
+			&asset("Bitcoin", "BTC"),
+			coinbase.price,
+			coinbase.volume,
+			coinbase.time,
+		);
+
+		assert_eq!(quotation.price, Decimal::new(3732998, 2));
+		assert_eq!(quotation.volume_yesterday, Decimal::new(7465996, 2));
+		assert_eq!(quotation.time.timestamp(), 1700474692);
+		assert_eq!(quotation.source, "Coinbase");
+
+		let bitstamp: BitstampTicker =
+			serde_json::from_str(r#"{"timestamp":"1700474693","last":"37338","volume":"1.5"}"#)
+				.unwrap();
+		assert_eq!(bitstamp.last, Decimal::new(37338, 0));
+		assert_eq!(bitstamp.timestamp, "1700474693");
+	}
+
+	#[cfg(feature = "kraken")]
+	#[test]
+	fn test_parse_kraken_ticker() {
+		let response: KrakenResponse = serde_json::from_str(
+			r#"{"error":[],"result":{"XXBTZUSD":{"c":["37338.10000","0.001"],"v":["1163.1","3049.5"],"p":["1","1"]}}}"#,
+		)
+		.unwrap();
+		let ticker = response.result.into_values().next().unwrap();
+
+		assert_eq!(ticker.c.0, Decimal::new(373381, 1));
+		assert_eq!(ticker.v.1, Decimal::new(30495, 1));
+		assert_eq!(kraken_base("btc"), "XBT");
+		assert_eq!(kraken_base("ETH"), "ETH");
+		let unknown: KrakenResponse =
+			serde_json::from_str(r#"{"error":["EQuery:Unknown asset pair"]}"#).unwrap();
+		assert!(unknown.result.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_router_adds_exchange_only_assets() {
+		let router = ExchangeRouter::new(
+			MockDia,
+			"Bitcoin:BTC=coinbase, Ethereum:ETH=bitstamp".parse().unwrap(),
+		);
+
+		let assets = router.get_quotable_assets().await.unwrap();
+
+		assert_eq!(
+			assets
+				.iter()
+				.map(|quoted| (quoted.asset.blockchain.as_str(), quoted.asset.symbol.as_str()))
+				.collect::<Vec<_>>(),
+			vec![("Bitcoin", "BTC"), ("Ethereum", "ETH")]
+		);
+		assert_eq!(router.quotation_source(&asset("Bitcoin", "BTC")), "Coinbase");
+		assert_eq!(router.quotation_source(&asset("Polkadot", "DOT")), "diadata.org");
+		assert!("Bitcoin:BTC=kraken".parse::<ExchangeSources>().is_err());
+	}
+
+	#[test]
+	fn test_retry_policy() {
+		let retries = RetryPolicy { retries: 3, backoff: Duration::from_millis(100) };
+
+		assert_eq!(retries.delay(1, 0.0), Duration::from_millis(100));
+		assert_eq!(retries.delay(3, 0.0), Duration::from_millis(400));
+		assert_eq!(retries.delay(2, 0.5), Duration::from_millis(300));
+		assert_eq!(retries.delay(1, 2.0), Duration::from_millis(200));
+		assert!(is_retryable(&SourceTimeout(Duration::from_millis(100))));
+	}
+
+	#[tokio::test]
+	async fn test_router_retries() {
+		struct FlakyDia(std::sync::atomic::AtomicU32);
+
+		#[async_trait]
+		impl DiaApi for FlakyDia {
+			async fn get_quotable_assets(
+				&self,
+			) -> Result<Vec<QuotedAsset>, Box<dyn Error + Send + Sync>> {
+				Ok(vec![])
+			}
+
+			async fn get_quotation(
+				&self,
+				_: &QuotedAsset,
+			) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+				match self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+					0 => Err("connection reset".into()),
+					1 => {
+						tokio::time::delay_for(Duration::from_millis(100)).await;
+						Ok(Quotation::default())
+					},
+					_ => Ok(Quotation::default()),
+				}
+			}
+		}
+
+		let router = ExchangeRouter::new(FlakyDia(Default::default()), Default::default())
+			.with_source_timeouts(Duration::from_millis(10), Default::default())
+			.with_retry_policy(RetryPolicy { retries: 2, backoff: Duration::from_millis(1) });
+		let quoted = QuotedAsset { asset: asset("Polkadot", "DOT"), volume: 0.0 };
+
+		assert!(router.get_quotation(&quoted).await.is_ok());
+		assert_eq!(router.api.0.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+		let router = router.with_retry_policy(RetryPolicy::default());
+		router.api.0.store(1, std::sync::atomic::Ordering::SeqCst);
+		let err = router.get_quotation(&quoted).await.unwrap_err();
+		assert!(err.is::<SourceTimeout>());
+		assert_eq!(
+			"dia=500, coinbase=2000".parse::<SourceTimeouts>().unwrap().0.get(&Source::Dia),
+			Some(&500)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_router_falls_back() {
+		let router = ExchangeRouter::new(
+			MockDia,
+			"Bitcoin:BTC=coinbase, Unknown:NOT-A-COIN=coinbase".parse().unwrap(),
+		)
+		.with_fallback_sources(
+			"Bitcoin:BTC=bitstamp+dia+coinbase, Unknown:NOT-A-COIN=dia".parse().unwrap(),
+		);
+
+		assert_eq!(
+			router.sources(&asset("Bitcoin", "BTC")),
+			vec![
+				Source::Exchange(Exchange::Coinbase),
+				Source::Exchange(Exchange::Bitstamp),
+				Source::Dia
+			]
+		);
+		assert_eq!(router.sources(&asset("Polkadot", "DOT")), vec![Source::Dia]);
+		assert_eq!(router.quotation_source(&asset("Bitcoin", "BTC")), "Coinbase");
+		// Coinbase doesn't list the asset, so the mock answers
+		let quoted = QuotedAsset { asset: asset("Unknown", "NOT-A-COIN"), volume: 0.0 };
+		assert!(router.get_quotation(&quoted).await.is_ok());
+		assert!("Bitcoin:BTC=coinbase+kraken".parse::<FallbackSources>().is_err());
+	}
+
+	#[test]
+	fn test_router_blockchain_sources() {
+		let router = ExchangeRouter::new(MockDia, "FIAT:USD-EUR=bitstamp".parse().unwrap())
+			.with_fallback_sources("FIAT:USD-CHF=dia".parse().unwrap())
+			.with_blockchain_sources("FIAT=dia+coinbase, Bitcoin=coinbase".parse().unwrap());
+		let coinbase = Source::Exchange(Exchange::Coinbase);
+		let bitstamp = Source::Exchange(Exchange::Bitstamp);
+
+		let exchangerate = Source::ExchangeRate;
+
+		assert_eq!(
+			router.sources(&asset("FIAT", "MXN-USD")),
+			vec![Source::Dia, coinbase, exchangerate]
+		);
+		assert_eq!(
+			router.sources(&asset("FIAT", "USD-EUR")),
+			vec![bitstamp, Source::Dia, coinbase, exchangerate]
+		);
+		assert_eq!(router.sources(&asset("FIAT", "USD-CHF")), vec![Source::Dia, exchangerate]);
+		assert_eq!(router.sources(&asset("Polkadot", "DOT")), vec![Source::Dia]);
+		assert_eq!(router.quotation_source(&asset("Bitcoin", "BTC")), "Coinbase");
+		assert!("FIAT".parse::<BlockchainSources>().is_err());
+		assert!("FIAT=".parse::<BlockchainSources>().is_err());
+	}
+
+	#[test]
+	fn test_router_skips_quarantined_sources() {
+		let quarantine = Arc::new(Quarantine::default());
+		let router = ExchangeRouter::new(MockDia, "Bitcoin:BTC=coinbase".parse().unwrap())
+			.with_fallback_sources("Bitcoin:BTC=bitstamp".parse().unwrap())
+			.with_quarantine(quarantine.clone());
+		let btc = asset("Bitcoin", "BTC");
+
+		quarantine.quarantine("Bitcoin", "BTC", "Coinbase", 0);
+		assert_eq!(router.routed_sources(&btc), vec![Source::Exchange(Exchange::Bitstamp)]);
+		// Without a source left the asset is routed to all of them
+		quarantine.quarantine("Bitcoin", "BTC", "Bitstamp", 0);
+		assert_eq!(router.routed_sources(&btc), router.sources(&btc));
+		assert!(quarantine.release("Bitcoin", "BTC", "Coinbase"));
+		assert_eq!(router.routed_sources(&btc), vec![Source::Exchange(Exchange::Coinbase)]);
+	}
+}
@@ -1,18 +1,501 @@
-use crate::storage::{CoinInfo, CoinInfoStorage};
+use crate::admin_auth::AdminRequest;
+use crate::latency::{AssetLatency, LatencyTracker};
+use crate::logging::LogFilter;
+use crate::merkle::{MerkleProof, MerkleTree};
+use crate::price_log::{candles, Candle, PriceLog};
+use crate::quarantine::{Quarantine, QuarantinedSource};
+use crate::report::{FeedStatistics, Health, SpendEstimate};
+use crate::response_signing::ResponseSigner;
+use crate::schedule::UpdateSchedule;
+use crate::smoothing::{PriceSmoothing, SmoothedPrice};
+use crate::status::{AssetStatus, AssetStatuses};
+use crate::storage::{AssetError, AssetErrorLog, AssetErrorLogUsage, CoinInfo, CoinInfoStorage};
+use crate::twap::{ONE_DAY, ONE_HOUR};
+use crate::{conflicts, AssetSpecifier, SupportedAssets};
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorNotFound, ErrorServiceUnavailable};
 use actix_web::web::Json;
-use actix_web::{post, web};
+use actix_web::{get, post, web, HttpResponse};
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
 
+/// Maximum time a request may take to be handled if not configured otherwise
+pub const DEFAULT_MAX_HANDLING_TIME: Duration = Duration::from_secs(1);
+
+/// Maximum time the storage query of a request may take before it is answered with 503
+#[derive(Debug, Clone, Copy)]
+pub struct MaxHandlingTime(pub Duration);
+
+/// Logs requests whose handler is dropped before it responded, which actix does when the client
+/// disconnects
+struct DisconnectGuard {
+	responded: bool,
+}
+
+impl Drop for DisconnectGuard {
+	fn drop(&mut self) {
+		if !self.responded {
+			debug!("Client disconnected before its request was handled");
+		}
+	}
+}
+
+/// Runs `query` on the blocking thread pool and fails with 503 if it didn't finish within
+/// `max_handling_time`
+async fn within<T, F>(max_handling_time: Duration, query: F) -> Result<T, actix_web::Error>
+where
+	T: Send + 'static,
+	F: FnOnce() -> T + Send + 'static,
+{
+	let mut guard = DisconnectGuard { responded: false };
+	let result =
+		tokio::time::timeout(max_handling_time, web::block(move || Ok::<_, Infallible>(query())))
+			.await;
+	guard.responded = true;
+	match result {
+		Ok(Ok(value)) => Ok(value),
+		Ok(Err(err)) => Err(ErrorServiceUnavailable(err.to_string())),
+		Err(_) => Err(ErrorServiceUnavailable(format!(
+			"Request wasn't handled within {}ms",
+			max_handling_time.as_millis()
+		))),
+	}
+}
+
+/// JSON response with `value`, with the signature of the body and the public key in the headers
+/// if the server signs its responses
+fn signed_json<T: Serialize>(
+	value: &T,
+	signer: Option<web::Data<ResponseSigner>>,
+) -> Result<HttpResponse, actix_web::Error> {
+	let body = serde_json::to_vec(value)?;
+	let mut response = HttpResponse::Ok();
+	response.content_type("application/json");
+	if let Some(signer) = signer {
+		for (name, value) in signer.headers(&body).iter() {
+			response.header(*name, value.as_str());
+		}
+	}
+	Ok(response.body(body))
+}
+
+/// Coin infos of the requested currencies the server knows, ordered by blockchain and then symbol
+/// regardless of the order of the request, so equal prices always serialize to the same response.
+/// The storage is queried off the request thread for at most the `MaxHandlingTime`. With
+/// `?changedSince=<unix timestamp>` only the coin infos whose price changed since are returned.
 #[post("/currencies")]
 pub async fn currencies_post(
 	web::Json(currencies): web::Json<Vec<Currency>>,
+	query: web::Query<ChangedSinceQuery>,
+	storage: web::Data<CoinInfoStorage>,
+	max_handling_time: Option<web::Data<MaxHandlingTime>>,
+	signer: Option<web::Data<ResponseSigner>>,
+) -> Result<HttpResponse, actix_web::Error> {
+	debug!("Request currencies {:?}", currencies);
+	let Json(coin_infos) =
+		lookup_currencies(currencies, query.changed_since, storage, max_handling_time).await?;
+	signed_json(&coin_infos, signer)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangedSinceQuery {
+	/// Unix timestamp from which on the price of an asset has to have changed to be returned
+	#[serde(rename = "changedSince")]
+	changed_since: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CurrenciesQuery {
+	/// Comma separated `<blockchain>:<symbol>` list
+	#[serde(default)]
+	assets: String,
+	/// Whether to wrap every coin info in a `ScheduledCoinInfo`
+	#[serde(default)]
+	envelope: bool,
+	/// Same as for `POST /currencies`
+	#[serde(rename = "changedSince")]
+	changed_since: Option<u64>,
+}
+
+/// Coin info with the earliest unix timestamp its asset is fetched again at, `None` if this
+/// process doesn't fetch the prices or hasn't finished an update iteration yet, and the
+/// time-weighted average prices of the last hour and day, `None` without a price history
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledCoinInfo {
+	pub coin_info: CoinInfo,
+	pub next_update_at: Option<u64>,
+	pub twap_1h: Option<u128>,
+	pub twap_24h: Option<u128>,
+}
+
+/// Same as `POST /currencies` with the currencies given as `?assets=<blockchain>:<symbol>,...`.
+/// With `&envelope=true` every coin info comes with the time it is updated next and its
+/// time-weighted average prices.
+#[get("/currencies")]
+pub async fn currencies_get(
+	query: web::Query<CurrenciesQuery>,
+	storage: web::Data<CoinInfoStorage>,
+	max_handling_time: Option<web::Data<MaxHandlingTime>>,
+	update_schedule: Option<web::Data<UpdateSchedule>>,
+	signer: Option<web::Data<ResponseSigner>>,
+) -> Result<HttpResponse, actix_web::Error> {
+	let currencies = query
+		.assets
+		.split(',')
+		.filter(|asset| !asset.trim().is_empty())
+		.map(|asset| {
+			let AssetSpecifier { blockchain, symbol } = asset.parse()?;
+			Ok(Currency { blockchain, symbol })
+		})
+		.collect::<Result<Vec<_>, String>>()
+		.map_err(ErrorBadRequest)?;
+	debug!("Request currencies {:?}", currencies);
+	let Json(coin_infos) =
+		lookup_currencies(currencies, query.changed_since, storage.clone(), max_handling_time)
+			.await?;
+	if !query.envelope {
+		return signed_json(&coin_infos, signer)
+	}
+	let now = chrono::Utc::now().timestamp().unsigned_abs();
+	let scheduled: Vec<ScheduledCoinInfo> = coin_infos
+		.into_iter()
+		.map(|coin_info| {
+			let (blockchain, symbol) = (&coin_info.blockchain, &coin_info.symbol);
+			ScheduledCoinInfo {
+				next_update_at: update_schedule
+					.as_ref()
+					.and_then(|update_schedule| update_schedule.next_update_at(blockchain, symbol)),
+				twap_1h: storage.twap(blockchain, symbol, now, ONE_HOUR),
+				twap_24h: storage.twap(blockchain, symbol, now, ONE_DAY),
+				coin_info,
+			}
+		})
+		.collect();
+	signed_json(&scheduled, signer)
+}
+
+async fn lookup_currencies(
+	currencies: Vec<Currency>,
+	changed_since: Option<u64>,
+	storage: web::Data<CoinInfoStorage>,
+	max_handling_time: Option<web::Data<MaxHandlingTime>>,
+) -> Result<Json<Vec<CoinInfo>>, actix_web::Error> {
+	let max_handling_time = max_handling_time
+		.map_or(DEFAULT_MAX_HANDLING_TIME, |max_handling_time| max_handling_time.0);
+	let coin_infos = within(max_handling_time, move || {
+		let storage = storage.get_ref();
+		let mut coin_infos = storage.get_currencies_by_blockchains_and_symbols(currencies);
+		if let Some(since) = changed_since {
+			coin_infos.retain(|coin_info| {
+				storage.is_changed_since(&coin_info.blockchain, &coin_info.symbol, since)
+			});
+		}
+		coin_infos.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		coin_infos
+	})
+	.await?;
+	Ok(Json(coin_infos))
+}
+
+#[get("/debug/errors/{blockchain}/{symbol}")]
+pub async fn asset_errors_get(
+	path: web::Path<(String, String)>,
+	errors: web::Data<AssetErrorLog>,
+) -> Json<Vec<AssetError>> {
+	let (blockchain, symbol) = path.into_inner();
+	Json(errors.get_ref().get_errors(&blockchain, &symbol))
+}
+
+/// Source, last successful fetch, last error and consecutive failures of every configured asset
+#[get("/status/assets")]
+pub async fn asset_statuses_get(statuses: web::Data<AssetStatuses>) -> Json<Vec<AssetStatus>> {
+	Json(statuses.statuses())
+}
+
+/// Proof of the published coin info of an asset against the merkle root over all published coin
+/// infos, 404 if the asset isn't published
+#[get("/proof/{blockchain}/{symbol}")]
+pub async fn proof_get(
+	path: web::Path<(String, String)>,
+	storage: web::Data<CoinInfoStorage>,
+) -> Option<Json<MerkleProof>> {
+	let (blockchain, symbol) = path.into_inner();
+	MerkleTree::new(storage.get_all_currencies())
+		.proof(&blockchain, &symbol)
+		.map(Json)
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+	pub coin_infos: usize,
+	pub coin_infos_bytes: usize,
+	pub error_log: AssetErrorLogUsage,
+}
+
+#[get("/debug/memory")]
+pub async fn memory_usage_get(
 	storage: web::Data<CoinInfoStorage>,
-) -> Json<Vec<CoinInfo>> {
-	println!("Request currencies {:?}", currencies);
-	Json(storage.get_ref().get_currencies_by_blockchains_and_symbols(currencies))
+	errors: web::Data<AssetErrorLog>,
+) -> Json<MemoryUsage> {
+	Json(MemoryUsage {
+		coin_infos: storage.len(),
+		coin_infos_bytes: storage.memory_usage(),
+		error_log: errors.usage(),
+	})
+}
+
+/// Estimated spend on the sources since the last feed report and in the current month
+#[get("/debug/costs")]
+pub async fn costs_get(statistics: web::Data<FeedStatistics>) -> Json<SpendEstimate> {
+	Json(statistics.spend_estimate(chrono::Utc::now().timestamp().unsigned_abs()))
+}
+
+/// Latencies `/debug/latency` serves, `None` without `--latency-rpc-url`
+pub struct LatencyTracking {
+	pub tracker: Option<Arc<LatencyTracker>>,
+}
+
+/// Latency histograms of every asset from its upstream quotation through its publication to the
+/// chain, 404 unless the chain is watched with `--latency-rpc-url`
+#[get("/debug/latency")]
+pub async fn latency_get(
+	tracking: web::Data<LatencyTracking>,
+) -> Result<Json<Vec<AssetLatency>>, actix_web::Error> {
+	let tracker = tracking
+		.tracker
+		.as_ref()
+		.ok_or_else(|| ErrorNotFound("Latencies are only tracked with --latency-rpc-url"))?;
+	Ok(Json(tracker.latencies()))
+}
+
+/// Quoted prices of the assets smoothed with `--smoothing-alphas` next to the averages published
+/// in their place
+#[get("/debug/smoothing")]
+pub async fn smoothing_get(smoothing: web::Data<PriceSmoothing>) -> Json<Vec<SmoothedPrice>> {
+	Json(smoothing.prices())
+}
+
+/// Seconds without a finished iteration of the update loop or a published price of a source after
+/// which `/health` answers 503, `None` if this process doesn't fetch the prices it serves
+#[derive(Debug, Clone, Copy)]
+pub struct MaxUpdateAge(pub Option<u64>);
+
+/// Last finished iteration of the update loop and last published price of every source, 503 if
+/// one of them is older than the `MaxUpdateAge`
+#[get("/health")]
+pub async fn health_get(
+	statistics: web::Data<FeedStatistics>,
+	max_update_age: web::Data<MaxUpdateAge>,
+) -> HttpResponse {
+	let now = chrono::Utc::now().timestamp().unsigned_abs();
+	let health: Health = statistics.health(now, max_update_age.0.unwrap_or(u64::MAX));
+	if health.healthy {
+		HttpResponse::Ok().json(health)
+	} else {
+		HttpResponse::ServiceUnavailable().json(health)
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+	pub ready: bool,
+	pub coin_infos: usize,
+}
+
+/// 503 until there are prices to serve
+#[get("/readiness")]
+pub async fn readiness_get(storage: web::Data<CoinInfoStorage>) -> HttpResponse {
+	let coin_infos = storage.len();
+	let readiness = Readiness { ready: coin_infos > 0, coin_infos };
+	if readiness.ready {
+		HttpResponse::Ok().json(readiness)
+	} else {
+		HttpResponse::ServiceUnavailable().json(readiness)
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct LogLevel {
+	pub directives: String,
+}
+
+/// Replaces the log filter with `RUST_LOG` style directives, e.g.
+/// `info,dia_batching_server::dia=trace`, and answers with the filter in effect
+#[post("/admin/loglevel")]
+pub async fn log_level_post(
+	admin: AdminRequest,
+	log_filter: web::Data<LogFilter>,
+) -> Result<Json<LogLevel>, actix_web::Error> {
+	let log_level: LogLevel = admin.json()?;
+	log_filter.set_directives(&log_level.directives).map_err(ErrorBadRequest)?;
+	info!("Log filter set to '{}'", log_level.directives);
+	Ok(Json(LogLevel { directives: log_filter.directives() }))
+}
+
+/// Supported currencies the update loop fetches, `None` if they are read from the chain or this
+/// process doesn't fetch prices, so there is nothing `POST /admin/currencies` may replace
+pub struct ReloadableCurrencies {
+	pub assets: Option<Arc<SupportedAssets>>,
+	pub allow_asset_conflicts: bool,
+}
+
+/// Replaces the supported currencies from the next iteration of the update loop on, answering
+/// with the new set ordered by blockchain and then symbol
+#[post("/admin/currencies")]
+pub async fn supported_currencies_post(
+	admin: AdminRequest,
+	reloadable: web::Data<ReloadableCurrencies>,
+) -> Result<Json<Vec<Currency>>, actix_web::Error> {
+	let currencies: Vec<Currency> = admin.json()?;
+	let supported = reloadable.assets.as_ref().ok_or_else(|| {
+		ErrorConflict("Supported currencies are read from the chain or fetched by another process")
+	})?;
+	let assets: HashSet<AssetSpecifier> = currencies
+		.into_iter()
+		.map(|Currency { blockchain, symbol }| AssetSpecifier { blockchain, symbol })
+		.collect();
+	if assets.is_empty() {
+		return Err(ErrorBadRequest("No currencies given"))
+	}
+	let conflicts = conflicts::normalization_conflicts(&assets);
+	if !conflicts.is_empty() && !reloadable.allow_asset_conflicts {
+		return Err(ErrorBadRequest(format!(
+			"Currencies only differ in case: {}",
+			conflicts.join("; ")
+		)))
+	}
+
+	let mut currencies: Vec<Currency> = assets
+		.iter()
+		.map(|asset| Currency {
+			blockchain: asset.blockchain.clone(),
+			symbol: asset.symbol.clone(),
+		})
+		.collect();
+	currencies.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+	supported.store(Some(Arc::new(assets)));
+	info!("Supported currencies replaced: {} assets", currencies.len());
+	Ok(Json(currencies))
+}
+
+/// Quarantine of the sources the update loop routes around, `None` if this process doesn't fetch
+/// prices, so there is nothing `/admin/quarantine` may change
+pub struct QuarantineControl {
+	pub quarantine: Option<Arc<Quarantine>>,
+}
+
+impl QuarantineControl {
+	fn quarantine(&self) -> Result<&Quarantine, actix_web::Error> {
+		self.quarantine
+			.as_deref()
+			.ok_or_else(|| ErrorConflict("Prices are fetched by another process"))
+	}
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+pub struct QuarantineRequest {
+	pub blockchain: String,
+	pub symbol: String,
+	pub source: String,
+}
+
+/// Sources quarantined for individual assets, ordered by blockchain, symbol and source
+#[get("/admin/quarantine")]
+pub async fn quarantine_get(
+	_admin: AdminRequest,
+	control: web::Data<QuarantineControl>,
+) -> Result<Json<Vec<QuarantinedSource>>, actix_web::Error> {
+	Ok(Json(control.quarantine()?.entries()))
+}
+
+/// Quarantines a source for an asset until it is released through `/admin/quarantine/release`,
+/// answering with all quarantined sources
+#[post("/admin/quarantine")]
+pub async fn quarantine_post(
+	admin: AdminRequest,
+	control: web::Data<QuarantineControl>,
+) -> Result<Json<Vec<QuarantinedSource>>, actix_web::Error> {
+	let request: QuarantineRequest = admin.json()?;
+	let quarantine = control.quarantine()?;
+	let now = chrono::Utc::now().timestamp().unsigned_abs();
+	quarantine.quarantine(&request.blockchain, &request.symbol, &request.source, now);
+	info!("Quarantined {} for {}:{}", request.source, request.blockchain, request.symbol);
+	Ok(Json(quarantine.entries()))
+}
+
+/// Releases a quarantined source of an asset, answering with the quarantined sources left
+#[post("/admin/quarantine/release")]
+pub async fn quarantine_release_post(
+	admin: AdminRequest,
+	control: web::Data<QuarantineControl>,
+) -> Result<Json<Vec<QuarantinedSource>>, actix_web::Error> {
+	let request: QuarantineRequest = admin.json()?;
+	let quarantine = control.quarantine()?;
+	if !quarantine.release(&request.blockchain, &request.symbol, &request.source) {
+		return Err(ErrorNotFound(format!(
+			"{} isn't quarantined for {}:{}",
+			request.source, request.blockchain, request.symbol
+		)))
+	}
+	info!("Released {} for {}:{}", request.source, request.blockchain, request.symbol);
+	Ok(Json(quarantine.entries()))
+}
+
+/// Most candles a `/history` request may ask for
+pub const MAX_CANDLES: u64 = 10_000;
+
+/// Log of the served prices `/history` reads, `None` without `--price-log-dir`
+pub struct PriceLogs {
+	pub log: Option<Arc<PriceLog>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryQuery {
+	/// `<blockchain>:<symbol>`
+	asset: String,
+	from: u64,
+	to: u64,
+	/// Seconds per candle
+	#[serde(default = "default_interval")]
+	interval: u64,
+}
+
+fn default_interval() -> u64 {
+	ONE_HOUR
+}
+
+/// Candles of the prices served for an asset from the unix timestamp `from` up to but excluding
+/// `to` in buckets of `interval` seconds, an hour by default, leaving out buckets without an update
+#[get("/history")]
+pub async fn history_get(
+	query: web::Query<HistoryQuery>,
+	price_logs: web::Data<PriceLogs>,
+) -> Result<Json<Vec<Candle>>, actix_web::Error> {
+	let log = price_logs
+		.log
+		.clone()
+		.ok_or_else(|| ErrorNotFound("Prices are only logged with --price-log-dir"))?;
+	let AssetSpecifier { blockchain, symbol } = query.asset.parse().map_err(ErrorBadRequest)?;
+	let (from, to, interval) = (query.from, query.to, query.interval);
+	if interval == 0 || to <= from {
+		return Err(ErrorBadRequest("Expected from < to and an interval of at least a second"))
+	}
+	if (to - from - 1) / interval >= MAX_CANDLES {
+		return Err(ErrorBadRequest(format!("At most {} candles are returned", MAX_CANDLES)))
+	}
+	let observations = web::block(move || log.read(&blockchain, &symbol, from, to))
+		.await
+		.map_err(|err| ErrorServiceUnavailable(err.to_string()))?;
+	Ok(Json(candles(&observations, from, interval)))
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Currency {
 	pub blockchain: String,
 	pub symbol: String,
@@ -21,8 +504,32 @@ pub struct Currency {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::admin_auth::AdminKey;
+	use crate::request_signing::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
 	use actix_web::{http, test, App};
-	use std::sync::Arc;
+
+	const ADMIN_KEY: &[u8] = b"admin";
+
+	fn admin_key() -> web::Data<AdminKey> {
+		web::Data::new(AdminKey::new(ADMIN_KEY.to_vec(), 300))
+	}
+
+	/// Request to an admin endpoint signed with `ADMIN_KEY`
+	fn admin_request(method: http::Method, path: &str, body: Vec<u8>) -> test::TestRequest {
+		let now = chrono::Utc::now().timestamp().unsigned_abs();
+		let (timestamp, signature) = admin_key().sign(now, method.as_str(), path, &body);
+		test::TestRequest::default()
+			.method(method)
+			.uri(path)
+			.header(TIMESTAMP_HEADER, timestamp)
+			.header(SIGNATURE_HEADER, signature)
+			.header(http::header::CONTENT_TYPE, "application/json")
+			.set_payload(body)
+	}
+
+	fn admin_post<T: Serialize>(path: &str, body: &T) -> test::TestRequest {
+		admin_request(http::Method::POST, path, serde_json::to_vec(body).unwrap())
+	}
 
 	fn get_storage() -> Arc<CoinInfoStorage> {
 		let storage = Arc::new(CoinInfoStorage::default());
@@ -57,6 +564,330 @@ mod tests {
 		assert_eq!(r.len(), 2);
 	}
 
+	#[tokio::test]
+	async fn test_currencies_post_changed_since() {
+		let data = web::Data::from(get_storage());
+		let mut app = test::init_service(App::new().app_data(data).service(currencies_post)).await;
+		let now = chrono::Utc::now().timestamp().unsigned_abs();
+		let post = |since: u64| {
+			test::TestRequest::post()
+				.uri(&format!("http://localhost:8080/currencies?changedSince={}", since))
+				.set_json(&vec![Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() }])
+				.to_request()
+		};
+
+		let resp = test::call_service(&mut app, post(now - 60)).await;
+		let r: Vec<CoinInfo> = test::read_body_json(resp).await;
+		assert_eq!(r.len(), 1);
+
+		let resp = test::call_service(&mut app, post(now + 60)).await;
+		let r: Vec<CoinInfo> = test::read_body_json(resp).await;
+		assert!(r.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_currencies_post_signed() {
+		use dia_common::signing::{verify_response, PUBLIC_KEY_HEADER, RESPONSE_SIGNATURE_HEADER};
+
+		let data = web::Data::from(get_storage());
+		let signer: ResponseSigner = hex::encode([7u8; 32]).parse().unwrap();
+		let public_key = signer.public_key();
+		let mut app = test::init_service(
+			App::new()
+				.app_data(data)
+				.app_data(web::Data::new(signer))
+				.service(currencies_post),
+		)
+		.await;
+		let req = test::TestRequest::post()
+			.uri("http://localhost:8080/currencies")
+			.set_json(&vec![Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() }])
+			.to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+		let header = |name: &str| resp.headers().get(name).unwrap().as_bytes().to_vec();
+		let (signature, claimed_key) =
+			(header(RESPONSE_SIGNATURE_HEADER), header(PUBLIC_KEY_HEADER));
+		assert_eq!(claimed_key, public_key.as_bytes());
+		let body = test::read_body(resp).await;
+		assert_eq!(verify_response(public_key.as_bytes(), &body, &signature), Ok(()));
+	}
+
+	#[tokio::test]
+	async fn test_currencies_get() {
+		let storage = get_storage();
+		let data = web::Data::from(storage.clone());
+
+		let mut app = test::init_service(
+			App::new()
+				.app_data(data.clone())
+				.service(currencies_post)
+				.service(currencies_get),
+		)
+		.await;
+		let get = test::TestRequest::get()
+			.uri("http://localhost:8080/currencies?assets=Ethereum:ETH,Bitcoin:BTC,Bitcoin:DASH")
+			.to_request();
+		let post = test::TestRequest::post()
+			.uri("http://localhost:8080/currencies")
+			.set_json(&vec![
+				Currency { blockchain: "Ethereum".into(), symbol: "ETH".into() },
+				Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
+				Currency { blockchain: "Bitcoin".into(), symbol: "DASH".into() },
+			])
+			.to_request();
+
+		let get = test::call_service(&mut app, get).await;
+		let post = test::call_service(&mut app, post).await;
+
+		assert_eq!(get.status(), http::StatusCode::OK);
+		assert_eq!(test::read_body(get).await, test::read_body(post).await);
+
+		let empty = test::TestRequest::get().uri("http://localhost:8080/currencies").to_request();
+		let r: Vec<CoinInfo> = test::read_response_json(&mut app, empty).await;
+		assert_eq!(r.len(), 0);
+
+		let invalid = test::TestRequest::get()
+			.uri("http://localhost:8080/currencies?assets=BTC")
+			.to_request();
+		let resp = test::call_service(&mut app, invalid).await;
+		assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+	}
+
+	#[tokio::test]
+	async fn test_responses_decode_with_client_types() {
+		let data = web::Data::from(get_storage());
+		let mut app = test::init_service(
+			App::new().app_data(data).service(currencies_post).service(currencies_get),
+		)
+		.await;
+
+		let post = test::TestRequest::post()
+			.uri("http://localhost:8080/currencies")
+			.set_json(&vec![dia_batching_client::Currency::new("Bitcoin", "BTC")])
+			.to_request();
+		let r: Vec<dia_batching_client::CoinInfo> = test::read_response_json(&mut app, post).await;
+		assert_eq!(r[0].symbol, "BTC");
+
+		let get = test::TestRequest::get()
+			.uri("http://localhost:8080/currencies?assets=Ethereum:ETH&envelope=true")
+			.to_request();
+		let r: Vec<dia_batching_client::ScheduledCoinInfo> =
+			test::read_response_json(&mut app, get).await;
+		assert_eq!((r[0].coin_info.symbol.as_str(), r[0].twap_1h), ("ETH", None));
+	}
+
+	#[tokio::test]
+	async fn test_currencies_get_envelope() {
+		let storage = Arc::new(CoinInfoStorage::default().with_price_history(ONE_DAY));
+		storage.replace_currencies_by_symbols(vec![CoinInfo {
+			symbol: "BTC".into(),
+			blockchain: "Bitcoin".into(),
+			last_update_timestamp: chrono::Utc::now().timestamp().unsigned_abs() - 60,
+			price: 5,
+			..Default::default()
+		}]);
+		let data = web::Data::from(storage);
+		let update_schedule = UpdateSchedule::new("FIAT:MXN-USD=16:00-16:15".parse().unwrap());
+		update_schedule.set_next_iteration(1700496000);
+		let mut app = test::init_service(
+			App::new()
+				.app_data(data)
+				.app_data(web::Data::new(update_schedule))
+				.service(currencies_get),
+		)
+		.await;
+
+		let get = test::TestRequest::get()
+			.uri("http://localhost:8080/currencies?assets=Bitcoin:BTC&envelope=true")
+			.to_request();
+		let r: Vec<ScheduledCoinInfo> = test::read_response_json(&mut app, get).await;
+
+		assert_eq!(r.len(), 1);
+		assert_eq!(r[0].coin_info.symbol, "BTC");
+		assert_eq!(r[0].next_update_at, Some(1700496000));
+		assert_eq!((r[0].twap_1h, r[0].twap_24h), (Some(5), Some(5)));
+	}
+
+	#[tokio::test]
+	async fn test_quarantine_admin() {
+		let quarantine = Arc::new(Quarantine::default());
+		let control = web::Data::new(QuarantineControl { quarantine: Some(quarantine.clone()) });
+		let mut app = test::init_service(
+			App::new()
+				.app_data(control)
+				.app_data(admin_key())
+				.service(quarantine_get)
+				.service(quarantine_post)
+				.service(quarantine_release_post),
+		)
+		.await;
+		let body = QuarantineRequest {
+			blockchain: "Bitcoin".into(),
+			symbol: "BTC".into(),
+			source: "Coinbase".into(),
+		};
+		let request = |uri: &str| admin_post(uri, &body).to_request();
+
+		let r: Vec<QuarantinedSource> =
+			test::read_response_json(&mut app, request("/admin/quarantine")).await;
+		assert_eq!((r[0].source.as_str(), r[0].manual), ("Coinbase", true));
+		assert!(quarantine.is_quarantined("Bitcoin", "BTC", "Coinbase"));
+
+		let get = admin_request(http::Method::GET, "/admin/quarantine", vec![]).to_request();
+		let r: Vec<QuarantinedSource> = test::read_response_json(&mut app, get).await;
+		assert_eq!(r.len(), 1);
+		let unsigned = test::TestRequest::get().uri("/admin/quarantine").to_request();
+		let resp = test::call_service(&mut app, unsigned).await;
+		assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+		let r: Vec<QuarantinedSource> =
+			test::read_response_json(&mut app, request("/admin/quarantine/release")).await;
+		assert!(r.is_empty());
+		let resp = test::call_service(&mut app, request("/admin/quarantine/release")).await;
+		assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn test_history_get() {
+		let dir = std::env::temp_dir()
+			.join(format!("dia-batching-server-history-{}", std::process::id()));
+		let log = Arc::new(PriceLog::open(dir.clone()).unwrap());
+		let coin_info = |price, last_update_timestamp| CoinInfo {
+			symbol: "BRL-USD".into(),
+			blockchain: "FIAT".into(),
+			price,
+			last_update_timestamp,
+			..Default::default()
+		};
+		log.append(&[coin_info(2, 3600), coin_info(1, 4000), coin_info(3, 7200)])
+			.unwrap();
+		let price_logs = web::Data::new(PriceLogs { log: Some(log) });
+		let mut app =
+			test::init_service(App::new().app_data(price_logs).service(history_get)).await;
+
+		let get = test::TestRequest::get()
+			.uri("/history?asset=FIAT:BRL-USD&from=3600&to=10800")
+			.to_request();
+		let r: Vec<dia_batching_client::Candle> = test::read_response_json(&mut app, get).await;
+		assert_eq!(r.len(), 2);
+		assert_eq!((r[0].start, r[0].open, r[0].low, r[0].close), (3600, 2, 1, 1));
+		assert_eq!((r[1].start, r[1].updates), (7200, 1));
+
+		for uri in [
+			"/history?asset=BRL-USD&from=0&to=3600",
+			"/history?asset=FIAT:BRL-USD&from=3600&to=3600",
+			"/history?asset=FIAT:BRL-USD&from=0&to=36000000&interval=1",
+		] {
+			let resp =
+				test::call_service(&mut app, test::TestRequest::get().uri(uri).to_request()).await;
+			assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+		}
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_supported_currencies_post() {
+		let supported = Arc::new(SupportedAssets::empty());
+		let reloadable = web::Data::new(ReloadableCurrencies {
+			assets: Some(supported.clone()),
+			allow_asset_conflicts: false,
+		});
+		let mut app = test::init_service(
+			App::new()
+				.app_data(reloadable)
+				.app_data(admin_key())
+				.service(supported_currencies_post),
+		)
+		.await;
+		let currencies = |currencies: Vec<(&str, &str)>| {
+			currencies
+				.into_iter()
+				.map(|(blockchain, symbol)| Currency {
+					blockchain: blockchain.into(),
+					symbol: symbol.into(),
+				})
+				.collect::<Vec<_>>()
+		};
+		let post = |assets| admin_post("/admin/currencies", &currencies(assets)).to_request();
+
+		let resp =
+			test::call_service(&mut app, post(vec![("Polkadot", "DOT"), ("Bitcoin", "BTC")])).await;
+		assert_eq!(resp.status(), http::StatusCode::OK);
+		let r: Vec<Currency> = test::read_body_json(resp).await;
+		assert_eq!(r[0].symbol, "BTC");
+		assert_eq!(supported.load().as_ref().map(|assets| assets.len()), Some(2));
+
+		let resp =
+			test::call_service(&mut app, post(vec![("Polkadot", "DOT"), ("polkadot", "dot")]))
+				.await;
+		assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+		let resp = test::call_service(&mut app, post(vec![])).await;
+		assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+		assert_eq!(supported.load().as_ref().map(|assets| assets.len()), Some(2));
+
+		// The signature covers the body, so it can't be replaced
+		let (signed, tampered) = (currencies(vec![("Polkadot", "DOT")]), currencies(vec![]));
+		let req = admin_post("/admin/currencies", &signed)
+			.set_payload(serde_json::to_vec(&tampered).unwrap())
+			.to_request();
+		let resp = test::call_service(&mut app, req).await;
+		assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+		let unsigned = test::TestRequest::post()
+			.uri("/admin/currencies")
+			.set_json(&signed)
+			.to_request();
+		let resp = test::call_service(&mut app, unsigned).await;
+		assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+		let synced =
+			web::Data::new(ReloadableCurrencies { assets: None, allow_asset_conflicts: false });
+		let mut app = test::init_service(
+			App::new()
+				.app_data(synced)
+				.app_data(admin_key())
+				.service(supported_currencies_post),
+		)
+		.await;
+		let resp = test::call_service(&mut app, post(vec![("Polkadot", "DOT")])).await;
+		assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+		// Without an admin key the endpoint is disabled
+		let reloadable = web::Data::new(ReloadableCurrencies {
+			assets: Some(supported),
+			allow_asset_conflicts: false,
+		});
+		let mut app =
+			test::init_service(App::new().app_data(reloadable).service(supported_currencies_post))
+				.await;
+		let resp = test::call_service(&mut app, post(vec![("Polkadot", "DOT")])).await;
+		assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+	}
+
+	#[tokio::test]
+	async fn test_log_level_post_unsigned() {
+		let mut app =
+			test::init_service(App::new().app_data(admin_key()).service(log_level_post)).await;
+		let unsigned = test::TestRequest::post()
+			.uri("/admin/loglevel")
+			.set_json(&LogLevel { directives: "trace".into() })
+			.to_request();
+
+		let resp = test::call_service(&mut app, unsigned).await;
+		assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+	}
+
+	#[tokio::test]
+	async fn test_within_max_handling_time() {
+		assert_eq!(within(Duration::from_secs(1), || 42).await.unwrap(), 42);
+
+		let err =
+			within(Duration::from_millis(1), || std::thread::sleep(Duration::from_millis(200)))
+				.await
+				.unwrap_err();
+		assert_eq!(err.as_response_error().status_code(), http::StatusCode::SERVICE_UNAVAILABLE);
+	}
+
 	#[tokio::test]
 	async fn test_currencies_post_empty() {
 		let storage = get_storage();
@@ -144,6 +975,100 @@ mod tests {
 
 		assert_eq!(r.len(), 0);
 	}
+	#[tokio::test]
+	async fn test_currencies_post_ordering() {
+		let storage = Arc::new(CoinInfoStorage::default());
+		storage.replace_currencies_by_symbols(vec![
+			CoinInfo { symbol: "USDC".into(), blockchain: "Stellar".into(), ..Default::default() },
+			CoinInfo { symbol: "USDC".into(), blockchain: "Ethereum".into(), ..Default::default() },
+			CoinInfo { symbol: "ETH".into(), blockchain: "Ethereum".into(), ..Default::default() },
+			CoinInfo { symbol: "BTC".into(), blockchain: "Bitcoin".into(), ..Default::default() },
+		]);
+		let data = web::Data::from(storage.clone());
+
+		let mut app =
+			test::init_service(App::new().app_data(data.clone()).service(currencies_post)).await;
+		let req = test::TestRequest::post()
+			.uri("http://localhost:8080/currencies")
+			.set_json(&vec![
+				Currency { blockchain: "Stellar".into(), symbol: "USDC".into() },
+				Currency { blockchain: "Ethereum".into(), symbol: "USDC".into() },
+				Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
+				Currency { blockchain: "Ethereum".into(), symbol: "ETH".into() },
+			])
+			.to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: Vec<CoinInfo> = test::read_body_json(resp).await;
+
+		assert_eq!(
+			r.iter()
+				.map(|info| (info.blockchain.as_str(), info.symbol.as_str()))
+				.collect::<Vec<_>>(),
+			vec![
+				("Bitcoin", "BTC"),
+				("Ethereum", "ETH"),
+				("Ethereum", "USDC"),
+				("Stellar", "USDC")
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_asset_errors_get() {
+		let errors = Arc::new(AssetErrorLog::default());
+		errors.record(
+			"FIAT",
+			"TZS-USD",
+			AssetError {
+				timestamp: 1,
+				source: "YahooFinance".into(),
+				kind: "timeout".into(),
+				message: "operation timed out".into(),
+			},
+		);
+		let data = web::Data::from(errors.clone());
+
+		let mut app =
+			test::init_service(App::new().app_data(data.clone()).service(asset_errors_get)).await;
+		let req = test::TestRequest::get()
+			.uri("http://localhost:8080/debug/errors/FIAT/TZS-USD")
+			.to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: Vec<AssetError> = test::read_body_json(resp).await;
+
+		assert_eq!(r.len(), 1);
+		assert_eq!(r[0].kind, "timeout");
+	}
+
+	#[tokio::test]
+	async fn test_asset_statuses_get() {
+		let statuses = Arc::new(AssetStatuses::default());
+		statuses.record_failure("FIAT", "TZS-USD", "YahooFinance", "operation timed out".into());
+		let data = web::Data::from(statuses.clone());
+
+		let mut app =
+			test::init_service(App::new().app_data(data).service(asset_statuses_get)).await;
+		let req = test::TestRequest::get().uri("http://localhost:8080/status/assets").to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: Vec<AssetStatus> = test::read_body_json(resp).await;
+
+		assert_eq!(r.len(), 1);
+		assert_eq!(r[0].last_error.as_deref(), Some("operation timed out"));
+		assert_eq!(r[0].consecutive_failures, 1);
+	}
+
 	#[tokio::test]
 	async fn test_currencies_post_special_char() {
 		let storage = get_storage();
@@ -164,4 +1089,157 @@ mod tests {
 
 		assert_eq!(r.len(), 0);
 	}
+
+	#[tokio::test]
+	async fn test_memory_usage_get() {
+		let storage = get_storage();
+		let errors = Arc::new(AssetErrorLog::default().with_max_memory_usage(Some(1024)));
+
+		let mut app = test::init_service(
+			App::new()
+				.app_data(web::Data::from(storage.clone()))
+				.app_data(web::Data::from(errors.clone()))
+				.service(memory_usage_get),
+		)
+		.await;
+		let req = test::TestRequest::get().uri("http://localhost:8080/debug/memory").to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: MemoryUsage = test::read_body_json(resp).await;
+
+		assert_eq!(r.coin_infos, 2);
+		assert_eq!(r.coin_infos_bytes, storage.memory_usage());
+		assert_eq!(r.error_log.max_bytes, Some(1024));
+	}
+
+	#[tokio::test]
+	async fn test_costs_get() {
+		let statistics =
+			Arc::new(FeedStatistics::new(1).with_source_costs("diadata.org=0.5".parse().unwrap()));
+		statistics.record("Bitcoin", "BTC", "diadata.org", true);
+
+		let mut app = test::init_service(
+			App::new().app_data(web::Data::from(statistics.clone())).service(costs_get),
+		)
+		.await;
+		let req = test::TestRequest::get().uri("http://localhost:8080/debug/costs").to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: SpendEstimate = test::read_body_json(resp).await;
+
+		assert_eq!(r.since, 1);
+		assert_eq!(r.spend_by_source.get("diadata.org"), Some(&0.5));
+		assert_eq!(r.month_to_date_spend_by_source.get("diadata.org"), Some(&0.5));
+	}
+
+	#[tokio::test]
+	async fn test_latency_get() {
+		let tracker = Arc::new(LatencyTracker::default());
+		let coin_info =
+			CoinInfo { symbol: "BTC".into(), blockchain: "Bitcoin".into(), ..Default::default() };
+		tracker.record_publication(&[CoinInfo { last_update_timestamp: 10, ..coin_info }], 12);
+		let tracking = web::Data::new(LatencyTracking { tracker: Some(tracker) });
+		let mut app = test::init_service(App::new().app_data(tracking).service(latency_get)).await;
+
+		let get = test::TestRequest::get().uri("/debug/latency").to_request();
+		let r: Vec<AssetLatency> = test::read_response_json(&mut app, get).await;
+		assert_eq!(r[0].symbol, "BTC");
+		assert_eq!((r[0].quote_to_publication.count, r[0].quote_to_publication.p50), (1, Some(2)));
+
+		let tracking = web::Data::new(LatencyTracking { tracker: None });
+		let mut app = test::init_service(App::new().app_data(tracking).service(latency_get)).await;
+		let get = test::TestRequest::get().uri("/debug/latency").to_request();
+		let resp = test::call_service(&mut app, get).await;
+		assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn test_smoothing_get() {
+		let smoothing = PriceSmoothing::new("Bitcoin:BTC=0.5".parse().unwrap());
+		for price in [100, 200] {
+			let coin_info = CoinInfo {
+				symbol: "BTC".into(),
+				blockchain: "Bitcoin".into(),
+				price,
+				..Default::default()
+			};
+			smoothing.commit(smoothing.smooth(&coin_info).unwrap());
+		}
+		let mut app = test::init_service(
+			App::new().app_data(web::Data::new(smoothing)).service(smoothing_get),
+		)
+		.await;
+
+		let get = test::TestRequest::get().uri("/debug/smoothing").to_request();
+		let r: Vec<SmoothedPrice> = test::read_response_json(&mut app, get).await;
+		assert_eq!(r.len(), 1);
+		assert_eq!((r[0].raw_price, r[0].smoothed_price), (200, 150));
+	}
+
+	#[tokio::test]
+	async fn test_proof_get() {
+		let storage = get_storage();
+
+		let mut app = test::init_service(
+			App::new().app_data(web::Data::from(storage.clone())).service(proof_get),
+		)
+		.await;
+		let req = test::TestRequest::get()
+			.uri("http://localhost:8080/proof/Bitcoin/BTC")
+			.to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		let r: MerkleProof = test::read_body_json(resp).await;
+
+		assert_eq!(r.leaf_count, 2);
+		assert!(crate::merkle::verify_proof(&r));
+
+		let req = test::TestRequest::get()
+			.uri("http://localhost:8080/proof/Bitcoin/DASH")
+			.to_request();
+
+		let resp = test::call_service(&mut app, req).await;
+
+		assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn test_health_and_readiness() {
+		let now = chrono::Utc::now().timestamp().unsigned_abs();
+		let statistics = web::Data::new(FeedStatistics::new(now - 100));
+		statistics.record_iteration(now);
+		let mut app = test::init_service(
+			App::new()
+				.app_data(web::Data::new(CoinInfoStorage::default()))
+				.app_data(statistics.clone())
+				.app_data(web::Data::new(MaxUpdateAge(Some(60))))
+				.service(health_get)
+				.service(readiness_get),
+		)
+		.await;
+
+		let req = test::TestRequest::get().uri("/health").to_request();
+		let resp = test::call_service(&mut app, req).await;
+		assert_eq!(resp.status(), http::StatusCode::OK);
+
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", false);
+		let req = test::TestRequest::get().uri("/health").to_request();
+		let resp = test::call_service(&mut app, req).await;
+		assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+		let health: Health = test::read_body_json(resp).await;
+		assert_eq!(health.stale_sources, vec!["YahooFinance"]);
+
+		let req = test::TestRequest::get().uri("/readiness").to_request();
+		let resp = test::call_service(&mut app, req).await;
+		assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+	}
 }
@@ -0,0 +1,73 @@
+use crate::args::HistoryArgs;
+use crate::chain::{ChainClient, OnChainAssetId, OnChainCoinInfo};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+pub const CSV_HEADER: &str = "block,block_hash,last_update_timestamp,price,supply";
+
+pub fn csv_row(block: u64, block_hash: &str, coin_info: &OnChainCoinInfo) -> String {
+	format!(
+		"{},{},{},{},{}",
+		block, block_hash, coin_info.last_update_timestamp, coin_info.price, coin_info.supply
+	)
+}
+
+/// Walks the blocks `from_block..=to_block` and writes the coin info of the asset at every `step`
+/// block as a CSV row. Blocks before the asset got its first price are skipped.
+pub async fn run(args: HistoryArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+	if args.step == 0 {
+		return Err("--step needs to be greater than 0".into())
+	}
+	if args.from_block > args.to_block {
+		return Err("--from-block needs to be less than or equal to --to-block".into())
+	}
+	let client = ChainClient::new(args.rpc_url, args.pallet_name);
+	let asset_id = OnChainAssetId {
+		blockchain: args.asset.blockchain.into_bytes(),
+		symbol: args.asset.symbol.into_bytes(),
+	};
+	let mut out: Box<dyn Write> = match &args.output {
+		Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+		None => Box::new(std::io::stdout()),
+	};
+
+	writeln!(out, "{}", CSV_HEADER)?;
+	let mut rows = 0;
+	for block in (args.from_block..=args.to_block).step_by(args.step as usize) {
+		let block_hash = client.block_hash(block).await?;
+		if let Some(coin_info) = client.coin_info(&asset_id, Some(&block_hash)).await? {
+			writeln!(out, "{}", csv_row(block, &block_hash, &coin_info))?;
+			rows += 1;
+		}
+	}
+	out.flush()?;
+
+	if let Some(path) = args.output {
+		println!("Wrote {} rows to {}", rows, path.display());
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_csv_row() {
+		let coin_info = OnChainCoinInfo {
+			symbol: b"MXN-USD".to_vec(),
+			name: b"MXN-USD".to_vec(),
+			blockchain: b"FIAT".to_vec(),
+			supply: 0,
+			last_update_timestamp: 1700474693,
+			price: 53712327000,
+		};
+
+		assert_eq!(csv_row(42, "0x01", &coin_info), "42,0x01,1700474693,53712327000,0");
+		assert_eq!(
+			CSV_HEADER.split(',').count(),
+			csv_row(42, "0x01", &coin_info).split(',').count()
+		);
+	}
+}
@@ -0,0 +1,120 @@
+use crate::{parse_asset_map, storage::CoinInfo, AssetSpecifier};
+use log::debug;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{collections::HashMap, str::FromStr};
+
+/// Smallest change of the price of an asset that is published, parsed from a USD amount like
+/// `0.000001` into the 12 decimals of published prices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicationThreshold(pub u128);
+
+impl FromStr for PublicationThreshold {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid threshold '{}', expected a non-negative amount", s);
+		let threshold = Decimal::from_str(s.trim()).map_err(|_| invalid())?;
+		if threshold.is_sign_negative() {
+			return Err(invalid())
+		}
+		(threshold * Decimal::from(1_000_000_000_000_u64))
+			.trunc()
+			.to_u128()
+			.map(PublicationThreshold)
+			.ok_or_else(invalid)
+	}
+}
+
+/// Thresholds of individual assets, parsed from `<blockchain>:<symbol>=<threshold>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicationThresholds(pub HashMap<AssetSpecifier, PublicationThreshold>);
+
+impl FromStr for PublicationThresholds {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(PublicationThresholds)
+	}
+}
+
+/// Keeps publishing the previous coin info of assets with a threshold while their price moves by
+/// at most the threshold, so prices oscillating around a peg don't churn the storage and the
+/// chain. The previous coin info is replaced anyway once it is older than the heartbeat.
+#[derive(Debug, Clone, Default)]
+pub struct Hysteresis {
+	thresholds: PublicationThresholds,
+	heartbeat: Option<u64>,
+}
+
+impl Hysteresis {
+	pub fn new(thresholds: PublicationThresholds, heartbeat: Option<u64>) -> Self {
+		Self { thresholds, heartbeat }
+	}
+
+	/// Coin info to publish in place of `coin_info` at the unix timestamp `now`
+	pub fn apply(&self, previous: Option<CoinInfo>, coin_info: CoinInfo, now: u64) -> CoinInfo {
+		let asset = AssetSpecifier {
+			blockchain: coin_info.blockchain.to_string(),
+			symbol: coin_info.symbol.to_string(),
+		};
+		let (threshold, previous) = match (self.thresholds.0.get(&asset), previous) {
+			(Some(threshold), Some(previous)) => (threshold, previous),
+			_ => return coin_info,
+		};
+		let within_heartbeat = self.heartbeat.map_or(true, |heartbeat| {
+			now.saturating_sub(previous.last_update_timestamp) < heartbeat
+		});
+		if within_heartbeat && coin_info.price.abs_diff(previous.price) <= threshold.0 {
+			debug!(
+				"Keeping published price {} of {}:{} over {}",
+				previous.price, asset.blockchain, asset.symbol, coin_info.price
+			);
+			previous
+		} else {
+			coin_info
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(symbol: &str, price: u128, last_update_timestamp: u64) -> CoinInfo {
+		CoinInfo {
+			symbol: symbol.into(),
+			name: symbol.into(),
+			blockchain: "Stellar".into(),
+			supply: 0,
+			last_update_timestamp,
+			price,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_hysteresis() {
+		let thresholds: PublicationThresholds = "Stellar:USDC=0.000001".parse().unwrap();
+		let hysteresis = Hysteresis::new(thresholds, Some(3600));
+		let published = coin_info("USDC", 1_000_000_000_000, 1000);
+
+		let oscillated = coin_info("USDC", 1_000_000_800_000, 1060);
+		assert_eq!(hysteresis.apply(Some(published.clone()), oscillated, 1060), published);
+		let moved = coin_info("USDC", 1_000_001_100_000, 1060);
+		assert_eq!(hysteresis.apply(Some(published.clone()), moved.clone(), 1060), moved);
+		let after_heartbeat = coin_info("USDC", 1_000_000_800_000, 4600);
+		assert_eq!(
+			hysteresis.apply(Some(published.clone()), after_heartbeat.clone(), 4600),
+			after_heartbeat
+		);
+		let xlm = coin_info("XLM", 100_000_000_001, 1060);
+		assert_eq!(
+			hysteresis.apply(Some(coin_info("XLM", 100_000_000_000, 1000)), xlm.clone(), 1060),
+			xlm
+		);
+		assert_eq!(hysteresis.apply(None, published.clone(), 1060), published);
+
+		assert!("Stellar:USDC=-0.1".parse::<PublicationThresholds>().is_err());
+		assert!("Stellar:USDC=tiny".parse::<PublicationThresholds>().is_err());
+	}
+}
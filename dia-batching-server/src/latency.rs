@@ -0,0 +1,240 @@
+use crate::chain::ChainClient;
+use crate::storage::{storage_key, CoinInfo};
+use chrono::Utc;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, RecvError};
+
+/// Upper bounds in seconds of the histogram buckets, a last bucket takes the larger latencies
+pub const BUCKET_BOUNDS: [u64; 11] = [1, 2, 5, 10, 30, 60, 120, 300, 600, 1800, 3600];
+
+/// Publications per asset kept to match the coin infos landing on chain with
+const PUBLICATIONS_PER_ASSET: usize = 16;
+
+/// Number of latencies of at most `le` seconds, all latencies if `le` is `None`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistogramBucket {
+	pub le: Option<u64>,
+	pub count: u64,
+}
+
+/// Latencies in seconds in cumulative buckets like Prometheus histograms, with the upper bounds of
+/// the buckets the median and the 95th percentile fall into
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Histogram {
+	pub buckets: Vec<HistogramBucket>,
+	pub count: u64,
+	pub sum: u64,
+	pub max: u64,
+	pub p50: Option<u64>,
+	pub p95: Option<u64>,
+}
+
+impl Default for Histogram {
+	fn default() -> Self {
+		let buckets = BUCKET_BOUNDS
+			.iter()
+			.map(|bound| Some(*bound))
+			.chain(std::iter::once(None))
+			.map(|le| HistogramBucket { le, count: 0 })
+			.collect();
+		Self { buckets, count: 0, sum: 0, max: 0, p50: None, p95: None }
+	}
+}
+
+impl Histogram {
+	pub fn observe(&mut self, latency: u64) {
+		for bucket in &mut self.buckets {
+			if bucket.le.map_or(true, |le| latency <= le) {
+				bucket.count += 1;
+			}
+		}
+		self.count += 1;
+		self.sum = self.sum.saturating_add(latency);
+		self.max = self.max.max(latency);
+		self.p50 = self.quantile(0.5);
+		self.p95 = self.quantile(0.95);
+	}
+
+	/// Upper bound of the first bucket holding the share `q` of the latencies, the largest latency
+	/// if that is the last bucket
+	fn quantile(&self, q: f64) -> Option<u64> {
+		let rank = (q * self.count as f64).ceil() as u64;
+		self.buckets
+			.iter()
+			.find(|bucket| bucket.count >= rank.max(1))
+			.map(|bucket| bucket.le.unwrap_or(self.max))
+	}
+}
+
+/// Latencies of an asset from the time of its upstream quotation to its publication by the server
+/// and to its coin info landing on chain
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetLatency {
+	pub blockchain: String,
+	pub symbol: String,
+	pub quote_to_publication: Histogram,
+	pub publication_to_chain: Histogram,
+	pub quote_to_chain: Histogram,
+}
+
+#[derive(Debug, Default)]
+struct AssetLatencyState {
+	/// Quotation timestamps of the recent publications with the time they were published at
+	publications: VecDeque<(u64, u64)>,
+	/// Quotation timestamp of the coin info last seen on chain
+	on_chain: Option<u64>,
+	latency: AssetLatency,
+}
+
+/// Latencies of every asset from its quotation through its publication to the chain
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+	assets: Mutex<HashMap<(SmolStr, SmolStr), AssetLatencyState>>,
+}
+
+impl LatencyTracker {
+	/// Records the coin infos the server started serving at `now`
+	pub fn record_publication(&self, coin_infos: &[CoinInfo], now: u64) {
+		let mut assets = self.assets.lock().expect("poisoned lock");
+		for coin_info in coin_infos {
+			let state = asset_state(&mut assets, coin_info);
+			state
+				.latency
+				.quote_to_publication
+				.observe(now.saturating_sub(coin_info.last_update_timestamp));
+			if state.publications.len() == PUBLICATIONS_PER_ASSET {
+				state.publications.pop_front();
+			}
+			state.publications.push_back((coin_info.last_update_timestamp, now));
+		}
+	}
+
+	/// Records the coin infos read from chain at `now`. The coin info already on chain when an
+	/// asset is first seen only serves as the reference for later updates, its latency is unknown.
+	pub fn record_on_chain(&self, coin_infos: &[CoinInfo], now: u64) {
+		let mut assets = self.assets.lock().expect("poisoned lock");
+		for coin_info in coin_infos {
+			let state = asset_state(&mut assets, coin_info);
+			let quoted_at = coin_info.last_update_timestamp;
+			let previous = state.on_chain.replace(quoted_at.max(state.on_chain.unwrap_or(0)));
+			if previous.map_or(true, |previous| quoted_at <= previous) {
+				continue
+			}
+			state.latency.quote_to_chain.observe(now.saturating_sub(quoted_at));
+			let published_at = state
+				.publications
+				.iter()
+				.find(|(quotation, _)| *quotation == quoted_at)
+				.map(|(_, published_at)| *published_at);
+			if let Some(published_at) = published_at {
+				state.latency.publication_to_chain.observe(now.saturating_sub(published_at));
+			}
+		}
+	}
+
+	/// Latencies of every asset ordered by blockchain and symbol
+	pub fn latencies(&self) -> Vec<AssetLatency> {
+		let assets = self.assets.lock().expect("poisoned lock");
+		let mut latencies: Vec<AssetLatency> =
+			assets.values().map(|state| state.latency.clone()).collect();
+		latencies.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		latencies
+	}
+}
+
+fn asset_state<'a>(
+	assets: &'a mut HashMap<(SmolStr, SmolStr), AssetLatencyState>,
+	coin_info: &CoinInfo,
+) -> &'a mut AssetLatencyState {
+	assets
+		.entry(storage_key(&coin_info.blockchain, &coin_info.symbol))
+		.or_insert_with(|| AssetLatencyState {
+			latency: AssetLatency {
+				blockchain: coin_info.blockchain.to_string(),
+				symbol: coin_info.symbol.to_string(),
+				..Default::default()
+			},
+			..Default::default()
+		})
+}
+
+/// Records the publication of every update of the storage and reads the coin infos on chain in
+/// every `interval`, which bounds the resolution of the latencies to the chain
+pub fn run_latency_loops(
+	client: ChainClient,
+	tracker: Arc<LatencyTracker>,
+	mut updates: broadcast::Receiver<Arc<Vec<CoinInfo>>>,
+	interval: std::time::Duration,
+) {
+	let publications = tracker.clone();
+	let _ = tokio::spawn(async move {
+		loop {
+			match updates.recv().await {
+				Ok(updated) =>
+					publications.record_publication(&updated, Utc::now().timestamp().unsigned_abs()),
+				Err(RecvError::Lagged(missed)) =>
+					warn!("Latency tracking missed {} publications", missed),
+				Err(RecvError::Closed) => return,
+			}
+		}
+	});
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			match client.coin_infos(None).await {
+				Ok(coin_infos) => {
+					let coin_infos: Vec<CoinInfo> = coin_infos
+						.iter()
+						.filter_map(|coin_info| CoinInfo::try_from(coin_info).ok())
+						.collect();
+					tracker.record_on_chain(&coin_infos, Utc::now().timestamp().unsigned_abs());
+				},
+				Err(err) => error!("Failed to read coin infos from chain: {}", err),
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(last_update_timestamp: u64) -> CoinInfo {
+		CoinInfo {
+			symbol: "DOT".into(),
+			blockchain: "Polkadot".into(),
+			last_update_timestamp,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_latency_tracking() {
+		let tracker = LatencyTracker::default();
+		// Already on chain before the tracking started
+		tracker.record_on_chain(&[coin_info(90)], 100);
+		tracker.record_publication(&[coin_info(100)], 103);
+		tracker.record_on_chain(&[coin_info(100)], 110);
+		tracker.record_on_chain(&[coin_info(100)], 116);
+		tracker.record_publication(&[coin_info(120)], 150);
+		tracker.record_on_chain(&[coin_info(120)], 200);
+
+		let latencies = tracker.latencies();
+		assert_eq!(latencies.len(), 1);
+		let latency = &latencies[0];
+		assert_eq!((latency.quote_to_publication.count, latency.quote_to_publication.sum), (2, 33));
+		assert_eq!((latency.publication_to_chain.count, latency.publication_to_chain.sum), (2, 57));
+		assert_eq!((latency.quote_to_chain.count, latency.quote_to_chain.max), (2, 80));
+		assert_eq!(latency.quote_to_chain.buckets[3], HistogramBucket { le: Some(10), count: 1 });
+		assert_eq!(latency.quote_to_chain.buckets[11], HistogramBucket { le: None, count: 2 });
+		assert_eq!((latency.quote_to_chain.p50, latency.quote_to_chain.p95), (Some(10), Some(120)));
+	}
+}
@@ -0,0 +1,186 @@
+use crate::args::LoadTestArgs;
+use crate::handlers::Currency;
+use crate::request_signing::{RequestSigner, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use chrono::Utc;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadTestReport {
+	pub requests: usize,
+	pub errors: usize,
+	pub elapsed: Duration,
+	pub p50: Duration,
+	pub p90: Duration,
+	pub p99: Duration,
+	pub max: Duration,
+}
+
+impl LoadTestReport {
+	/// Builds the report of a run from the latency of each request, `None` for failed requests
+	pub fn new(results: Vec<Option<Duration>>, elapsed: Duration) -> Self {
+		let requests = results.len();
+		let mut latencies: Vec<Duration> = results.into_iter().flatten().collect();
+		latencies.sort();
+		Self {
+			requests,
+			errors: requests - latencies.len(),
+			elapsed,
+			p50: percentile(&latencies, 50.0),
+			p90: percentile(&latencies, 90.0),
+			p99: percentile(&latencies, 99.0),
+			max: latencies.last().copied().unwrap_or_default(),
+		}
+	}
+}
+
+impl Display for LoadTestReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let seconds = self.elapsed.as_secs_f64().max(f64::EPSILON);
+		writeln!(
+			f,
+			"{} requests in {:.1}s ({:.1} req/s)",
+			self.requests,
+			seconds,
+			self.requests as f64 / seconds
+		)?;
+		writeln!(
+			f,
+			"errors: {} ({:.2}%)",
+			self.errors,
+			100.0 * self.errors as f64 / self.requests.max(1) as f64
+		)?;
+		write!(
+			f,
+			"latency p50: {:?}, p90: {:?}, p99: {:?}, max: {:?}",
+			self.p50, self.p90, self.p99, self.max
+		)
+	}
+}
+
+/// Nearest-rank percentile of sorted `latencies`
+pub fn percentile(latencies: &[Duration], percentile: f64) -> Duration {
+	if latencies.is_empty() {
+		return Duration::default()
+	}
+	let rank = (percentile / 100.0 * latencies.len() as f64).ceil() as usize;
+	latencies[rank.clamp(1, latencies.len()) - 1]
+}
+
+/// Body of every request: `currencies` first, filled up to `assets` with assets the server doesn't
+/// know, which it has to look up and skip all the same
+pub fn request_body(currencies: &[String], assets: usize) -> Vec<Currency> {
+	let known = currencies.iter().filter_map(|currency| {
+		let (blockchain, symbol) = currency.trim().split_once(':')?;
+		Some(Currency { blockchain: blockchain.into(), symbol: symbol.into() })
+	});
+	let unknown =
+		(0..).map(|i| Currency { blockchain: "LoadTest".into(), symbol: format!("ASSET-{}", i) });
+	known.chain(unknown).take(assets).collect()
+}
+
+async fn send(
+	client: &reqwest::Client,
+	url: &str,
+	path: &str,
+	body: &[Currency],
+	signer: Option<&RequestSigner>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+	let mut request = client.post(url).json(body);
+	if let Some(signer) = signer {
+		let (timestamp, signature) = signer.sign(Utc::now().timestamp().unsigned_abs(), path);
+		request = request.header(TIMESTAMP_HEADER, timestamp).header(SIGNATURE_HEADER, signature);
+	}
+	request.send().await?.error_for_status()?;
+	Ok(())
+}
+
+/// Sends `POST /currencies` requests to the target at a fixed rate and prints latency
+/// percentiles and the error rate
+pub async fn run(args: LoadTestArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+	if args.rps == 0 {
+		return Err("--rps needs to be greater than 0".into())
+	}
+	let url = reqwest::Url::parse(&args.target)?;
+	let path = match url.query() {
+		Some(query) => format!("{}?{}", url.path(), query),
+		None => url.path().to_string(),
+	};
+	let url = url.to_string();
+	let body = Arc::new(request_body(&args.currencies.0, args.assets));
+	let signer = args.hmac_key.map(|key| Arc::new(RequestSigner::new(key.into_bytes(), 0)));
+	let client = reqwest::Client::builder()
+		.timeout(Duration::from_millis(args.request_timeout_in_milliseconds))
+		.build()?;
+	let total = args.rps * args.duration_in_seconds;
+
+	println!(
+		"Sending {} requests with {} assets each to {} at {} req/s",
+		total,
+		body.len(),
+		url,
+		args.rps
+	);
+	let started = Instant::now();
+	let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / args.rps as f64));
+	let mut handles = Vec::with_capacity(total as usize);
+	for _ in 0..total {
+		interval.tick().await;
+		let (client, url, path, body, signer) =
+			(client.clone(), url.clone(), path.clone(), body.clone(), signer.clone());
+		handles.push(tokio::spawn(async move {
+			let sent = Instant::now();
+			send(&client, &url, &path, &body, signer.as_deref()).await.ok()?;
+			Some(sent.elapsed())
+		}));
+	}
+	let results = futures::future::join_all(handles)
+		.await
+		.into_iter()
+		.map(|result| result.ok().flatten())
+		.collect();
+
+	println!("{}", LoadTestReport::new(results, started.elapsed()));
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_percentile() {
+		let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+		assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(50));
+		assert_eq!(percentile(&latencies, 99.0), Duration::from_millis(99));
+		assert_eq!(percentile(&latencies, 100.0), Duration::from_millis(100));
+		assert_eq!(percentile(&latencies[..1], 0.0), Duration::from_millis(1));
+		assert_eq!(percentile(&[], 50.0), Duration::default());
+	}
+
+	#[test]
+	fn test_report_counts_errors() {
+		let report = LoadTestReport::new(
+			vec![Some(Duration::from_millis(3)), None, Some(Duration::from_millis(1))],
+			Duration::from_secs(1),
+		);
+
+		assert_eq!(report.requests, 3);
+		assert_eq!(report.errors, 1);
+		assert_eq!(report.p50, Duration::from_millis(1));
+		assert_eq!(report.max, Duration::from_millis(3));
+	}
+
+	#[test]
+	fn test_request_body() {
+		let body = request_body(&["Bitcoin:BTC".into(), "invalid".into()], 3);
+
+		assert_eq!(body.len(), 3);
+		assert_eq!((body[0].blockchain.as_str(), body[0].symbol.as_str()), ("Bitcoin", "BTC"));
+		assert_eq!((body[2].blockchain.as_str(), body[2].symbol.as_str()), ("LoadTest", "ASSET-1"));
+		assert_eq!(request_body(&["Bitcoin:BTC".into()], 0).len(), 0);
+	}
+}
@@ -0,0 +1,164 @@
+use arc_swap::ArcSwap;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+/// Format of the log lines, set through `DIA_LOG_FORMAT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+	/// Colored lines of `pretty_env_logger`
+	Pretty,
+	/// One JSON object per line with the fields of the event and of the spans it happened in, like
+	/// the `cycle_id` of the update cycle
+	Json,
+}
+
+impl FromStr for LogFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"" | "pretty" => Ok(LogFormat::Pretty),
+			"json" => Ok(LogFormat::Json),
+			other => Err(format!("Invalid log format '{}', expected one of pretty, json", other)),
+		}
+	}
+}
+
+type Reload = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+enum Backend {
+	Pretty(Arc<ArcSwap<Box<dyn Log>>>),
+	/// `tracing` subscriber the `log` records are forwarded to, with a reloadable filter
+	Json(Reload),
+}
+
+/// `RUST_LOG` style directives of the installed logger, which replaces its filter when they are
+/// set through `POST /admin/loglevel`
+pub struct LogFilter {
+	directives: ArcSwap<String>,
+	backend: Backend,
+}
+
+impl LogFilter {
+	/// Installs a logger in the `DIA_LOG_FORMAT` filtering by `RUST_LOG`
+	pub fn init() -> Result<Arc<Self>, String> {
+		let directives = std::env::var("RUST_LOG").unwrap_or_default();
+		let format: LogFormat = std::env::var("DIA_LOG_FORMAT").unwrap_or_default().parse()?;
+		let (logger, max_level) = build_logger(&directives);
+		let backend = match format {
+			LogFormat::Pretty => {
+				let logger = Arc::new(ArcSwap::from_pointee(logger));
+				log::set_boxed_logger(Box::new(InstalledLogger(logger.clone())))
+					.expect("no logger was installed before");
+				Backend::Pretty(logger)
+			},
+			LogFormat::Json => {
+				let builder = tracing_subscriber::fmt()
+					.json()
+					.flatten_event(true)
+					.with_writer(std::io::stderr)
+					.with_env_filter(build_env_filter(&directives)?)
+					.with_filter_reloading();
+				let handle = builder.reload_handle();
+				// Also forwards the `log` records to the subscriber
+				builder.try_init().map_err(|err| err.to_string())?;
+				Backend::Json(Box::new(move |directives| {
+					handle.reload(build_env_filter(directives)?).map_err(|err| err.to_string())
+				}))
+			},
+		};
+		log::set_max_level(max_level);
+		Ok(Arc::new(LogFilter { directives: ArcSwap::from_pointee(directives), backend }))
+	}
+
+	pub fn directives(&self) -> String {
+		self.directives.load().as_ref().clone()
+	}
+
+	/// Replaces the filter of the logger, keeping it if `directives` are invalid
+	pub fn set_directives(&self, directives: &str) -> Result<(), String> {
+		validate_directives(directives)?;
+		let (logger, max_level) = build_logger(directives);
+		match &self.backend {
+			Backend::Pretty(installed) => installed.store(Arc::new(logger)),
+			Backend::Json(reload) => reload(directives)?,
+		}
+		self.directives.store(Arc::new(directives.to_string()));
+		log::set_max_level(max_level);
+		Ok(())
+	}
+}
+
+struct InstalledLogger(Arc<ArcSwap<Box<dyn Log>>>);
+
+impl Log for InstalledLogger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		self.0.load().enabled(metadata)
+	}
+
+	fn log(&self, record: &Record) {
+		self.0.load().log(record)
+	}
+
+	fn flush(&self) {
+		self.0.load().flush()
+	}
+}
+
+/// `tracing` filter of the directives, which doesn't support the regex filters after a `/`
+fn build_env_filter(directives: &str) -> Result<EnvFilter, String> {
+	EnvFilter::try_new(directives)
+		.map_err(|err| format!("Invalid log directives '{}': {}", directives, err))
+}
+
+fn build_logger(directives: &str) -> (Box<dyn Log>, LevelFilter) {
+	let mut builder = pretty_env_logger::formatted_builder();
+	builder.parse_filters(directives);
+	let logger = builder.build();
+	let max_level = logger.filter();
+	(Box::new(logger), max_level)
+}
+
+/// Rejects directives `env_logger` would silently ignore, which are entries with a level that
+/// doesn't parse. Regex filters after a `/` are passed on unchecked.
+fn validate_directives(directives: &str) -> Result<(), String> {
+	let directives = directives.split('/').next().unwrap_or_default();
+	for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+		let level = match directive.split_once('=') {
+			Some((_, level)) => level,
+			// A bare module name enables all levels of it
+			None if directive.parse::<LevelFilter>().is_err() => continue,
+			None => directive,
+		};
+		level
+			.trim()
+			.parse::<LevelFilter>()
+			.map_err(|_| format!("Invalid log level '{}' in '{}'", level, directive))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_validate_directives() {
+		assert!(validate_directives("").is_ok());
+		assert!(validate_directives("info").is_ok());
+		assert!(validate_directives("info,dia_batching_server::dia=trace").is_ok());
+		assert!(validate_directives("dia_batching_server::dia").is_ok());
+		assert!(validate_directives("warn/Published batch").is_ok());
+		assert!(validate_directives("dia_batching_server::dia=loud").is_err());
+		assert!(validate_directives("info,actix_web=").is_err());
+	}
+
+	#[test]
+	fn test_log_format_from_str() {
+		assert_eq!("".parse(), Ok(LogFormat::Pretty));
+		assert_eq!("JSON".parse(), Ok(LogFormat::Json));
+		assert!("logfmt".parse::<LogFormat>().is_err());
+	}
+}
@@ -1,38 +1,209 @@
+use crate::chain::ChainClient;
+use crate::coinmarketcap::CoinMarketCap;
+use crate::cors::cors;
+use crate::deviation::DeviationGuard;
 use crate::dia::Dia;
-use crate::handlers::currencies_post;
-use crate::storage::CoinInfoStorage;
+use crate::exchanges::{ExchangeRouter, RetryPolicy};
+use crate::handlers::{
+	asset_errors_get, asset_statuses_get, costs_get, currencies_get, currencies_post, health_get,
+	history_get, latency_get, log_level_post, memory_usage_get, proof_get, quarantine_get,
+	quarantine_post, quarantine_release_post, readiness_get, smoothing_get,
+	supported_currencies_post, LatencyTracking, MaxHandlingTime, MaxUpdateAge, PriceLogs,
+	QuarantineControl, ReloadableCurrencies,
+};
+use crate::hysteresis::Hysteresis;
+use crate::latency::LatencyTracker;
+use crate::logging::LogFilter;
+use crate::price_log::PriceLog;
+use crate::quarantine::Quarantine;
+use crate::rate_limit::RateLimiter;
+use crate::report::{FeedStatistics, ReportTargets};
+use crate::response_signing::ResponseSigner;
+use crate::schedule::UpdateSchedule;
+use crate::smoothing::PriceSmoothing;
+use crate::snapshot::Snapshot;
+use crate::status::AssetStatuses;
+use crate::storage::{AssetErrorLog, CoinInfoStorage};
+use crate::supply::Supplies;
+use crate::timestamps::TimestampPolicy;
+use crate::tls::ReloadableCertificate;
+use crate::ws::ws_get;
 use std::error::Error;
 
-use crate::args::DiaApiArgs;
+use crate::admin_auth::AdminKey;
+use crate::args::{Command, DiaApiArgs};
+use crate::request_signing::RequestSigner;
+use actix_web::dev::Service;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::middleware::Condition;
 use actix_web::{web, App, HttpServer};
-use log::error;
+use arc_swap::ArcSwapOption;
+use chrono::Utc;
+use futures::future::{self, Either};
+use log::{debug, error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::sync::Arc;
 use structopt::StructOpt;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 
+mod admin_auth;
 mod args;
+mod chain;
+mod coinmarketcap;
+mod conflicts;
+mod cors;
+mod cross_check;
+mod deviation;
 mod dia;
+mod exchangerate;
+mod exchanges;
 mod handlers;
+mod history;
+mod hysteresis;
+mod latency;
+mod loadtest;
+mod logging;
+mod merkle;
+mod one_shot;
+mod price_log;
 mod price_updater;
+mod probes;
+mod publication;
+mod quarantine;
+mod rate_limit;
+mod reconcile;
+mod report;
+mod request_signing;
+mod response_signing;
+mod schedule;
+mod self_test;
+mod smoothing;
+mod snapshot;
+mod spread;
+mod status;
 mod storage;
+mod supply;
+mod timestamps;
+mod tls;
+mod twap;
+mod verify;
+mod watch;
+mod ws;
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AssetSpecifier {
 	blockchain: String,
 	symbol: String,
 }
 
+impl FromStr for AssetSpecifier {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (blockchain, symbol) = s
+			.trim()
+			.split_once(':')
+			.ok_or_else(|| format!("Invalid asset '{}', expected <blockchain>:<symbol>", s))?;
+		Ok(AssetSpecifier { blockchain: blockchain.into(), symbol: symbol.into() })
+	}
+}
+
+/// Parses per-asset options of the form `<blockchain>:<symbol>=<value>,...`
+pub fn parse_asset_map<T>(s: &str) -> Result<HashMap<AssetSpecifier, T>, String>
+where
+	T: FromStr<Err = String>,
+{
+	s.split(',')
+		.filter(|entry| !entry.trim().is_empty())
+		.map(|entry| {
+			let (asset, value) = entry.split_once('=').ok_or_else(|| {
+				format!("Invalid entry '{}', expected <blockchain>:<symbol>=<value>", entry)
+			})?;
+			Ok((asset.parse()?, value.parse()?))
+		})
+		.collect()
+}
+
+/// Assets the server fetches prices for, all quotable assets if `None`
+pub type SupportedAssets = ArcSwapOption<HashSet<AssetSpecifier>>;
+
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-	pretty_env_logger::init();
+	let log_filter = web::Data::from(LogFilter::init()?);
+
+	let args: DiaApiArgs = DiaApiArgs::from_args_and_env();
+	// Without a subcommand the same process fetches and serves the prices
+	let (fetch, serve, snapshot_args) = match args.command {
+		Some(Command::Verify(verify_args)) => return verify::run(verify_args).await,
+		Some(Command::LoadTest(load_test_args)) => return loadtest::run(load_test_args).await,
+		Some(Command::History(history_args)) => return history::run(history_args).await,
+		Some(Command::Watch(watch_args)) => return watch::run(watch_args).await,
+		Some(Command::Reconcile(reconcile_args)) => return reconcile::run(reconcile_args).await,
+		Some(Command::Fetch(snapshot_args)) => (true, false, Some(snapshot_args)),
+		Some(Command::Serve(snapshot_args)) => (false, true, Some(snapshot_args)),
+		None => (true, true, None),
+	};
+	if args.one_shot && !fetch {
+		return Err("--one-shot needs a command fetching the prices".into())
+	}
 
-	let args: DiaApiArgs = DiaApiArgs::from_args();
-	let storage = Arc::new(CoinInfoStorage::default());
+	let storage = Arc::new(
+		CoinInfoStorage::default()
+			.with_max_age(args.max_price_age_in_seconds)
+			.with_price_history(args.price_history_in_seconds)
+			.with_change_epsilon(args.change_epsilon_in_bps),
+	);
 	let data = web::Data::from(storage.clone());
+	let errors = Arc::new(
+		AssetErrorLog::new(args.errors_per_asset).with_max_memory_usage(args.max_error_log_bytes),
+	);
+	let errors_data = web::Data::from(errors.clone());
+	if args.state_file.is_some() && snapshot_args.is_some() {
+		return Err("--state-file is for running without a command, use --snapshot-file".into())
+	}
+	if let Some(path) = args.state_file.as_ref().filter(|path| path.exists()) {
+		match snapshot::read_snapshot(path).await {
+			Ok(snapshot) => {
+				info!("Restored {} coin infos from {}", snapshot.coin_infos.len(), path.display());
+				snapshot.restore(&storage, &errors);
+			},
+			Err(err) => warn!("Failed to restore the state from {}: {}", path.display(), err),
+		}
+	}
+	// Restored prices were logged when they were served before
+	let price_log = match args.price_log_dir {
+		Some(dir) => {
+			let log = Arc::new(PriceLog::open(dir)?);
+			price_log::run_price_log_loop(log.clone(), storage.subscribe());
+			Some(log)
+		},
+		None => None,
+	};
+	let price_logs = web::Data::new(PriceLogs { log: price_log });
+	let latency_tracker = match args.latency_rpc_url {
+		Some(url) => {
+			let tracker = Arc::new(LatencyTracker::default());
+			latency::run_latency_loops(
+				ChainClient::new(url, args.chain_pallet_name.clone()),
+				tracker.clone(),
+				storage.subscribe(),
+				std::time::Duration::from_secs(args.latency_poll_interval_in_seconds),
+			);
+			Some(tracker)
+		},
+		None => None,
+	};
+	let latency_tracking = web::Data::new(LatencyTracking { tracker: latency_tracker });
+	let max_handling_time = web::Data::new(MaxHandlingTime(std::time::Duration::from_millis(
+		args.max_handling_time_in_milliseconds,
+	)));
 
 	let supported_currencies_vec = Some(args.supported_currencies.0);
 
-	price_updater::run_update_prices_loop(
-		storage,
+	let supported_currencies: Option<HashSet<AssetSpecifier>> =
 		supported_currencies_vec.filter(|x| x.len() > 0).map(|curs| {
 			curs.into_iter()
 				.filter_map(|asset| {
@@ -43,19 +214,450 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
 					Some(AssetSpecifier { blockchain: blockchain.into(), symbol: symbol.into() })
 				})
 				.collect()
-		}),
-		std::time::Duration::from_millis(args.request_timeout_in_milliseconds),
-		std::time::Duration::from_secs(args.iteration_timeout_in_seconds),
-		Dia,
-	)
-	.await?;
-
-	println!("Running dia-batching-server... (Press CTRL+C to quit)");
-	HttpServer::new(move || App::new().app_data(data.clone()).service(currencies_post))
-		.on_connect(|_, _| println!("Serving Request"))
-		.bind("0.0.0.0:8070")?
-		.run()
+		});
+
+	let synced_from_chain = args.chain_rpc_url.is_some();
+	let supported_currencies = match args.chain_rpc_url {
+		Some(url) => {
+			let client = ChainClient::new(url, args.chain_pallet_name.clone());
+			let currencies = client.supported_currencies().await?;
+			info!("Read {} supported currencies from {}", currencies.len(), client.url());
+			let currencies = Arc::new(SupportedAssets::from_pointee(currencies));
+			chain::run_supported_currencies_sync_loop(
+				client,
+				currencies.clone(),
+				std::time::Duration::from_secs(args.chain_sync_interval_in_seconds),
+			);
+			currencies
+		},
+		None => Arc::new(SupportedAssets::new(supported_currencies.map(Arc::new))),
+	};
+
+	if let Some(assets) = supported_currencies.load_full() {
+		for symbols in conflicts::shared_symbols(&assets) {
+			warn!("Supported currencies on different blockchains share a symbol: {}", symbols);
+		}
+		let configured = args
+			.exchange_sources
+			.0
+			.keys()
+			.chain(args.fallback_sources.0.keys())
+			.chain(args.coinmarketcap_ids.0.keys())
+			.chain(args.timestamp_sources.0.keys())
+			.chain(args.publication_schedules.0.keys())
+			.chain(args.publication_thresholds.0.keys())
+			.chain(args.price_spreads.0.keys())
+			.chain(args.smoothing_alphas.0.keys())
+			.chain(args.deviation_limits.0.keys())
+			.chain(args.supply_sources.0.keys());
+		for mismatch in conflicts::configured_label_mismatches(&assets, configured) {
+			warn!("Options are configured for an asset that isn't supported: {}", mismatch);
+		}
+		let conflicts = conflicts::normalization_conflicts(&assets);
+		if !conflicts.is_empty() {
+			let report = format!(
+				"Supported currencies only differ in case and would be fetched from the same \
+				 quotation: {}",
+				conflicts.join("; ")
+			);
+			if !args.allow_asset_conflicts {
+				return Err(report.into())
+			}
+			warn!("{}", report);
+		}
+	}
+
+	let hmac_max_skew_in_seconds = args.hmac_max_skew_in_seconds;
+	let admin_key = args.admin_key.filter(|key| !key.is_empty());
+	if admin_key.is_some() && admin_key == args.hmac_key {
+		return Err("--admin-key has to differ from the --hmac-key of the offchain workers".into())
+	}
+	let admin_key = admin_key.map(|key| AdminKey::new(key.into_bytes(), hmac_max_skew_in_seconds));
+	if admin_key.is_none() && serve {
+		warn!("No --admin-key set, the /admin endpoints answer 403");
+	}
+	let signer = args
+		.hmac_key
+		.map(|key| RequestSigner::new(key.into_bytes(), hmac_max_skew_in_seconds));
+	let response_signer = match (args.response_signing_key, &args.response_signing_key_file) {
+		(Some(_), Some(_)) =>
+			return Err("Give either --response-signing-key or --response-signing-key-file".into()),
+		(Some(key), None) => Some(key.parse::<ResponseSigner>()?),
+		(None, Some(path)) => Some(ResponseSigner::read(path).await?),
+		(None, None) => None,
+	};
+	if let Some(response_signer) = &response_signer {
+		info!("Signing responses with public key {}", response_signer.public_key());
+	}
+	let certificate = match args.tls_cert.zip(args.tls_key) {
+		Some((cert_path, key_path)) =>
+			Some(Arc::new(ReloadableCertificate::read(cert_path, key_path)?)),
+		None => None,
+	};
+	let api_keys: HashSet<String> = args
+		.api_keys
+		.iter()
+		.flat_map(|keys| keys.split(','))
+		.map(|key| key.trim().to_string())
+		.filter(|key| !key.is_empty())
+		.collect();
+	let rate_limiter = Arc::new(RateLimiter::new(args.rate_limits, api_keys));
+	let rate_limit_forwarded_for = args.rate_limit_forwarded_for;
+	let (cors_origins, cors_methods) = (args.cors_origins, args.cors_methods);
+
+	if let Some(interval) = args.self_test_interval_in_seconds.filter(|_| serve) {
+		self_test::run_self_test_loop(
+			args.port,
+			certificate.is_some(),
+			supported_currencies.clone(),
+			std::time::Duration::from_secs(interval),
+			signer.clone(),
+		);
+	}
+
+	let reloadable_currencies = web::Data::new(ReloadableCurrencies {
+		assets: Some(supported_currencies.clone()).filter(|_| fetch && !synced_from_chain),
+		allow_asset_conflicts: args.allow_asset_conflicts,
+	});
+
+	let statistics = Arc::new(
+		FeedStatistics::new(Utc::now().timestamp().unsigned_abs())
+			.with_source_costs(args.source_costs),
+	);
+	let statistics_data = web::Data::from(statistics.clone());
+	let statuses =
+		Arc::new(AssetStatuses::default().with_configured_assets(supported_currencies.clone()));
+	let statuses_data = web::Data::from(statuses.clone());
+	// Prices served from a snapshot are fetched and judged by another process
+	let max_update_age = Some(args.max_update_age_in_seconds).filter(|_| fetch);
+	let max_update_age = web::Data::new(MaxUpdateAge(max_update_age));
+	let update_schedule = Arc::new(UpdateSchedule::new(args.publication_schedules));
+	let update_schedule_data = web::Data::from(update_schedule.clone());
+	let quarantine = Arc::new(Quarantine::new(
+		args.deviation_limits.clone(),
+		args.quarantine_after_rejections,
+		args.quarantine_probation_samples,
+	));
+	let quarantine_control = web::Data::new(QuarantineControl {
+		quarantine: Some(quarantine.clone()).filter(|_| fetch),
+	});
+	let smoothing = Arc::new(PriceSmoothing::new(args.smoothing_alphas));
+	let smoothing_data = web::Data::from(smoothing.clone());
+	let mut price_updates = None;
+	if fetch {
+		let report_targets = ReportTargets { file: args.report_file, webhook: args.report_webhook };
+		if !args.one_shot && (report_targets.file.is_some() || report_targets.webhook.is_some()) {
+			report::run_report_loop(
+				statistics.clone(),
+				report_targets,
+				std::time::Duration::from_secs(args.report_interval_in_seconds),
+			);
+		}
+
+		let router = ExchangeRouter::new(Dia, args.exchange_sources.clone())
+			.with_fallback_sources(args.fallback_sources)
+			.with_blockchain_sources(args.blockchain_sources)
+			.with_source_timeouts(
+				std::time::Duration::from_millis(args.source_timeout_in_milliseconds),
+				args.source_timeouts,
+			)
+			.with_retry_policy(RetryPolicy {
+				retries: args.source_retries,
+				backoff: std::time::Duration::from_millis(
+					args.source_retry_backoff_in_milliseconds,
+				),
+			})
+			.with_quarantine(quarantine.clone());
+		let api = Arc::new(match args.coinmarketcap_api_key {
+			Some(api_key) =>
+				router.with_coinmarketcap(CoinMarketCap::new(api_key, args.coinmarketcap_ids)),
+			None => router,
+		});
+
+		if args.one_shot {
+			let supplies = Supplies::default();
+			supply::read_supplies_once(args.supply_sources, &supplies).await;
+			price_updater::update_prices_once(
+				storage.clone(),
+				&errors,
+				&statistics,
+				&statuses,
+				&TimestampPolicy::new(args.default_timestamp_source, args.timestamp_sources),
+				update_schedule.schedules(),
+				&args.price_spreads,
+				&smoothing,
+				&Hysteresis::new(
+					args.publication_thresholds,
+					Some(args.publication_threshold_heartbeat_in_seconds),
+				),
+				&DeviationGuard::new(args.deviation_limits, args.deviation_hold_cycles),
+				&supplies,
+				&supported_currencies,
+				std::time::Duration::from_millis(args.request_timeout_in_milliseconds),
+				&api,
+			)
+			.await;
+			let report = statistics.take_report(Utc::now().timestamp().unsigned_abs());
+			let supported = supported_currencies.load_full();
+			return one_shot::print(storage.get_all_currencies(), &report, supported.as_deref())
+		}
+
+		let probed_assets = match supported_currencies.load_full() {
+			Some(assets) => assets.as_ref().clone(),
+			None => args.exchange_sources.0.keys().cloned().collect(),
+		};
+		let probes = probes::probe_sources(
+			&api,
+			&probed_assets,
+			std::time::Duration::from_millis(args.request_timeout_in_milliseconds),
+		)
+		.await;
+		let failures = probes::report(&probes);
+		if args.fail_fast && !failures.is_empty() {
+			return Err(format!("Sources failed the startup probe: {}", failures.join("; ")).into())
+		}
+
+		if let Some(url) = args.seed_rpc_url {
+			let client = ChainClient::new(url, args.chain_pallet_name.clone());
+			let supported = supported_currencies.load_full();
+			match chain::seed_storage(
+				&client,
+				&storage,
+				supported.as_deref(),
+				std::time::Duration::from_secs(args.seed_timeout_in_seconds),
+			)
+			.await
+			{
+				Ok(seeded) => info!("Seeded {} coin infos from {}", seeded, client.url()),
+				Err(err) => warn!("Failed to seed coin infos from {}: {}", client.url(), err),
+			}
+		}
+
+		if let Some(interval) = args.cross_check_interval_in_seconds {
+			cross_check::run_cross_check_loop(
+				storage.clone(),
+				errors.clone(),
+				supported_currencies.clone(),
+				args.cross_check_tolerance_in_bps,
+				std::time::Duration::from_secs(interval),
+			);
+		}
+
+		let supplies = Arc::new(Supplies::default());
+		if !args.supply_sources.0.is_empty() {
+			supply::run_supply_loop(
+				args.supply_sources,
+				supplies.clone(),
+				std::time::Duration::from_secs(args.supply_refresh_interval_in_seconds),
+			);
+		}
+
+		if args.quarantine_after_rejections.is_some() {
+			quarantine::run_probation_loop(
+				api.clone(),
+				storage.clone(),
+				quarantine.clone(),
+				std::time::Duration::from_secs(args.quarantine_probe_interval_in_seconds),
+			);
+		}
+
+		let (stop_updates, updates_stopped) = oneshot::channel();
+		let update_loop = price_updater::run_update_prices_loop(
+			storage.clone(),
+			errors.clone(),
+			statistics,
+			statuses,
+			TimestampPolicy::new(args.default_timestamp_source, args.timestamp_sources),
+			update_schedule,
+			args.price_spreads,
+			smoothing,
+			Hysteresis::new(
+				args.publication_thresholds,
+				Some(args.publication_threshold_heartbeat_in_seconds),
+			),
+			DeviationGuard::new(args.deviation_limits, args.deviation_hold_cycles)
+				.with_quarantine(quarantine),
+			supplies,
+			supported_currencies,
+			std::time::Duration::from_millis(args.request_timeout_in_milliseconds),
+			std::time::Duration::from_secs(args.iteration_timeout_in_seconds),
+			api,
+			updates_stopped,
+		)
 		.await?;
+		price_updates = Some((stop_updates, update_loop));
+	}
 
+	let state_file = args.state_file;
+	if let Some(path) = &state_file {
+		snapshot::run_write_snapshot_loop(
+			path.clone(),
+			storage.clone(),
+			errors.clone(),
+			std::time::Duration::from_secs(args.state_interval_in_seconds),
+		);
+	}
+
+	if let Some(snapshot_args) = snapshot_args {
+		let interval = std::time::Duration::from_secs(snapshot_args.snapshot_interval_in_seconds);
+		if serve {
+			snapshot::run_read_snapshot_loop(
+				snapshot_args.snapshot_file,
+				storage,
+				errors,
+				interval,
+			);
+		} else {
+			info!(
+				"Fetching prices to {}... (Press CTRL+C to quit)",
+				snapshot_args.snapshot_file.display()
+			);
+			snapshot::run_write_snapshot_loop(
+				snapshot_args.snapshot_file.clone(),
+				storage.clone(),
+				errors.clone(),
+				interval,
+			);
+			tokio::signal::ctrl_c().await?;
+			shut_down(price_updates, &storage, &statistics_data).await;
+			// Keep the prices of the last iteration for the serving process
+			let snapshot = Snapshot::take(&storage, &errors);
+			snapshot::write_snapshot(&snapshot_args.snapshot_file, &snapshot).await?;
+			return Ok(())
+		}
+	}
+
+	// The app takes the data it serves, which the shutdown needs as well
+	let served = (data.clone(), errors_data.clone(), statistics_data.clone());
+	info!("Running dia-batching-server... (Press CTRL+C to quit)");
+	let server = HttpServer::new(move || {
+		let signer = signer.clone();
+		let response_signer = response_signer.clone();
+		let admin_key = admin_key.clone();
+		let rate_limiter = rate_limiter.clone();
+		App::new()
+			.wrap_fn(move |req, srv| {
+				let verified = match &signer {
+					Some(signer)
+						if req.path().starts_with("/currencies") ||
+							req.path().starts_with("/ws") =>
+						signer.verify_request(&req, Utc::now().timestamp().unsigned_abs()),
+					_ => Ok(()),
+				};
+				match verified {
+					Ok(()) => Either::Left(srv.call(req)),
+					Err(err) => {
+						warn!("Rejected request to {}: {}", req.path(), err);
+						Either::Right(future::err(ErrorUnauthorized(err.to_string())))
+					},
+				}
+			})
+			// Wraps the signature check, so limited requests are rejected before verifying them
+			.wrap_fn(move |req, srv| {
+				if !rate_limiter.is_enabled() {
+					return Either::Left(srv.call(req))
+				}
+				let client = match rate_limit_forwarded_for {
+					true => req.connection_info().realip_remote_addr().and_then(parse_client),
+					false => req.peer_addr().map(|addr| addr.ip()),
+				};
+				let api_key = req
+					.headers()
+					.get(rate_limit::API_KEY_HEADER)
+					.map(|key| key.to_str().unwrap_or_default().to_string());
+				let now = std::time::Instant::now();
+				match rate_limiter.check(req.path(), client, api_key.as_deref(), now) {
+					Ok(()) => Either::Left(srv.call(req)),
+					Err(err) => Either::Right(future::err(actix_web::Error::from(err))),
+				}
+			})
+			// Wraps the rate limit and signature check, so preflight requests pass without a
+			// signature
+			.wrap(Condition::new(cors_origins.is_enabled(), cors(&cors_origins, &cors_methods)))
+			.app_data(data.clone())
+			.app_data(errors_data.clone())
+			.app_data(statistics_data.clone())
+			.app_data(statuses_data.clone())
+			.app_data(max_handling_time.clone())
+			.app_data(max_update_age.clone())
+			.app_data(log_filter.clone())
+			.app_data(reloadable_currencies.clone())
+			.app_data(update_schedule_data.clone())
+			.app_data(quarantine_control.clone())
+			.app_data(price_logs.clone())
+			.app_data(latency_tracking.clone())
+			.app_data(smoothing_data.clone())
+			.configure(|config| {
+				if let Some(response_signer) = response_signer {
+					config.data(response_signer);
+				}
+				// Admin requests are verified by their handlers, which have to read the body
+				if let Some(admin_key) = admin_key {
+					config.data(admin_key);
+				}
+			})
+			.service(currencies_post)
+			.service(currencies_get)
+			.service(asset_errors_get)
+			.service(asset_statuses_get)
+			.service(memory_usage_get)
+			.service(latency_get)
+			.service(smoothing_get)
+			.service(costs_get)
+			.service(proof_get)
+			.service(health_get)
+			.service(readiness_get)
+			.service(log_level_post)
+			.service(supported_currencies_post)
+			.service(quarantine_get)
+			.service(quarantine_post)
+			.service(quarantine_release_post)
+			.service(history_get)
+			.service(ws_get)
+	})
+	.on_connect(|_, _| debug!("Serving Request"))
+	.shutdown_timeout(args.shutdown_timeout_in_seconds);
+	let server = match certificate {
+		Some(certificate) => {
+			tls::run_reload_on_hangup_loop(certificate.clone())?;
+			server.bind_rustls(("0.0.0.0", args.port), certificate.server_config())?
+		},
+		None => server.bind(("0.0.0.0", args.port))?,
+	};
+	server.run().await?;
+
+	let (storage, errors, statistics) = served;
+	shut_down(price_updates, &storage, &statistics).await;
+	if let Some(path) = state_file {
+		snapshot::write_snapshot(&path, &Snapshot::take(&storage, &errors)).await?;
+	}
 	Ok(())
 }
+
+/// Address of a client from the `Forwarded` or `X-Forwarded-For` header, with or without port
+fn parse_client(addr: &str) -> Option<IpAddr> {
+	addr.parse::<SocketAddr>()
+		.map(|addr| addr.ip())
+		.or_else(|_| addr.trim_matches(|c| c == '[' || c == ']').parse())
+		.ok()
+}
+
+/// Stops the price updates, if this process runs them, and logs the state the server stops with
+async fn shut_down(
+	price_updates: Option<(oneshot::Sender<()>, JoinHandle<()>)>,
+	storage: &CoinInfoStorage,
+	statistics: &FeedStatistics,
+) {
+	if let Some((stop_updates, update_loop)) = price_updates {
+		let _ = stop_updates.send(());
+		if let Err(err) = update_loop.await {
+			error!("Price updates didn't stop cleanly: {}", err);
+		}
+	}
+	let health = statistics.health(Utc::now().timestamp().unsigned_abs(), u64::MAX);
+	info!(
+		"Shut down with {} prices, the last update iteration finished at {:?}",
+		storage.len(),
+		health.last_iteration
+	);
+}
@@ -0,0 +1,208 @@
+//! Merkle tree over the published coin infos, identical to the price root the dia-oracle pallet
+//! commits to: leaves are `blake2_256(0x00 ++ SCALE((AssetId, CoinInfo)))` ordered by the SCALE
+//! encoded `AssetId`, inner nodes `blake2_256(0x01 ++ left ++ right)`, a node without sibling is
+//! carried up.
+use crate::{
+	chain::{OnChainAssetId, OnChainCoinInfo},
+	storage::CoinInfo,
+};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use codec::Encode;
+use serde::{Deserialize, Serialize};
+
+/// Byte the preimage of a leaf hash starts with, as in the pallet
+const LEAF_PREFIX: u8 = 0x00;
+
+/// Byte the preimage of an inner node hash starts with, as in the pallet
+const NODE_PREFIX: u8 = 0x01;
+
+pub type Hash = [u8; 32];
+
+fn blake2_256(data: &[u8]) -> Hash {
+	Blake2b::<U32>::digest(data).into()
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+	blake2_256(&[&[NODE_PREFIX][..], &left[..], &right[..]].concat())
+}
+
+fn to_hex(hash: &Hash) -> String {
+	format!("0x{}", hex::encode(hash))
+}
+
+fn asset_id(coin_info: &CoinInfo) -> OnChainAssetId {
+	OnChainAssetId {
+		blockchain: coin_info.blockchain.as_bytes().to_vec(),
+		symbol: coin_info.symbol.as_bytes().to_vec(),
+	}
+}
+
+pub fn leaf_hash(coin_info: &CoinInfo) -> Hash {
+	let encoded = (asset_id(coin_info), OnChainCoinInfo::from(coin_info)).encode();
+	blake2_256(&[&[LEAF_PREFIX][..], &encoded].concat())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Position {
+	Left,
+	Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofNode {
+	pub hash: String,
+	/// Side the sibling is on when hashing it with the current node
+	pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleProof {
+	pub root: String,
+	pub leaf: String,
+	pub leaf_index: usize,
+	pub leaf_count: usize,
+	pub proof: Vec<ProofNode>,
+	pub coin_info: CoinInfo,
+}
+
+pub struct MerkleTree {
+	coin_infos: Vec<CoinInfo>,
+	layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+	pub fn new(coin_infos: Vec<CoinInfo>) -> Self {
+		let mut leaves: Vec<(Vec<u8>, Hash, CoinInfo)> = coin_infos
+			.into_iter()
+			.map(|coin_info| (asset_id(&coin_info).encode(), leaf_hash(&coin_info), coin_info))
+			.collect();
+		leaves.sort_by(|a, b| a.0.cmp(&b.0));
+
+		let mut layers = vec![leaves.iter().map(|(_, hash, _)| *hash).collect::<Vec<_>>()];
+		while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+			let next = layer
+				.chunks(2)
+				.map(|pair| match pair {
+					[left, right] => hash_pair(left, right),
+					[single] => *single,
+					_ => unreachable!("chunks of two"),
+				})
+				.collect();
+			layers.push(next);
+		}
+
+		Self { coin_infos: leaves.into_iter().map(|(_, _, coin_info)| coin_info).collect(), layers }
+	}
+
+	pub fn root(&self) -> Hash {
+		self.layers.last().and_then(|layer| layer.first()).copied().unwrap_or([0u8; 32])
+	}
+
+	pub fn proof(&self, blockchain: &str, symbol: &str) -> Option<MerkleProof> {
+		let leaf_index = self.coin_infos.iter().position(|coin_info| {
+			coin_info.blockchain == blockchain && coin_info.symbol == symbol
+		})?;
+
+		let mut proof = Vec::new();
+		let mut index = leaf_index;
+		for layer in &self.layers[..self.layers.len() - 1] {
+			let sibling = index ^ 1;
+			if let Some(hash) = layer.get(sibling) {
+				let position = if sibling < index { Position::Left } else { Position::Right };
+				proof.push(ProofNode { hash: to_hex(hash), position });
+			}
+			index /= 2;
+		}
+
+		Some(MerkleProof {
+			root: to_hex(&self.root()),
+			leaf: to_hex(&self.layers[0][leaf_index]),
+			leaf_index,
+			leaf_count: self.coin_infos.len(),
+			proof,
+			coin_info: self.coin_infos[leaf_index].clone(),
+		})
+	}
+}
+
+/// Recomputes the root from the coin info and the proof
+#[cfg(test)]
+pub fn verify_proof(proof: &MerkleProof) -> bool {
+	use std::convert::TryFrom;
+
+	let mut hash = leaf_hash(&proof.coin_info);
+	for node in &proof.proof {
+		let sibling = match hex::decode(node.hash.trim_start_matches("0x"))
+			.ok()
+			.and_then(|bytes| <Hash>::try_from(bytes).ok())
+		{
+			Some(sibling) => sibling,
+			None => return false,
+		};
+		hash = match node.position {
+			Position::Left => hash_pair(&sibling, &hash),
+			Position::Right => hash_pair(&hash, &sibling),
+		};
+	}
+	to_hex(&hash) == proof.root
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_infos() -> Vec<CoinInfo> {
+		vec![
+			CoinInfo {
+				symbol: "BTC".into(),
+				name: "Bitcoin".into(),
+				blockchain: "Bitcoin".into(),
+				supply: 9,
+				last_update_timestamp: 9,
+				price: 9,
+				..Default::default()
+			},
+			CoinInfo { symbol: "ETH".into(), blockchain: "Ethereum".into(), ..Default::default() },
+			CoinInfo {
+				symbol: "MXN-USD".into(),
+				name: "MXNUSD=X".into(),
+				blockchain: "FIAT".into(),
+				supply: 0,
+				last_update_timestamp: 1700000000,
+				price: 53712327000,
+				..Default::default()
+			},
+		]
+	}
+
+	#[test]
+	fn test_root_matches_pallet() {
+		// Same vector is checked on the pallet side
+		assert_eq!(
+			to_hex(&MerkleTree::new(coin_infos()).root()),
+			"0x30e0de58492619605a24af7f8ab8d301ad2db756312774b28ebd15575c7ae034"
+		);
+		assert_eq!(MerkleTree::new(vec![]).root(), [0u8; 32]);
+	}
+
+	#[test]
+	fn test_proofs_verify() {
+		let tree = MerkleTree::new(coin_infos());
+
+		for (blockchain, symbol) in [("Bitcoin", "BTC"), ("Ethereum", "ETH"), ("FIAT", "MXN-USD")] {
+			let proof = tree.proof(blockchain, symbol).expect("asset is in the tree");
+			assert_eq!(proof.coin_info.symbol, symbol);
+			assert!(verify_proof(&proof));
+
+			let mut tampered = proof.clone();
+			tampered.coin_info.price += 1;
+			assert!(!verify_proof(&tampered));
+		}
+		// The unpaired leaf is carried up and only needs the root of the other pair
+		assert_eq!(tree.proof("Ethereum", "ETH").map(|proof| proof.proof.len()), Some(1));
+		assert_eq!(tree.proof("Bitcoin", "DASH"), None);
+	}
+}
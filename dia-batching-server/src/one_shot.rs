@@ -0,0 +1,90 @@
+use crate::report::FeedReport;
+use crate::storage::CoinInfo;
+use crate::AssetSpecifier;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Assets of a single update iteration that didn't resolve to a coin info: assets whose update
+/// failed and supported currencies none of the sources quoted
+pub fn unresolved(
+	coin_infos: &[CoinInfo],
+	report: &FeedReport,
+	supported: Option<&HashSet<AssetSpecifier>>,
+) -> Vec<String> {
+	let failed = report
+		.assets
+		.iter()
+		.filter(|asset| asset.updates < asset.attempts)
+		.map(|asset| format!("{}:{}", asset.blockchain, asset.symbol));
+	let missing = supported
+		.into_iter()
+		.flatten()
+		.filter(|asset| {
+			!coin_infos.iter().any(|coin_info| {
+				coin_info.blockchain.eq_ignore_ascii_case(&asset.blockchain) &&
+					coin_info.symbol.eq_ignore_ascii_case(&asset.symbol)
+			})
+		})
+		.map(|asset| format!("{}:{}", asset.blockchain, asset.symbol));
+	let mut unresolved: Vec<String> = failed.chain(missing).collect();
+	unresolved.sort();
+	unresolved.dedup();
+	unresolved
+}
+
+/// Prints the coin infos as JSON sorted by blockchain and symbol to stdout. Fails if any asset
+/// didn't resolve.
+pub fn print(
+	mut coin_infos: Vec<CoinInfo>,
+	report: &FeedReport,
+	supported: Option<&HashSet<AssetSpecifier>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+	coin_infos.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+	println!("{}", serde_json::to_string_pretty(&coin_infos)?);
+	let unresolved = unresolved(&coin_infos, report, supported);
+	if !unresolved.is_empty() {
+		return Err(format!("Failed to resolve {}", unresolved.join(", ")).into())
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::report::AssetReport;
+
+	#[test]
+	fn test_unresolved() {
+		let coin_infos = vec![
+			CoinInfo { blockchain: "Polkadot".into(), symbol: "DOT".into(), ..Default::default() },
+			CoinInfo { blockchain: "Stellar".into(), symbol: "XLM".into(), ..Default::default() },
+		];
+		let asset_report = |blockchain: &str, symbol: &str, updates: u64| AssetReport {
+			blockchain: blockchain.into(),
+			symbol: symbol.into(),
+			attempts: 1,
+			updates,
+			uptime: updates as f64,
+			staleness_incidents: 0,
+			spend: 0.0,
+		};
+		let report = FeedReport {
+			from: 0,
+			to: 0,
+			assets: vec![asset_report("Polkadot", "DOT", 1), asset_report("Stellar", "XLM", 0)],
+			requests_by_source: Default::default(),
+			spend_by_source: Default::default(),
+			month_to_date_spend_by_source: Default::default(),
+		};
+		let supported: HashSet<AssetSpecifier> = ["polkadot:DOT", "Kusama:KSM"]
+			.iter()
+			.map(|asset| asset.parse().unwrap())
+			.collect();
+
+		assert_eq!(
+			unresolved(&coin_infos, &report, Some(&supported)),
+			vec!["Kusama:KSM".to_string(), "Stellar:XLM".to_string()]
+		);
+		assert_eq!(unresolved(&coin_infos, &report, None), vec!["Stellar:XLM".to_string()]);
+	}
+}
@@ -0,0 +1,196 @@
+use crate::storage::{storage_key, CoinInfo};
+use crate::twap::Observation;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, RecvError};
+
+/// Open, highest, lowest and last price served during `interval` seconds from `start`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Candle {
+	pub start: u64,
+	pub open: u128,
+	pub high: u128,
+	pub low: u128,
+	pub close: u128,
+	pub updates: usize,
+}
+
+/// Keeps only ASCII letters, digits and dashes of a blockchain or symbol for a file name
+fn file_name_part(part: &str) -> String {
+	part.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+		.collect()
+}
+
+/// Append-only log of every price served per asset, one `<timestamp>,<price>` line per update in
+/// the file `<BLOCKCHAIN>_<SYMBOL>.csv` of the directory
+#[derive(Debug)]
+pub struct PriceLog {
+	dir: PathBuf,
+}
+
+impl PriceLog {
+	/// Log in `dir`, which is created if missing and keeps the logs of previous runs
+	pub fn open(dir: PathBuf) -> io::Result<Self> {
+		std::fs::create_dir_all(&dir)?;
+		Ok(Self { dir })
+	}
+
+	fn path(&self, blockchain: &str, symbol: &str) -> PathBuf {
+		let (blockchain, symbol) = storage_key(blockchain, symbol);
+		self.dir
+			.join(format!("{}_{}.csv", file_name_part(&blockchain), file_name_part(&symbol)))
+	}
+
+	/// Appends the price of every coin info to the log of its asset
+	pub fn append(&self, coin_infos: &[CoinInfo]) -> io::Result<()> {
+		let mut lines: HashMap<PathBuf, String> = HashMap::new();
+		for coin_info in coin_infos {
+			let line = format!("{},{}\n", coin_info.last_update_timestamp, coin_info.price);
+			lines
+				.entry(self.path(&coin_info.blockchain, &coin_info.symbol))
+				.or_default()
+				.push_str(&line);
+		}
+		for (path, lines) in lines {
+			OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(path)?
+				.write_all(lines.as_bytes())?;
+		}
+		Ok(())
+	}
+
+	/// Logged prices of an asset from `from` up to but excluding `to`, ordered like they were
+	/// served. Lines that can't be parsed, like one being written, are skipped.
+	pub fn read(
+		&self,
+		blockchain: &str,
+		symbol: &str,
+		from: u64,
+		to: u64,
+	) -> io::Result<Vec<Observation>> {
+		let file = match std::fs::File::open(self.path(blockchain, symbol)) {
+			Ok(file) => file,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(err) => return Err(err),
+		};
+		let mut observations = vec![];
+		for line in BufReader::new(file).lines() {
+			let line = line?;
+			let observation = line.split_once(',').and_then(|(timestamp, price)| {
+				Some(Observation { timestamp: timestamp.parse().ok()?, price: price.parse().ok()? })
+			});
+			match observation {
+				Some(observation) if (from..to).contains(&observation.timestamp) =>
+					observations.push(observation),
+				Some(_) => (),
+				None => warn!("Skipping malformed line '{}' of the price log", line),
+			}
+		}
+		Ok(observations)
+	}
+}
+
+/// Candles of the observations in buckets of `interval` seconds from `from`, leaving out buckets
+/// without observations
+pub fn candles(observations: &[Observation], from: u64, interval: u64) -> Vec<Candle> {
+	let mut candles: Vec<Candle> = vec![];
+	for observation in observations {
+		let start = from + (observation.timestamp.saturating_sub(from) / interval) * interval;
+		match candles.last_mut() {
+			Some(candle) if candle.start == start => {
+				candle.high = candle.high.max(observation.price);
+				candle.low = candle.low.min(observation.price);
+				candle.close = observation.price;
+				candle.updates += 1;
+			},
+			_ => candles.push(Candle {
+				start,
+				open: observation.price,
+				high: observation.price,
+				low: observation.price,
+				close: observation.price,
+				updates: 1,
+			}),
+		}
+	}
+	candles
+}
+
+/// Appends the coin infos of every update of the storage to the log
+pub fn run_price_log_loop(
+	log: Arc<PriceLog>,
+	mut updates: broadcast::Receiver<Arc<Vec<CoinInfo>>>,
+) {
+	let _ = tokio::spawn(async move {
+		loop {
+			let updated = match updates.recv().await {
+				Ok(updated) => updated,
+				Err(RecvError::Lagged(missed)) => {
+					error!("Price log missed {} updates", missed);
+					continue
+				},
+				Err(RecvError::Closed) => return,
+			};
+			let log = log.clone();
+			match tokio::task::spawn_blocking(move || log.append(&updated)).await {
+				Ok(Ok(())) => (),
+				Ok(Err(err)) => error!("Failed to append to the price log: {}", err),
+				Err(err) => error!("Failed to append to the price log: {}", err),
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(
+		blockchain: &str,
+		symbol: &str,
+		price: u128,
+		last_update_timestamp: u64,
+	) -> CoinInfo {
+		CoinInfo {
+			blockchain: blockchain.into(),
+			symbol: symbol.into(),
+			price,
+			last_update_timestamp,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_price_log_candles() {
+		let dir = std::env::temp_dir()
+			.join(format!("dia-batching-server-price-log-{}", std::process::id()));
+		let log = PriceLog::open(dir.clone()).unwrap();
+		log.append(&[coin_info("FIAT", "BRL-USD", 100, 10), coin_info("Polkadot", "DOT", 5, 10)])
+			.unwrap();
+		log.append(&[coin_info("FIAT", "BRL-USD", 300, 20)]).unwrap();
+		log.append(&[coin_info("FIAT", "BRL-USD", 200, 30)]).unwrap();
+		log.append(&[coin_info("fiat", "brl-usd", 400, 70)]).unwrap();
+
+		let observations = log.read("FIAT", "BRL-USD", 20, 80).unwrap();
+		assert_eq!(observations.len(), 3);
+		assert_eq!(
+			candles(&observations, 20, 30),
+			vec![
+				Candle { start: 20, open: 300, high: 300, low: 200, close: 200, updates: 2 },
+				Candle { start: 50, open: 400, high: 400, low: 400, close: 400, updates: 1 },
+			]
+		);
+		assert_eq!(log.read("Polkadot", "DOT", 0, 10).unwrap(), vec![]);
+		assert_eq!(log.read("Kusama", "KSM", 0, 100).unwrap(), vec![]);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,41 +1,142 @@
+use crate::chain::OnChainCoinInfo;
+use crate::conflicts::{blockchain_mismatches, BlockchainMismatch};
+use crate::deviation::{DeviationError, DeviationGuard};
 use crate::dia::{Asset, DiaApi, Quotation, QuotedAsset};
-use crate::storage::{CoinInfo, CoinInfoStorage};
-use crate::AssetSpecifier;
-use log::{error, info};
+use crate::exchanges::SourceTimeout;
+use crate::hysteresis::Hysteresis;
+use crate::publication::{publication_hash, PUBLICATION_LOG_PREFIX};
+use crate::report::FeedStatistics;
+use crate::schedule::{PublicationSchedules, UpdateSchedule};
+use crate::smoothing::PriceSmoothing;
+use crate::spread::PriceSpreads;
+use crate::status::AssetStatuses;
+use crate::supply::Supplies;
+use crate::storage::{AssetError, AssetErrorLog, CoinInfo, CoinInfoStorage};
+use crate::timestamps::{ensure_not_backwards, TimestampError, TimestampPolicy};
+use crate::{AssetSpecifier, SupportedAssets};
+use chrono::Utc;
+use log::{debug, error, info, warn};
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{error::Error, sync::Arc};
-
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// Updates the prices in every `duration` until `shutdown` receives a value or its sender is
+/// dropped. An iteration in progress is abandoned then, which keeps the prices of the previous one
+/// as the storage is only replaced at the end of an iteration.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_update_prices_loop<T>(
 	storage: Arc<CoinInfoStorage>,
-	maybe_supported_currencies: Option<HashSet<AssetSpecifier>>,
+	errors: Arc<AssetErrorLog>,
+	statistics: Arc<FeedStatistics>,
+	statuses: Arc<AssetStatuses>,
+	timestamps: TimestampPolicy,
+	schedule: Arc<UpdateSchedule>,
+	spreads: PriceSpreads,
+	smoothing: Arc<PriceSmoothing>,
+	hysteresis: Hysteresis,
+	deviation_guard: DeviationGuard,
+	supplies: Arc<Supplies>,
+	supported_currencies: Arc<SupportedAssets>,
 	rate: std::time::Duration,
 	duration: std::time::Duration,
 	api: T,
-) -> Result<(), Box<dyn Error + Send + Sync + 'static>>
+	mut shutdown: oneshot::Receiver<()>,
+) -> Result<JoinHandle<()>, Box<dyn Error + Send + Sync + 'static>>
 where
 	T: DiaApi + Send + Sync + 'static,
 {
 	let coins = Arc::clone(&storage);
-	let _ = tokio::spawn(async move {
+	let handle = tokio::spawn(async move {
 		loop {
 			let time_elapsed = std::time::Instant::now();
+			let started = Utc::now().timestamp().unsigned_abs();
 
 			let coins = Arc::clone(&coins);
-
-			update_prices(coins, &maybe_supported_currencies, &api, rate).await;
-
-			tokio::time::delay_for(duration.saturating_sub(time_elapsed.elapsed())).await;
+			let maybe_supported_currencies = supported_currencies.load_full().as_deref().cloned();
+
+			let iteration = async {
+				update_prices(
+					coins,
+					&errors,
+					&statistics,
+					&statuses,
+					&timestamps,
+					schedule.schedules(),
+					&spreads,
+					&smoothing,
+					&hysteresis,
+					&deviation_guard,
+					&supplies,
+					&maybe_supported_currencies,
+					&api,
+					rate,
+				)
+				.await;
+				schedule.set_next_iteration(
+					(started + duration.as_secs()).max(Utc::now().timestamp().unsigned_abs()),
+				);
+
+				tokio::time::delay_for(duration.saturating_sub(time_elapsed.elapsed())).await;
+			};
+			tokio::select! {
+				_ = iteration => {},
+				_ = &mut shutdown => break,
+			}
 		}
+		info!("Stopped updating prices");
 	});
 
-	Ok(())
+	Ok(handle)
+}
+
+/// Runs a single iteration of `run_update_prices_loop` and returns once it finished
+#[allow(clippy::too_many_arguments)]
+pub async fn update_prices_once<T>(
+	storage: Arc<CoinInfoStorage>,
+	errors: &AssetErrorLog,
+	statistics: &FeedStatistics,
+	statuses: &AssetStatuses,
+	timestamps: &TimestampPolicy,
+	schedules: &PublicationSchedules,
+	spreads: &PriceSpreads,
+	smoothing: &PriceSmoothing,
+	hysteresis: &Hysteresis,
+	deviation_guard: &DeviationGuard,
+	supplies: &Supplies,
+	supported_currencies: &SupportedAssets,
+	rate: std::time::Duration,
+	api: &T,
+) where
+	T: DiaApi + Send + Sync + 'static,
+{
+	let maybe_supported_currencies = supported_currencies.load_full().as_deref().cloned();
+	update_prices(
+		storage,
+		errors,
+		statistics,
+		statuses,
+		timestamps,
+		schedules,
+		spreads,
+		smoothing,
+		hysteresis,
+		deviation_guard,
+		supplies,
+		&maybe_supported_currencies,
+		api,
+		rate,
+	)
+	.await
 }
 
 fn convert_to_coin_info(value: Quotation) -> Result<CoinInfo, Box<dyn Error + Sync + Send>> {
-	let Quotation { name, symbol, blockchain, price, time, volume_yesterday, .. } = value;
+	let Quotation { name, symbol, blockchain, price, time, volume_yesterday, source, .. } = value;
 
 	let price = convert_decimal_to_u128(&price)?;
 	let supply = convert_decimal_to_u128(&volume_yesterday)?;
@@ -47,6 +148,8 @@ fn convert_to_coin_info(value: Quotation) -> Result<CoinInfo, Box<dyn Error + Sy
 		price,
 		last_update_timestamp: time.timestamp().unsigned_abs(),
 		supply,
+		source: source.into(),
+		fetched_at: Utc::now().timestamp().unsigned_abs(),
 	};
 
 	info!("Coin Price: {:#?}", price);
@@ -56,19 +159,237 @@ fn convert_to_coin_info(value: Quotation) -> Result<CoinInfo, Box<dyn Error + Sy
 	Ok(coin_info)
 }
 
+fn error_kind(err: &(dyn Error + Send + Sync + 'static)) -> &'static str {
+	if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+		if err.is_timeout() {
+			"timeout"
+		} else if err.is_status() {
+			"status"
+		} else if err.is_decode() {
+			"decode"
+		} else {
+			"request"
+		}
+	} else if err.is::<SourceTimeout>() {
+		"timeout"
+	} else if err.is::<BlockchainMismatch>() {
+		"blockchain"
+	} else if err.is::<DeviationError>() {
+		"deviation"
+	} else if err.is::<DelayedQuotation>() {
+		"delayed"
+	} else if err.is::<ConvertingError>() {
+		"conversion"
+	} else if err.is::<TimestampError>() {
+		"timestamp"
+	} else {
+		"other"
+	}
+}
+
+fn record_error(
+	errors: &AssetErrorLog,
+	asset: &Asset,
+	source: &str,
+	err: &(dyn Error + Send + Sync + 'static),
+) {
+	errors.record(
+		&asset.blockchain,
+		&asset.symbol,
+		AssetError {
+			timestamp: Utc::now().timestamp().unsigned_abs(),
+			source: source.into(),
+			kind: error_kind(err).into(),
+			message: err.to_string(),
+		},
+	);
+}
+
+/// Records an error of kind `delayed` if the quotation is older than its source declares
+/// quotations to be at most. The quotation is published regardless.
+fn record_if_delayed(errors: &AssetErrorLog, asset: &Asset, quotation: Quotation) -> Quotation {
+	if quotation.is_delayed(Utc::now()) {
+		let err = DelayedQuotation { time: quotation.time };
+		warn!("Delayed quotation for {}:{}: {}", asset.blockchain, asset.symbol, err);
+		record_error(errors, asset, &quotation.source, &err);
+	}
+	quotation
+}
+
+/// Applies the asset's timestamp source, spread, smoothing and hysteresis. Keeps the published
+/// coin info if the timestamp would go backwards or the deviation guard holds the price back,
+/// which counts towards quarantining the `quoted_source` of the price. The deviation guard sees
+/// the quoted price, which only moves the smoothed average once it passed.
+#[allow(clippy::too_many_arguments)]
+fn post_process(
+	coins: &CoinInfoStorage,
+	errors: &AssetErrorLog,
+	statistics: &FeedStatistics,
+	statuses: &AssetStatuses,
+	timestamps: &TimestampPolicy,
+	spreads: &PriceSpreads,
+	smoothing: &PriceSmoothing,
+	hysteresis: &Hysteresis,
+	deviation_guard: &DeviationGuard,
+	asset: &Asset,
+	source: &str,
+	quoted_source: &str,
+	mut coin_info: CoinInfo,
+) -> Option<CoinInfo> {
+	let now = Utc::now().timestamp().unsigned_abs();
+	timestamps.apply(&mut coin_info, now);
+	spreads.apply(&mut coin_info);
+	let previous = coins.get_currency(&coin_info.blockchain, &coin_info.symbol);
+	let checked: Result<(), Box<dyn Error + Send + Sync>> =
+		match ensure_not_backwards(previous.as_ref(), &coin_info) {
+			Ok(()) => {
+				let checked = deviation_guard.check(previous.as_ref(), &coin_info);
+				deviation_guard.report(asset, quoted_source, checked.is_err());
+				checked.map_err(Into::into)
+			},
+			Err(err) => Err(err.into()),
+		};
+	match checked {
+		Ok(()) => {
+			if let Some(smoothed) = smoothing.smooth(&coin_info) {
+				coin_info.price = smoothed.smoothed_price;
+				smoothing.commit(smoothed);
+			}
+			statistics.record(&asset.blockchain, &asset.symbol, source, true);
+			statuses.record_success(&asset.blockchain, &asset.symbol, quoted_source, now);
+			Some(hysteresis.apply(previous, coin_info, now))
+		},
+		Err(err) => {
+			error!("Keeping published quotation for {:?}: {}", asset, err);
+			record_error(errors, asset, source, err.as_ref());
+			statistics.record(&asset.blockchain, &asset.symbol, source, false);
+			statuses.record_failure(&asset.blockchain, &asset.symbol, source, err.to_string());
+			previous
+		},
+	}
+}
+
+/// Whether assets of the blockchain are quoted by custom sources instead of DIA
+fn is_custom_blockchain(blockchain: &str) -> bool {
+	matches!(blockchain, "FIAT" | "Amplitude" | "Pendulum" | "Bifrost")
+}
+
+/// Published coin info of an asset outside of its publication windows
+fn carry_forward(coins: &CoinInfoStorage, asset: &AssetSpecifier) -> Option<CoinInfo> {
+	debug!(
+		"Carrying forward {}:{} outside of its publication windows",
+		asset.blockchain, asset.symbol
+	);
+	coins.get_currency(&asset.blockchain, &asset.symbol)
+}
+
+/// Records an error of kind `blockchain` for every supported currency the sources only quote on
+/// other blockchains, which is usually a supported currency labelled differently than by the source
+fn record_blockchain_mismatches<T: DiaApi>(
+	errors: &AssetErrorLog,
+	supported_currencies: &HashSet<AssetSpecifier>,
+	quotable_assets: &[QuotedAsset],
+	api: &T,
+) {
+	let quotable: HashSet<AssetSpecifier> = quotable_assets
+		.iter()
+		.map(|QuotedAsset { asset, .. }| AssetSpecifier {
+			blockchain: asset.blockchain.clone(),
+			symbol: asset.symbol.clone(),
+		})
+		.collect();
+	// FIAT, Amplitude, Pendulum and Bifrost currencies are quoted without being listed as quotable
+	let listed: HashSet<AssetSpecifier> = supported_currencies
+		.iter()
+		.filter(|asset| !is_custom_blockchain(&asset.blockchain))
+		.cloned()
+		.collect();
+	for mismatch in blockchain_mismatches(&listed, &quotable) {
+		warn!("{}", mismatch);
+		let asset = Asset {
+			symbol: mismatch.asset.symbol.clone(),
+			name: "".to_string(),
+			address: "".to_string(),
+			decimals: 0,
+			blockchain: mismatch.asset.blockchain.clone(),
+		};
+		record_error(errors, &asset, api.quotation_source(&asset), &mismatch);
+	}
+}
+
+/// Quotations fetched from a source during an update cycle
+#[derive(Debug, Default)]
+struct SourceFetches {
+	assets: usize,
+	errors: usize,
+	duration: std::time::Duration,
+}
+
+impl SourceFetches {
+	fn record<T, E>(&mut self, result: &Result<T, E>, duration: std::time::Duration) {
+		self.assets += 1;
+		self.errors += result.is_err() as usize;
+		self.duration += duration;
+	}
+
+	fn merge(&mut self, other: SourceFetches) {
+		self.assets += other.assets;
+		self.errors += other.errors;
+		self.duration += other.duration;
+	}
+}
+
+static NEXT_CYCLE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Span of the fetch of a quotation, so the logs of the source and its fallbacks carry the asset
+fn fetch_span(source: &'static str, asset: &Asset) -> tracing::Span {
+	tracing::info_span!("fetch", source, blockchain = %asset.blockchain, symbol = %asset.symbol)
+}
+
+/// Runs in the span of an update cycle with an ID counting the cycles since the start, which the
+/// logs of the cycle carry in JSON mode
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+	name = "update_cycle",
+	skip_all,
+	fields(cycle_id = NEXT_CYCLE_ID.fetch_add(1, Ordering::Relaxed))
+)]
 async fn update_prices<T>(
 	coins: Arc<CoinInfoStorage>,
+	errors: &AssetErrorLog,
+	statistics: &FeedStatistics,
+	statuses: &AssetStatuses,
+	timestamps: &TimestampPolicy,
+	schedules: &PublicationSchedules,
+	spreads: &PriceSpreads,
+	smoothing: &PriceSmoothing,
+	hysteresis: &Hysteresis,
+	deviation_guard: &DeviationGuard,
+	supplies: &Supplies,
 	maybe_supported_currencies: &Option<HashSet<AssetSpecifier>>,
 	api: &T,
 	rate: std::time::Duration,
 ) where
 	T: DiaApi + Send + Sync + 'static,
 {
-	let mut currencies = vec![];
-
-	if let Ok(quotable_assets) = api.get_quotable_assets().await {
+	let now = Utc::now().timestamp().unsigned_abs();
+	let coins = &coins;
+
+	// The assets quotable by DIA are fetched one after the other in the `rate`, the custom assets
+	// all at once and alongside them, so a slow source of either doesn't hold up the other
+	let quotable = async {
+		let mut currencies = vec![];
+		let mut fetches: HashMap<&'static str, SourceFetches> = HashMap::new();
+		let quotable_assets = match api.get_quotable_assets().await {
+			Ok(quotable_assets) => quotable_assets,
+			Err(_) => return (currencies, fetches),
+		};
 		info!("No. of quotable assets to retrieve : {}", quotable_assets.len());
 
+		if let Some(supported_currencies) = maybe_supported_currencies.as_ref() {
+			record_blockchain_mismatches(errors, supported_currencies, &quotable_assets, api);
+		}
+
 		for quotable_asset in quotable_assets {
 			let asset = AssetSpecifier {
 				blockchain: quotable_asset.asset.blockchain.clone(),
@@ -79,47 +400,162 @@ async fn update_prices<T>(
 				.as_ref()
 				.map_or(true, |supported| supported.contains(&asset))
 			{
-				match api.get_quotation(&quotable_asset).await.and_then(convert_to_coin_info) {
-					Ok(coin_info) => {
-						currencies.push(coin_info);
+				if !schedules.is_open(&asset.blockchain, &asset.symbol, now) {
+					currencies.extend(carry_forward(coins, &asset));
+					continue
+				}
+				let source = api.quotation_source(&quotable_asset.asset);
+				let fetch_started = std::time::Instant::now();
+				let quotation = api
+					.get_quotation(&quotable_asset)
+					.instrument(fetch_span(source, &quotable_asset.asset))
+					.await;
+				fetches.entry(source).or_default().record(&quotation, fetch_started.elapsed());
+				match quotation
+					.map(|quotation| record_if_delayed(errors, &quotable_asset.asset, quotation))
+					.and_then(|quotation| {
+						let quoted_source = quotation.source.clone();
+						Ok((quoted_source, convert_to_coin_info(quotation)?))
+					}) {
+					Ok((quoted_source, coin_info)) => {
+						currencies.extend(post_process(
+							coins,
+							errors,
+							statistics,
+							statuses,
+							timestamps,
+							spreads,
+							smoothing,
+							hysteresis,
+							deviation_guard,
+							&quotable_asset.asset,
+							source,
+							&quoted_source,
+							coin_info,
+						));
 					},
 					Err(err) => {
-						error!("Error while retrieving quotation for {:?}: {}", quotable_asset, err)
+						error!(
+							"Error while retrieving quotation for {:?}: {}",
+							quotable_asset, err
+						);
+						record_error(errors, &quotable_asset.asset, source, err.as_ref());
+						statistics.record(
+							&quotable_asset.asset.blockchain,
+							&quotable_asset.asset.symbol,
+							source,
+							false,
+						);
+						statuses.record_failure(
+							&quotable_asset.asset.blockchain,
+							&quotable_asset.asset.symbol,
+							source,
+							err.to_string(),
+						);
 					},
 				}
 				tokio::time::delay_for(rate).await;
 			}
 		}
-	}
+		(currencies, fetches)
+	};
 
-	if let Some(supported_currencies) = maybe_supported_currencies.as_ref() {
-		for asset in supported_currencies.iter() {
-			// We do support both these 'blockchain' identifiers while DIA doesn't provide data for them
-			if asset.blockchain == "FIAT" || asset.blockchain == "Amplitude" {
-				// Create dummy QuotedAsset. We only need it to have the symbol and blockchain
-				let quoted_asset = QuotedAsset {
-					asset: Asset {
-						symbol: asset.symbol.clone(),
-						name: "".to_string(),
-						address: "".to_string(),
-						decimals: 0,
-						blockchain: asset.blockchain.clone(),
-					},
-					volume: Default::default(),
-				};
-				match api.get_quotation(&quoted_asset).await.and_then(convert_to_coin_info) {
-					Ok(coin_info) => {
-						currencies.push(coin_info);
-					},
-					Err(err) => {
-						error!("Error while retrieving quotation for {:?}: {}", quoted_asset, err)
-					},
-				}
-			}
+	// We do support these 'blockchain' identifiers while DIA doesn't provide data for them
+	let custom_assets = maybe_supported_currencies
+		.iter()
+		.flatten()
+		.filter(|asset| is_custom_blockchain(&asset.blockchain));
+	let custom = futures::future::join_all(custom_assets.map(|asset| async move {
+		if !schedules.is_open(&asset.blockchain, &asset.symbol, now) {
+			return (carry_forward(coins, asset), None)
+		}
+		// Create dummy QuotedAsset. We only need it to have the symbol and blockchain
+		let quoted_asset = QuotedAsset {
+			asset: Asset {
+				symbol: asset.symbol.clone(),
+				name: "".to_string(),
+				address: "".to_string(),
+				decimals: 0,
+				blockchain: asset.blockchain.clone(),
+			},
+			volume: Default::default(),
+		};
+		let source = api.quotation_source(&quoted_asset.asset);
+		let fetch_started = std::time::Instant::now();
+		let quotation = api
+			.get_quotation(&quoted_asset)
+			.instrument(fetch_span(source, &quoted_asset.asset))
+			.await;
+		let mut fetched = SourceFetches::default();
+		fetched.record(&quotation, fetch_started.elapsed());
+		let coin_info = match quotation
+			.map(|quotation| record_if_delayed(errors, &quoted_asset.asset, quotation))
+			.and_then(|quotation| {
+				let quoted_source = quotation.source.clone();
+				Ok((quoted_source, convert_to_coin_info(quotation)?))
+			}) {
+			Ok((quoted_source, coin_info)) => post_process(
+				coins,
+				errors,
+				statistics,
+				statuses,
+				timestamps,
+				spreads,
+				smoothing,
+				hysteresis,
+				deviation_guard,
+				&quoted_asset.asset,
+				source,
+				&quoted_source,
+				coin_info,
+			),
+			Err(err) => {
+				error!("Error while retrieving quotation for {:?}: {}", quoted_asset, err);
+				record_error(errors, &quoted_asset.asset, source, err.as_ref());
+				statistics.record(
+					&quoted_asset.asset.blockchain,
+					&quoted_asset.asset.symbol,
+					source,
+					false,
+				);
+				statuses.record_failure(
+					&quoted_asset.asset.blockchain,
+					&quoted_asset.asset.symbol,
+					source,
+					err.to_string(),
+				);
+				None
+			},
+		};
+		(coin_info, Some((source, fetched)))
+	}));
+
+	let ((mut currencies, mut fetches), custom) = futures::join!(quotable, custom);
+	for (coin_info, fetched) in custom {
+		currencies.extend(coin_info);
+		if let Some((source, fetched)) = fetched {
+			fetches.entry(source).or_default().merge(fetched);
 		}
 	}
 
+	for (source, fetches) in fetches {
+		tracing::info!(
+			source,
+			assets = fetches.assets,
+			errors = fetches.errors,
+			duration_ms = fetches.duration.as_millis() as u64,
+			"Fetched quotations"
+		);
+	}
+	supplies.apply(&mut currencies);
+	info!(
+		"{} {} ({} assets)",
+		PUBLICATION_LOG_PREFIX,
+		publication_hash(currencies.iter().map(OnChainCoinInfo::from).collect()),
+		currencies.len()
+	);
 	coins.replace_currencies_by_symbols(currencies);
+	statistics.record_iteration(Utc::now().timestamp().unsigned_abs());
 	info!("Currencies Updated");
 }
 
@@ -138,6 +574,19 @@ impl Display for ConvertingError {
 
 impl Error for ConvertingError {}
 
+#[derive(Debug)]
+pub struct DelayedQuotation {
+	pub time: chrono::DateTime<Utc>,
+}
+
+impl Display for DelayedQuotation {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Quoted at {}, longer ago than the source quotes at most", self.time)
+	}
+}
+
+impl Error for DelayedQuotation {}
+
 fn convert_decimal_to_u128(input: &Decimal) -> Result<u128, ConvertingError> {
 	let fract = (input.fract() * Decimal::from(1_000_000_000_000_u128))
 		.to_u128()
@@ -158,7 +607,6 @@ mod tests {
 	use std::{collections::HashMap, error::Error, sync::Arc};
 
 	use async_trait::async_trait;
-	use chrono::Utc;
 	use rust_decimal_macros::dec;
 
 	use super::*;
@@ -318,7 +766,23 @@ mod tests {
 		let storage = Arc::new(CoinInfoStorage::default());
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![
 			Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
@@ -333,6 +797,8 @@ mod tests {
 		assert_eq!(c[1].price, 1000000000000);
 
 		assert_eq!(c[1].name, "ETH");
+		assert_eq!(c[1].source, "diadata.org");
+		assert!(c[1].fetched_at > 0);
 	}
 
 	#[tokio::test]
@@ -348,7 +814,23 @@ mod tests {
 			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "MXN-USD".into() });
 		let all_currencies = Some(all_currencies);
 
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![
 			Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
@@ -373,7 +855,23 @@ mod tests {
 			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "USD-USD".into() });
 		let all_currencies = Some(all_currencies);
 
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![Currency {
 			blockchain: "FIAT".into(),
@@ -387,13 +885,179 @@ mod tests {
 		assert_eq!(c[0].name, "USD-X");
 	}
 
+	#[tokio::test]
+	async fn test_update_prices_records_errors() {
+		let mock_api = MockDia::new();
+		let storage = Arc::new(CoinInfoStorage::default());
+		let errors = AssetErrorLog::default();
+		let statuses = AssetStatuses::default();
+
+		let mut all_currencies = HashSet::new();
+		all_currencies
+			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "TZS-USD".into() });
+		let all_currencies = Some(all_currencies);
+
+		update_prices(
+			storage,
+			&errors,
+			&FeedStatistics::default(),
+			&statuses,
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
+
+		let e = errors.get_errors("FIAT", "TZS-USD");
+
+		assert_eq!(1, e.len());
+		assert_eq!(e[0].source, "YahooFinance");
+		assert_eq!(e[0].kind, "other");
+		assert_eq!(e[0].message, "Error Finding Quotation");
+
+		let status = &statuses.statuses()[0];
+		assert_eq!(status.source.as_deref(), Some("YahooFinance"));
+		assert_eq!(status.last_error.as_deref(), Some("Error Finding Quotation"));
+		assert_eq!(status.consecutive_failures, 1);
+	}
+
+	#[tokio::test]
+	async fn test_update_prices_keeps_published_coin_info_if_timestamp_goes_backwards() {
+		let mock_api = MockDia::new();
+		let storage = Arc::new(CoinInfoStorage::default());
+		let errors = AssetErrorLog::default();
+		let published = CoinInfo {
+			symbol: "MXN-USD".into(),
+			name: "MXNUSD=X".into(),
+			blockchain: "FIAT".into(),
+			supply: 0,
+			last_update_timestamp: u64::MAX,
+			price: 1,
+			..Default::default()
+		};
+		storage.replace_currencies_by_symbols(vec![published.clone()]);
+
+		let mut all_currencies = HashSet::new();
+		all_currencies
+			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "MXN-USD".into() });
+		let all_currencies = Some(all_currencies);
+
+		let smoothing = PriceSmoothing::new("FIAT:MXN-USD=0.5".parse().unwrap());
+
+		update_prices(
+			storage.clone(),
+			&errors,
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&smoothing,
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
+
+		assert_eq!(storage.get_currency("FIAT", "MXN-USD"), Some(published));
+		let e = errors.get_errors("FIAT", "MXN-USD");
+		assert_eq!(1, e.len());
+		assert_eq!(e[0].kind, "timestamp");
+		// The rejected price doesn't start the average
+		assert!(smoothing.prices().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_update_prices_carries_forward_outside_of_publication_windows() {
+		let mock_api = MockDia::new();
+		let storage = Arc::new(CoinInfoStorage::default());
+		let statistics = FeedStatistics::default();
+		let published = CoinInfo {
+			symbol: "MXN-USD".into(),
+			name: "MXNUSD=X".into(),
+			blockchain: "FIAT".into(),
+			supply: 0,
+			last_update_timestamp: 1,
+			price: 1,
+			..Default::default()
+		};
+		storage.replace_currencies_by_symbols(vec![published.clone()]);
+		// A window opening in two minutes
+		let start = (Utc::now().timestamp() / 60 + 2) % (24 * 60);
+		let end = (start + 1) % (24 * 60);
+		let schedules: PublicationSchedules = format!(
+			"FIAT:MXN-USD={:02}:{:02}-{:02}:{:02}",
+			start / 60,
+			start % 60,
+			end / 60,
+			end % 60
+		)
+		.parse()
+		.unwrap();
+
+		let mut all_currencies = HashSet::new();
+		all_currencies
+			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "MXN-USD".into() });
+		all_currencies
+			.insert(AssetSpecifier { blockchain: "FIAT".into(), symbol: "USD-USD".into() });
+		let all_currencies = Some(all_currencies);
+
+		update_prices(
+			storage.clone(),
+			&AssetErrorLog::default(),
+			&statistics,
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&schedules,
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
+
+		assert_eq!(storage.get_currency("FIAT", "MXN-USD"), Some(published));
+		assert_eq!(storage.get_currency("FIAT", "USD-USD").map(|c| c.price), Some(1000000000000));
+		assert_eq!(statistics.take_report(0).requests_by_source.get("YahooFinance"), Some(&1));
+	}
+
 	#[tokio::test]
 	async fn test_update_prices_non_existent() {
 		let mock_api = MockDia::new();
 		let storage = Arc::new(CoinInfoStorage::default());
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![
 			Currency { blockchain: "Bitcoin".into(), symbol: "BTCCash".into() },
@@ -409,7 +1073,23 @@ mod tests {
 		let storage = Arc::new(CoinInfoStorage::default());
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![
 			Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
@@ -429,7 +1109,23 @@ mod tests {
 		let storage = Arc::new(CoinInfoStorage::default());
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![]);
 
@@ -443,7 +1139,23 @@ mod tests {
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
 
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![Currency {
 			blockchain: "Bitcoin".into(),
@@ -460,7 +1172,23 @@ mod tests {
 		let coins = Arc::clone(&storage);
 		let all_currencies = None;
 
-		update_prices(coins, &all_currencies, &mock_api, std::time::Duration::from_secs(1)).await;
+		update_prices(
+			coins,
+			&AssetErrorLog::default(),
+			&FeedStatistics::default(),
+			&AssetStatuses::default(),
+			&TimestampPolicy::default(),
+			&PublicationSchedules::default(),
+			&PriceSpreads::default(),
+			&PriceSmoothing::default(),
+			&Hysteresis::default(),
+			&DeviationGuard::default(),
+			&Supplies::default(),
+			&all_currencies,
+			&mock_api,
+			std::time::Duration::from_secs(1),
+		)
+		.await;
 
 		let c = storage.get_currencies_by_blockchains_and_symbols(vec![
 			Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
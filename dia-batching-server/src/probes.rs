@@ -0,0 +1,164 @@
+use crate::dia::{Asset, DiaApi, QuotedAsset};
+use crate::AssetSpecifier;
+use log::{info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Source quoting every asset that isn't routed anywhere else
+const DIA_SOURCE: &str = "diadata.org";
+
+/// Outcome of requesting one source once at startup
+#[derive(Debug)]
+pub struct Probe {
+	pub source: &'static str,
+	/// Asset quoted to reach the source, `None` if the list of quotable assets was requested
+	pub asset: Option<AssetSpecifier>,
+	pub elapsed: Duration,
+	pub result: Result<(), String>,
+}
+
+fn quoted_asset(asset: &AssetSpecifier) -> QuotedAsset {
+	QuotedAsset {
+		asset: Asset {
+			symbol: asset.symbol.clone(),
+			name: asset.symbol.clone(),
+			address: "".to_string(),
+			decimals: 0,
+			blockchain: asset.blockchain.clone(),
+		},
+		volume: 0.0,
+	}
+}
+
+/// One asset per source the `assets` are quoted by, the first one in order. The USD-USD pair is
+/// never picked, it is quoted without asking a source. DIA is always included, it is asked for the
+/// quotable assets in every iteration.
+fn probe_assets<T: DiaApi>(
+	api: &T,
+	assets: &HashSet<AssetSpecifier>,
+) -> BTreeMap<&'static str, Option<AssetSpecifier>> {
+	let mut assets: Vec<&AssetSpecifier> = assets.iter().collect();
+	assets.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+
+	let mut probes = BTreeMap::new();
+	probes.insert(DIA_SOURCE, None);
+	for asset in assets {
+		if asset.blockchain.eq_ignore_ascii_case("FIAT") &&
+			asset.symbol.eq_ignore_ascii_case("USD-USD")
+		{
+			continue
+		}
+		let source = api.quotation_source(&quoted_asset(asset).asset);
+		if source != DIA_SOURCE {
+			probes.entry(source).or_insert_with(|| Some(asset.clone()));
+		}
+	}
+	probes
+}
+
+/// Requests every source the `assets` are quoted by once, giving each at most `timeout`
+pub async fn probe_sources<T: DiaApi>(
+	api: &T,
+	assets: &HashSet<AssetSpecifier>,
+	timeout: Duration,
+) -> Vec<Probe> {
+	let mut probes = Vec::new();
+	for (source, asset) in probe_assets(api, assets) {
+		let start = Instant::now();
+		let request = async {
+			match &asset {
+				Some(asset) => api.get_quotation(&quoted_asset(asset)).await.map(|_| ()),
+				None => api.get_quotable_assets().await.map(|_| ()),
+			}
+		};
+		let result = match tokio::time::timeout(timeout, request).await {
+			Ok(result) => result.map_err(|err| err.to_string()),
+			Err(_) => Err(format!("No response within {}ms", timeout.as_millis())),
+		};
+		probes.push(Probe { source, asset, elapsed: start.elapsed(), result });
+	}
+	probes
+}
+
+/// Logs the outcome of every probe and returns the descriptions of the failed ones
+pub fn report(probes: &[Probe]) -> Vec<String> {
+	let mut failures = Vec::new();
+	for probe in probes {
+		let target = match &probe.asset {
+			Some(asset) => format!("{} ({}:{})", probe.source, asset.blockchain, asset.symbol),
+			None => probe.source.to_string(),
+		};
+		match &probe.result {
+			Ok(()) => info!("Probed {} in {}ms", target, probe.elapsed.as_millis()),
+			Err(err) => {
+				warn!("Probing {} failed: {}", target, err);
+				failures.push(format!("{}: {}", target, err));
+			},
+		}
+	}
+	failures
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::dia::Quotation;
+	use async_trait::async_trait;
+	use std::error::Error;
+
+	struct MockDia;
+
+	#[async_trait]
+	impl DiaApi for MockDia {
+		async fn get_quotable_assets(
+			&self,
+		) -> Result<Vec<QuotedAsset>, Box<dyn Error + Send + Sync>> {
+			Ok(vec![])
+		}
+
+		async fn get_quotation(
+			&self,
+			asset: &QuotedAsset,
+		) -> Result<Quotation, Box<dyn Error + Send + Sync>> {
+			match asset.asset.symbol.as_str() {
+				"MXN-USD" => Err("Symbol not found".into()),
+				_ => Ok(Quotation::default()),
+			}
+		}
+
+		fn quotation_source(&self, asset: &Asset) -> &'static str {
+			match asset.blockchain.as_str() {
+				"FIAT" => "YahooFinance",
+				"Amplitude" => "squid",
+				_ => DIA_SOURCE,
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_probe_sources() {
+		let assets: HashSet<AssetSpecifier> =
+			["FIAT:USD-USD", "FIAT:MXN-USD", "FIAT:BRL-USD", "Amplitude:AMPE", "Polkadot:DOT"]
+				.iter()
+				.map(|asset| asset.parse().unwrap())
+				.collect();
+
+		let probes = probe_sources(&MockDia, &assets, Duration::from_secs(1)).await;
+
+		assert_eq!(
+			probes
+				.iter()
+				.map(|probe| (probe.source, probe.asset.clone(), probe.result.is_ok()))
+				.collect::<Vec<_>>(),
+			vec![
+				("YahooFinance", Some("FIAT:BRL-USD".parse().unwrap()), true),
+				(DIA_SOURCE, None, true),
+				("squid", Some("Amplitude:AMPE".parse().unwrap()), true),
+			]
+		);
+
+		let assets = vec!["FIAT:MXN-USD".parse().unwrap()].into_iter().collect();
+		let probes = probe_sources(&MockDia, &assets, Duration::from_secs(1)).await;
+		assert_eq!(report(&probes), vec!["YahooFinance (FIAT:MXN-USD): Symbol not found"]);
+	}
+}
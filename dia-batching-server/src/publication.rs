@@ -0,0 +1,59 @@
+use crate::chain::OnChainCoinInfo;
+use codec::Encode;
+use sha2::{Digest, Sha256};
+
+/// Prefix of the log line the publication hash of every update cycle is logged with
+pub const PUBLICATION_LOG_PREFIX: &str = "Published batch";
+
+/// Hex encoded sha256 of the SCALE encoded coin infos sorted by blockchain and symbol. The order
+/// the coin infos were fetched or stored in doesn't matter, so the hash of a batch the server
+/// published can be recomputed from the `CoinInfosMap` on chain.
+pub fn publication_hash(mut coin_infos: Vec<OnChainCoinInfo>) -> String {
+	coin_infos.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+	format!("0x{}", hex::encode(Sha256::digest(coin_infos.encode())))
+}
+
+/// Returns the first line of `log` the batch with the given publication `hash` was logged in
+pub fn find_in_log<'a>(log: &'a str, hash: &str) -> Option<&'a str> {
+	let needle = format!("{} {}", PUBLICATION_LOG_PREFIX, hash);
+	log.lines().find(|line| line.contains(&needle))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(blockchain: &str, symbol: &str, price: u128) -> OnChainCoinInfo {
+		OnChainCoinInfo {
+			symbol: symbol.as_bytes().to_vec(),
+			name: symbol.as_bytes().to_vec(),
+			blockchain: blockchain.as_bytes().to_vec(),
+			supply: 0,
+			last_update_timestamp: 1700000000,
+			price,
+		}
+	}
+
+	#[test]
+	fn test_publication_hash_ignores_order() {
+		let btc = coin_info("Bitcoin", "BTC", 1);
+		let mxn = coin_info("FIAT", "MXN-USD", 2);
+
+		assert_eq!(
+			publication_hash(vec![btc.clone(), mxn.clone()]),
+			publication_hash(vec![mxn.clone(), btc.clone()])
+		);
+		assert_ne!(
+			publication_hash(vec![btc, mxn.clone()]),
+			publication_hash(vec![coin_info("Bitcoin", "BTC", 3), mxn])
+		);
+	}
+
+	#[test]
+	fn test_find_in_log() {
+		let log = " INFO  dia_batching_server::price_updater > Published batch 0xab (2 assets)\n";
+
+		assert_eq!(find_in_log(log, "0xab"), log.lines().next());
+		assert_eq!(find_in_log(log, "0xcd"), None);
+	}
+}
@@ -0,0 +1,257 @@
+use crate::deviation::{deviation, DeviationLimits};
+use crate::dia::{Asset, DiaApi, QuotedAsset};
+use crate::exchanges::ExchangeRouter;
+use crate::storage::{storage_key, CoinInfoStorage};
+use crate::AssetSpecifier;
+use log::{debug, info, warn};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Source excluded from routing an asset
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantinedSource {
+	pub blockchain: String,
+	pub symbol: String,
+	pub source: String,
+	/// Quarantined through the admin API, which is the only way to release it then
+	pub manual: bool,
+	pub since: u64,
+	/// Consecutive shadow quotations of the source agreeing with the published price
+	pub agreeing: u32,
+}
+
+/// Blockchain, symbol and source, matched case-insensitively
+type Key = (SmolStr, SmolStr, String);
+
+fn key(blockchain: &str, symbol: &str, source: &str) -> Key {
+	let (blockchain, symbol) = storage_key(blockchain, symbol);
+	(blockchain, symbol, source.to_lowercase())
+}
+
+#[derive(Debug, Default)]
+struct QuarantineState {
+	rejections: HashMap<Key, u32>,
+	/// Quarantined sources with the asset to probe them with, which manually quarantined sources
+	/// may lack
+	quarantined: HashMap<Key, (QuarantinedSource, Option<Asset>)>,
+}
+
+/// Sources the deviation guard rejected `strikes` consecutive prices of for an asset, which the
+/// router doesn't ask for the asset until they quoted `probation` consecutive shadow quotations
+/// within the deviation limit of the asset
+#[derive(Debug, Default)]
+pub struct Quarantine {
+	limits: DeviationLimits,
+	strikes: Option<u32>,
+	probation: u32,
+	state: Mutex<QuarantineState>,
+}
+
+impl Quarantine {
+	/// Sources are only quarantined through the admin API without `strikes`
+	pub fn new(limits: DeviationLimits, strikes: Option<u32>, probation: u32) -> Self {
+		Self { limits, strikes, probation, state: Default::default() }
+	}
+
+	/// Counts a price of `source` for `asset` the deviation guard rejected or, resetting the count,
+	/// accepted
+	pub fn record(&self, asset: &Asset, source: &str, rejected: bool, now: u64) {
+		let strikes = match self.strikes {
+			Some(strikes) => strikes,
+			None => return,
+		};
+		let key = key(&asset.blockchain, &asset.symbol, source);
+		let mut state = self.state.lock().expect("poisoned lock");
+		if !rejected {
+			state.rejections.remove(&key);
+			return
+		}
+		let rejections = state.rejections.entry(key.clone()).or_default();
+		*rejections += 1;
+		if *rejections < strikes || state.quarantined.contains_key(&key) {
+			return
+		}
+		state.rejections.remove(&key);
+		warn!(
+			"Quarantining {} for {}:{} after {} rejected prices",
+			source, asset.blockchain, asset.symbol, strikes
+		);
+		let quarantined = QuarantinedSource {
+			blockchain: asset.blockchain.clone(),
+			symbol: asset.symbol.clone(),
+			source: source.to_string(),
+			manual: false,
+			since: now,
+			agreeing: 0,
+		};
+		state.quarantined.insert(key, (quarantined, Some(asset.clone())));
+	}
+
+	pub fn is_quarantined(&self, blockchain: &str, symbol: &str, source: &str) -> bool {
+		let state = self.state.lock().expect("poisoned lock");
+		state.quarantined.contains_key(&key(blockchain, symbol, source))
+	}
+
+	/// Quarantines `source` for the asset until it is released through `release`
+	pub fn quarantine(&self, blockchain: &str, symbol: &str, source: &str, now: u64) {
+		let mut state = self.state.lock().expect("poisoned lock");
+		let key = key(blockchain, symbol, source);
+		let asset = state.quarantined.remove(&key).and_then(|(_, asset)| asset);
+		let quarantined = QuarantinedSource {
+			blockchain: blockchain.to_string(),
+			symbol: symbol.to_string(),
+			source: source.to_string(),
+			manual: true,
+			since: now,
+			agreeing: 0,
+		};
+		state.rejections.remove(&key);
+		state.quarantined.insert(key, (quarantined, asset));
+	}
+
+	/// Routes the asset to `source` again, false if it wasn't quarantined
+	pub fn release(&self, blockchain: &str, symbol: &str, source: &str) -> bool {
+		let mut state = self.state.lock().expect("poisoned lock");
+		state.quarantined.remove(&key(blockchain, symbol, source)).is_some()
+	}
+
+	/// Quarantined sources ordered by blockchain, symbol and source
+	pub fn entries(&self) -> Vec<QuarantinedSource> {
+		let state = self.state.lock().expect("poisoned lock");
+		let mut entries: Vec<QuarantinedSource> =
+			state.quarantined.values().map(|(quarantined, _)| quarantined.clone()).collect();
+		entries.sort_by(|a, b| {
+			(&a.blockchain, &a.symbol, &a.source).cmp(&(&b.blockchain, &b.symbol, &b.source))
+		});
+		entries
+	}
+
+	/// Quarantined sources to probe with the assets to probe them with
+	fn on_probation(&self) -> Vec<(QuarantinedSource, Asset)> {
+		let state = self.state.lock().expect("poisoned lock");
+		state
+			.quarantined
+			.values()
+			.filter(|(quarantined, _)| !quarantined.manual)
+			.filter_map(|(quarantined, asset)| Some((quarantined.clone(), asset.clone()?)))
+			.collect()
+	}
+
+	/// Whether a shadow quotation `price` is within the deviation limit of the asset from the
+	/// `published` price with 12 decimals
+	pub fn agrees(&self, blockchain: &str, symbol: &str, published: u128, price: Decimal) -> bool {
+		let asset =
+			AssetSpecifier { blockchain: blockchain.to_string(), symbol: symbol.to_string() };
+		let price = (price * Decimal::from(1_000_000_000_000_u64)).trunc().to_u128();
+		match (self.limits.0.get(&asset), price) {
+			(Some(limit), Some(price)) if published != 0 => deviation(published, price) <= limit.0,
+			_ => false,
+		}
+	}
+
+	/// Counts a shadow quotation of a quarantined source, true if that released the source
+	pub fn record_probe(&self, blockchain: &str, symbol: &str, source: &str, agrees: bool) -> bool {
+		let mut state = self.state.lock().expect("poisoned lock");
+		let key = key(blockchain, symbol, source);
+		let quarantined = match state.quarantined.get_mut(&key) {
+			Some((quarantined, _)) => quarantined,
+			None => return false,
+		};
+		quarantined.agreeing = if agrees { quarantined.agreeing + 1 } else { 0 };
+		if quarantined.agreeing < self.probation {
+			return false
+		}
+		state.quarantined.remove(&key);
+		true
+	}
+}
+
+/// Asks every source quarantined through rejected prices for a shadow quotation of its asset in
+/// every `interval`, which isn't published, and releases it once it agreed with the published price
+/// `probation` consecutive times
+pub fn run_probation_loop<T>(
+	api: Arc<ExchangeRouter<T>>,
+	storage: Arc<CoinInfoStorage>,
+	quarantine: Arc<Quarantine>,
+	interval: std::time::Duration,
+) where
+	T: DiaApi + Send + Sync + 'static,
+{
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			for (quarantined, asset) in quarantine.on_probation() {
+				let QuarantinedSource { blockchain, symbol, source, .. } = &quarantined;
+				let published = match storage.get_currency(blockchain, symbol) {
+					Some(published) => published,
+					None => continue,
+				};
+				let quoted_asset = QuotedAsset { asset, volume: 0.0 };
+				let agrees = match api.get_quotation_by(source, &quoted_asset).await {
+					Ok(quotation) =>
+						quarantine.agrees(blockchain, symbol, published.price, quotation.price),
+					Err(err) => {
+						debug!(
+							"Shadow quotation of {}:{} by {} failed: {}",
+							blockchain, symbol, source, err
+						);
+						false
+					},
+				};
+				if quarantine.record_probe(blockchain, symbol, source, agrees) {
+					info!("Released {} for {}:{} after its probation", source, blockchain, symbol);
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_decimal_macros::dec;
+
+	fn asset() -> Asset {
+		Asset {
+			symbol: "BTC".into(),
+			name: "Bitcoin".into(),
+			address: "0x0".into(),
+			decimals: 8,
+			blockchain: "Bitcoin".into(),
+		}
+	}
+
+	#[test]
+	fn test_quarantine_and_probation() {
+		let quarantine = Quarantine::new("Bitcoin:BTC=10".parse().unwrap(), Some(2), 2);
+
+		quarantine.record(&asset(), "Coinbase", true, 1);
+		quarantine.record(&asset(), "Coinbase", false, 2);
+		quarantine.record(&asset(), "Coinbase", true, 3);
+		assert!(!quarantine.is_quarantined("Bitcoin", "BTC", "Coinbase"));
+		quarantine.record(&asset(), "Coinbase", true, 4);
+		assert!(quarantine.is_quarantined("bitcoin", "btc", "coinbase"));
+		assert_eq!(quarantine.on_probation().len(), 1);
+
+		let published = 100_000_000_000_000;
+		assert!(quarantine.agrees("Bitcoin", "BTC", published, dec!(105)));
+		assert!(!quarantine.agrees("Bitcoin", "BTC", published, dec!(120)));
+		assert!(!quarantine.record_probe("Bitcoin", "BTC", "Coinbase", true));
+		assert!(!quarantine.record_probe("Bitcoin", "BTC", "Coinbase", false));
+		assert!(!quarantine.record_probe("Bitcoin", "BTC", "Coinbase", true));
+		assert!(quarantine.record_probe("Bitcoin", "BTC", "Coinbase", true));
+		assert!(quarantine.entries().is_empty());
+
+		// Manually quarantined sources aren't probed
+		quarantine.quarantine("Bitcoin", "BTC", "Kraken", 5);
+		assert_eq!(quarantine.entries()[0].source, "Kraken");
+		assert!(quarantine.on_probation().is_empty());
+		assert!(quarantine.release("Bitcoin", "BTC", "kraken"));
+		assert!(!quarantine.release("Bitcoin", "BTC", "Kraken"));
+	}
+}
@@ -0,0 +1,239 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Buckets kept before the full ones, of clients that stayed away long enough, are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+/// Token bucket refilled with `rate` requests per second up to `burst` requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+	pub rate: f64,
+	pub burst: f64,
+}
+
+impl FromStr for RateLimit {
+	type Err = String;
+
+	/// Parses `<requests per second>[:<burst>]`, the burst defaults to a second of requests
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid rate limit '{}', expected <rate>[:<burst>]", s);
+		let (rate, burst) = match s.trim().split_once(':') {
+			Some((rate, burst)) => (rate, Some(burst)),
+			None => (s.trim(), None),
+		};
+		let rate: f64 = rate.parse().map_err(|_| invalid())?;
+		let burst: f64 = match burst {
+			Some(burst) => burst.parse().map_err(|_| invalid())?,
+			None => rate.max(1.0),
+		};
+		if !(rate > 0.0 && burst >= 1.0) {
+			return Err(invalid())
+		}
+		Ok(RateLimit { rate, burst })
+	}
+}
+
+/// Rate limits of the routes starting with a path prefix, parsed from
+/// `<path prefix>=<rate>[:<burst>],...`. The longest matching prefix applies.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteLimits(pub Vec<(String, RateLimit)>);
+
+impl FromStr for RouteLimits {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut limits = s
+			.split(',')
+			.filter(|entry| !entry.trim().is_empty())
+			.map(|entry| {
+				let (prefix, limit) = entry.split_once('=').ok_or_else(|| {
+					format!("Invalid entry '{}', expected <path prefix>=<rate>[:<burst>]", entry)
+				})?;
+				Ok((prefix.trim().to_string(), limit.parse()?))
+			})
+			.collect::<Result<Vec<(String, RateLimit)>, String>>()?;
+		limits.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+		Ok(RouteLimits(limits))
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RateLimitError {
+	InvalidApiKey,
+	TooManyRequests { retry_after: u64 },
+}
+
+impl Display for RateLimitError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RateLimitError::InvalidApiKey => write!(f, "Unknown API key"),
+			RateLimitError::TooManyRequests { retry_after } =>
+				write!(f, "Too many requests, retry after {}s", retry_after),
+		}
+	}
+}
+
+impl ResponseError for RateLimitError {
+	fn status_code(&self) -> StatusCode {
+		match self {
+			RateLimitError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+			RateLimitError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+		}
+	}
+
+	fn error_response(&self) -> HttpResponse {
+		let mut response = HttpResponse::build(self.status_code());
+		if let RateLimitError::TooManyRequests { retry_after } = self {
+			response.header(header::RETRY_AFTER, retry_after.to_string());
+		}
+		response.body(self.to_string())
+	}
+}
+
+#[derive(Debug)]
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+/// Per-IP token buckets of the routes with a rate limit. Requests with one of the API keys in the
+/// `X-Api-Key` header aren't limited, requests with another key are rejected.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+	limits: RouteLimits,
+	api_keys: HashSet<String>,
+	/// Keyed by the index of the route limit and the client address
+	buckets: Mutex<HashMap<(usize, IpAddr), Bucket>>,
+}
+
+impl RateLimiter {
+	pub fn new(limits: RouteLimits, api_keys: HashSet<String>) -> Self {
+		Self { limits, api_keys, buckets: Default::default() }
+	}
+
+	/// Whether requests are checked at all
+	pub fn is_enabled(&self) -> bool {
+		!self.limits.0.is_empty() || !self.api_keys.is_empty()
+	}
+
+	/// Takes a token from the bucket of `client` for the route of `path`, if it has a limit
+	pub fn check(
+		&self,
+		path: &str,
+		client: Option<IpAddr>,
+		api_key: Option<&str>,
+		now: Instant,
+	) -> Result<(), RateLimitError> {
+		if let Some(api_key) = api_key {
+			return match self.api_keys.contains(api_key) {
+				true => Ok(()),
+				false => Err(RateLimitError::InvalidApiKey),
+			}
+		}
+		let route = match self.limits.0.iter().position(|(prefix, _)| path.starts_with(prefix)) {
+			Some(route) => route,
+			None => return Ok(()),
+		};
+		// Clients without an address, like Unix sockets, share a bucket
+		let client = client.unwrap_or(IpAddr::from([0, 0, 0, 0]));
+		let RateLimit { rate, burst } = self.limits.0[route].1;
+		let mut buckets = self.buckets.lock().expect("poisoned lock");
+		if buckets.len() >= MAX_BUCKETS {
+			// Buckets that refilled by now are dropped, judged by the limit of their own route
+			let limits = &self.limits.0;
+			buckets.retain(|(route, _), bucket| {
+				let RateLimit { rate, burst } = limits[*route].1;
+				let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+				bucket.tokens + elapsed * rate < burst
+			});
+		}
+		let bucket =
+			buckets.entry((route, client)).or_insert(Bucket { tokens: burst, updated: now });
+		let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+		bucket.updated = now;
+		if bucket.tokens < 1.0 {
+			let retry_after = ((1.0 - bucket.tokens) / rate).ceil() as u64;
+			return Err(RateLimitError::TooManyRequests { retry_after: retry_after.max(1) })
+		}
+		bucket.tokens -= 1.0;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn test_route_limits_from_str() {
+		let limits: RouteLimits = "/currencies=10:50, /=0.5".parse().unwrap();
+		assert_eq!(
+			limits,
+			RouteLimits(vec![
+				("/currencies".into(), RateLimit { rate: 10.0, burst: 50.0 }),
+				("/".into(), RateLimit { rate: 0.5, burst: 1.0 }),
+			])
+		);
+		assert!("/currencies".parse::<RouteLimits>().is_err());
+		assert!("/currencies=0".parse::<RouteLimits>().is_err());
+		assert!("/currencies=1:x".parse::<RouteLimits>().is_err());
+	}
+
+	#[test]
+	fn test_rate_limiter() {
+		let limiter = RateLimiter::new(
+			"/currencies=1:2".parse().unwrap(),
+			vec!["trusted".to_string()].into_iter().collect(),
+		);
+		let client = Some(IpAddr::from([10, 0, 0, 1]));
+		let other = Some(IpAddr::from([10, 0, 0, 2]));
+		let start = Instant::now();
+
+		assert_eq!(limiter.check("/currencies", client, None, start), Ok(()));
+		assert_eq!(limiter.check("/currencies", client, None, start), Ok(()));
+		assert_eq!(
+			limiter.check("/currencies", client, None, start),
+			Err(RateLimitError::TooManyRequests { retry_after: 1 })
+		);
+		assert_eq!(limiter.check("/currencies", other, None, start), Ok(()));
+		assert_eq!(limiter.check("/health", client, None, start), Ok(()));
+		assert_eq!(limiter.check("/currencies", client, Some("trusted"), start), Ok(()));
+		assert_eq!(
+			limiter.check("/currencies", client, Some("guess"), start),
+			Err(RateLimitError::InvalidApiKey)
+		);
+
+		let later = start + Duration::from_secs(1);
+		assert_eq!(limiter.check("/currencies", client, None, later), Ok(()));
+		assert!(limiter.check("/currencies", client, None, later).is_err());
+	}
+
+	#[test]
+	fn test_rate_limiter_evicts_buckets_by_their_own_limit() {
+		let limiter =
+			RateLimiter::new("/slow=0.001:2, /fast=1000:1000".parse().unwrap(), Default::default());
+		let client = Some(IpAddr::from([10, 0, 0, 1]));
+		let start = Instant::now();
+		assert_eq!(limiter.check("/slow", client, None, start), Ok(()));
+		assert_eq!(limiter.check("/slow", client, None, start), Ok(()));
+		for i in 0..MAX_BUCKETS - 1 {
+			let other = Some(IpAddr::from([10, 1, (i >> 8) as u8, i as u8]));
+			assert_eq!(limiter.check("/fast", other, None, start), Ok(()));
+		}
+
+		// The full map drops the refilled buckets of /fast, but not the empty one of /slow
+		let later = start + Duration::from_secs(1);
+		assert_eq!(limiter.check("/fast", Some(IpAddr::from([10, 2, 0, 0])), None, later), Ok(()));
+		assert!(limiter.check("/slow", client, None, later).is_err());
+	}
+}
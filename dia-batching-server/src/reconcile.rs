@@ -0,0 +1,197 @@
+use crate::args::ReconcileArgs;
+use crate::chain::ChainClient;
+use crate::AssetSpecifier;
+use futures::future::join_all;
+use std::collections::HashSet;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Chains to reconcile, parsed from `<name>=<rpc url>,...` and kept in the given order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainUrls(pub Vec<(String, String)>);
+
+impl FromStr for ChainUrls {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.filter(|entry| !entry.trim().is_empty())
+			.map(|entry| {
+				let (name, url) = entry.split_once('=').ok_or_else(|| {
+					format!("Invalid entry '{}', expected <name>=<rpc url>", entry)
+				})?;
+				Ok((name.trim().to_string(), url.trim().to_string()))
+			})
+			.collect::<Result<_, _>>()
+			.map(ChainUrls)
+	}
+}
+
+fn display(asset: &AssetSpecifier) -> String {
+	format!("{}:{}", asset.blockchain, asset.symbol)
+}
+
+fn sorted(mut assets: Vec<&AssetSpecifier>) -> Vec<&AssetSpecifier> {
+	assets.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+	assets
+}
+
+/// Differences between the supported currencies of a chain and the currencies configured for the
+/// server. Currencies missing on one side that are likely the same asset as one missing on the
+/// other, because they only differ in case or share the symbol, are reported as mapping issues
+/// instead of as missing.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+	pub missing_on_server: Vec<String>,
+	pub missing_on_chain: Vec<String>,
+	pub mapping_issues: Vec<String>,
+}
+
+impl Reconciliation {
+	pub fn new(on_chain: &HashSet<AssetSpecifier>, configured: &HashSet<AssetSpecifier>) -> Self {
+		let missing_on_server = sorted(on_chain.difference(configured).collect());
+		let missing_on_chain = sorted(configured.difference(on_chain).collect());
+
+		let mut reconciliation = Reconciliation::default();
+		let mut paired: HashSet<&AssetSpecifier> = HashSet::new();
+		for on_server in &missing_on_chain {
+			let same_symbol = |on_chain: &&&AssetSpecifier| {
+				!paired.contains(**on_chain) &&
+					on_chain.symbol.eq_ignore_ascii_case(&on_server.symbol)
+			};
+			let candidates: Vec<&&AssetSpecifier> =
+				missing_on_server.iter().filter(same_symbol).collect();
+			// Prefer the currency differing only in case over one on another blockchain
+			let counterpart = candidates
+				.iter()
+				.find(|on_chain| on_chain.blockchain.eq_ignore_ascii_case(&on_server.blockchain))
+				.or_else(|| candidates.first());
+			match counterpart {
+				Some(counterpart) => {
+					reconciliation.mapping_issues.push(format!(
+						"{} on server, {} on chain",
+						display(on_server),
+						display(counterpart)
+					));
+					paired.insert(*on_server);
+					paired.insert(**counterpart);
+				},
+				None => reconciliation.missing_on_chain.push(display(on_server)),
+			}
+		}
+		reconciliation.missing_on_server = missing_on_server
+			.into_iter()
+			.filter(|asset| !paired.contains(asset))
+			.map(display)
+			.collect();
+		reconciliation
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.missing_on_server.is_empty() &&
+			self.missing_on_chain.is_empty() &&
+			self.mapping_issues.is_empty()
+	}
+}
+
+/// Reads the supported currencies of all chains at once and prints how each differs from the
+/// configured currencies. Fails if any chain differs or can't be read.
+pub async fn run(args: ReconcileArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+	if args.chains.0.is_empty() {
+		return Err("--chains needs at least one <name>=<rpc url>".into())
+	}
+	let configured: HashSet<AssetSpecifier> = args
+		.supported_currencies
+		.0
+		.iter()
+		.map(|asset| asset.parse())
+		.collect::<Result<_, _>>()?;
+
+	let pallet_name = args.pallet_name;
+	let results = join_all(args.chains.0.iter().map(|(_, url)| {
+		let client = ChainClient::new(url.clone(), pallet_name.clone());
+		async move { client.supported_currencies().await }
+	}))
+	.await;
+
+	let mut drifted = Vec::new();
+	for ((name, url), result) in args.chains.0.iter().zip(results) {
+		let on_chain = match result {
+			Ok(on_chain) => on_chain,
+			Err(err) => {
+				println!("{} ({}): failed to read supported currencies: {}", name, url, err);
+				drifted.push(name.as_str());
+				continue
+			},
+		};
+		let reconciliation = Reconciliation::new(&on_chain, &configured);
+		println!(
+			"{} ({}): {} supported on chain, {} configured",
+			name,
+			url,
+			on_chain.len(),
+			configured.len()
+		);
+		if reconciliation.is_empty() {
+			println!("  in sync");
+			continue
+		}
+		drifted.push(name.as_str());
+		for (label, assets) in [
+			("missing on server", &reconciliation.missing_on_server),
+			("missing on chain", &reconciliation.missing_on_chain),
+			("mapping issues", &reconciliation.mapping_issues),
+		] {
+			if !assets.is_empty() {
+				println!("  {}: {}", label, assets.join("; "));
+			}
+		}
+	}
+
+	if !drifted.is_empty() {
+		return Err(format!("Supported currencies drifted on {}", drifted.join(", ")).into())
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assets(assets: &[&str]) -> HashSet<AssetSpecifier> {
+		assets.iter().map(|asset| asset.parse().unwrap()).collect()
+	}
+
+	#[test]
+	fn test_reconciliation() {
+		let on_chain = assets(&[
+			"Polkadot:DOT",
+			"BifrostPolkadot:BNC",
+			"Stellar:USDC",
+			"FIAT:BRL-USD",
+			"Kusama:KSM",
+		]);
+		let configured =
+			assets(&["Polkadot:DOT", "Bifrost:BNC", "stellar:USDC", "FIAT:MXN-USD", "Kusama:KSM"]);
+
+		assert_eq!(
+			Reconciliation::new(&on_chain, &configured),
+			Reconciliation {
+				missing_on_server: vec!["FIAT:BRL-USD".into()],
+				missing_on_chain: vec!["FIAT:MXN-USD".into()],
+				mapping_issues: vec![
+					"Bifrost:BNC on server, BifrostPolkadot:BNC on chain".into(),
+					"stellar:USDC on server, Stellar:USDC on chain".into(),
+				],
+			}
+		);
+		assert!(Reconciliation::new(&on_chain, &on_chain).is_empty());
+		assert_eq!(
+			"pendulum=http://a, amplitude=http://b".parse::<ChainUrls>().unwrap().0,
+			vec![
+				("pendulum".to_string(), "http://a".to_string()),
+				("amplitude".to_string(), "http://b".to_string())
+			]
+		);
+	}
+}
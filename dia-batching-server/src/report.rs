@@ -0,0 +1,398 @@
+use chrono::{Datelike, TimeZone, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, Default)]
+struct AssetStatistics {
+	attempts: u64,
+	updates: u64,
+	staleness_incidents: u64,
+	spend: f64,
+	/// Whether the last attempt failed and the asset is served with an outdated price since
+	stale: bool,
+}
+
+#[derive(Debug, Default)]
+struct FeedStatisticsState {
+	/// When the statistics were created, kept across reports
+	started: u64,
+	since: u64,
+	/// When the update loop last finished an iteration
+	last_iteration: Option<u64>,
+	/// When each source last delivered a published price, `None` if it never did. Kept across
+	/// reports.
+	last_update_by_source: HashMap<SmolStr, Option<u64>>,
+	assets: HashMap<(SmolStr, SmolStr), AssetStatistics>,
+	requests_by_source: HashMap<SmolStr, u64>,
+	spend_by_source: HashMap<SmolStr, f64>,
+	/// Year and month of the last report and the spend of the reports in that month
+	month: Option<(i32, u32)>,
+	month_spend_by_source: HashMap<SmolStr, f64>,
+}
+
+/// Estimated cost of a single request to each source, parsed from `<source>=<cost>,...`. Sources
+/// are named like in the feed report, e.g. `diadata.org`, `YahooFinance` or `Coinbase`, and
+/// matched case-insensitively. Requests to sources without a cost are free.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceCosts(pub HashMap<SmolStr, f64>);
+
+impl FromStr for SourceCosts {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.filter(|entry| !entry.trim().is_empty())
+			.map(|entry| {
+				let (source, cost) = entry.split_once('=').ok_or_else(|| {
+					format!("Invalid entry '{}', expected <source>=<cost>", entry)
+				})?;
+				let cost: f64 = cost
+					.trim()
+					.parse()
+					.ok()
+					.filter(|cost: &f64| cost.is_finite() && *cost >= 0.0)
+					.ok_or_else(|| format!("Invalid cost '{}' of source {}", cost, source))?;
+				Ok((source.trim().to_lowercase().into(), cost))
+			})
+			.collect::<Result<_, _>>()
+			.map(SourceCosts)
+	}
+}
+
+/// Estimated spend on the sources since the last report, and in the current month including it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendEstimate {
+	pub since: u64,
+	pub spend_by_source: BTreeMap<SmolStr, f64>,
+	pub month_to_date_spend_by_source: BTreeMap<SmolStr, f64>,
+}
+
+fn month_of(timestamp: u64) -> Option<(i32, u32)> {
+	let time = Utc.timestamp_opt(timestamp as i64, 0).single()?;
+	Some((time.year(), time.month()))
+}
+
+impl FeedStatisticsState {
+	fn spend_estimate(&self, now: u64) -> SpendEstimate {
+		let mut month_to_date: BTreeMap<SmolStr, f64> = BTreeMap::new();
+		if self.month == month_of(now) {
+			month_to_date.extend(self.month_spend_by_source.clone());
+		}
+		for (source, spend) in &self.spend_by_source {
+			*month_to_date.entry(source.clone()).or_default() += spend;
+		}
+		SpendEstimate {
+			since: self.since,
+			spend_by_source: self.spend_by_source.clone().into_iter().collect(),
+			month_to_date_spend_by_source: month_to_date,
+		}
+	}
+}
+
+/// Counts the update attempts of every asset and the requests sent to every source between two
+/// reports
+#[derive(Debug, Default)]
+pub struct FeedStatistics {
+	state: Mutex<FeedStatisticsState>,
+	costs: SourceCosts,
+}
+
+/// Whether the update loop and the sources kept the prices current
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+	pub healthy: bool,
+	pub last_iteration: Option<u64>,
+	pub last_update_by_source: BTreeMap<SmolStr, Option<u64>>,
+	/// Sources that didn't deliver a published price in the maximum age
+	pub stale_sources: Vec<SmolStr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetReport {
+	pub blockchain: SmolStr,
+	pub symbol: SmolStr,
+	pub attempts: u64,
+	pub updates: u64,
+	/// Share of the attempts that updated the published price
+	pub uptime: f64,
+	/// Number of times updating the asset started failing, leaving an outdated or no price
+	pub staleness_incidents: u64,
+	/// Estimated cost of the requests for the asset
+	pub spend: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedReport {
+	pub from: u64,
+	pub to: u64,
+	pub assets: Vec<AssetReport>,
+	pub requests_by_source: BTreeMap<SmolStr, u64>,
+	pub spend_by_source: BTreeMap<SmolStr, f64>,
+	/// Spend of the reports in the calendar month of `to`, including this one
+	pub month_to_date_spend_by_source: BTreeMap<SmolStr, f64>,
+}
+
+impl FeedStatistics {
+	pub fn new(since: u64) -> Self {
+		Self {
+			state: Mutex::new(FeedStatisticsState { started: since, since, ..Default::default() }),
+			costs: Default::default(),
+		}
+	}
+
+	pub fn with_source_costs(mut self, costs: SourceCosts) -> Self {
+		self.costs = costs;
+		self
+	}
+
+	/// Records an attempt to update an asset, which sent a request to `source`
+	pub fn record(&self, blockchain: &str, symbol: &str, source: &str, updated: bool) {
+		let cost = self.costs.0.get(source.to_lowercase().as_str()).copied().unwrap_or_default();
+		let mut state = self.state.lock().expect("poisoned lock");
+		*state.requests_by_source.entry(source.into()).or_default() += 1;
+		let last_update = state.last_update_by_source.entry(source.into()).or_default();
+		if updated {
+			*last_update = Some(Utc::now().timestamp().unsigned_abs());
+		}
+		if cost > 0.0 {
+			*state.spend_by_source.entry(source.into()).or_default() += cost;
+		}
+		let asset = state.assets.entry((blockchain.into(), symbol.into())).or_default();
+		asset.attempts += 1;
+		asset.spend += cost;
+		if updated {
+			asset.updates += 1;
+			asset.stale = false;
+		} else if !asset.stale {
+			asset.staleness_incidents += 1;
+			asset.stale = true;
+		}
+	}
+
+	/// Records that the update loop finished an iteration at `now`
+	pub fn record_iteration(&self, now: u64) {
+		self.state.lock().expect("poisoned lock").last_iteration = Some(now);
+	}
+
+	/// Unhealthy if the update loop didn't finish an iteration or a source didn't deliver a
+	/// published price in the last `max_age` seconds. Both are counted from the start until they
+	/// did for the first time.
+	pub fn health(&self, now: u64, max_age: u64) -> Health {
+		let state = self.state.lock().expect("poisoned lock");
+		let is_stale =
+			|last: Option<u64>| now.saturating_sub(last.unwrap_or(state.started)) > max_age;
+		let mut stale_sources: Vec<SmolStr> = state
+			.last_update_by_source
+			.iter()
+			.filter(|(_, last_update)| is_stale(**last_update))
+			.map(|(source, _)| source.clone())
+			.collect();
+		stale_sources.sort();
+		Health {
+			healthy: !is_stale(state.last_iteration) && stale_sources.is_empty(),
+			last_iteration: state.last_iteration,
+			last_update_by_source: state.last_update_by_source.clone().into_iter().collect(),
+			stale_sources,
+		}
+	}
+
+	/// Current estimate of the spend, without starting a new report
+	pub fn spend_estimate(&self, now: u64) -> SpendEstimate {
+		self.state.lock().expect("poisoned lock").spend_estimate(now)
+	}
+
+	/// Returns the report since the last one and starts counting anew. Assets served with an
+	/// outdated price stay stale, so a staleness incident is only counted once. The spend of a
+	/// report counts towards the month it is taken in.
+	pub fn take_report(&self, now: u64) -> FeedReport {
+		let mut state = self.state.lock().expect("poisoned lock");
+		let spend = state.spend_estimate(now);
+		let mut assets: Vec<AssetReport> = state
+			.assets
+			.iter_mut()
+			.filter(|(_, asset)| asset.attempts > 0)
+			.map(|((blockchain, symbol), asset)| {
+				let report = AssetReport {
+					blockchain: blockchain.clone(),
+					symbol: symbol.clone(),
+					attempts: asset.attempts,
+					updates: asset.updates,
+					uptime: asset.updates as f64 / asset.attempts.max(1) as f64,
+					staleness_incidents: asset.staleness_incidents,
+					spend: asset.spend,
+				};
+				*asset = AssetStatistics { stale: asset.stale, ..Default::default() };
+				report
+			})
+			.collect();
+		assets.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		let report = FeedReport {
+			from: state.since,
+			to: now,
+			assets,
+			requests_by_source: state.requests_by_source.drain().collect(),
+			spend_by_source: spend.spend_by_source,
+			month_to_date_spend_by_source: spend.month_to_date_spend_by_source.clone(),
+		};
+		state.since = now;
+		state.spend_by_source.clear();
+		state.month = month_of(now);
+		state.month_spend_by_source = spend.month_to_date_spend_by_source.into_iter().collect();
+		report
+	}
+}
+
+/// Where the periodic feed reports go to, a file they are appended to as JSON lines and/or a
+/// webhook they are posted to as JSON
+#[derive(Debug, Clone, Default)]
+pub struct ReportTargets {
+	pub file: Option<PathBuf>,
+	pub webhook: Option<String>,
+}
+
+async fn append_report(
+	path: &Path,
+	report: &FeedReport,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+	let mut line = serde_json::to_vec(report)?;
+	line.push(b'\n');
+	let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+	file.write_all(&line).await?;
+	Ok(())
+}
+
+async fn post_report(url: &str, report: &FeedReport) -> Result<(), Box<dyn Error + Send + Sync>> {
+	reqwest::Client::new().post(url).json(report).send().await?.error_for_status()?;
+	Ok(())
+}
+
+pub fn run_report_loop(
+	statistics: Arc<FeedStatistics>,
+	targets: ReportTargets,
+	interval: Duration,
+) {
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			let report = statistics.take_report(chrono::Utc::now().timestamp().unsigned_abs());
+			info!("Feed report of {} assets since {}", report.assets.len(), report.from);
+			if let Some(path) = &targets.file {
+				if let Err(err) = append_report(path, &report).await {
+					error!("Failed to write feed report to {}: {}", path.display(), err);
+				}
+			}
+			if let Some(url) = &targets.webhook {
+				if let Err(err) = post_report(url, &report).await {
+					error!("Failed to post feed report to {}: {}", url, err);
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_take_report() {
+		let statistics = FeedStatistics::new(100);
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", true);
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", false);
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", false);
+		statistics.record("Bitcoin", "BTC", "diadata.org", true);
+
+		let report = statistics.take_report(200);
+
+		assert_eq!((report.from, report.to), (100, 200));
+		assert_eq!(report.assets.len(), 2);
+		assert_eq!(report.assets[1].symbol, "MXN-USD");
+		assert_eq!(report.assets[1].attempts, 3);
+		assert_eq!(report.assets[1].updates, 1);
+		assert_eq!(report.assets[1].staleness_incidents, 1);
+		assert!((report.assets[1].uptime - 1.0 / 3.0).abs() < f64::EPSILON);
+		assert_eq!(report.requests_by_source.get("YahooFinance"), Some(&3));
+
+		// Still stale from the previous report
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", false);
+		let report = statistics.take_report(300);
+
+		assert_eq!(report.from, 200);
+		assert_eq!(report.assets.len(), 1);
+		assert_eq!(report.assets[0].attempts, 1);
+		assert_eq!(report.assets[0].staleness_incidents, 0);
+		assert_eq!(report.assets[0].uptime, 0.0);
+	}
+
+	#[test]
+	fn test_spend() {
+		// 2023-11-30 and 2023-12-01
+		let (november, december) = (1701302400, 1701388800);
+		let statistics = FeedStatistics::new(november - 10)
+			.with_source_costs("diadata.org=0.5, yahoofinance=0.25".parse().unwrap());
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", true);
+		statistics.record("Bitcoin", "BTC", "diadata.org", true);
+		statistics.record("Bitcoin", "BTC", "diadata.org", false);
+		statistics.record("Bitcoin", "BTC", "Coinbase", true);
+
+		assert_eq!(
+			statistics.spend_estimate(november).spend_by_source.get("diadata.org"),
+			Some(&1.0)
+		);
+		let report = statistics.take_report(november);
+
+		assert_eq!(report.assets[0].spend, 1.0);
+		assert_eq!(report.spend_by_source.get("YahooFinance"), Some(&0.25));
+		assert_eq!(report.spend_by_source.get("Coinbase"), None);
+		assert_eq!(report.month_to_date_spend_by_source.get("diadata.org"), Some(&1.0));
+
+		statistics.record("Bitcoin", "BTC", "diadata.org", true);
+		let estimate = statistics.spend_estimate(november + 10);
+		assert_eq!(estimate.since, november);
+		assert_eq!(estimate.month_to_date_spend_by_source.get("diadata.org"), Some(&1.5));
+
+		// A new month starts from the spend of its first report
+		let report = statistics.take_report(december);
+		assert_eq!(report.month_to_date_spend_by_source.get("diadata.org"), Some(&0.5));
+		assert_eq!(report.month_to_date_spend_by_source.get("YahooFinance"), None);
+
+		assert!("diadata.org=-1".parse::<SourceCosts>().is_err());
+		assert!("diadata.org".parse::<SourceCosts>().is_err());
+	}
+
+	#[test]
+	fn test_health() {
+		let now = Utc::now().timestamp().unsigned_abs();
+		let statistics = FeedStatistics::new(now - 100);
+
+		assert!(statistics.health(now - 40, 60).healthy);
+		assert!(!statistics.health(now, 60).healthy);
+
+		statistics.record("Bitcoin", "BTC", "diadata.org", true);
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", false);
+		statistics.record_iteration(now);
+		let health = statistics.health(now, 60);
+
+		assert!(!health.healthy);
+		assert_eq!(health.last_iteration, Some(now));
+		assert_eq!(health.stale_sources, vec!["YahooFinance"]);
+		assert!(matches!(health.last_update_by_source.get("diadata.org"), Some(Some(_))));
+
+		statistics.record("FIAT", "MXN-USD", "YahooFinance", true);
+		statistics.take_report(now);
+		assert!(statistics.health(now, 60).healthy);
+	}
+}
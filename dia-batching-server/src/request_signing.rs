@@ -0,0 +1,138 @@
+use actix_web::dev::ServiceRequest;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt::{Display, Formatter};
+
+pub const TIMESTAMP_HEADER: &str = "X-Dia-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Dia-Signature";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Shared secret the dia-oracle offchain worker signs its requests with. The signature is the
+/// hex encoded HMAC-SHA256 of `"{timestamp}\n{path}"`, the same scheme the pallet uses.
+#[derive(Clone)]
+pub struct RequestSigner {
+	key: Vec<u8>,
+	max_skew_in_seconds: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+	MissingHeader(&'static str),
+	InvalidTimestamp,
+	TimestampOutOfRange,
+	InvalidSignature,
+}
+
+impl Display for SignatureError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SignatureError::MissingHeader(header) => write!(f, "Missing header {}", header),
+			SignatureError::InvalidTimestamp => write!(f, "Timestamp is not a number"),
+			SignatureError::TimestampOutOfRange => write!(f, "Timestamp is too far off"),
+			SignatureError::InvalidSignature => write!(f, "Signature is invalid"),
+		}
+	}
+}
+
+impl std::error::Error for SignatureError {}
+
+impl RequestSigner {
+	pub fn new(key: Vec<u8>, max_skew_in_seconds: u64) -> Self {
+		Self { key, max_skew_in_seconds }
+	}
+
+	fn mac(&self, timestamp: &str, path: &str) -> HmacSha256 {
+		let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+		mac.update(timestamp.as_bytes());
+		mac.update(b"\n");
+		mac.update(path.as_bytes());
+		mac
+	}
+
+	/// Returns the values of the timestamp and signature headers for a request to `path`
+	pub fn sign(&self, timestamp: u64, path: &str) -> (String, String) {
+		let timestamp = timestamp.to_string();
+		let signature = hex::encode(self.mac(&timestamp, path).finalize().into_bytes());
+		(timestamp, signature)
+	}
+
+	pub fn verify(
+		&self,
+		timestamp: &str,
+		signature: &str,
+		path: &str,
+		now: u64,
+	) -> Result<(), SignatureError> {
+		let seconds = timestamp.parse::<u64>().map_err(|_| SignatureError::InvalidTimestamp)?;
+		if seconds.max(now) - seconds.min(now) > self.max_skew_in_seconds {
+			return Err(SignatureError::TimestampOutOfRange)
+		}
+		let signature = hex::decode(signature).map_err(|_| SignatureError::InvalidSignature)?;
+		self.mac(timestamp, path)
+			.verify_slice(&signature)
+			.map_err(|_| SignatureError::InvalidSignature)
+	}
+
+	pub fn verify_request(&self, req: &ServiceRequest, now: u64) -> Result<(), SignatureError> {
+		let header = |name: &'static str| {
+			req.headers()
+				.get(name)
+				.and_then(|value| value.to_str().ok())
+				.ok_or(SignatureError::MissingHeader(name))
+		};
+		let path = match req.uri().query() {
+			Some(query) => format!("{}?{}", req.path(), query),
+			None => req.path().to_string(),
+		};
+		self.verify(header(TIMESTAMP_HEADER)?, header(SIGNATURE_HEADER)?, &path, now)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sign_matches_pallet() {
+		let signer = RequestSigner::new(b"secret".to_vec(), 300);
+
+		// Same vector is checked on the pallet side
+		assert_eq!(
+			signer.sign(1700000000, "/currencies/"),
+			(
+				"1700000000".to_string(),
+				"12cdbc46add9bc26f32f3ddf831f673da6960abc2cbdbceccf6ec59e0d830c21".to_string()
+			)
+		);
+	}
+
+	#[test]
+	fn test_verify() {
+		let signer = RequestSigner::new(b"secret".to_vec(), 300);
+		let (timestamp, signature) = signer.sign(1700000000, "/currencies");
+
+		assert_eq!(signer.verify(&timestamp, &signature, "/currencies", 1700000100), Ok(()));
+		assert_eq!(
+			signer.verify(&timestamp, &signature, "/currencies", 1700000301),
+			Err(SignatureError::TimestampOutOfRange)
+		);
+		assert_eq!(
+			signer.verify(&timestamp, &signature, "/debug", 1700000000),
+			Err(SignatureError::InvalidSignature)
+		);
+		assert_eq!(
+			RequestSigner::new(b"other".to_vec(), 300).verify(
+				&timestamp,
+				&signature,
+				"/currencies",
+				1700000000
+			),
+			Err(SignatureError::InvalidSignature)
+		);
+		assert_eq!(
+			signer.verify("yesterday", &signature, "/currencies", 1700000000),
+			Err(SignatureError::InvalidTimestamp)
+		);
+	}
+}
@@ -0,0 +1,47 @@
+use dia_common::signing::{
+	public_key, sign_response, PUBLIC_KEY_HEADER, RESPONSE_SIGNATURE_HEADER,
+};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Ed25519 key the bodies of `/currencies` responses are signed with, so clients can check that
+/// prices were served by this server. Verify them with `dia_common::signing::verify_response`.
+#[derive(Clone)]
+pub struct ResponseSigner {
+	seed: [u8; 32],
+}
+
+impl FromStr for ResponseSigner {
+	type Err = String;
+
+	/// Parses the hex encoded 32 byte seed of the key
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut seed = [0u8; 32];
+		hex::decode_to_slice(s.trim().trim_start_matches("0x"), &mut seed).map_err(|err| {
+			format!("Invalid response signing key, expected 32 hex bytes: {}", err)
+		})?;
+		Ok(Self { seed })
+	}
+}
+
+impl ResponseSigner {
+	/// Reads the hex encoded seed of the key from `path`
+	pub async fn read(path: &Path) -> Result<Self, String> {
+		tokio::fs::read_to_string(path)
+			.await
+			.map_err(|err| format!("Failed to read {}: {}", path.display(), err))?
+			.parse()
+	}
+
+	pub fn public_key(&self) -> String {
+		hex::encode(public_key(self.seed))
+	}
+
+	/// Signature and public key headers of a response with `body`
+	pub fn headers(&self, body: &[u8]) -> [(&'static str, String); 2] {
+		[
+			(RESPONSE_SIGNATURE_HEADER, hex::encode(sign_response(self.seed, body))),
+			(PUBLIC_KEY_HEADER, self.public_key()),
+		]
+	}
+}
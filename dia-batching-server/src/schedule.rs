@@ -0,0 +1,188 @@
+use crate::{parse_asset_map, AssetSpecifier};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+fn parse_time(s: &str) -> Result<u32, String> {
+	let invalid = || format!("Invalid time '{}', expected HH:MM", s);
+	let (hours, minutes) = s.trim().split_once(':').ok_or_else(invalid)?;
+	let hours: u32 = hours.parse().map_err(|_| invalid())?;
+	let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+	if hours > 23 || minutes > 59 {
+		return Err(invalid())
+	}
+	Ok(hours * 60 + minutes)
+}
+
+/// Daily window in UTC parsed from `HH:MM-HH:MM`, from the start minute up to but excluding the
+/// end minute. Windows ending before they start span midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicationWindow {
+	start: u32,
+	end: u32,
+}
+
+impl FromStr for PublicationWindow {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (start, end) = s
+			.split_once('-')
+			.ok_or_else(|| format!("Invalid window '{}', expected HH:MM-HH:MM", s))?;
+		let window = PublicationWindow { start: parse_time(start)?, end: parse_time(end)? };
+		if window.start == window.end {
+			return Err(format!("Window '{}' is empty", s))
+		}
+		Ok(window)
+	}
+}
+
+impl PublicationWindow {
+	fn contains(&self, minute_of_day: u32) -> bool {
+		if self.start < self.end {
+			(self.start..self.end).contains(&minute_of_day)
+		} else {
+			minute_of_day >= self.start || minute_of_day < self.end
+		}
+	}
+
+	/// Minutes from `minute_of_day` until the window opens next, zero if it is open
+	fn minutes_until_open(&self, minute_of_day: u32) -> u32 {
+		if self.contains(minute_of_day) {
+			0
+		} else {
+			(self.start + MINUTES_PER_DAY - minute_of_day) % MINUTES_PER_DAY
+		}
+	}
+}
+
+/// Windows of one asset joined by `+`, e.g. `04:00-04:15+16:00-16:15`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicationWindows(pub Vec<PublicationWindow>);
+
+impl FromStr for PublicationWindows {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split('+').map(str::parse).collect::<Result<_, _>>().map(PublicationWindows)
+	}
+}
+
+/// Windows individual assets are fetched in, parsed from `<blockchain>:<symbol>=<windows>,...`.
+/// Outside of them the published coin info is carried forward without asking the source. Assets
+/// without a schedule are fetched in every iteration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicationSchedules(pub HashMap<AssetSpecifier, PublicationWindows>);
+
+impl FromStr for PublicationSchedules {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(PublicationSchedules)
+	}
+}
+
+impl PublicationSchedules {
+	/// Whether the asset is to be fetched at the unix timestamp `now`
+	pub fn is_open(&self, blockchain: &str, symbol: &str, now: u64) -> bool {
+		let asset = AssetSpecifier { blockchain: blockchain.into(), symbol: symbol.into() };
+		let minute_of_day = ((now / 60) % MINUTES_PER_DAY as u64) as u32;
+		self.0
+			.get(&asset)
+			.into_iter()
+			.all(|windows| windows.0.iter().any(|window| window.contains(minute_of_day)))
+	}
+
+	/// First unix timestamp from `at` on at which the asset is to be fetched, to the minute
+	pub fn next_open(&self, blockchain: &str, symbol: &str, at: u64) -> u64 {
+		let asset = AssetSpecifier { blockchain: blockchain.into(), symbol: symbol.into() };
+		let minute_of_day = ((at / 60) % MINUTES_PER_DAY as u64) as u32;
+		let minutes = self
+			.0
+			.get(&asset)
+			.and_then(|windows| {
+				windows.0.iter().map(|window| window.minutes_until_open(minute_of_day)).min()
+			})
+			.unwrap_or(0);
+		if minutes == 0 {
+			at
+		} else {
+			(at / 60 + minutes as u64) * 60
+		}
+	}
+}
+
+/// Publication schedules of the update loop together with the time it starts its next iteration
+#[derive(Debug, Default)]
+pub struct UpdateSchedule {
+	schedules: PublicationSchedules,
+	next_iteration: AtomicU64,
+}
+
+impl UpdateSchedule {
+	pub fn new(schedules: PublicationSchedules) -> Self {
+		Self { schedules, next_iteration: AtomicU64::new(0) }
+	}
+
+	pub fn schedules(&self) -> &PublicationSchedules {
+		&self.schedules
+	}
+
+	/// Sets the unix timestamp the update loop starts its next iteration at
+	pub fn set_next_iteration(&self, at: u64) {
+		self.next_iteration.store(at, Ordering::Relaxed);
+	}
+
+	/// Earliest unix timestamp the asset is fetched again at, the first iteration in one of its
+	/// windows. `None` if this process doesn't update the prices or didn't finish an iteration yet.
+	pub fn next_update_at(&self, blockchain: &str, symbol: &str) -> Option<u64> {
+		let next_iteration = self.next_iteration.load(Ordering::Relaxed);
+		if next_iteration == 0 {
+			return None
+		}
+		Some(self.schedules.next_open(blockchain, symbol, next_iteration))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_publication_schedules() {
+		let schedules: PublicationSchedules =
+			"FIAT:MXN-USD=16:00-16:15, FIAT:BRL-USD=23:50-00:10+12:00-12:01"
+				.parse()
+				.unwrap();
+		// 2023-11-20 16:00 UTC
+		let four_pm = 1700496000;
+
+		assert!(schedules.is_open("FIAT", "MXN-USD", four_pm));
+		assert!(schedules.is_open("FIAT", "MXN-USD", four_pm + 14 * 60 + 59));
+		assert!(!schedules.is_open("FIAT", "MXN-USD", four_pm + 15 * 60));
+		assert!(!schedules.is_open("FIAT", "MXN-USD", four_pm - 1));
+		assert!(schedules.is_open("FIAT", "BRL-USD", four_pm + 8 * 3600 - 5 * 60));
+		assert!(schedules.is_open("FIAT", "BRL-USD", four_pm + 8 * 3600 + 5 * 60));
+		assert!(schedules.is_open("FIAT", "BRL-USD", four_pm - 4 * 3600));
+		assert!(!schedules.is_open("FIAT", "BRL-USD", four_pm));
+		assert!(schedules.is_open("Bitcoin", "BTC", four_pm));
+
+		assert_eq!(schedules.next_open("FIAT", "MXN-USD", four_pm + 60), four_pm + 60);
+		assert_eq!(schedules.next_open("FIAT", "MXN-USD", four_pm + 15 * 60), four_pm + 24 * 3600);
+		// 23:50 is the next window opening
+		assert_eq!(schedules.next_open("FIAT", "BRL-USD", four_pm + 30), four_pm + 470 * 60);
+		assert_eq!(schedules.next_open("Bitcoin", "BTC", four_pm + 30), four_pm + 30);
+
+		let update_schedule = UpdateSchedule::new(schedules);
+		assert_eq!(update_schedule.next_update_at("Bitcoin", "BTC"), None);
+		update_schedule.set_next_iteration(four_pm + 16 * 60);
+		assert_eq!(update_schedule.next_update_at("Bitcoin", "BTC"), Some(four_pm + 16 * 60));
+		assert_eq!(update_schedule.next_update_at("FIAT", "MXN-USD"), Some(four_pm + 24 * 3600));
+
+		assert!("FIAT:MXN-USD=16:00".parse::<PublicationSchedules>().is_err());
+		assert!("FIAT:MXN-USD=24:00-01:00".parse::<PublicationSchedules>().is_err());
+		assert!("FIAT:MXN-USD=16:00-16:00".parse::<PublicationSchedules>().is_err());
+	}
+}
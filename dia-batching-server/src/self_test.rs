@@ -0,0 +1,204 @@
+use crate::handlers::Currency;
+use crate::request_signing::{RequestSigner, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::{AssetSpecifier, SupportedAssets};
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+const SELF_TEST_PATH: &str = "/currencies";
+
+/// Mirror of the `CoinInfo` the dia-oracle pallet deserializes in its offchain worker.
+/// Kept separate from `storage::CoinInfo` on purpose: a change to the server's type must not
+/// silently change what the probe accepts.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct OcwCoinInfo {
+	symbol: String,
+	name: String,
+	blockchain: String,
+	supply: u128,
+	last_update_timestamp: u64,
+	price: u128,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SelfTestError {
+	/// The body does not deserialize the way the pallet deserializes it
+	Malformed(String),
+	/// A coin was returned that was not requested, the pallet would store it under a foreign key
+	Unrequested { blockchain: String, symbol: String },
+	/// A coin has an empty blockchain or symbol
+	EmptyKey,
+	/// A coin claims to be updated in the future
+	TimestampInFuture { blockchain: String, symbol: String, timestamp: u64 },
+}
+
+impl Display for SelfTestError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SelfTestError::Malformed(e) =>
+				write!(f, "Response is not parsable by the pallet: {}", e),
+			SelfTestError::Unrequested { blockchain, symbol } =>
+				write!(f, "Response contains unrequested asset {}:{}", blockchain, symbol),
+			SelfTestError::EmptyKey => write!(f, "Response contains an asset with empty key"),
+			SelfTestError::TimestampInFuture { blockchain, symbol, timestamp } => write!(
+				f,
+				"Asset {}:{} has a timestamp in the future ({})",
+				blockchain, symbol, timestamp
+			),
+		}
+	}
+}
+
+impl Error for SelfTestError {}
+
+/// Checks a `/currencies` response body against the constraints the pallet offchain worker
+/// applies. Returns the requested assets missing from the response.
+pub fn validate_response(
+	body: &[u8],
+	requested: &[Currency],
+	now: u64,
+) -> Result<Vec<String>, SelfTestError> {
+	let coins: Vec<OcwCoinInfo> =
+		serde_json::from_slice(body).map_err(|e| SelfTestError::Malformed(e.to_string()))?;
+
+	let requested: HashSet<(&str, &str)> =
+		requested.iter().map(|c| (c.blockchain.as_str(), c.symbol.as_str())).collect();
+	let mut returned = HashSet::new();
+
+	for coin in coins.iter() {
+		if coin.blockchain.is_empty() || coin.symbol.is_empty() {
+			return Err(SelfTestError::EmptyKey)
+		}
+		if !requested.contains(&(coin.blockchain.as_str(), coin.symbol.as_str())) {
+			return Err(SelfTestError::Unrequested {
+				blockchain: coin.blockchain.clone(),
+				symbol: coin.symbol.clone(),
+			})
+		}
+		if coin.last_update_timestamp > now {
+			return Err(SelfTestError::TimestampInFuture {
+				blockchain: coin.blockchain.clone(),
+				symbol: coin.symbol.clone(),
+				timestamp: coin.last_update_timestamp,
+			})
+		}
+		returned.insert((coin.blockchain.as_str(), coin.symbol.as_str()));
+	}
+
+	Ok(requested
+		.difference(&returned)
+		.map(|(blockchain, symbol)| format!("{}:{}", blockchain, symbol))
+		.collect())
+}
+
+async fn probe(
+	client: &reqwest::Client,
+	url: &str,
+	assets: &[Currency],
+	signer: Option<&RequestSigner>,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+	let mut request = client.post(url).json(assets);
+	if let Some(signer) = signer {
+		let (timestamp, signature) =
+			signer.sign(Utc::now().timestamp().unsigned_abs(), SELF_TEST_PATH);
+		request = request.header(TIMESTAMP_HEADER, timestamp).header(SIGNATURE_HEADER, signature);
+	}
+	let body = request.send().await?.error_for_status()?.bytes().await?;
+	let now = Utc::now().timestamp().unsigned_abs();
+	Ok(validate_response(&body, assets, now)?)
+}
+
+/// Periodically requests all supported assets from the server's own `/currencies` endpoint and logs
+/// an error whenever the response would be rejected by the pallet offchain worker.
+pub fn run_self_test_loop(
+	port: u16,
+	tls: bool,
+	supported_currencies: Arc<SupportedAssets>,
+	interval: std::time::Duration,
+	signer: Option<RequestSigner>,
+) {
+	let scheme = if tls { "https" } else { "http" };
+	let url = format!("{}://127.0.0.1:{}{}", scheme, port, SELF_TEST_PATH);
+	let _ = tokio::spawn(async move {
+		// The certificate is issued for the public host name, not the loopback address
+		let client = reqwest::Client::builder()
+			.danger_accept_invalid_certs(tls)
+			.build()
+			.expect("default TLS backend is available");
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			let assets: Vec<Currency> = supported_currencies
+				.load()
+				.iter()
+				.flat_map(|assets| assets.iter())
+				.map(|AssetSpecifier { blockchain, symbol }| Currency {
+					blockchain: blockchain.clone(),
+					symbol: symbol.clone(),
+				})
+				.collect();
+			match probe(&client, &url, &assets, signer.as_ref()).await {
+				Ok(missing) if missing.is_empty() => info!("Self test passed"),
+				Ok(missing) => warn!("Self test passed, but assets are missing: {:?}", missing),
+				Err(err) => error!("Self test failed: {}", err),
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn requested() -> Vec<Currency> {
+		vec![
+			Currency { blockchain: "Bitcoin".into(), symbol: "BTC".into() },
+			Currency { blockchain: "FIAT".into(), symbol: "MXN-USD".into() },
+		]
+	}
+
+	#[test]
+	fn test_validate_response_works() {
+		let body = br#"[{"symbol":"BTC","name":"Bitcoin","blockchain":"Bitcoin","supply":0,"lastUpdateTimestamp":10,"price":340282366920938463463374607431768211455}]"#;
+
+		let missing = validate_response(body, &requested(), 20).expect("should be valid");
+
+		assert_eq!(missing, vec!["FIAT:MXN-USD".to_string()]);
+	}
+
+	#[test]
+	fn test_validate_response_rejects_malformed() {
+		let body = br#"[{"symbol":"BTC","name":"Bitcoin","blockchain":"Bitcoin","supply":0,"lastUpdateTimestamp":-1,"price":1}]"#;
+
+		assert!(matches!(
+			validate_response(body, &requested(), 20),
+			Err(SelfTestError::Malformed(_))
+		));
+	}
+
+	#[test]
+	fn test_validate_response_rejects_unrequested() {
+		let body = br#"[{"symbol":"ETH","name":"Ether","blockchain":"Ethereum","supply":0,"lastUpdateTimestamp":10,"price":1}]"#;
+
+		assert_eq!(
+			validate_response(body, &requested(), 20),
+			Err(SelfTestError::Unrequested { blockchain: "Ethereum".into(), symbol: "ETH".into() })
+		);
+	}
+
+	#[test]
+	fn test_validate_response_rejects_future_timestamp() {
+		let body = br#"[{"symbol":"BTC","name":"Bitcoin","blockchain":"Bitcoin","supply":0,"lastUpdateTimestamp":30,"price":1}]"#;
+
+		assert!(matches!(
+			validate_response(body, &requested(), 20),
+			Err(SelfTestError::TimestampInFuture { .. })
+		));
+	}
+}
@@ -0,0 +1,155 @@
+use crate::storage::{storage_key, CoinInfo};
+use crate::{parse_asset_map, AssetSpecifier};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Weight of the latest price in the exponential moving average of an asset, in `(0, 1]`. The
+/// smaller, the slower the published price follows the quoted one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmoothingAlpha(pub Decimal);
+
+impl FromStr for SmoothingAlpha {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid smoothing alpha '{}', expected a number in (0, 1]", s);
+		let alpha = Decimal::from_str(s.trim()).map_err(|_| invalid())?;
+		if alpha <= Decimal::ZERO || alpha > Decimal::ONE {
+			return Err(invalid())
+		}
+		Ok(SmoothingAlpha(alpha))
+	}
+}
+
+/// Alphas of individual assets, parsed from `<blockchain>:<symbol>=<alpha>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmoothingAlphas(pub HashMap<AssetSpecifier, SmoothingAlpha>);
+
+impl FromStr for SmoothingAlphas {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(SmoothingAlphas)
+	}
+}
+
+/// Last quoted price of a smoothed asset next to the average published in its place
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SmoothedPrice {
+	pub blockchain: String,
+	pub symbol: String,
+	pub alpha: Decimal,
+	pub raw_price: u128,
+	pub smoothed_price: u128,
+	pub last_update_timestamp: u64,
+}
+
+/// Exponential moving averages of the prices of the assets with an alpha, which are published
+/// instead of the quoted prices so volatile assets cause fewer updates on chain
+#[derive(Debug, Default)]
+pub struct PriceSmoothing {
+	alphas: SmoothingAlphas,
+	prices: Mutex<HashMap<(SmolStr, SmolStr), SmoothedPrice>>,
+}
+
+impl PriceSmoothing {
+	pub fn new(alphas: SmoothingAlphas) -> Self {
+		Self { alphas, prices: Default::default() }
+	}
+
+	/// Average of the asset of a freshly quoted coin info including its price, if it has an alpha.
+	/// The first price of an asset starts the average. The average isn't recorded until it is
+	/// committed.
+	pub fn smooth(&self, coin_info: &CoinInfo) -> Option<SmoothedPrice> {
+		let asset = AssetSpecifier {
+			blockchain: coin_info.blockchain.to_string(),
+			symbol: coin_info.symbol.to_string(),
+		};
+		let SmoothingAlpha(alpha) = match self.alphas.0.get(&asset) {
+			Some(alpha) => *alpha,
+			None => return None,
+		};
+		let prices = self.prices.lock().expect("poisoned lock");
+		let key = storage_key(&coin_info.blockchain, &coin_info.symbol);
+		// Prices beyond the range of `Decimal` restart the average
+		let smoothed = prices
+			.get(&key)
+			.and_then(|previous| {
+				let price = Decimal::from_u128(coin_info.price)?;
+				let previous = Decimal::from_u128(previous.smoothed_price)?;
+				let average = price
+					.checked_mul(alpha)?
+					.checked_add(previous.checked_mul(Decimal::ONE - alpha)?)?;
+				average.round().to_u128()
+			})
+			.unwrap_or(coin_info.price);
+		Some(SmoothedPrice {
+			blockchain: asset.blockchain,
+			symbol: asset.symbol,
+			alpha,
+			raw_price: coin_info.price,
+			smoothed_price: smoothed,
+			last_update_timestamp: coin_info.last_update_timestamp,
+		})
+	}
+
+	/// Records the average of a published price, which the next average of its asset continues
+	/// from
+	pub fn commit(&self, smoothed: SmoothedPrice) {
+		let key = storage_key(&smoothed.blockchain, &smoothed.symbol);
+		self.prices.lock().expect("poisoned lock").insert(key, smoothed);
+	}
+
+	/// Raw and smoothed prices of every smoothed asset ordered by blockchain and symbol
+	pub fn prices(&self) -> Vec<SmoothedPrice> {
+		let prices = self.prices.lock().expect("poisoned lock");
+		let mut prices: Vec<SmoothedPrice> = prices.values().cloned().collect();
+		prices.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		prices
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(symbol: &str, price: u128) -> CoinInfo {
+		CoinInfo {
+			symbol: symbol.into(),
+			blockchain: "Bitcoin".into(),
+			price,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_price_smoothing() {
+		let smoothing = PriceSmoothing::new("Bitcoin:BTC=0.25".parse().unwrap());
+
+		let first = smoothing.smooth(&coin_info("BTC", 1_000)).unwrap();
+		assert_eq!(first.smoothed_price, 1_000);
+		smoothing.commit(first);
+
+		// Averages that aren't committed don't move the next one
+		let rejected = smoothing.smooth(&coin_info("BTC", 9_000)).unwrap();
+		assert_eq!(rejected.smoothed_price, 3_000);
+		let second = smoothing.smooth(&coin_info("BTC", 2_000)).unwrap();
+		assert_eq!(second.smoothed_price, 1_250);
+		smoothing.commit(second);
+
+		assert!(smoothing.smooth(&coin_info("WBTC", 2_000)).is_none());
+
+		let prices = smoothing.prices();
+		assert_eq!(prices.len(), 1);
+		assert_eq!((prices[0].raw_price, prices[0].smoothed_price), (2_000, 1_250));
+
+		assert!("Bitcoin:BTC=0".parse::<SmoothingAlphas>().is_err());
+		assert!("Bitcoin:BTC=1.5".parse::<SmoothingAlphas>().is_err());
+	}
+}
@@ -0,0 +1,138 @@
+use crate::storage::{AssetErrorLog, AssetErrorsSnapshot, CoinInfo, CoinInfoStorage};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// State the fetching process hands to the serving process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+	pub coin_infos: Vec<CoinInfo>,
+	pub errors: Vec<AssetErrorsSnapshot>,
+}
+
+impl Snapshot {
+	pub fn take(storage: &CoinInfoStorage, errors: &AssetErrorLog) -> Self {
+		Self { coin_infos: storage.get_all_currencies(), errors: errors.snapshot() }
+	}
+
+	pub fn restore(self, storage: &CoinInfoStorage, errors: &AssetErrorLog) {
+		storage.replace_currencies_by_symbols(self.coin_infos);
+		errors.restore(self.errors);
+	}
+}
+
+/// Writes `snapshot` to a temporary file next to `path` and moves it in place, so readers never
+/// see a partially written snapshot
+pub async fn write_snapshot(
+	path: &Path,
+	snapshot: &Snapshot,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+	tokio::fs::write(&temporary, serde_json::to_vec(snapshot)?).await?;
+	tokio::fs::rename(&temporary, path).await?;
+	Ok(())
+}
+
+pub async fn read_snapshot(path: &Path) -> Result<Snapshot, Box<dyn Error + Send + Sync>> {
+	Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+}
+
+pub fn run_write_snapshot_loop(
+	path: PathBuf,
+	storage: Arc<CoinInfoStorage>,
+	errors: Arc<AssetErrorLog>,
+	interval: Duration,
+) {
+	let _ = tokio::spawn(async move {
+		loop {
+			tokio::time::delay_for(interval).await;
+
+			let snapshot = Snapshot::take(&storage, &errors);
+			if let Err(err) = write_snapshot(&path, &snapshot).await {
+				error!("Failed to write snapshot to {}: {}", path.display(), err);
+			}
+		}
+	});
+}
+
+/// Restores the storage from the snapshot at `path` whenever it changed. A snapshot that can't be
+/// read keeps the previous state in place.
+pub fn run_read_snapshot_loop(
+	path: PathBuf,
+	storage: Arc<CoinInfoStorage>,
+	errors: Arc<AssetErrorLog>,
+	interval: Duration,
+) {
+	let _ = tokio::spawn(async move {
+		let mut last_modified: Option<SystemTime> = None;
+		loop {
+			let modified =
+				tokio::fs::metadata(&path).await.and_then(|metadata| metadata.modified());
+			match modified {
+				Ok(modified) if last_modified == Some(modified) => {},
+				Ok(modified) => match read_snapshot(&path).await {
+					Ok(snapshot) => {
+						debug!(
+							"Read {} coin infos from snapshot {}",
+							snapshot.coin_infos.len(),
+							path.display()
+						);
+						snapshot.restore(&storage, &errors);
+						last_modified = Some(modified);
+					},
+					Err(err) => error!("Failed to read snapshot {}: {}", path.display(), err),
+				},
+				Err(err) => warn!("Snapshot {} isn't available: {}", path.display(), err),
+			}
+
+			tokio::time::delay_for(interval).await;
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::storage::AssetError;
+
+	#[tokio::test]
+	async fn test_snapshot_round_trip() {
+		let storage = CoinInfoStorage::default();
+		storage.replace_currencies_by_symbols(vec![CoinInfo {
+			symbol: "MXN-USD".into(),
+			blockchain: "FIAT".into(),
+			price: 53712327000,
+			..Default::default()
+		}]);
+		let errors = AssetErrorLog::default();
+		errors.record(
+			"FIAT",
+			"TZS-USD",
+			AssetError {
+				timestamp: 1,
+				source: "diadata.org".into(),
+				kind: "request".into(),
+				message: "timed out".into(),
+			},
+		);
+		let path = std::env::temp_dir()
+			.join(format!("dia-batching-server-snapshot-{}.json", std::process::id()));
+
+		write_snapshot(&path, &Snapshot::take(&storage, &errors)).await.unwrap();
+		let restored_storage = CoinInfoStorage::default();
+		let restored_errors = AssetErrorLog::default();
+		read_snapshot(&path).await.unwrap().restore(&restored_storage, &restored_errors);
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(restored_storage.get_all_currencies(), storage.get_all_currencies());
+		assert_eq!(
+			restored_errors.get_errors("FIAT", "TZS-USD"),
+			errors.get_errors("FIAT", "TZS-USD")
+		);
+	}
+}
@@ -0,0 +1,90 @@
+use crate::{parse_asset_map, storage::CoinInfo, AssetSpecifier};
+use log::warn;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr};
+
+/// Adjustment of the price of an asset in basis points, negative to publish less than the quoted
+/// price and positive to publish more
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spread(pub Decimal);
+
+impl FromStr for Spread {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid spread '{}', expected basis points above -10000", s);
+		let spread = Decimal::from_str(s.trim()).map_err(|_| invalid())?;
+		if spread <= Decimal::new(-10_000, 0) {
+			return Err(invalid())
+		}
+		Ok(Spread(spread))
+	}
+}
+
+/// Spreads of individual assets, parsed from `<blockchain>:<symbol>=<basis points>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PriceSpreads(pub HashMap<AssetSpecifier, Spread>);
+
+impl FromStr for PriceSpreads {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(PriceSpreads)
+	}
+}
+
+impl PriceSpreads {
+	/// Adjusts the price of a freshly quoted coin info by the spread of its asset, if it has one
+	pub fn apply(&self, coin_info: &mut CoinInfo) {
+		let asset = AssetSpecifier {
+			blockchain: coin_info.blockchain.to_string(),
+			symbol: coin_info.symbol.to_string(),
+		};
+		let Spread(spread) = match self.0.get(&asset) {
+			Some(spread) => *spread,
+			None => return,
+		};
+		let basis = Decimal::new(10_000, 0);
+		let adjusted = Decimal::from_u128(coin_info.price)
+			.and_then(|price| price.checked_mul(basis + spread))
+			.and_then(|price| price.checked_div(basis))
+			.and_then(|price| price.round().to_u128());
+		match adjusted {
+			Some(price) => coin_info.price = price,
+			None => warn!(
+				"Publishing the price of {}:{} without its spread, {} overflows",
+				asset.blockchain, asset.symbol, coin_info.price
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(symbol: &str, price: u128) -> CoinInfo {
+		CoinInfo { symbol: symbol.into(), blockchain: "FIAT".into(), price, ..Default::default() }
+	}
+
+	#[test]
+	fn test_apply_spreads() {
+		let spreads: PriceSpreads = "FIAT:BRL-USD=-5,FIAT:MXN-USD=12.5".parse().unwrap();
+
+		let mut brl = coin_info("BRL-USD", 200_000_000_000);
+		spreads.apply(&mut brl);
+		assert_eq!(brl.price, 199_900_000_000);
+
+		let mut mxn = coin_info("MXN-USD", 58_000_000_000);
+		spreads.apply(&mut mxn);
+		assert_eq!(mxn.price, 58_072_500_000);
+
+		let mut usd = coin_info("USD-USD", 1_000_000_000_000);
+		spreads.apply(&mut usd);
+		assert_eq!(usd.price, 1_000_000_000_000);
+
+		assert!("FIAT:BRL-USD=-10000".parse::<PriceSpreads>().is_err());
+		assert!("FIAT:BRL-USD=5%".parse::<PriceSpreads>().is_err());
+	}
+}
@@ -0,0 +1,139 @@
+use crate::storage::storage_key;
+use crate::SupportedAssets;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Outcome of the fetches of an asset since the start
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetStatus {
+	pub blockchain: SmolStr,
+	pub symbol: SmolStr,
+	/// Source of the last fetch, the one that quoted the price if it succeeded
+	pub source: Option<SmolStr>,
+	pub last_success: Option<u64>,
+	pub last_error: Option<String>,
+	/// Failed fetches since the last successful one
+	pub consecutive_failures: u64,
+}
+
+/// Fetch outcomes of every asset the update loop fetched, matched case-insensitively
+#[derive(Debug, Default)]
+pub struct AssetStatuses {
+	configured: Option<Arc<SupportedAssets>>,
+	statuses: Mutex<HashMap<(SmolStr, SmolStr), AssetStatus>>,
+}
+
+impl AssetStatuses {
+	/// Lists the statuses of the supported currencies, including the ones never fetched
+	pub fn with_configured_assets(mut self, assets: Arc<SupportedAssets>) -> Self {
+		self.configured = Some(assets);
+		self
+	}
+
+	fn update(&self, blockchain: &str, symbol: &str, update: impl FnOnce(&mut AssetStatus)) {
+		let mut statuses = self.statuses.lock().expect("poisoned lock");
+		let status =
+			statuses.entry(storage_key(blockchain, symbol)).or_insert_with(|| AssetStatus {
+				blockchain: blockchain.into(),
+				symbol: symbol.into(),
+				..Default::default()
+			});
+		update(status);
+	}
+
+	/// Records a price of the asset quoted by `source` at `now`
+	pub fn record_success(&self, blockchain: &str, symbol: &str, source: &str, now: u64) {
+		self.update(blockchain, symbol, |status| {
+			status.source = Some(source.into());
+			status.last_success = Some(now);
+			status.consecutive_failures = 0;
+		});
+	}
+
+	/// Records a fetch of the asset from `source` that didn't yield a published price
+	pub fn record_failure(&self, blockchain: &str, symbol: &str, source: &str, error: String) {
+		self.update(blockchain, symbol, |status| {
+			status.source = Some(source.into());
+			status.last_error = Some(error);
+			status.consecutive_failures += 1;
+		});
+	}
+
+	/// Statuses of the configured assets, or of every fetched asset if all quotable assets are
+	/// fetched, ordered by blockchain and then symbol
+	pub fn statuses(&self) -> Vec<AssetStatus> {
+		let configured = self.configured.as_ref().and_then(|assets| assets.load_full());
+		let fetched = self.statuses.lock().expect("poisoned lock");
+		let mut statuses: Vec<AssetStatus> = match configured {
+			Some(configured) => configured
+				.iter()
+				.map(|asset| {
+					fetched
+						.get(&storage_key(&asset.blockchain, &asset.symbol))
+						.cloned()
+						.unwrap_or_else(|| AssetStatus {
+							blockchain: asset.blockchain.as_str().into(),
+							symbol: asset.symbol.as_str().into(),
+							..Default::default()
+						})
+				})
+				.collect(),
+			None => fetched.values().cloned().collect(),
+		};
+		statuses.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+		statuses
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::AssetSpecifier;
+	use std::collections::HashSet;
+
+	#[test]
+	fn test_statuses() {
+		let statuses = AssetStatuses::default();
+		statuses.record_failure("Bitcoin", "BTC", "diadata.org", "timed out".into());
+		statuses.record_failure("Bitcoin", "BTC", "diadata.org", "status 502".into());
+		statuses.record_success("FIAT", "MXN-USD", "Polygon", 10);
+		statuses.record_failure("FIAT", "mxn-usd", "Polygon", "decode".into());
+		statuses.record_success("FIAT", "MXN-USD", "YahooFinance", 20);
+
+		let btc = AssetStatus {
+			blockchain: "Bitcoin".into(),
+			symbol: "BTC".into(),
+			source: Some("diadata.org".into()),
+			last_success: None,
+			last_error: Some("status 502".into()),
+			consecutive_failures: 2,
+		};
+		let mxn = AssetStatus {
+			blockchain: "FIAT".into(),
+			symbol: "MXN-USD".into(),
+			source: Some("YahooFinance".into()),
+			last_success: Some(20),
+			last_error: Some("decode".into()),
+			consecutive_failures: 0,
+		};
+		assert_eq!(statuses.statuses(), vec![btc.clone(), mxn]);
+
+		let configured: HashSet<AssetSpecifier> =
+			vec!["Bitcoin:BTC".parse().unwrap(), "Polkadot:DOT".parse().unwrap()]
+				.into_iter()
+				.collect();
+		let statuses = AssetStatuses {
+			configured: Some(Arc::new(SupportedAssets::from_pointee(configured))),
+			statuses: statuses.statuses,
+		};
+		let dot = AssetStatus {
+			blockchain: "Polkadot".into(),
+			symbol: "DOT".into(),
+			..Default::default()
+		};
+		assert_eq!(statuses.statuses(), vec![btc, dot]);
+	}
+}
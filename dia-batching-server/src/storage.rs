@@ -1,10 +1,14 @@
 use arc_swap::ArcSwap;
+use chrono::Utc;
+use log::debug;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
 
 use crate::handlers::Currency;
+use crate::twap::PriceHistory;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,35 +19,612 @@ pub struct CoinInfo {
 	pub supply: u128,
 	pub last_update_timestamp: u64,
 	pub price: u128,
+	/// Upstream that quoted the price, e.g. `diadata.org` or `Coinbase`, empty for coin infos
+	/// read from the chain
+	#[serde(default)]
+	pub source: SmolStr,
+	/// Unix timestamp the price was fetched from the `source` at, 0 for coin infos read from the
+	/// chain
+	#[serde(default)]
+	pub fetched_at: u64,
 }
 
-#[derive(Debug, Default)]
+impl CoinInfo {
+	fn memory_usage(&self) -> usize {
+		std::mem::size_of::<Self>() +
+			self.symbol.len() +
+			self.name.len() +
+			self.blockchain.len() +
+			self.source.len()
+	}
+
+	/// Whether both coin infos carry the same quotation, fetched at the same time or not
+	fn is_same_quotation(&self, other: &CoinInfo) -> bool {
+		CoinInfo { fetched_at: other.fetched_at, ..self.clone() } == *other
+	}
+}
+
+/// Key coin infos are stored and looked up by. The sources match blockchains and symbols
+/// case-insensitively, so the storage does as well.
+pub fn storage_key(blockchain: &str, symbol: &str) -> (SmolStr, SmolStr) {
+	(blockchain.to_uppercase().into(), symbol.to_uppercase().into())
+}
+
+/// Number of updates kept for subscribers that haven't received them yet
+const UPDATES_CAPACITY: usize = 16;
+
+/// Unix timestamp of the last replacement that changed the price of an asset, and the price it
+/// changed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Change {
+	timestamp: u64,
+	price: u128,
+}
+
+#[derive(Debug)]
 pub struct CoinInfoStorage {
 	currencies_by_blockchain_and_symbol: ArcSwap<HashMap<(SmolStr, SmolStr), CoinInfo>>,
+	max_age: Option<u64>,
+	updates: broadcast::Sender<Arc<Vec<CoinInfo>>>,
+	history: Option<PriceHistory>,
+	changes: Mutex<HashMap<(SmolStr, SmolStr), Change>>,
+	change_epsilon: u128,
+}
+
+impl Default for CoinInfoStorage {
+	fn default() -> Self {
+		Self {
+			currencies_by_blockchain_and_symbol: Default::default(),
+			max_age: None,
+			updates: broadcast::channel(UPDATES_CAPACITY).0,
+			history: None,
+			changes: Default::default(),
+			change_epsilon: 0,
+		}
+	}
 }
 
 impl CoinInfoStorage {
+	/// Omits coin infos with a `last_update_timestamp` more than `max_age` seconds ago from
+	/// requested currencies
+	pub fn with_max_age(mut self, max_age: Option<u64>) -> Self {
+		self.max_age = max_age;
+		self
+	}
+
+	/// Keeps the prices of the last `retention` seconds for time-weighted averages
+	pub fn with_price_history(mut self, retention: u64) -> Self {
+		self.history = Some(PriceHistory::new(retention));
+		self
+	}
+
+	/// Counts only price moves of more than `change_epsilon` basis points since the last change of
+	/// an asset as a change
+	pub fn with_change_epsilon(mut self, change_epsilon: u128) -> Self {
+		self.change_epsilon = change_epsilon;
+		self
+	}
+
+	/// Time-weighted average price of an asset during the `window` seconds before `now`, `None`
+	/// without a price history
+	pub fn twap(&self, blockchain: &str, symbol: &str, now: u64, window: u64) -> Option<u128> {
+		self.history.as_ref()?.twap(blockchain, symbol, now, window)
+	}
+
+	fn is_stale(&self, coin_info: &CoinInfo, now: u64) -> bool {
+		self.max_age
+			.into_iter()
+			.any(|max_age| now.saturating_sub(coin_info.last_update_timestamp) > max_age)
+	}
+
+	/// Coin infos of the requested currencies, with the blockchain and symbol cased like in the
+	/// request. The offchain worker stores them under these, which keeps the keys of assets
+	/// already on chain whatever casing the coin info was stored with here.
 	pub fn get_currencies_by_blockchains_and_symbols(
 		&self,
 		blockchain_and_symbols: Vec<Currency>,
 	) -> Vec<CoinInfo> {
 		let reference = self.currencies_by_blockchain_and_symbol.load();
+		let now = Utc::now().timestamp().unsigned_abs();
 		blockchain_and_symbols
-			.iter()
+			.into_iter()
 			.filter_map(|Currency { blockchain, symbol }| {
-				reference.get(&(blockchain.into(), symbol.into()))
+				let coin_info = reference.get(&storage_key(&blockchain, &symbol))?;
+				if self.is_stale(coin_info, now) {
+					debug!(
+						"Not serving {}:{} last updated at {}",
+						blockchain, symbol, coin_info.last_update_timestamp
+					);
+					return None
+				}
+				Some(CoinInfo {
+					blockchain: blockchain.into(),
+					symbol: symbol.into(),
+					..coin_info.clone()
+				})
 			})
-			.cloned()
 			.collect()
 	}
 
+	/// Whether the price of an asset changed at or after the unix timestamp `since`
+	pub fn is_changed_since(&self, blockchain: &str, symbol: &str, since: u64) -> bool {
+		let changes = self.changes.lock().expect("poisoned lock");
+		changes
+			.get(&storage_key(blockchain, symbol))
+			.map_or(false, |change| change.timestamp >= since)
+	}
+
+	/// Coin infos of the assets whose price changed at or after the unix timestamp `since`
+	pub fn get_changed_since(&self, since: u64) -> Vec<CoinInfo> {
+		let reference = self.currencies_by_blockchain_and_symbol.load();
+		let changes = self.changes.lock().expect("poisoned lock");
+		reference
+			.iter()
+			.filter(|(key, _)| changes.get(*key).map_or(false, |change| change.timestamp >= since))
+			.map(|(_, coin_info)| coin_info.clone())
+			.collect()
+	}
+
+	pub fn get_all_currencies(&self) -> Vec<CoinInfo> {
+		self.currencies_by_blockchain_and_symbol.load().values().cloned().collect()
+	}
+
+	pub fn get_currency(&self, blockchain: &str, symbol: &str) -> Option<CoinInfo> {
+		self.currencies_by_blockchain_and_symbol
+			.load()
+			.get(&storage_key(blockchain, symbol))
+			.cloned()
+	}
+
+	/// Estimated number of bytes used by the stored coin infos and their price history
+	pub fn memory_usage(&self) -> usize {
+		let coin_infos: usize = self
+			.currencies_by_blockchain_and_symbol
+			.load()
+			.iter()
+			.map(|((blockchain, symbol), coin_info)| {
+				blockchain.len() + symbol.len() + coin_info.memory_usage()
+			})
+			.sum();
+		coin_infos + self.history.as_ref().map_or(0, PriceHistory::memory_usage)
+	}
+
+	pub fn len(&self) -> usize {
+		self.currencies_by_blockchain_and_symbol.load().len()
+	}
+
+	/// Receives the coin infos every replacement of the currencies added or changed
+	pub fn subscribe(&self) -> broadcast::Receiver<Arc<Vec<CoinInfo>>> {
+		self.updates.subscribe()
+	}
+
 	#[allow(dead_code)]
 	pub fn replace_currencies_by_symbols(&self, currencies: Vec<CoinInfo>) {
-		let map_to_replace_with = currencies
+		let map_to_replace_with: Arc<HashMap<_, _>> = Arc::new(
+			currencies
+				.into_iter()
+				.map(|x| (storage_key(&x.blockchain, &x.symbol), x))
+				.collect(),
+		);
+
+		self.track_changes(&map_to_replace_with, Utc::now().timestamp().unsigned_abs());
+		let previous = self.currencies_by_blockchain_and_symbol.swap(map_to_replace_with.clone());
+		if self.history.is_none() && self.updates.receiver_count() == 0 {
+			return
+		}
+		let updated: Vec<CoinInfo> = map_to_replace_with
+			.iter()
+			.filter(|(key, coin_info)| {
+				previous
+					.get(*key)
+					.map_or(true, |previous| !previous.is_same_quotation(coin_info))
+			})
+			.map(|(_, coin_info)| coin_info.clone())
+			.collect();
+		if let Some(history) = &self.history {
+			history.record(&updated);
+		}
+		if !updated.is_empty() && self.updates.receiver_count() > 0 {
+			// Fails only if the last subscriber just went away
+			let _ = self.updates.send(Arc::new(updated));
+		}
+	}
+
+	/// Marks the assets that are new or whose price moved by more than the change epsilon as
+	/// changed at `now` and forgets the assets no longer stored
+	fn track_changes(&self, currencies: &HashMap<(SmolStr, SmolStr), CoinInfo>, now: u64) {
+		let mut changes = self.changes.lock().expect("poisoned lock");
+		changes.retain(|key, _| currencies.contains_key(key));
+		for (key, coin_info) in currencies {
+			let moved = changes.get(key).map_or(true, |change| {
+				let delta = coin_info.price.abs_diff(change.price);
+				delta.saturating_mul(10_000) > change.price.saturating_mul(self.change_epsilon)
+			});
+			if moved {
+				changes.insert(key.clone(), Change { timestamp: now, price: coin_info.price });
+			}
+		}
+	}
+}
+
+/// Number of errors kept per asset if not configured otherwise
+pub const DEFAULT_ERRORS_PER_ASSET: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetError {
+	pub timestamp: u64,
+	pub source: SmolStr,
+	pub kind: SmolStr,
+	pub message: String,
+}
+
+impl AssetError {
+	fn memory_usage(&self) -> usize {
+		std::mem::size_of::<Self>() + self.source.len() + self.kind.len() + self.message.len()
+	}
+}
+
+/// Errors of a single asset as handed between processes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetErrorsSnapshot {
+	pub blockchain: SmolStr,
+	pub symbol: SmolStr,
+	pub errors: Vec<AssetError>,
+}
+
+#[derive(Debug, Default)]
+struct AssetErrors {
+	errors: VecDeque<AssetError>,
+	/// Value of the log's query counter when the errors were last queried or first recorded
+	last_queried: u64,
+}
+
+#[derive(Debug, Default)]
+struct AssetErrorLogState {
+	errors_by_blockchain_and_symbol: HashMap<(SmolStr, SmolStr), AssetErrors>,
+	queries: u64,
+	memory_usage: usize,
+	evictions: u64,
+}
+
+/// Keeps the last errors that occurred while fetching each asset, oldest first. If a memory cap is
+/// set, the errors of the least recently queried assets are evicted first once it's exceeded.
+#[derive(Debug)]
+pub struct AssetErrorLog {
+	capacity: usize,
+	max_memory_usage: Option<usize>,
+	state: Mutex<AssetErrorLogState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetErrorLogUsage {
+	pub assets: usize,
+	pub bytes: usize,
+	pub max_bytes: Option<usize>,
+	pub evictions: u64,
+}
+
+impl Default for AssetErrorLog {
+	fn default() -> Self {
+		Self::new(DEFAULT_ERRORS_PER_ASSET)
+	}
+}
+
+impl AssetErrorLog {
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, max_memory_usage: None, state: Default::default() }
+	}
+
+	/// Caps the estimated memory usage of the log at `max_memory_usage` bytes
+	pub fn with_max_memory_usage(mut self, max_memory_usage: Option<usize>) -> Self {
+		self.max_memory_usage = max_memory_usage;
+		self
+	}
+
+	pub fn record(&self, blockchain: &str, symbol: &str, error: AssetError) {
+		if self.capacity == 0 {
+			return
+		}
+		let mut state = self.state.lock().expect("poisoned lock");
+		let state = &mut *state;
+		let key = (SmolStr::from(blockchain), SmolStr::from(symbol));
+		let queries = state.queries;
+		let entry = state
+			.errors_by_blockchain_and_symbol
+			.entry(key.clone())
+			.or_insert_with(|| AssetErrors { last_queried: queries, ..Default::default() });
+		if entry.errors.len() == self.capacity {
+			if let Some(dropped) = entry.errors.pop_front() {
+				state.memory_usage -= dropped.memory_usage();
+			}
+		}
+		state.memory_usage += error.memory_usage();
+		entry.errors.push_back(error);
+
+		if let Some(max_memory_usage) = self.max_memory_usage {
+			Self::evict(state, &key, max_memory_usage);
+		}
+	}
+
+	/// Evicts the errors of the least recently queried assets other than `keep` and then the
+	/// oldest errors of `keep` until the memory usage is below `max_memory_usage`
+	fn evict(state: &mut AssetErrorLogState, keep: &(SmolStr, SmolStr), max_memory_usage: usize) {
+		while state.memory_usage > max_memory_usage {
+			let least_recently_queried = state
+				.errors_by_blockchain_and_symbol
+				.iter()
+				.filter(|(key, _)| *key != keep)
+				.min_by_key(|(_, entry)| entry.last_queried)
+				.map(|(key, _)| key.clone());
+			match least_recently_queried {
+				Some(key) => {
+					if let Some(entry) = state.errors_by_blockchain_and_symbol.remove(&key) {
+						state.memory_usage -=
+							entry.errors.iter().map(AssetError::memory_usage).sum::<usize>();
+					}
+				},
+				None => {
+					let dropped = state
+						.errors_by_blockchain_and_symbol
+						.get_mut(keep)
+						.and_then(|entry| entry.errors.pop_front());
+					match dropped {
+						Some(dropped) => state.memory_usage -= dropped.memory_usage(),
+						None => break,
+					}
+				},
+			}
+			state.evictions += 1;
+		}
+	}
+
+	pub fn get_errors(&self, blockchain: &str, symbol: &str) -> Vec<AssetError> {
+		let mut state = self.state.lock().expect("poisoned lock");
+		state.queries += 1;
+		let queries = state.queries;
+		state
+			.errors_by_blockchain_and_symbol
+			.get_mut(&(blockchain.into(), symbol.into()))
+			.map(|entry| {
+				entry.last_queried = queries;
+				entry.errors.iter().cloned().collect()
+			})
+			.unwrap_or_default()
+	}
+
+	pub fn snapshot(&self) -> Vec<AssetErrorsSnapshot> {
+		let state = self.state.lock().expect("poisoned lock");
+		state
+			.errors_by_blockchain_and_symbol
+			.iter()
+			.map(|((blockchain, symbol), entry)| AssetErrorsSnapshot {
+				blockchain: blockchain.clone(),
+				symbol: symbol.clone(),
+				errors: entry.errors.iter().cloned().collect(),
+			})
+			.collect()
+	}
+
+	/// Replaces the errors of all assets with `snapshot`. Only the capacity per asset is applied,
+	/// the memory cap is left to the process that recorded the errors.
+	pub fn restore(&self, snapshot: Vec<AssetErrorsSnapshot>) {
+		let mut state = self.state.lock().expect("poisoned lock");
+		let queries = state.queries;
+		let errors_by_blockchain_and_symbol: HashMap<_, _> = snapshot
 			.into_iter()
-			.map(|x| ((x.blockchain.clone(), x.symbol.clone()), x))
+			.map(|AssetErrorsSnapshot { blockchain, symbol, errors }| {
+				let skip = errors.len().saturating_sub(self.capacity);
+				let errors = errors.into_iter().skip(skip).collect();
+				((blockchain, symbol), AssetErrors { errors, last_queried: queries })
+			})
 			.collect();
+		state.memory_usage = errors_by_blockchain_and_symbol
+			.values()
+			.flat_map(|entry| entry.errors.iter())
+			.map(AssetError::memory_usage)
+			.sum();
+		state.errors_by_blockchain_and_symbol = errors_by_blockchain_and_symbol;
+	}
+
+	pub fn usage(&self) -> AssetErrorLogUsage {
+		let state = self.state.lock().expect("poisoned lock");
+		AssetErrorLogUsage {
+			assets: state.errors_by_blockchain_and_symbol.len(),
+			bytes: state.memory_usage,
+			max_bytes: self.max_memory_usage,
+			evictions: state.evictions,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(timestamp: u64) -> AssetError {
+		AssetError {
+			timestamp,
+			source: "diadata.org".into(),
+			kind: "request".into(),
+			message: "timed out".into(),
+		}
+	}
+
+	#[test]
+	fn test_coin_info_lookup_ignores_case() {
+		let storage = CoinInfoStorage::default();
+		storage.replace_currencies_by_symbols(vec![CoinInfo {
+			symbol: "AMPE".into(),
+			blockchain: "Amplitude".into(),
+			price: 3,
+			..Default::default()
+		}]);
+
+		let coin_infos = storage.get_currencies_by_blockchains_and_symbols(vec![Currency {
+			blockchain: "AMPLITUDE".into(),
+			symbol: "ampe".into(),
+		}]);
+
+		assert_eq!(coin_infos.len(), 1);
+		assert_eq!(
+			(coin_infos[0].blockchain.as_str(), coin_infos[0].symbol.as_str()),
+			("AMPLITUDE", "ampe")
+		);
+		assert_eq!(coin_infos[0].price, 3);
+		assert_eq!(
+			storage.get_currency("amplitude", "Ampe").map(|info| info.symbol),
+			Some("AMPE".into())
+		);
+	}
+
+	#[test]
+	fn test_stale_coin_infos_are_not_served() {
+		let storage = CoinInfoStorage::default().with_max_age(Some(600));
+		let now = Utc::now().timestamp().unsigned_abs();
+		storage.replace_currencies_by_symbols(vec![
+			CoinInfo {
+				symbol: "DOT".into(),
+				blockchain: "Polkadot".into(),
+				last_update_timestamp: now - 60,
+				..Default::default()
+			},
+			CoinInfo {
+				symbol: "MXN-USD".into(),
+				blockchain: "FIAT".into(),
+				last_update_timestamp: now - 3600,
+				..Default::default()
+			},
+		]);
+
+		let coin_infos = storage.get_currencies_by_blockchains_and_symbols(vec![
+			Currency { blockchain: "Polkadot".into(), symbol: "DOT".into() },
+			Currency { blockchain: "FIAT".into(), symbol: "MXN-USD".into() },
+		]);
+
+		assert_eq!(coin_infos.len(), 1);
+		assert_eq!(coin_infos[0].symbol, "DOT");
+		assert!(storage.get_currency("FIAT", "MXN-USD").is_some());
+	}
+
+	#[test]
+	fn test_replacement_notifies_subscribers_of_updated_coin_infos() {
+		let storage = CoinInfoStorage::default();
+		let coin_info = |symbol: &str, price| CoinInfo {
+			symbol: symbol.into(),
+			blockchain: "Polkadot".into(),
+			price,
+			..Default::default()
+		};
+		storage.replace_currencies_by_symbols(vec![coin_info("DOT", 1), coin_info("GLMR", 1)]);
+		let mut updates = storage.subscribe();
+
+		storage.replace_currencies_by_symbols(vec![coin_info("DOT", 2), coin_info("GLMR", 1)]);
+		storage.replace_currencies_by_symbols(vec![coin_info("DOT", 2), coin_info("GLMR", 1)]);
+		// Fetching the same quotation again isn't an update
+		let refetched = CoinInfo { fetched_at: 10, ..coin_info("GLMR", 1) };
+		storage.replace_currencies_by_symbols(vec![coin_info("DOT", 2), refetched]);
+
+		assert_eq!(*updates.try_recv().unwrap(), vec![coin_info("DOT", 2)]);
+		assert!(updates.try_recv().is_err());
+	}
+
+	#[test]
+	fn test_asset_error_log_drops_oldest() {
+		let log = AssetErrorLog::new(2);
+		log.record("FIAT", "TZS-USD", error(1));
+		log.record("FIAT", "TZS-USD", error(2));
+		log.record("FIAT", "TZS-USD", error(3));
+
+		assert_eq!(log.get_errors("FIAT", "TZS-USD"), vec![error(2), error(3)]);
+		assert_eq!(log.get_errors("FIAT", "MXN-USD"), vec![]);
+		assert_eq!(log.usage().bytes, 2 * error(1).memory_usage());
+	}
+
+	#[test]
+	fn test_asset_error_log_evicts_least_recently_queried() {
+		let log = AssetErrorLog::new(2).with_max_memory_usage(Some(2 * error(1).memory_usage()));
+		log.record("FIAT", "TZS-USD", error(1));
+		log.record("FIAT", "MXN-USD", error(2));
+		log.get_errors("FIAT", "TZS-USD");
+		log.record("FIAT", "BRL-USD", error(3));
+
+		assert_eq!(log.get_errors("FIAT", "TZS-USD"), vec![error(1)]);
+		assert_eq!(log.get_errors("FIAT", "MXN-USD"), vec![]);
+		assert_eq!(log.get_errors("FIAT", "BRL-USD"), vec![error(3)]);
+		assert_eq!(log.usage().evictions, 1);
+	}
+
+	#[test]
+	fn test_asset_error_log_trims_single_asset_to_cap() {
+		let log = AssetErrorLog::new(4).with_max_memory_usage(Some(error(1).memory_usage()));
+		log.record("FIAT", "TZS-USD", error(1));
+		log.record("FIAT", "TZS-USD", error(2));
+
+		assert_eq!(log.get_errors("FIAT", "TZS-USD"), vec![error(2)]);
+		assert_eq!(log.usage().bytes, error(2).memory_usage());
+	}
+
+	#[test]
+	fn test_asset_error_log_restores_snapshot() {
+		let log = AssetErrorLog::new(4);
+		log.record("FIAT", "TZS-USD", error(1));
+		log.record("FIAT", "TZS-USD", error(2));
+		let restored = AssetErrorLog::new(1);
+		restored.record("FIAT", "MXN-USD", error(3));
+
+		restored.restore(log.snapshot());
+
+		assert_eq!(restored.get_errors("FIAT", "TZS-USD"), vec![error(2)]);
+		assert_eq!(restored.get_errors("FIAT", "MXN-USD"), vec![]);
+		assert_eq!(restored.usage().bytes, error(2).memory_usage());
+	}
+
+	fn currencies(prices: &[(&str, u128)]) -> HashMap<(SmolStr, SmolStr), CoinInfo> {
+		prices
+			.iter()
+			.map(|(symbol, price)| {
+				let coin_info = CoinInfo {
+					symbol: (*symbol).into(),
+					blockchain: "Bitcoin".into(),
+					price: *price,
+					..Default::default()
+				};
+				(storage_key("Bitcoin", symbol), coin_info)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_changed_since() {
+		let storage = CoinInfoStorage::default().with_change_epsilon(100);
+		let changed_since = |since: u64| {
+			let mut symbols: Vec<SmolStr> =
+				storage.get_changed_since(since).into_iter().map(|c| c.symbol).collect();
+			symbols.sort();
+			symbols
+		};
+
+		let first = currencies(&[("BTC", 10_000), ("WBTC", 10_000)]);
+		storage.currencies_by_blockchain_and_symbol.store(Arc::new(first.clone()));
+		storage.track_changes(&first, 10);
+		assert_eq!(changed_since(10), vec!["BTC", "WBTC"]);
+
+		// Moves of up to 1% since the last change don't count, even if they add up
+		for (now, price) in [(20, 10_060), (30, 10_100), (40, 10_101)] {
+			let next = currencies(&[("BTC", price), ("WBTC", 10_000)]);
+			storage.currencies_by_blockchain_and_symbol.store(Arc::new(next.clone()));
+			storage.track_changes(&next, now);
+		}
+		assert_eq!(changed_since(11), vec!["BTC"]);
+		assert!(storage.is_changed_since("BITCOIN", "btc", 40));
+		assert!(!storage.is_changed_since("Bitcoin", "BTC", 41));
+		assert!(!storage.is_changed_since("Bitcoin", "WBTC", 11));
 
-		self.currencies_by_blockchain_and_symbol.store(Arc::new(map_to_replace_with));
+		let last = currencies(&[("BTC", 10_101)]);
+		storage.track_changes(&last, 50);
+		assert!(!storage.is_changed_since("Bitcoin", "WBTC", 0));
 	}
 }
@@ -0,0 +1,188 @@
+use crate::chain::ChainClient;
+use crate::storage::CoinInfo;
+use crate::{parse_asset_map, AssetSpecifier};
+use arc_swap::ArcSwap;
+use log::{error, info};
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Number of decimals of the supply in coin infos, the same as of prices
+const SUPPLY_DECIMALS: u32 = 12;
+
+/// RPC url of a Substrate chain whose `Balances::TotalIssuance` is the supply of an asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupplySource(pub String);
+
+impl FromStr for SupplySource {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let url = s.trim();
+		if !url.starts_with("http://") && !url.starts_with("https://") {
+			return Err(format!("Invalid supply source '{}', expected an http(s) RPC url", s))
+		}
+		Ok(SupplySource(url.to_string()))
+	}
+}
+
+/// Chains the supply of individual assets is read from, parsed from
+/// `<blockchain>:<symbol>=<rpc url>,...`. Other assets keep the supply of their quotation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SupplySources(pub HashMap<AssetSpecifier, SupplySource>);
+
+impl FromStr for SupplySources {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(SupplySources)
+	}
+}
+
+/// Last total issuance read for every asset with a supply source, with `SUPPLY_DECIMALS`
+#[derive(Debug, Default)]
+pub struct Supplies(ArcSwap<HashMap<AssetSpecifier, u128>>);
+
+impl Supplies {
+	pub fn get(&self, blockchain: &str, symbol: &str) -> Option<u128> {
+		self.0
+			.load()
+			.iter()
+			.find(|(asset, _)| {
+				asset.blockchain.eq_ignore_ascii_case(blockchain) &&
+					asset.symbol.eq_ignore_ascii_case(symbol)
+			})
+			.map(|(_, supply)| *supply)
+	}
+
+	fn insert(&self, asset: &AssetSpecifier, supply: u128) {
+		self.0.rcu(|supplies| {
+			let mut supplies = HashMap::clone(supplies);
+			supplies.insert(asset.clone(), supply);
+			supplies
+		});
+	}
+
+	/// Replaces the supply of the coin infos of assets with a known total issuance
+	pub fn apply(&self, coin_infos: &mut [CoinInfo]) {
+		for coin_info in coin_infos {
+			if let Some(supply) = self.get(&coin_info.blockchain, &coin_info.symbol) {
+				coin_info.supply = supply;
+			}
+		}
+	}
+}
+
+/// Converts an amount of the smallest unit of a token with `decimals` to `SUPPLY_DECIMALS`
+pub fn to_supply_decimals(amount: u128, decimals: u32) -> Option<u128> {
+	if decimals <= SUPPLY_DECIMALS {
+		amount.checked_mul(10u128.checked_pow(SUPPLY_DECIMALS - decimals)?)
+	} else {
+		Some(amount / 10u128.checked_pow(decimals - SUPPLY_DECIMALS)?)
+	}
+}
+
+async fn read_supply(client: &ChainClient) -> Result<u128, Box<dyn Error + Send + Sync>> {
+	let decimals = client.token_decimals().await?;
+	let total_issuance = client.total_issuance(None).await?;
+	to_supply_decimals(total_issuance, decimals).ok_or_else(|| {
+		format!("Total issuance {} with {} decimals overflows", total_issuance, decimals).into()
+	})
+}
+
+fn clients(sources: SupplySources) -> Vec<(AssetSpecifier, ChainClient)> {
+	sources
+		.0
+		.into_iter()
+		.map(|(asset, SupplySource(url))| (asset, ChainClient::new(url, String::new())))
+		.collect()
+}
+
+async fn read_supplies(clients: &[(AssetSpecifier, ChainClient)], supplies: &Supplies) {
+	for (asset, client) in clients {
+		match read_supply(client).await {
+			Ok(supply) => {
+				info!(
+					"Read supply {} of {}:{} from {}",
+					supply,
+					asset.blockchain,
+					asset.symbol,
+					client.url()
+				);
+				supplies.insert(asset, supply);
+			},
+			Err(err) => error!(
+				"Failed to read supply of {}:{} from {}: {}",
+				asset.blockchain,
+				asset.symbol,
+				client.url(),
+				err
+			),
+		}
+	}
+}
+
+/// Reads the total issuance of every supply source once
+pub async fn read_supplies_once(sources: SupplySources, supplies: &Supplies) {
+	read_supplies(&clients(sources), supplies).await
+}
+
+/// Reads the total issuance of every supply source now and again in every `interval`. An asset
+/// keeps its last supply while its chain can't be read.
+pub fn run_supply_loop(
+	sources: SupplySources,
+	supplies: Arc<Supplies>,
+	interval: std::time::Duration,
+) {
+	let clients = clients(sources);
+	let _ = tokio::spawn(async move {
+		loop {
+			read_supplies(&clients, &supplies).await;
+
+			tokio::time::delay_for(interval).await;
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_supplies() {
+		let sources: SupplySources =
+			"Amplitude:AMPE=https://rpc-amplitude.pendulumchain.tech, Pendulum:PEN=http://localhost:9933"
+				.parse()
+				.unwrap();
+		assert_eq!(sources.0.len(), 2);
+		assert!("Amplitude:AMPE=wss://rpc-amplitude.pendulumchain.tech"
+			.parse::<SupplySources>()
+			.is_err());
+
+		assert_eq!(to_supply_decimals(5, 12), Some(5));
+		assert_eq!(to_supply_decimals(5, 10), Some(500));
+		assert_eq!(to_supply_decimals(5_000_000, 18), Some(5));
+		assert_eq!(to_supply_decimals(u128::MAX, 0), None);
+
+		let supplies = Supplies::default();
+		supplies.insert(&"Amplitude:AMPE".parse().unwrap(), 200_000_000_000_000_000_000);
+		let mut coin_infos = vec![
+			CoinInfo {
+				blockchain: "amplitude".into(),
+				symbol: "AMPE".into(),
+				..Default::default()
+			},
+			CoinInfo {
+				blockchain: "FIAT".into(),
+				symbol: "MXN-USD".into(),
+				supply: 7,
+				..Default::default()
+			},
+		];
+		supplies.apply(&mut coin_infos);
+
+		assert_eq!(coin_infos[0].supply, 200_000_000_000_000_000_000);
+		assert_eq!(coin_infos[1].supply, 7);
+	}
+}
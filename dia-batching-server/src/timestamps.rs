@@ -0,0 +1,176 @@
+use crate::{parse_asset_map, storage::CoinInfo, AssetSpecifier};
+use std::{
+	collections::HashMap,
+	error::Error,
+	fmt::{Display, Formatter},
+	str::FromStr,
+};
+
+/// Where the published `last_update_timestamp` of an asset comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampSource {
+	/// The time the upstream API reports for its quotation
+	#[default]
+	Upstream,
+	/// The time the server fetched the quotation
+	Server,
+	/// The later of both
+	Max,
+}
+
+impl TimestampSource {
+	pub fn timestamp(&self, upstream: u64, server: u64) -> u64 {
+		match self {
+			TimestampSource::Upstream => upstream,
+			TimestampSource::Server => server,
+			TimestampSource::Max => upstream.max(server),
+		}
+	}
+}
+
+impl FromStr for TimestampSource {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"upstream" => Ok(TimestampSource::Upstream),
+			"server" => Ok(TimestampSource::Server),
+			"max" => Ok(TimestampSource::Max),
+			other => Err(format!(
+				"Invalid timestamp source '{}', expected one of upstream, server, max",
+				other
+			)),
+		}
+	}
+}
+
+/// Timestamp sources of individual assets, parsed from `<blockchain>:<symbol>=<source>,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimestampSources(pub HashMap<AssetSpecifier, TimestampSource>);
+
+impl FromStr for TimestampSources {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_asset_map(s).map(TimestampSources)
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimestampPolicy {
+	default: TimestampSource,
+	sources: TimestampSources,
+}
+
+impl TimestampPolicy {
+	pub fn new(default: TimestampSource, sources: TimestampSources) -> Self {
+		Self { default, sources }
+	}
+
+	pub fn source(&self, blockchain: &str, symbol: &str) -> TimestampSource {
+		let asset = AssetSpecifier { blockchain: blockchain.into(), symbol: symbol.into() };
+		self.sources.0.get(&asset).copied().unwrap_or(self.default)
+	}
+
+	/// Replaces the upstream timestamp of `coin_info` according to the asset's timestamp source
+	pub fn apply(&self, coin_info: &mut CoinInfo, now: u64) {
+		coin_info.last_update_timestamp = self
+			.source(&coin_info.blockchain, &coin_info.symbol)
+			.timestamp(coin_info.last_update_timestamp, now);
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimestampError {
+	WentBackwards { previous: u64, current: u64 },
+}
+
+impl Display for TimestampError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TimestampError::WentBackwards { previous, current } => write!(
+				f,
+				"Timestamp {} is older than the published timestamp {}",
+				current, previous
+			),
+		}
+	}
+}
+
+impl Error for TimestampError {}
+
+/// Fails if `coin_info` would replace a published coin info with a later timestamp
+pub fn ensure_not_backwards(
+	previous: Option<&CoinInfo>,
+	coin_info: &CoinInfo,
+) -> Result<(), TimestampError> {
+	match previous {
+		Some(previous) if coin_info.last_update_timestamp < previous.last_update_timestamp =>
+			Err(TimestampError::WentBackwards {
+				previous: previous.last_update_timestamp,
+				current: coin_info.last_update_timestamp,
+			}),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(last_update_timestamp: u64) -> CoinInfo {
+		CoinInfo {
+			symbol: "MXN-USD".into(),
+			name: "MXNUSD=X".into(),
+			blockchain: "FIAT".into(),
+			last_update_timestamp,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_parse_timestamp_sources() {
+		let sources: TimestampSources = "FIAT:MXN-USD=server, Bitcoin:BTC=max".parse().unwrap();
+
+		assert_eq!(
+			sources
+				.0
+				.get(&AssetSpecifier { blockchain: "FIAT".into(), symbol: "MXN-USD".into() }),
+			Some(&TimestampSource::Server)
+		);
+		assert_eq!(
+			sources
+				.0
+				.get(&AssetSpecifier { blockchain: "Bitcoin".into(), symbol: "BTC".into() }),
+			Some(&TimestampSource::Max)
+		);
+		assert_eq!("".parse::<TimestampSources>(), Ok(TimestampSources::default()));
+		assert!("FIAT:MXN-USD=later".parse::<TimestampSources>().is_err());
+		assert!("MXN-USD=server".parse::<TimestampSources>().is_err());
+	}
+
+	#[test]
+	fn test_apply_timestamp_policy() {
+		let policy =
+			TimestampPolicy::new(TimestampSource::Max, "FIAT:MXN-USD=server".parse().unwrap());
+		let mut mxn = coin_info(100);
+		let mut brl = CoinInfo { symbol: "BRL-USD".into(), ..coin_info(300) };
+
+		policy.apply(&mut mxn, 200);
+		policy.apply(&mut brl, 200);
+
+		assert_eq!(mxn.last_update_timestamp, 200);
+		assert_eq!(brl.last_update_timestamp, 300);
+		assert_eq!(TimestampPolicy::default().source("FIAT", "MXN-USD"), TimestampSource::Upstream);
+	}
+
+	#[test]
+	fn test_ensure_not_backwards() {
+		assert_eq!(ensure_not_backwards(None, &coin_info(100)), Ok(()));
+		assert_eq!(ensure_not_backwards(Some(&coin_info(100)), &coin_info(100)), Ok(()));
+		assert_eq!(
+			ensure_not_backwards(Some(&coin_info(100)), &coin_info(99)),
+			Err(TimestampError::WentBackwards { previous: 100, current: 99 })
+		);
+	}
+}
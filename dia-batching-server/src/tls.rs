@@ -0,0 +1,101 @@
+use arc_swap::ArcSwap;
+use log::{error, info};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, PrivateKey, ResolvesServerCert, ServerConfig};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Certificate chain and private key the server terminates TLS with, read again from their files
+/// on SIGHUP so renewed certificates are served without a restart
+pub struct ReloadableCertificate {
+	cert_path: PathBuf,
+	key_path: PathBuf,
+	certified_key: ArcSwap<CertifiedKey>,
+}
+
+impl ReloadableCertificate {
+	/// Reads the PEM encoded certificate chain and PKCS#8 or RSA private key
+	pub fn read(cert_path: PathBuf, key_path: PathBuf) -> Result<Self, String> {
+		let certified_key = read_certified_key(&cert_path, &key_path)?;
+		Ok(Self { cert_path, key_path, certified_key: ArcSwap::from_pointee(certified_key) })
+	}
+
+	/// Reads the files again, keeping the previous certificate if they aren't valid
+	pub fn reload(&self) -> Result<(), String> {
+		let certified_key = read_certified_key(&self.cert_path, &self.key_path)?;
+		self.certified_key.store(Arc::new(certified_key));
+		Ok(())
+	}
+
+	/// Config of the rustls server presenting the current certificate to every client
+	pub fn server_config(self: Arc<Self>) -> ServerConfig {
+		let mut config = ServerConfig::new(NoClientAuth::new());
+		config.cert_resolver = self;
+		config
+	}
+}
+
+impl ResolvesServerCert for ReloadableCertificate {
+	fn resolve(&self, _client_hello: ClientHello) -> Option<CertifiedKey> {
+		Some(CertifiedKey::clone(&self.certified_key.load()))
+	}
+}
+
+fn read_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, String> {
+	let read = |path: &Path| {
+		std::fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+	};
+	let chain = certs(&mut read(cert_path)?.as_slice())
+		.map_err(|_| format!("Invalid certificate chain in {}", cert_path.display()))?;
+	if chain.is_empty() {
+		return Err(format!("No certificate in {}", cert_path.display()))
+	}
+	let key = private_key(&read(key_path)?)
+		.ok_or_else(|| format!("No PKCS#8 or RSA private key in {}", key_path.display()))?;
+	let key = sign::any_supported_type(&key)
+		.map_err(|_| format!("Unsupported private key in {}", key_path.display()))?;
+	Ok(CertifiedKey::new(chain, Arc::new(key)))
+}
+
+fn private_key(pem: &[u8]) -> Option<PrivateKey> {
+	let pkcs8 = pkcs8_private_keys(&mut &pem[..]).unwrap_or_default();
+	let rsa = rsa_private_keys(&mut &pem[..]).unwrap_or_default();
+	pkcs8.into_iter().chain(rsa).next()
+}
+
+/// Reloads the certificate whenever the process receives SIGHUP, e.g. from a certbot deploy hook
+/// after a renewal
+pub fn run_reload_on_hangup_loop(certificate: Arc<ReloadableCertificate>) -> std::io::Result<()> {
+	let mut hangups = signal(SignalKind::hangup())?;
+	let _ = tokio::spawn(async move {
+		while hangups.recv().await.is_some() {
+			match certificate.reload() {
+				Ok(()) => info!("Reloaded TLS certificate {}", certificate.cert_path.display()),
+				Err(err) =>
+					error!("Failed to reload TLS certificate, keeping the previous one: {}", err),
+			}
+		}
+	});
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_read_invalid_certificate() {
+		let dir = std::env::temp_dir();
+		let (cert_path, key_path) =
+			(dir.join("dia-tls-test-cert.pem"), dir.join("dia-tls-test-key.pem"));
+		std::fs::write(&cert_path, "not a certificate").unwrap();
+		std::fs::write(&key_path, "not a key").unwrap();
+
+		assert!(ReloadableCertificate::read(cert_path, key_path.clone()).is_err());
+		assert!(
+			ReloadableCertificate::read(dir.join("dia-tls-test-missing.pem"), key_path).is_err()
+		);
+	}
+}
@@ -0,0 +1,127 @@
+use crate::storage::{storage_key, CoinInfo};
+use smol_str::SmolStr;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+pub const ONE_HOUR: u64 = 60 * 60;
+pub const ONE_DAY: u64 = 24 * ONE_HOUR;
+
+/// Price of an asset published at a unix timestamp, which holds until the next observation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+	pub timestamp: u64,
+	pub price: u128,
+}
+
+/// Time-weighted average of the prices in effect during the `window` seconds before `now`, `None`
+/// without observations. Every price is weighted by the time until the next observation, the last
+/// one by the time until `now`.
+pub fn twap(observations: &VecDeque<Observation>, now: u64, window: u64) -> Option<u128> {
+	let start = now.saturating_sub(window);
+	let mut weighted_sum: u128 = 0;
+	let mut total: u64 = 0;
+	for (index, observation) in observations.iter().enumerate() {
+		let end = observations.get(index + 1).map_or(now, |next| next.timestamp.min(now));
+		let begin = observation.timestamp.max(start);
+		if end <= begin {
+			continue
+		}
+		weighted_sum =
+			weighted_sum.checked_add(observation.price.checked_mul(u128::from(end - begin))?)?;
+		total += end - begin;
+	}
+	if total == 0 {
+		return observations.back().map(|observation| observation.price)
+	}
+	Some(weighted_sum / u128::from(total))
+}
+
+/// Ring buffers of the prices of every asset during the last `retention` seconds
+#[derive(Debug)]
+pub struct PriceHistory {
+	observations: Mutex<HashMap<(SmolStr, SmolStr), VecDeque<Observation>>>,
+	retention: u64,
+}
+
+impl PriceHistory {
+	pub fn new(retention: u64) -> Self {
+		Self { observations: Default::default(), retention }
+	}
+
+	/// Appends the price of every coin info to the observations of its asset and drops the
+	/// observations no longer in effect during the retention
+	pub fn record(&self, coin_infos: &[CoinInfo]) {
+		let mut observations = self.observations.lock().expect("poisoned lock");
+		for coin_info in coin_infos {
+			let observed =
+				Observation { timestamp: coin_info.last_update_timestamp, price: coin_info.price };
+			let asset = observations
+				.entry(storage_key(&coin_info.blockchain, &coin_info.symbol))
+				.or_default();
+			match asset.back_mut() {
+				Some(last) if last.timestamp == observed.timestamp => *last = observed,
+				Some(last) if last.timestamp > observed.timestamp => continue,
+				_ => asset.push_back(observed),
+			}
+			let cutoff = observed.timestamp.saturating_sub(self.retention);
+			while asset.get(1).map_or(false, |next| next.timestamp <= cutoff) {
+				asset.pop_front();
+			}
+		}
+	}
+
+	pub fn twap(&self, blockchain: &str, symbol: &str, now: u64, window: u64) -> Option<u128> {
+		let observations = self.observations.lock().expect("poisoned lock");
+		twap(observations.get(&storage_key(blockchain, symbol))?, now, window.min(self.retention))
+	}
+
+	/// Estimated number of bytes used by the observations
+	pub fn memory_usage(&self) -> usize {
+		self.observations
+			.lock()
+			.expect("poisoned lock")
+			.iter()
+			.map(|((blockchain, symbol), observations)| {
+				blockchain.len() +
+					symbol.len() + observations.len() * std::mem::size_of::<Observation>()
+			})
+			.sum()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(price: u128, last_update_timestamp: u64) -> CoinInfo {
+		CoinInfo {
+			symbol: "DOT".into(),
+			blockchain: "Polkadot".into(),
+			price,
+			last_update_timestamp,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_twap() {
+		let history = PriceHistory::new(ONE_DAY);
+		history.record(&[coin_info(100, 0)]);
+		history.record(&[coin_info(200, ONE_DAY - ONE_HOUR)]);
+		history.record(&[coin_info(400, ONE_DAY - ONE_HOUR / 2)]);
+
+		// 200 for the first and 400 for the second half hour
+		assert_eq!(history.twap("Polkadot", "DOT", ONE_DAY, ONE_HOUR), Some(300));
+		// 100 for 23 hours, then 300 on average for an hour
+		assert_eq!(history.twap("POLKADOT", "dot", ONE_DAY, ONE_DAY), Some((100 * 23 + 300) / 24));
+		assert_eq!(history.twap("Polkadot", "DOT", ONE_DAY - ONE_HOUR / 2, 0), Some(400));
+		assert_eq!(history.twap("Kusama", "KSM", ONE_DAY, ONE_HOUR), None);
+
+		// Only the observation in effect a day before the last one is kept of the older ones
+		history.record(&[coin_info(500, 2 * ONE_DAY)]);
+		assert_eq!(
+			history.twap("Polkadot", "DOT", 2 * ONE_DAY + ONE_HOUR, 3 * ONE_DAY),
+			Some((400 * 23 + 500) / 24)
+		);
+	}
+}
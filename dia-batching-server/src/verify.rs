@@ -0,0 +1,31 @@
+use crate::args::VerifyArgs;
+use crate::chain::ChainClient;
+use crate::publication::{find_in_log, publication_hash};
+use std::error::Error;
+
+/// Recomputes the publication hash from the `CoinInfosMap` on chain at the given block and looks
+/// it up in the server log
+pub async fn run(args: VerifyArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+	let client = ChainClient::new(args.rpc_url, args.pallet_name);
+	let block_hash = client.block_hash(args.block).await?;
+	let coin_infos = client.coin_infos(Some(&block_hash)).await?;
+	let count = coin_infos.len();
+	let hash = publication_hash(coin_infos);
+	println!(
+		"Block {} ({}): {} coin infos, publication hash {}",
+		args.block, block_hash, count, hash
+	);
+
+	if let Some(log_file) = args.log_file {
+		let log = std::fs::read_to_string(&log_file)?;
+		match find_in_log(&log, &hash) {
+			Some(line) => println!("Found in {}: {}", log_file.display(), line.trim()),
+			None =>
+				return Err(
+					format!("Publication hash {} not found in {}", hash, log_file.display()).into()
+				),
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,207 @@
+use crate::args::WatchArgs;
+use crate::handlers::Currency;
+use crate::request_signing::{RequestSigner, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::storage::CoinInfo;
+use crate::AssetSpecifier;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use smol_str::SmolStr;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Duration;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Number of decimals of the fixed point prices served by `/currencies`
+const PRICE_DECIMALS: u32 = 12;
+
+pub type Prices = BTreeMap<(SmolStr, SmolStr), CoinInfo>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+	Added(CoinInfo),
+	Updated { previous: CoinInfo, current: CoinInfo },
+	Removed(CoinInfo),
+}
+
+pub fn prices(coin_infos: Vec<CoinInfo>) -> Prices {
+	coin_infos
+		.into_iter()
+		.map(|coin_info| ((coin_info.blockchain.clone(), coin_info.symbol.clone()), coin_info))
+		.collect()
+}
+
+/// Assets that got a price, changed their price or lost their price from `previous` to `current`,
+/// ordered by blockchain and symbol
+pub fn diff(previous: &Prices, current: &Prices) -> Vec<Change> {
+	let mut changes: Vec<(&(SmolStr, SmolStr), Change)> = current
+		.iter()
+		.filter_map(|(key, current)| match previous.get(key) {
+			None => Some((key, Change::Added(current.clone()))),
+			Some(previous) if previous.price != current.price => Some((
+				key,
+				Change::Updated { previous: previous.clone(), current: current.clone() },
+			)),
+			Some(_) => None,
+		})
+		.collect();
+	changes.extend(
+		previous
+			.iter()
+			.filter(|(key, _)| !current.contains_key(*key))
+			.map(|(key, previous)| (key, Change::Removed(previous.clone()))),
+	);
+	changes.sort_by_key(|(key, _)| *key);
+	changes.into_iter().map(|(_, change)| change).collect()
+}
+
+fn price(coin_info: &CoinInfo) -> Decimal {
+	Decimal::from_i128_with_scale(coin_info.price as i128, PRICE_DECIMALS).normalize()
+}
+
+fn paint(text: String, color: &str, colored: bool) -> String {
+	if colored {
+		format!("{}{}{}", color, text, RESET)
+	} else {
+		text
+	}
+}
+
+pub fn format_change(change: &Change, colored: bool) -> String {
+	match change {
+		Change::Added(coin_info) => paint(
+			format!("{}:{} {}", coin_info.blockchain, coin_info.symbol, price(coin_info)),
+			BOLD,
+			colored,
+		),
+		Change::Updated { previous, current } => {
+			let (previous_price, current_price) = (price(previous), price(current));
+			let percentage = if previous_price.is_zero() {
+				String::new()
+			} else {
+				let change = (current_price - previous_price) / previous_price * Decimal::from(100);
+				format!(" ({:+.4}%)", change)
+			};
+			paint(
+				format!(
+					"{}:{} {} -> {}{}",
+					current.blockchain, current.symbol, previous_price, current_price, percentage
+				),
+				if current_price > previous_price { GREEN } else { RED },
+				colored,
+			)
+		},
+		Change::Removed(coin_info) => paint(
+			format!("{}:{} no longer served", coin_info.blockchain, coin_info.symbol),
+			YELLOW,
+			colored,
+		),
+	}
+}
+
+async fn fetch(
+	client: &reqwest::Client,
+	url: &str,
+	path: &str,
+	body: &[Currency],
+	signer: Option<&RequestSigner>,
+) -> Result<Vec<CoinInfo>, Box<dyn Error + Send + Sync>> {
+	let mut request = client.post(url).json(body);
+	if let Some(signer) = signer {
+		let (timestamp, signature) = signer.sign(Utc::now().timestamp().unsigned_abs(), path);
+		request = request.header(TIMESTAMP_HEADER, timestamp).header(SIGNATURE_HEADER, signature);
+	}
+	Ok(request.send().await?.error_for_status()?.json().await?)
+}
+
+/// Polls `POST /currencies` of the target and prints every change of the watched prices
+pub async fn run(args: WatchArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+	let body = args
+		.assets
+		.0
+		.iter()
+		.map(|asset| {
+			let AssetSpecifier { blockchain, symbol } = asset.parse()?;
+			Ok(Currency { blockchain, symbol })
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	let url = reqwest::Url::parse(&args.target)?;
+	let path = match url.query() {
+		Some(query) => format!("{}?{}", url.path(), query),
+		None => url.path().to_string(),
+	};
+	let url = url.to_string();
+	let signer = args.hmac_key.map(|key| RequestSigner::new(key.into_bytes(), 0));
+	let client = reqwest::Client::builder()
+		.timeout(Duration::from_millis(args.request_timeout_in_milliseconds))
+		.build()?;
+	let colored = !args.no_color;
+
+	println!("Watching {} assets at {}... (Press CTRL+C to quit)", body.len(), url);
+	let mut previous = Prices::new();
+	loop {
+		let time = Utc::now().format("%H:%M:%S");
+		match fetch(&client, &url, &path, &body, signer.as_ref()).await {
+			Ok(coin_infos) => {
+				let current = prices(coin_infos);
+				for change in diff(&previous, &current) {
+					println!("{} {}", time, format_change(&change, colored));
+				}
+				previous = current;
+			},
+			Err(err) =>
+				println!("{} {}", time, paint(format!("Request failed: {}", err), RED, colored)),
+		}
+
+		tokio::time::delay_for(Duration::from_millis(args.interval_in_milliseconds)).await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(blockchain: &str, symbol: &str, price: u128) -> CoinInfo {
+		CoinInfo {
+			blockchain: blockchain.into(),
+			symbol: symbol.into(),
+			price,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_diff() {
+		let previous = prices(vec![
+			coin_info("Polkadot", "DOT", 5_000_000_000_000),
+			coin_info("FIAT", "MXN-USD", 53_712_327_000),
+			coin_info("Bitcoin", "BTC", 37_000_000_000_000_000),
+		]);
+		let current = prices(vec![
+			coin_info("Polkadot", "DOT", 5_500_000_000_000),
+			coin_info("FIAT", "MXN-USD", 53_712_327_000),
+			coin_info("Stellar", "XLM", 120_000_000_000),
+		]);
+
+		let changes = diff(&previous, &current);
+
+		assert_eq!(
+			changes,
+			vec![
+				Change::Removed(coin_info("Bitcoin", "BTC", 37_000_000_000_000_000)),
+				Change::Updated {
+					previous: coin_info("Polkadot", "DOT", 5_000_000_000_000),
+					current: coin_info("Polkadot", "DOT", 5_500_000_000_000),
+				},
+				Change::Added(coin_info("Stellar", "XLM", 120_000_000_000)),
+			]
+		);
+		assert_eq!(format_change(&changes[1], false), "Polkadot:DOT 5 -> 5.5 (+10.0000%)");
+		assert_eq!(format_change(&changes[2], true), "\x1b[1mStellar:XLM 0.12\x1b[0m");
+		assert_eq!(format_change(&changes[0], false), "Bitcoin:BTC no longer served");
+	}
+}
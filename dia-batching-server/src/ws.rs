@@ -0,0 +1,252 @@
+use crate::handlers::Currency;
+use crate::storage::{storage_key, CoinInfo, CoinInfoStorage};
+use crate::AssetSpecifier;
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::RecvError;
+
+/// Interval in which the server pings every client
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Time after the last message of a client, a pong included, its connection is closed after
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ClientMessage {
+	/// Pushes the prices of the `<blockchain>:<symbol>` assets now and whenever they are updated
+	Subscribe {
+		assets: Vec<String>,
+	},
+	Unsubscribe {
+		assets: Vec<String>,
+	},
+	/// Keep-alive for clients that can't answer WebSocket pings, answered with a pong message
+	Ping,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerMessage {
+	Prices { prices: Vec<CoinInfo> },
+	Pong,
+	Error { message: String },
+}
+
+fn parse_assets(assets: &[String]) -> Result<Vec<Currency>, String> {
+	assets
+		.iter()
+		.map(|asset| {
+			let AssetSpecifier { blockchain, symbol } = asset.parse()?;
+			Ok(Currency { blockchain, symbol })
+		})
+		.collect()
+}
+
+/// Coin infos of `updated` the client subscribed to, with the blockchain and symbol cased like in
+/// the subscription
+fn subscribed(
+	subscriptions: &HashMap<(SmolStr, SmolStr), Currency>,
+	updated: &[CoinInfo],
+) -> Vec<CoinInfo> {
+	let mut prices: Vec<CoinInfo> = updated
+		.iter()
+		.filter_map(|coin_info| {
+			let currency =
+				subscriptions.get(&storage_key(&coin_info.blockchain, &coin_info.symbol))?;
+			Some(CoinInfo {
+				blockchain: currency.blockchain.as_str().into(),
+				symbol: currency.symbol.as_str().into(),
+				..coin_info.clone()
+			})
+		})
+		.collect();
+	prices.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+	prices
+}
+
+/// Connection of a client subscribed to the prices of some assets
+struct PriceSubscription {
+	storage: Arc<CoinInfoStorage>,
+	subscriptions: HashMap<(SmolStr, SmolStr), Currency>,
+	last_seen: Instant,
+}
+
+impl PriceSubscription {
+	fn send(&self, ctx: &mut ws::WebsocketContext<Self>, message: &ServerMessage) {
+		match serde_json::to_string(message) {
+			Ok(text) => ctx.text(text),
+			Err(err) => warn!("Failed to serialize a message to a subscriber: {}", err),
+		}
+	}
+
+	/// Answer to a text message of the client, if any
+	fn handle_text(&mut self, text: &str) -> Option<ServerMessage> {
+		let message = match serde_json::from_str(text) {
+			Ok(message) => message,
+			Err(err) => return Some(ServerMessage::Error { message: err.to_string() }),
+		};
+		match message {
+			ClientMessage::Subscribe { assets } => {
+				let currencies = match parse_assets(&assets) {
+					Ok(currencies) => currencies,
+					Err(message) => return Some(ServerMessage::Error { message }),
+				};
+				for currency in &currencies {
+					self.subscriptions.insert(
+						storage_key(&currency.blockchain, &currency.symbol),
+						currency.clone(),
+					);
+				}
+				let mut prices = self.storage.get_currencies_by_blockchains_and_symbols(currencies);
+				prices.sort_by(|a, b| (&a.blockchain, &a.symbol).cmp(&(&b.blockchain, &b.symbol)));
+				Some(ServerMessage::Prices { prices })
+			},
+			ClientMessage::Unsubscribe { assets } => match parse_assets(&assets) {
+				Ok(currencies) => {
+					for currency in currencies {
+						self.subscriptions
+							.remove(&storage_key(&currency.blockchain, &currency.symbol));
+					}
+					None
+				},
+				Err(message) => Some(ServerMessage::Error { message }),
+			},
+			ClientMessage::Ping => Some(ServerMessage::Pong),
+		}
+	}
+}
+
+impl Actor for PriceSubscription {
+	type Context = ws::WebsocketContext<Self>;
+
+	fn started(&mut self, ctx: &mut Self::Context) {
+		ctx.add_stream(self.storage.subscribe());
+		ctx.run_interval(HEARTBEAT_INTERVAL, |subscription, ctx| {
+			if subscription.last_seen.elapsed() > CLIENT_TIMEOUT {
+				debug!("Closing the connection of an unresponsive subscriber");
+				ctx.stop();
+				return
+			}
+			ctx.ping(b"");
+		});
+	}
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for PriceSubscription {
+	fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+		self.last_seen = Instant::now();
+		match message {
+			Ok(ws::Message::Ping(message)) => ctx.pong(&message),
+			Ok(ws::Message::Text(text)) =>
+				if let Some(answer) = self.handle_text(&text) {
+					self.send(ctx, &answer);
+				},
+			Ok(ws::Message::Binary(_)) => self.send(
+				ctx,
+				&ServerMessage::Error { message: "Expected a JSON text message".into() },
+			),
+			Ok(ws::Message::Close(reason)) => {
+				ctx.close(reason);
+				ctx.stop();
+			},
+			Ok(_) => (),
+			Err(err) => {
+				debug!("Closing the connection of a subscriber: {}", err);
+				ctx.stop();
+			},
+		}
+	}
+}
+
+impl StreamHandler<Result<Arc<Vec<CoinInfo>>, RecvError>> for PriceSubscription {
+	fn handle(&mut self, update: Result<Arc<Vec<CoinInfo>>, RecvError>, ctx: &mut Self::Context) {
+		let prices = match update {
+			Ok(updated) => subscribed(&self.subscriptions, &updated),
+			// Too slow to keep up with the updates, catch up with the current prices
+			Err(RecvError::Lagged(missed)) => {
+				debug!("Subscriber missed {} updates", missed);
+				self.storage.get_currencies_by_blockchains_and_symbols(
+					self.subscriptions.values().cloned().collect(),
+				)
+			},
+			Err(RecvError::Closed) => {
+				ctx.stop();
+				return
+			},
+		};
+		if !prices.is_empty() {
+			self.send(ctx, &ServerMessage::Prices { prices });
+		}
+	}
+}
+
+/// Pushes the prices of the assets a client subscribes to whenever they are updated. Clients send
+/// `{"type":"subscribe","assets":["<blockchain>:<symbol>",...]}` and `{"type":"unsubscribe",...}`
+/// and receive `{"type":"prices","prices":[<coin info>,...]}`. The server pings every client and
+/// closes connections that stay silent for longer than `CLIENT_TIMEOUT`, `{"type":"ping"}` keeps a
+/// connection alive as well.
+#[get("/ws")]
+pub async fn ws_get(
+	req: HttpRequest,
+	stream: web::Payload,
+	storage: web::Data<CoinInfoStorage>,
+) -> Result<HttpResponse, actix_web::Error> {
+	let subscription = PriceSubscription {
+		storage: storage.into_inner(),
+		subscriptions: HashMap::new(),
+		last_seen: Instant::now(),
+	};
+	ws::start(subscription, &req, stream)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coin_info(blockchain: &str, symbol: &str, price: u128) -> CoinInfo {
+		CoinInfo {
+			blockchain: blockchain.into(),
+			symbol: symbol.into(),
+			price,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_subscription_messages() {
+		let storage = Arc::new(CoinInfoStorage::default());
+		storage.replace_currencies_by_symbols(vec![coin_info("Polkadot", "DOT", 1)]);
+		let mut subscription =
+			PriceSubscription { storage, subscriptions: HashMap::new(), last_seen: Instant::now() };
+
+		assert_eq!(
+			subscription
+				.handle_text(r#"{"type":"subscribe","assets":["polkadot:DOT","FIAT:MXN-USD"]}"#),
+			Some(ServerMessage::Prices { prices: vec![coin_info("polkadot", "DOT", 1)] })
+		);
+		let updated = vec![coin_info("Polkadot", "DOT", 2), coin_info("Kusama", "KSM", 3)];
+		assert_eq!(
+			subscribed(&subscription.subscriptions, &updated),
+			vec![coin_info("polkadot", "DOT", 2)]
+		);
+		assert_eq!(subscription.handle_text(r#"{"type":"ping"}"#), Some(ServerMessage::Pong));
+		assert_eq!(
+			subscription.handle_text(r#"{"type":"unsubscribe","assets":["Polkadot:DOT"]}"#),
+			None
+		);
+		assert!(subscribed(&subscription.subscriptions, &updated).is_empty());
+		assert!(matches!(
+			subscription.handle_text(r#"{"type":"subscribe","assets":["DOT"]}"#),
+			Some(ServerMessage::Error { .. })
+		));
+		assert_eq!(serde_json::to_string(&ServerMessage::Pong).unwrap(), r#"{"type":"pong"}"#);
+	}
+}
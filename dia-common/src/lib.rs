@@ -0,0 +1,96 @@
+//! Types the dia-oracle pallet stores on chain and its offchain worker reads from the batching
+//! server, shared by both so they agree on field names, SCALE encoding and integer precision.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod signing;
+
+/// Price of an asset as served by the batching server and stored by the pallet. Prices have 12
+/// decimals.
+#[derive(
+	Encode, Decode, TypeInfo, Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinInfo {
+	#[serde(serialize_with = "ser_bytes_to_string", deserialize_with = "de_string_to_bytes")]
+	pub symbol: Vec<u8>,
+	#[serde(serialize_with = "ser_bytes_to_string", deserialize_with = "de_string_to_bytes")]
+	pub name: Vec<u8>,
+	#[serde(serialize_with = "ser_bytes_to_string", deserialize_with = "de_string_to_bytes")]
+	pub blockchain: Vec<u8>,
+	pub supply: u128,
+	pub last_update_timestamp: u64,
+	pub price: u128,
+}
+
+pub fn de_string_to_bytes<'de, D>(de: D) -> Result<Vec<u8>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let s: String = Deserialize::deserialize(de)?;
+	Ok(s.into_bytes())
+}
+
+/// Writes bytes read by `de_string_to_bytes` back as a string, so coin infos round-trip through
+/// JSON like chain specs
+pub fn ser_bytes_to_string<S>(bytes: &[u8], ser: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	ser.serialize_str(core::str::from_utf8(bytes).map_err(S::Error::custom)?)
+}
+
+/// Asset the pallet keys coin infos and supported currencies by
+#[derive(Encode, Decode, TypeInfo, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AssetId {
+	pub blockchain: Vec<u8>,
+	pub symbol: Vec<u8>,
+}
+
+impl AssetId {
+	pub fn new(blockchain: Vec<u8>, symbol: Vec<u8>) -> Self {
+		AssetId { blockchain, symbol }
+	}
+
+	/// Whether the asset is `blockchain`:`symbol` ignoring ASCII case, like the batching server
+	/// matches assets
+	pub fn matches(&self, blockchain: &[u8], symbol: &[u8]) -> bool {
+		self.blockchain.eq_ignore_ascii_case(blockchain) && self.symbol.eq_ignore_ascii_case(symbol)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_coin_info_from_batching_server_json() {
+		let coin_info: CoinInfo = serde_json::from_str(
+			r#"{"symbol":"MXN-USD","name":"MXN-USD","blockchain":"FIAT","supply":0,"lastUpdateTimestamp":1700474693,"price":53712327000}"#,
+		)
+		.unwrap();
+
+		assert_eq!(
+			coin_info,
+			CoinInfo {
+				symbol: b"MXN-USD".to_vec(),
+				name: b"MXN-USD".to_vec(),
+				blockchain: b"FIAT".to_vec(),
+				supply: 0,
+				last_update_timestamp: 1700474693,
+				price: 53712327000,
+			}
+		);
+		assert_eq!(CoinInfo::decode(&mut &coin_info.encode()[..]), Ok(coin_info.clone()));
+		assert_eq!(
+			serde_json::from_value(serde_json::to_value(&coin_info).unwrap()).ok(),
+			Some(coin_info)
+		);
+	}
+}
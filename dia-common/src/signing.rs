@@ -0,0 +1,82 @@
+//! Ed25519 signatures the batching server attaches to its `/currencies` responses. The signature
+//! covers the exact response body, the canonical JSON of the coin infos ordered by blockchain and
+//! symbol, and is sent hex encoded with the hex encoded public key in the response headers.
+//! Signing responses needs the `signing` feature, which only the batching server enables, so the
+//! pallet only builds the verification.
+
+use core::convert::TryFrom;
+#[cfg(feature = "signing")]
+use ed25519_zebra::SigningKey;
+use ed25519_zebra::{Signature, VerificationKey};
+
+pub const RESPONSE_SIGNATURE_HEADER: &str = "X-Dia-Response-Signature";
+pub const PUBLIC_KEY_HEADER: &str = "X-Dia-Public-Key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationError {
+	/// Public key isn't 32 hex encoded bytes of a valid key
+	InvalidPublicKey,
+	/// Signature isn't 64 hex encoded bytes
+	MalformedSignature,
+	/// Signature doesn't match the body and public key
+	InvalidSignature,
+}
+
+/// Public key of the signing key with the 32 byte `seed`
+#[cfg(feature = "signing")]
+pub fn public_key(seed: [u8; 32]) -> [u8; 32] {
+	VerificationKey::from(&SigningKey::from(seed)).into()
+}
+
+/// Signature of a response `body` with the signing key with the 32 byte `seed`
+#[cfg(feature = "signing")]
+pub fn sign_response(seed: [u8; 32], body: &[u8]) -> [u8; 64] {
+	SigningKey::from(seed).sign(body).into()
+}
+
+/// Checks the signature of a response `body` given the hex encoded values of the
+/// `RESPONSE_SIGNATURE_HEADER` and of the public key the response is expected to be signed with.
+/// Compare `public_key` with a trusted key, not with the key a response claims in its
+/// `PUBLIC_KEY_HEADER`.
+pub fn verify_response(
+	public_key: &[u8],
+	body: &[u8],
+	signature: &[u8],
+) -> Result<(), VerificationError> {
+	let mut key = [0u8; 32];
+	hex::decode_to_slice(public_key, &mut key).map_err(|_| VerificationError::InvalidPublicKey)?;
+	let key = VerificationKey::try_from(key).map_err(|_| VerificationError::InvalidPublicKey)?;
+	let mut bytes = [0u8; 64];
+	hex::decode_to_slice(signature, &mut bytes)
+		.map_err(|_| VerificationError::MalformedSignature)?;
+	key.verify(&Signature::from(bytes), body)
+		.map_err(|_| VerificationError::InvalidSignature)
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_verify_response() {
+		let seed = [7u8; 32];
+		let body = br#"[{"symbol":"DOT","name":"Polkadot","blockchain":"Polkadot","supply":0,"lastUpdateTimestamp":1700474693,"price":5000000000000}]"#;
+		let public_key = hex::encode(super::public_key(seed));
+		let signature = hex::encode(sign_response(seed, body));
+
+		assert_eq!(verify_response(public_key.as_bytes(), body, signature.as_bytes()), Ok(()));
+		assert_eq!(
+			verify_response(public_key.as_bytes(), &body[1..], signature.as_bytes()),
+			Err(VerificationError::InvalidSignature)
+		);
+		assert_eq!(
+			verify_response(public_key.as_bytes(), body, b"00"),
+			Err(VerificationError::MalformedSignature)
+		);
+		let other_key = hex::encode(super::public_key([8u8; 32]));
+		assert_eq!(
+			verify_response(other_key.as_bytes(), body, signature.as_bytes()),
+			Err(VerificationError::InvalidSignature)
+		);
+	}
+}
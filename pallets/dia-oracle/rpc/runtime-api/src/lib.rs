@@ -5,8 +5,12 @@ use frame_support::sp_std::vec::Vec;
 use sp_runtime::DispatchError;
 
 sp_api::decl_runtime_apis! {
+	/// Queries of the oracle's coin infos by blockchain and symbol, served over RPC as
+	/// `dia_getCoinInfo` and `dia_getValue`
 	pub trait DiaOracleApi{
+		/// Coin info of the currency, `NoCoinInfoAvailable` unless it is available
 		fn get_coin_info(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<CoinInfo, DispatchError>;
-		fn get_value(lockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo,DispatchError>;
+		/// Price of the currency
+		fn get_value(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo,DispatchError>;
 	}
 }
@@ -3,64 +3,111 @@ use super::*;
 
 #[allow(unused)]
 use crate::Pallet as DiaOracle;
-use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite, whitelisted_caller};
-use frame_support::sp_std::{vec, vec::Vec};
+use frame_benchmarking::{
+	account, benchmarks, impl_benchmark_test_suite, whitelisted_caller, BenchmarkError,
+};
+use frame_support::{
+	sp_std::{vec, vec::Vec},
+	traits::EnsureOrigin,
+};
 use frame_system::RawOrigin;
 
 benchmarks! {
 	add_currency {
-		let caller: T::AccountId = whitelisted_caller();
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
-	}: _(RawOrigin::Signed(caller), vec![1,2,3],vec![1,2,3])
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	}: _<T::RuntimeOrigin>(origin, vec![1,2,3],vec![1,2,3])
 
 	remove_currency {
-		let caller: T::AccountId = whitelisted_caller();
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
-	} : _(RawOrigin::Signed(caller), vec![1,2,3], vec![1,2,3])
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	} : _<T::RuntimeOrigin>(origin, vec![1,2,3], vec![1,2,3])
 
 	authorize_account {
-		let account: T::AccountId = whitelisted_caller();
-	} : _(RawOrigin::Root, account)
+		let origin = T::ManageFeedersOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+		let account: T::AccountId = account("test",2,2);
+	} : _<T::RuntimeOrigin>(origin, account)
 
+	// Only meaningful for runtimes whose `ManageFeedersOrigin` accepts authorized accounts
 	authorize_account_signed {
 		let caller: T::AccountId = whitelisted_caller();
 		let account: T::AccountId = account("test",2,2);
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
+		<AuthorizedAccounts<T>>::insert(caller.clone(), ());
 	} : authorize_account(RawOrigin::Signed(caller), account)
 
 	deauthorize_account {
-		let account: T::AccountId = whitelisted_caller();
-	} : _(RawOrigin::Root, account)
+		let origin = T::ManageFeedersOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+		let account: T::AccountId = account("test",2,2);
+	} : _<T::RuntimeOrigin>(origin, account)
 
+	// Only meaningful for runtimes whose `ManageFeedersOrigin` accepts authorized accounts
 	deauthorize_account_signed {
 		let caller: T::AccountId = whitelisted_caller();
 		let account: T::AccountId = account("test",2,2);
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
+		<AuthorizedAccounts<T>>::insert(caller.clone(), ());
 	} : authorize_account(RawOrigin::Signed(caller), account)
 
 	set_updated_coin_infos {
-		let example_info: CoinInfo = CoinInfo {
-			symbol: vec![2, 2, 2],
-			name: vec![2, 2, 2],
-			blockchain: vec![2, 2, 2],
-			supply: 9,
-			last_update_timestamp: 9,
-			price: 9,
-		};
-		let coin_infos = (0..=5000).map(|_|{
-			((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone())
+		let n in 1 .. T::MaxCoinsPerBatch::get();
+		let coin_infos = (0..n).map(|i| {
+			let symbol = i.to_le_bytes().to_vec();
+			let example_info: CoinInfo = CoinInfo {
+				symbol: symbol.clone(),
+				name: vec![2, 2, 2],
+				blockchain: vec![2, 2, 2],
+				supply: 9,
+				last_update_timestamp: 9,
+				price: 9,
+			};
+			((vec![2, 2, 2], symbol), example_info)
 		}).collect::<Vec<_>>();
 
-		let caller: T::AccountId = whitelisted_caller();
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
+		let origin = T::SubmitPricesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
 
-	}: _(RawOrigin::Signed(caller), coin_infos)
+	}: _<T::RuntimeOrigin>(origin, coin_infos)
 
 	set_batching_api {
 		let api = "http://localhost:8070/currencies2".as_bytes().to_vec();
-		let caller: T::AccountId = whitelisted_caller();
-		DiaOracle::<T>::authorize_account(RawOrigin::Root.into(), caller.clone())?;
-	}: _(RawOrigin::Signed(caller), api)
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	}: _<T::RuntimeOrigin>(origin, api)
+
+	set_fallback_batching_apis {
+		let apis = (0..MAX_FALLBACK_BATCHING_APIS)
+			.map(|_| "http://localhost:8070/currencies2".as_bytes().to_vec())
+			.collect::<Vec<_>>();
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	}: _<T::RuntimeOrigin>(origin, apis)
+
+	set_account_expiry {
+		let origin = T::ManageFeedersOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+		let account: T::AccountId = account("test",2,2);
+		<AuthorizedAccounts<T>>::insert(account.clone(), ());
+	}: _<T::RuntimeOrigin>(origin, account, Some(1u32.into()))
+
+	set_zero_price_allowed {
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	}: _<T::RuntimeOrigin>(origin, vec![1,2,3], vec![1,2,3], true)
+
+	remove_stale_coin_infos {
+		let n in 1 .. MAX_COIN_INFOS_PER_REMOVAL;
+		for i in 0..n {
+			let symbol = i.to_le_bytes().to_vec();
+			let coin_info = BoundedCoinInfo { last_update_timestamp: 9, ..Default::default() };
+			<CoinInfosMap<T>>::insert(AssetId::new(vec![2, 2, 2], symbol), coin_info);
+		}
+		let origin = T::ManageCurrenciesOrigin::try_successful_origin()
+			.map_err(|_| BenchmarkError::Weightless)?;
+	}: _<T::RuntimeOrigin>(origin, 10, n)
+	verify {
+		assert_eq!(<CoinInfosMap<T>>::iter().count(), 0);
+	}
 
 
 }
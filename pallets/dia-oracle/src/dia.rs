@@ -1,61 +1,234 @@
 use codec::{Decode, Encode};
-use frame_support::{sp_runtime::DispatchError, sp_std::vec::Vec};
-use serde::{Deserialize, Deserializer, Serialize};
+pub use dia_common::{de_string_to_bytes, ser_bytes_to_string, AssetId, CoinInfo};
+use frame_support::{
+	pallet_prelude::{Get, MaxEncodedLen},
+	sp_runtime::{ArithmeticError, DispatchError},
+	sp_std::{
+		convert::{TryFrom, TryInto},
+		vec::Vec,
+	},
+	weights::Weight,
+	BoundedVec, CloneNoBound, DefaultNoBound, EqNoBound, PartialEqNoBound, RuntimeDebugNoBound,
+};
 use scale_info::TypeInfo;
 #[cfg(feature = "std")]
-use serde::Serializer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sp_core::U256;
 
 // TODO: Maybe it should be moved to it's own crate
 pub trait DiaOracle {
-	/// Returns the coin info by given name
+	/// Returns the coin info by given name, `NoCoinInfoAvailable` unless it is available
 	fn get_coin_info(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<CoinInfo, DispatchError>;
 
 	/// Returns the price by given name
 	fn get_value(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo, DispatchError>;
+
+	/// Returns the price by given name with the runtime's `Decimals` instead of the 12 of
+	/// `PRICE_UNIT`, which oracles without configured decimals keep
+	fn get_native_value(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo, DispatchError> {
+		Self::get_value(blockchain, symbol)
+	}
+
+	/// Returns the price by given name with `decimals` decimals instead of the 12 of `PRICE_UNIT`,
+	/// rounded down, `Overflow` if it doesn't fit into a u128 with as many decimals
+	fn get_value_with_decimals(
+		blockchain: Vec<u8>,
+		symbol: Vec<u8>,
+		decimals: u8,
+	) -> Result<PriceInfo, DispatchError> {
+		let PriceInfo { value } = Self::get_value(blockchain, symbol)?;
+		let value = rescale(value, PRICE_DECIMALS, decimals)
+			.ok_or(DispatchError::Arithmetic(ArithmeticError::Overflow))?;
+		Ok(PriceInfo { value })
+	}
+
+	/// Whether a price is available by given name, i.e. its coin info was set and its price isn't
+	/// zero unless zero prices are allowed for it
+	fn is_available(blockchain: Vec<u8>, symbol: Vec<u8>) -> bool {
+		Self::get_coin_info(blockchain, symbol).is_ok()
+	}
+
+	/// Whether no price is available by given name or its coin info is older than the oracle's
+	/// `StalenessThreshold`, so consumers reject stale prices alike. Oracles without a threshold
+	/// only count missing prices as stale.
+	fn is_price_stale(blockchain: Vec<u8>, symbol: Vec<u8>) -> bool {
+		!Self::is_available(blockchain, symbol)
+	}
+
+	/// Returns the coin info by given name if it was updated at most `max_age` seconds ago.
+	/// Oracles without a clock to measure the age against reject every available coin info as
+	/// too old unless they override it.
+	fn get_fresh_coin_info(
+		blockchain: Vec<u8>,
+		symbol: Vec<u8>,
+		_max_age: u64,
+	) -> Result<CoinInfo, DispatchError> {
+		Self::get_coin_info(blockchain, symbol)?;
+		Err(DispatchError::Other("PriceTooOld"))
+	}
+
+	/// Returns the price of `base` in units of `quote`, with the decimals of `PRICE_UNIT`, derived
+	/// from the USD prices of both if each was updated at most `max_age` seconds ago. Both are
+	/// given as `(blockchain, symbol)`.
+	fn get_exchange_rate(
+		base: (Vec<u8>, Vec<u8>),
+		quote: (Vec<u8>, Vec<u8>),
+		max_age: u64,
+	) -> Result<PriceInfo, DispatchError> {
+		let (blockchain, symbol) = base;
+		let base = Self::get_fresh_coin_info(blockchain, symbol, max_age)?;
+		let (blockchain, symbol) = quote;
+		let quote = Self::get_fresh_coin_info(blockchain, symbol, max_age)?;
+		if quote.price == 0 {
+			return Err(DispatchError::Arithmetic(ArithmeticError::DivisionByZero))
+		}
+		let value = exchange_rate(base.price, quote.price)
+			.ok_or(DispatchError::Arithmetic(ArithmeticError::Overflow))?;
+		Ok(PriceInfo { value })
+	}
+}
+
+/// Price of one unit, as prices and exchange rates have 12 decimals
+pub const PRICE_UNIT: u128 = 1_000_000_000_000;
+
+/// Decimals of prices and exchange rates
+pub const PRICE_DECIMALS: u8 = 12;
+
+/// Price `base_price` is worth in units of `quote_price`, with the decimals of `PRICE_UNIT`.
+/// `None` if `quote_price` is zero or the rate doesn't fit into a u128.
+pub fn exchange_rate(base_price: u128, quote_price: u128) -> Option<u128> {
+	// The product of two u128 always fits into a U256
+	let rate = U256::from(base_price) * U256::from(PRICE_UNIT);
+	rate.checked_div(U256::from(quote_price))?.try_into().ok()
+}
+
+/// Converts `value` with `from` decimals into one with `to` decimals, rounded down. `None` if the
+/// result doesn't fit into a u128.
+pub fn rescale(value: u128, from: u8, to: u8) -> Option<u128> {
+	if to >= from {
+		value.checked_mul(10u128.checked_pow(u32::from(to - from))?)
+	} else {
+		// Dividing by more than the largest power of ten a u128 holds leaves nothing
+		Some(10u128.checked_pow(u32::from(from - to)).map_or(0, |divisor| value / divisor))
+	}
+}
+
+/// Handler notified of coin infos set on chain, so dependent pallets can react in the same block
+/// instead of polling `CoinInfosMap`. Runs within `set_updated_coin_infos`, which charges
+/// `on_price_set_weight` for every coin info whose price or timestamp changed.
+pub trait OnPriceSet {
+	/// Called with the coin info stored for `asset_id` if its price or timestamp changed
+	fn on_price_set(asset_id: &AssetId, coin_info: &CoinInfo);
+
+	/// Upper bound of the weight of a single `on_price_set` call
+	fn on_price_set_weight() -> Weight {
+		Weight::zero()
+	}
+}
+
+impl OnPriceSet for () {
+	fn on_price_set(_asset_id: &AssetId, _coin_info: &CoinInfo) {}
+}
+
+macro_rules! impl_on_price_set_for_tuples {
+	($($handler:ident),+) => {
+		impl<$($handler: OnPriceSet),+> OnPriceSet for ($($handler,)+) {
+			fn on_price_set(asset_id: &AssetId, coin_info: &CoinInfo) {
+				$($handler::on_price_set(asset_id, coin_info);)+
+			}
+
+			fn on_price_set_weight() -> Weight {
+				Weight::zero()$(.saturating_add($handler::on_price_set_weight()))+
+			}
+		}
+	};
 }
 
+impl_on_price_set_for_tuples!(A);
+impl_on_price_set_for_tuples!(A, B);
+impl_on_price_set_for_tuples!(A, B, C);
+impl_on_price_set_for_tuples!(A, B, C, D);
+
+/// Bails out of the surrounding extrinsic with the oracle's `PriceTooOld` or
+/// `NoCoinInfoAvailable` error unless the coin info of `blockchain`:`symbol` was updated at most
+/// `max_age` seconds ago, and evaluates to the coin info otherwise.
+///
+/// ```ignore
+/// let dot = dia_oracle::ensure_price_fresh!(T::Oracle, b"Polkadot".to_vec(), b"DOT".to_vec(), 600);
+/// ```
+#[macro_export]
+macro_rules! ensure_price_fresh {
+	($oracle:ty, $blockchain:expr, $symbol:expr, $max_age:expr $(,)?) => {
+		<$oracle as $crate::DiaOracle>::get_fresh_coin_info($blockchain, $symbol, $max_age)?
+	};
+}
+
+/// Coin info as stored on chain, with the symbol, name and blockchain bounded by `S`. Encodes
+/// like the `CoinInfo` it converts from and to.
 #[derive(
 	Encode,
 	Decode,
+	MaxEncodedLen,
 	TypeInfo,
-	Debug,
-	Clone,
-	PartialEq,
-	Eq,
-	Default,
-	Deserialize,
-	Serialize,
+	CloneNoBound,
+	DefaultNoBound,
+	EqNoBound,
+	PartialEqNoBound,
+	RuntimeDebugNoBound,
 )]
-#[serde(rename_all = "camelCase")]
-pub struct CoinInfo {
-	#[serde(deserialize_with = "de_string_to_bytes")]
-	pub symbol: Vec<u8>,
-	#[serde(deserialize_with = "de_string_to_bytes")]
-	pub name: Vec<u8>,
-	#[serde(deserialize_with = "de_string_to_bytes")]
-	pub blockchain: Vec<u8>,
+#[scale_info(skip_type_params(S))]
+pub struct BoundedCoinInfo<S: Get<u32>> {
+	pub symbol: BoundedVec<u8, S>,
+	pub name: BoundedVec<u8, S>,
+	pub blockchain: BoundedVec<u8, S>,
 	pub supply: u128,
 	pub last_update_timestamp: u64,
 	pub price: u128,
 }
 
-pub fn de_string_to_bytes<'de, D>(de: D) -> Result<Vec<u8>, D::Error>
-where
-	D: Deserializer<'de>,
-{
-	let s: &str = Deserialize::deserialize(de)?;
-	Ok(s.as_bytes().to_vec())
+impl<S: Get<u32>> BoundedCoinInfo<S> {
+	/// Whether the symbol, name and blockchain of `coin_info` fit into `S`
+	pub fn fits(coin_info: &CoinInfo) -> bool {
+		let limit = S::get() as usize;
+		[&coin_info.symbol, &coin_info.name, &coin_info.blockchain]
+			.iter()
+			.all(|field| field.len() <= limit)
+	}
+
+	/// Bounds `coin_info`, cutting off whatever of its symbol, name and blockchain exceeds `S`
+	pub fn truncate_from(coin_info: CoinInfo) -> Self {
+		BoundedCoinInfo {
+			symbol: BoundedVec::truncate_from(coin_info.symbol),
+			name: BoundedVec::truncate_from(coin_info.name),
+			blockchain: BoundedVec::truncate_from(coin_info.blockchain),
+			supply: coin_info.supply,
+			last_update_timestamp: coin_info.last_update_timestamp,
+			price: coin_info.price,
+		}
+	}
 }
 
-#[derive(Encode, Decode, scale_info::TypeInfo, Debug, Deserialize, Serialize)]
-pub struct AssetId {
-	pub blockchain: Vec<u8>,
-	pub symbol: Vec<u8>,
+impl<S: Get<u32>> TryFrom<CoinInfo> for BoundedCoinInfo<S> {
+	type Error = CoinInfo;
+
+	fn try_from(coin_info: CoinInfo) -> Result<Self, Self::Error> {
+		if !Self::fits(&coin_info) {
+			return Err(coin_info)
+		}
+		Ok(Self::truncate_from(coin_info))
+	}
 }
 
-impl AssetId {
-	pub fn new(blockchain: Vec<u8>, symbol: Vec<u8>) -> Self {
-		AssetId { blockchain, symbol }
+impl<S: Get<u32>> From<BoundedCoinInfo<S>> for CoinInfo {
+	fn from(coin_info: BoundedCoinInfo<S>) -> Self {
+		CoinInfo {
+			symbol: coin_info.symbol.into_inner(),
+			name: coin_info.name.into_inner(),
+			blockchain: coin_info.blockchain.into_inner(),
+			supply: coin_info.supply,
+			last_update_timestamp: coin_info.last_update_timestamp,
+			price: coin_info.price,
+		}
 	}
 }
 
@@ -13,6 +13,9 @@ pub(crate) mod mock;
 
 pub mod dia;
 pub use dia::*;
+pub mod merkle;
+pub mod migrations;
+mod signing;
 pub mod weights;
 pub use sp_std::convert::TryInto;
 pub use weights::WeightInfo;
@@ -56,20 +59,45 @@ pub mod pallet {
 	use super::*;
 
 	use frame_support::{
-		dispatch::DispatchResult,
+		dispatch::{DispatchErrorWithPostInfo, DispatchResult, PostDispatchInfo},
 		pallet_prelude::*,
-		sp_runtime::offchain,
+		sp_runtime::{offchain, traits::IdentifyAccount, Permill, RuntimeAppPublic},
 		sp_std,
-		sp_std::{vec, vec::Vec},
+		sp_std::{collections::btree_map::BTreeMap, vec, vec::Vec},
+		traits::UnixTime,
 	};
 	use frame_system::{
-		ensure_root, ensure_signed,
-		offchain::{AppCrypto, CreateSignedTransaction, SendSignedTransaction, Signer},
+		ensure_signed,
+		offchain::{
+			AppCrypto, CreateSignedTransaction, SendSignedTransaction, Signer, SigningTypes,
+		},
 		pallet_prelude::*,
 	};
 
 	const BATCHING_ENDPOINT_FALLBACK: [u8; 31] = *b"http://0.0.0.0:8070/currencies/";
 
+	/// Responses of the batching server larger than this are rejected without being parsed
+	pub(crate) const MAX_RESPONSE_BODY_SIZE: usize = 1024 * 1024;
+
+	/// Most coin infos `remove_stale_coin_infos` looks at in one call
+	pub const MAX_COIN_INFOS_PER_REMOVAL: u32 = 1000;
+
+	/// Most coin infos checked for staleness at the end of a block
+	pub const MAX_COIN_INFOS_PER_STALENESS_CHECK: u32 = 100;
+
+	/// Most fallback batching apis `set_fallback_batching_apis` stores
+	pub const MAX_FALLBACK_BATCHING_APIS: u32 = 4;
+
+	/// Milliseconds the offchain worker waits for a batching server before trying the next one
+	const BATCHING_REQUEST_TIMEOUT_MILLIS: u64 = 5_000;
+
+	/// Offchain local storage key of the batching api that last served the coin infos
+	pub const LAST_BATCHING_API_STORAGE_KEY: &[u8] = b"dia-oracle::last-batching-api";
+
+	/// Offchain storage key of the encoded asset the last batch cut to `MaxCoinsPerBatch` ended
+	/// with, the next batch starts after it
+	pub const BATCH_CURSOR_STORAGE_KEY: &[u8] = b"dia-oracle::batch-cursor";
+
 	/// Configure the pallet by specifying the parameters and types on which it depends.
 	#[pallet::config]
 	pub trait Config: frame_system::Config + CreateSignedTransaction<Call<Self>> {
@@ -84,9 +112,84 @@ pub mod pallet {
 
 		/// Weight of pallet
 		type WeightInfo: weights::WeightInfo;
+
+		/// Origin allowed to authorize and deauthorize accounts
+		type ManageFeedersOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// Origin allowed to add and remove supported currencies and to set the batching api
+		type ManageCurrenciesOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// Origin allowed to submit updated coin infos
+		type SubmitPricesOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// Whether to commit to the coin infos updated in a block with a merkle root, see
+		/// [`merkle`]. The root is kept in `PriceRoot` and deposited as a digest item.
+		type PublishPriceRoot: Get<bool>;
+
+		/// Maximum length of the blockchain of a supported currency or updated coin info
+		#[pallet::constant]
+		type MaxBlockchainLength: Get<u32>;
+
+		/// Maximum length of the symbol of a supported currency or updated coin info
+		#[pallet::constant]
+		type MaxSymbolLength: Get<u32>;
+
+		/// Decimals the runtime uses for prices, which `DiaOracle::get_native_value` converts the
+		/// prices to
+		#[pallet::constant]
+		type Decimals: Get<u8>;
+
+		/// Maximum length of the symbol, name and blockchain of a stored coin info
+		#[pallet::constant]
+		type StringLimit: Get<u32>;
+
+		/// Maximum number of coin infos `set_updated_coin_infos` accepts at once
+		#[pallet::constant]
+		type MaxCoinsPerBatch: Get<u32>;
+
+		/// Number of distinct authorized accounts that have to submit a coin info of a currency
+		/// within the `QuorumWindow` for the median of their submissions to be set, see
+		/// `PendingSubmissions`. Zero or one sets every submitted coin info right away.
+		#[pallet::constant]
+		type FeederQuorum: Get<u32>;
+
+		/// Blocks after which a submission no longer counts towards the `FeederQuorum`
+		#[pallet::constant]
+		type QuorumWindow: Get<Self::BlockNumber>;
+
+		/// Time the age of coin infos is measured against, usually `pallet_timestamp`
+		type UnixTime: UnixTime;
+
+		/// Seconds after their `last_update_timestamp` from which the offchain worker no longer
+		/// submits coin infos served by the batching server
+		#[pallet::constant]
+		type MaxPriceAge: Get<u64>;
+
+		/// Share a price has to move by from the one on chain for the offchain worker to submit an
+		/// update, zero to submit every changed price
+		#[pallet::constant]
+		type UpdateDeviationThreshold: Get<Permill>;
+
+		/// Seconds after the `last_update_timestamp` of a coin info on chain from which the
+		/// offchain worker submits an update even if no price moved by the threshold
+		#[pallet::constant]
+		type UpdateHeartbeat: Get<u64>;
+
+		/// Seconds after its `last_update_timestamp` from which a stored coin info is stale, which
+		/// `PriceStale` is emitted for once. Zero disables the staleness check.
+		#[pallet::constant]
+		type StalenessThreshold: Get<u64>;
+
+		/// Notified of every coin info set by `set_updated_coin_infos`, `()` if no pallet depends
+		/// on price updates
+		type OnPriceSet: OnPriceSet;
 	}
 
+	/// Version 1 bounds the strings of stored coin infos, see `migrations::v1`
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	#[pallet::without_storage_info]
 	pub struct Pallet<T>(_);
 
@@ -95,6 +198,13 @@ pub mod pallet {
 	#[pallet::getter(fn authorized_accounts)]
 	pub type AuthorizedAccounts<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
 
+	/// Block number from which an authorized account is no longer authorized. Accounts without an
+	/// entry stay authorized until they are deauthorized.
+	#[pallet::storage]
+	#[pallet::getter(fn account_expiry)]
+	pub type AccountExpiries<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, T::BlockNumber>;
+
 	/// List of all supported currencies
 	#[pallet::storage]
 	#[pallet::getter(fn supported_currencies)]
@@ -104,10 +214,57 @@ pub mod pallet {
 	#[pallet::getter(fn batching_api)]
 	pub type BatchingApi<T: Config> = StorageValue<_, Vec<u8>>;
 
-	/// Map of all the coins names to their respective info and price
+	/// Batching apis the offchain worker tries in order if the `BatchingApi` fails
+	#[pallet::storage]
+	#[pallet::getter(fn fallback_batching_apis)]
+	pub type FallbackBatchingApis<T: Config> = StorageValue<_, Vec<Vec<u8>>, ValueQuery>;
+
+	/// Map of all the coins names to their respective info and price, `None` for assets never
+	/// updated
 	#[pallet::storage]
 	#[pallet::getter(fn prices_map)]
-	pub type CoinInfosMap<T> = StorageMap<_, Blake2_128Concat, AssetId, CoinInfo, ValueQuery>;
+	pub type CoinInfosMap<T: Config> =
+		StorageMap<_, Blake2_128Concat, AssetId, BoundedCoinInfo<T::StringLimit>, OptionQuery>;
+
+	/// Assets whose price may legitimately be zero. Zero prices of other assets are rejected, as
+	/// they can't be told apart from a missing price.
+	#[pallet::storage]
+	pub type ZeroPriceAllowed<T> = StorageMap<_, Blake2_128Concat, AssetId, ()>;
+
+	/// Merkle leaves of the coin infos updated in the current block, keyed by the encoded asset id.
+	/// Cleared when the block is finalized.
+	#[pallet::storage]
+	pub type UpdatedCoinInfoLeaves<T> =
+		StorageValue<_, BTreeMap<Vec<u8>, merkle::Hash>, ValueQuery>;
+
+	/// Assets `PriceStale` was emitted for since their coin info was last set
+	#[pallet::storage]
+	pub type StaleCoinInfos<T> = StorageMap<_, Blake2_128Concat, AssetId, ()>;
+
+	/// Coin infos submitted by authorized accounts which haven't reached the `FeederQuorum` yet,
+	/// with the block they were submitted in. Only used with a quorum of more than one.
+	#[pallet::storage]
+	pub type PendingSubmissions<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		AssetId,
+		Blake2_128Concat,
+		T::AccountId,
+		(T::BlockNumber, CoinInfo),
+	>;
+
+	/// Raw `CoinInfosMap` key `remove_stale_coin_infos` continues after while a removal is going on
+	#[pallet::storage]
+	pub type RemovalCursor<T> = StorageValue<_, Vec<u8>>;
+
+	/// Raw `CoinInfosMap` key the staleness check of the next block continues after
+	#[pallet::storage]
+	pub type StalenessCursor<T> = StorageValue<_, Vec<u8>>;
+
+	/// Block number and merkle root of the last block coin infos were updated in
+	#[pallet::storage]
+	#[pallet::getter(fn price_root)]
+	pub type PriceRoot<T: Config> = StorageValue<_, (T::BlockNumber, merkle::Hash)>;
 
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
@@ -118,12 +275,28 @@ pub mod pallet {
 		AccountIdAuthorized(T::AccountId),
 		/// Event is triggered when account is deauthorized
 		AccountIdDeauthorized(T::AccountId),
+		/// Event is triggered when the expiry of an authorized account is set or lifted
+		AccountExpirySet(T::AccountId, Option<T::BlockNumber>),
 		/// Event is triggered when currency is added to the list
 		CurrencyAdded(Vec<u8>, Vec<u8>),
 		/// Event is triggered when currency is remove from the list
 		CurrencyRemoved(Vec<u8>, Vec<u8>),
 		/// Event is triggered when batching api route is set from the list
 		BatchingApiRouteSet(Vec<u8>),
+		/// Event is triggered when zero prices are allowed or disallowed for a currency
+		ZeroPriceAllowedSet(Vec<u8>, Vec<u8>, bool),
+		/// Event is triggered when the coin info of a currency becomes older than the
+		/// `StalenessThreshold`, with its `last_update_timestamp`
+		PriceStale(Vec<u8>, Vec<u8>, u64),
+		/// Event is triggered when stale coin infos are removed, with their number and whether all
+		/// coin infos were looked at
+		StaleCoinInfosRemoved(u32, bool),
+		/// Event is triggered for every currency whose price or timestamp changed when coin infos
+		/// are updated, after `UpdatedPrices`. The weight of `set_updated_coin_infos` accounts for
+		/// one per coin info.
+		UpdatedPrice { blockchain: Vec<u8>, symbol: Vec<u8>, price: u128, timestamp: u64 },
+		/// Event is triggered when the fallback batching apis are set
+		FallbackBatchingApisSet(Vec<Vec<u8>>),
 	}
 
 	// Errors inform users that something went wrong.
@@ -132,6 +305,9 @@ pub mod pallet {
 		/// Error is returned if no information is available about given coin
 		NoCoinInfoAvailable,
 
+		/// Coin info was last updated longer ago than the requested maximum age
+		PriceTooOld,
+
 		/// AccountId is not authorized
 		ThisAccountIdIsNotAuthorized,
 
@@ -150,22 +326,58 @@ pub mod pallet {
 		/// Http request to Batching Server Failed
 		HttpRequestFailed,
 
+		/// Response of the Batching Server exceeds the maximum size
+		ResponseTooLarge,
+
 		/// Failed to send signed Transaction
 		FailedSignedTransaction,
 
+		/// None of the local offchain worker keys belongs to an authorized account
+		NoAuthorizedFeederKey,
+
 		/// User cannot deauthorized themself
 		UserUnableToDeauthorizeThemself,
 
 		/// BadOrigin
 		BadOrigin,
+
+		/// Blockchain is longer than `MaxBlockchainLength`
+		BlockchainTooLong,
+
+		/// Symbol is longer than `MaxSymbolLength`
+		SymbolTooLong,
+
+		/// Coin info of the quote currency of an exchange rate has a zero price
+		ZeroPrice,
+
+		/// Exchange rate doesn't fit into a u128
+		ExchangeRateOverflow,
+
+		/// Coin info has a zero price, which isn't allowed for its asset
+		ZeroPriceNotAllowed,
+
+		/// More coin infos were submitted at once than `MaxCoinsPerBatch`
+		TooManyCoinInfos,
+
+		/// Coin infos were submitted by an origin other than a signed account with a
+		/// `FeederQuorum` of more than one
+		QuorumNeedsSignedOrigin,
+
+		/// Symbol, name or blockchain of a coin info is longer than `StringLimit`
+		CoinInfoTooLong,
+
+		/// More fallback batching apis were given than `MAX_FALLBACK_BATCHING_APIS`
+		TooManyBatchingApis,
 	}
 
+	/// Authorized accounts, supported currencies and coin infos a chain starts with, e.g. to seed
+	/// a testnet with feeders and prices
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
 		pub authorized_accounts: Vec<T::AccountId>,
 		pub supported_currencies: Vec<AssetId>,
 		pub batching_api: Vec<u8>,
-		pub coin_infos_map: Vec<(Vec<u8>, CoinInfo)>,
+		pub coin_infos_map: Vec<(AssetId, CoinInfo)>,
 	}
 
 	#[pallet::genesis_build]
@@ -179,6 +391,44 @@ pub mod pallet {
 				<AuthorizedAccounts<T>>::insert(account_id.clone(), ());
 			}
 			<BatchingApi<T>>::put(self.batching_api.clone());
+
+			for (asset_id, coin_info) in &self.coin_infos_map {
+				let coin_info = BoundedCoinInfo::try_from(coin_info.clone())
+					.expect("coin infos at genesis fit into the StringLimit");
+				<CoinInfosMap<T>>::insert(asset_id, coin_info);
+			}
+		}
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config> GenesisConfig<T> {
+		/// Authorizes `account_id` from genesis on
+		pub fn with_authorized_account(mut self, account_id: T::AccountId) -> Self {
+			self.authorized_accounts.push(account_id);
+			self
+		}
+
+		/// Supports `blockchain`:`symbol` from genesis on
+		pub fn with_supported_currency(mut self, blockchain: Vec<u8>, symbol: Vec<u8>) -> Self {
+			self.supported_currencies.push(AssetId::new(blockchain, symbol));
+			self
+		}
+
+		/// Sets the route of the batching server the offchain worker fetches prices from
+		pub fn with_batching_api(mut self, batching_api: Vec<u8>) -> Self {
+			self.batching_api = batching_api;
+			self
+		}
+
+		/// Starts the chain with `coin_info` stored for `blockchain`:`symbol`
+		pub fn with_coin_info(
+			mut self,
+			blockchain: Vec<u8>,
+			symbol: Vec<u8>,
+			coin_info: CoinInfo,
+		) -> Self {
+			self.coin_infos_map.push((AssetId::new(blockchain, symbol), coin_info));
+			self
 		}
 	}
 
@@ -196,6 +446,33 @@ pub mod pallet {
 
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_n: T::BlockNumber) -> Weight {
+			let price_root_weight = if T::PublishPriceRoot::get() {
+				T::DbWeight::get().reads_writes(1, 2)
+			} else {
+				Weight::zero()
+			};
+			price_root_weight.saturating_add(Self::staleness_check_weight())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			// Checked at the end of the block, when the timestamp of the block is set
+			Self::flag_stale_coin_infos();
+			if !T::PublishPriceRoot::get() {
+				return
+			}
+			let leaves = <UpdatedCoinInfoLeaves<T>>::take();
+			if leaves.is_empty() {
+				return
+			}
+			let leaves: Vec<merkle::Hash> = leaves.into_values().collect();
+			let root = merkle::merkle_root(&leaves);
+			<PriceRoot<T>>::put((n, root));
+			<frame_system::Pallet<T>>::deposit_log(frame_support::sp_runtime::DigestItem::Other(
+				merkle::price_root_digest(&root),
+			));
+		}
+
 		fn offchain_worker(_n: T::BlockNumber) {
 			match Self::update_prices() {
 				Ok(_) => log::info!("Updated Prices"),
@@ -207,22 +484,312 @@ pub mod pallet {
 	impl<T: Config> DiaOracle for Pallet<T> {
 		fn get_coin_info(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<CoinInfo, DispatchError> {
 			let asset_id = AssetId { blockchain, symbol };
-			ensure!(<CoinInfosMap<T>>::contains_key(&asset_id), Error::<T>::NoCoinInfoAvailable);
-			let result = <CoinInfosMap<T>>::get(&asset_id);
-			Ok(result)
+			// Zero prices stored before they were rejected mean that there was no price
+			let coin_info = <CoinInfosMap<T>>::get(&asset_id)
+				.map(CoinInfo::from)
+				.filter(|coin_info| coin_info.price != 0 || Self::allows_zero_price(&asset_id))
+				.ok_or(Error::<T>::NoCoinInfoAvailable)?;
+			Ok(coin_info)
 		}
 
 		fn get_value(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo, DispatchError> {
 			<Pallet<T> as DiaOracle>::get_coin_info(blockchain, symbol)
 				.map(|info| PriceInfo { value: info.price })
 		}
+
+		fn get_native_value(
+			blockchain: Vec<u8>,
+			symbol: Vec<u8>,
+		) -> Result<PriceInfo, DispatchError> {
+			let decimals = T::Decimals::get();
+			<Pallet<T> as DiaOracle>::get_value_with_decimals(blockchain, symbol, decimals)
+		}
+
+		fn is_price_stale(blockchain: Vec<u8>, symbol: Vec<u8>) -> bool {
+			<Pallet<T> as DiaOracle>::get_coin_info(blockchain, symbol)
+				.map_or(true, |coin_info| Self::is_stale(&coin_info))
+		}
+
+		fn get_fresh_coin_info(
+			blockchain: Vec<u8>,
+			symbol: Vec<u8>,
+			max_age: u64,
+		) -> Result<CoinInfo, DispatchError> {
+			let coin_info = <Pallet<T> as DiaOracle>::get_coin_info(blockchain, symbol)?;
+			let now = T::UnixTime::now().as_secs();
+			ensure!(
+				now.saturating_sub(coin_info.last_update_timestamp) <= max_age,
+				Error::<T>::PriceTooOld
+			);
+			Ok(coin_info)
+		}
+
+		fn get_exchange_rate(
+			base: (Vec<u8>, Vec<u8>),
+			quote: (Vec<u8>, Vec<u8>),
+			max_age: u64,
+		) -> Result<PriceInfo, DispatchError> {
+			let (blockchain, symbol) = base;
+			let base = <Pallet<T> as DiaOracle>::get_fresh_coin_info(blockchain, symbol, max_age)?;
+			let (blockchain, symbol) = quote;
+			let quote = <Pallet<T> as DiaOracle>::get_fresh_coin_info(blockchain, symbol, max_age)?;
+			ensure!(quote.price != 0, Error::<T>::ZeroPrice);
+			let value =
+				exchange_rate(base.price, quote.price).ok_or(Error::<T>::ExchangeRateOverflow)?;
+			Ok(PriceInfo { value })
+		}
 	}
 
 	impl<T: Config> Pallet<T> {
-		fn update_prices() -> Result<(), Error<T>> {
+		/// Whether `account` is authorized and its expiry, if any, hasn't been reached yet
+		pub fn is_authorized(account: &T::AccountId) -> bool {
+			<AuthorizedAccounts<T>>::contains_key(account) &&
+				<AccountExpiries<T>>::get(account)
+					.map_or(true, |expiry| <frame_system::Pallet<T>>::block_number() < expiry)
+		}
+
+		/// Local offchain worker key to sign price updates with. Among the keys of authorized
+		/// accounts the one expiring last is picked, keys without an expiry first, so a rotated-in
+		/// key takes over as soon as it is authorized and the old key is set to expire.
+		pub(crate) fn feeder_key() -> Option<T::Public> {
+			type GenericPublic<T> = <<T as Config>::AuthorityId as AppCrypto<
+				<T as SigningTypes>::Public,
+				<T as SigningTypes>::Signature,
+			>>::GenericPublic;
+
+			<T::AuthorityId as AppCrypto<T::Public, T::Signature>>::RuntimeAppPublic::all()
+				.into_iter()
+				.filter_map(|key| {
+					let public: T::Public = GenericPublic::<T>::from(key).into();
+					let account = public.clone().into_account();
+					Self::is_authorized(&account)
+						.then(|| (public, <AccountExpiries<T>>::get(&account)))
+				})
+				.max_by_key(|(_, expiry)| (expiry.is_none(), *expiry))
+				.map(|(public, _)| public)
+		}
+
+		pub fn allows_zero_price(asset_id: &AssetId) -> bool {
+			<ZeroPriceAllowed<T>>::contains_key(asset_id)
+		}
+
+		/// Requests the coin infos of the `supported_currencies` JSON from the batching server at
+		/// `api`
+		fn fetch_coin_infos(
+			api: &[u8],
+			supported_currencies: &[u8],
+		) -> Result<Vec<CoinInfo>, Error<T>> {
+			let api = sp_std::str::from_utf8(api).map_err(|_| <Error<T>>::DeserializeStrError)?;
+
+			let signature = sp_io::offchain::local_storage_get(
+				offchain::StorageKind::PERSISTENT,
+				signing::HMAC_KEY_STORAGE_KEY,
+			)
+			.map(|key| {
+				let timestamp = sp_io::offchain::timestamp().unix_millis() / 1000;
+				signing::sign(&key, timestamp, signing::url_path(api))
+			});
+
+			let mut request = offchain::http::Request::post(api, vec![supported_currencies])
+				.add_header("content-type", "application/json");
+			if let Some((timestamp, signature)) = &signature {
+				request = request
+					.add_header(
+						signing::TIMESTAMP_HEADER,
+						sp_std::str::from_utf8(timestamp)
+							.map_err(|_| <Error<T>>::DeserializeStrError)?,
+					)
+					.add_header(
+						signing::SIGNATURE_HEADER,
+						sp_std::str::from_utf8(signature)
+							.map_err(|_| <Error<T>>::DeserializeStrError)?,
+					);
+			}
+
+			// A hanging batching server must leave time to try the fallbacks
+			let deadline = sp_io::offchain::timestamp()
+				.add(offchain::Duration::from_millis(BATCHING_REQUEST_TIMEOUT_MILLIS));
+			let pending = request
+				.deadline(deadline)
+				.send()
+				.map_err(|_| <Error<T>>::HttpRequestSendFailed)?;
+			let response = pending
+				.try_wait(deadline)
+				.map_err(|_| <Error<T>>::HttpRequestFailed)?
+				.map_err(|_| <Error<T>>::HttpRequestFailed)?;
+			let body = response.body().take(MAX_RESPONSE_BODY_SIZE + 1).collect::<Vec<u8>>();
+			ensure!(body.len() <= MAX_RESPONSE_BODY_SIZE, <Error<T>>::ResponseTooLarge);
+
+			serde_json::from_slice(&body).map_err(|_| <Error<T>>::DeserializeError)
+		}
+
+		/// Whether a coin info served by the batching server moved by more than the
+		/// `UpdateDeviationThreshold` from the one on chain, or the one on chain is older than the
+		/// `UpdateHeartbeat`
+		pub(crate) fn is_update_due(coin_info: &CoinInfo, now: u64) -> bool {
+			let asset_id = AssetId::new(coin_info.blockchain.clone(), coin_info.symbol.clone());
+			<CoinInfosMap<T>>::get(&asset_id).map_or(true, |on_chain| {
+				let deviation = coin_info.price.abs_diff(on_chain.price);
+				deviation > T::UpdateDeviationThreshold::get().mul_floor(on_chain.price) ||
+					now.saturating_sub(on_chain.last_update_timestamp) >=
+						T::UpdateHeartbeat::get()
+			})
+		}
+
+		/// Records the coin infos submitted by `feeder` in `PendingSubmissions` and returns, for
+		/// the currencies whose submissions within the `QuorumWindow` reach the `FeederQuorum`,
+		/// the submission with the median price, the lower one of an even number. The pending
+		/// submissions of these currencies are cleared.
+		pub(crate) fn aggregate_submissions(
+			feeder: T::AccountId,
+			coin_infos: Vec<((Vec<u8>, Vec<u8>), CoinInfo)>,
+		) -> Vec<((Vec<u8>, Vec<u8>), CoinInfo)> {
+			let now = <frame_system::Pallet<T>>::block_number();
+			let window = T::QuorumWindow::get();
+			let mut agreed = Vec::new();
+			for ((blockchain, symbol), coin_info) in coin_infos {
+				let asset_id = AssetId { blockchain, symbol };
+				<PendingSubmissions<T>>::insert(&asset_id, &feeder, (now, coin_info));
+
+				let pending: Vec<_> = <PendingSubmissions<T>>::iter_prefix(&asset_id).collect();
+				let mut submissions: Vec<CoinInfo> = Vec::new();
+				for (account, (submitted_at, coin_info)) in pending {
+					let expired = now.saturating_sub(submitted_at) >= window;
+					if expired || !Self::is_authorized(&account) {
+						<PendingSubmissions<T>>::remove(&asset_id, account);
+					} else {
+						submissions.push(coin_info);
+					}
+				}
+				if (submissions.len() as u32) < T::FeederQuorum::get() {
+					continue
+				}
+				let _ = <PendingSubmissions<T>>::clear_prefix(&asset_id, u32::MAX, None);
+				submissions.sort_by_key(|coin_info| coin_info.price);
+				let median = submissions.swap_remove((submissions.len() - 1) / 2);
+				agreed.push(((asset_id.blockchain, asset_id.symbol), median));
+			}
+			agreed
+		}
+
+		/// Whether `coin_info` was last updated more than `StalenessThreshold` seconds ago, never
+		/// if the threshold is zero
+		pub fn is_stale(coin_info: &CoinInfo) -> bool {
+			let threshold = T::StalenessThreshold::get();
+			threshold != 0 &&
+				T::UnixTime::now().as_secs().saturating_sub(coin_info.last_update_timestamp) >
+					threshold
+		}
+
+		/// Weight of `set_updated_coin_infos` with `n` coin infos, `prices_set` of which changed
+		/// and were passed to `OnPriceSet`
+		pub(crate) fn set_updated_coin_infos_weight(n: u32, prices_set: u32) -> Weight {
+			<T as Config>::WeightInfo::set_updated_coin_infos(n)
+				.saturating_add(
+					T::OnPriceSet::on_price_set_weight().saturating_mul(prices_set.into()),
+				)
+				.saturating_add(Self::aggregate_submissions_weight(n))
+		}
+
+		/// Weight of `aggregate_submissions` with `n` coin infos, zero without a quorum. A currency
+		/// has fewer than `FeederQuorum` pending submissions before a feeder submits, since they
+		/// are cleared once the quorum is reached, so each coin info reads at most `FeederQuorum`
+		/// submissions along with the `AuthorizedAccounts` and `AccountExpiries` of their feeders,
+		/// writes its own and removes at most `FeederQuorum`.
+		fn aggregate_submissions_weight(n: u32) -> Weight {
+			let quorum = T::FeederQuorum::get() as u64;
+			if quorum <= 1 {
+				return Weight::zero()
+			}
+			let n = n as u64;
+			T::DbWeight::get().reads_writes(3 * quorum * n, (quorum + 1) * n)
+		}
+
+		/// Weight `on_initialize` reserves for the staleness check of `on_finalize`, which looks at
+		/// up to `MAX_COIN_INFOS_PER_STALENESS_CHECK` coin infos and may flag all of them
+		fn staleness_check_weight() -> Weight {
+			if T::StalenessThreshold::get() == 0 {
+				return Weight::zero()
+			}
+			let coin_infos = MAX_COIN_INFOS_PER_STALENESS_CHECK as u64;
+			T::DbWeight::get().reads_writes(2 * coin_infos + 1, coin_infos + 1)
+		}
+
+		/// Emits `PriceStale` for the stored coin infos that became stale since they were set.
+		/// Looks at `MAX_COIN_INFOS_PER_STALENESS_CHECK` coin infos per block and continues where
+		/// the previous block stopped, so every coin info is checked once per round.
+		pub(crate) fn flag_stale_coin_infos() {
+			if T::StalenessThreshold::get() == 0 {
+				return
+			}
+			let limit = MAX_COIN_INFOS_PER_STALENESS_CHECK as usize;
+			let coin_infos: Vec<(AssetId, _)> = match <StalenessCursor<T>>::get() {
+				Some(cursor) => <CoinInfosMap<T>>::iter_from(cursor).take(limit).collect(),
+				None => <CoinInfosMap<T>>::iter().take(limit).collect(),
+			};
+			if coin_infos.len() < limit {
+				<StalenessCursor<T>>::kill();
+			} else if let Some((asset_id, _)) = coin_infos.last() {
+				<StalenessCursor<T>>::put(<CoinInfosMap<T>>::hashed_key_for(asset_id));
+			}
+
+			for (asset_id, coin_info) in coin_infos {
+				let coin_info = CoinInfo::from(coin_info);
+				if !Self::is_stale(&coin_info) || <StaleCoinInfos<T>>::contains_key(&asset_id) {
+					continue
+				}
+				Self::deposit_event(Event::<T>::PriceStale(
+					asset_id.blockchain.clone(),
+					asset_id.symbol.clone(),
+					coin_info.last_update_timestamp,
+				));
+				<StaleCoinInfos<T>>::insert(asset_id, ());
+			}
+		}
+
+		fn ensure_valid_asset_id(blockchain: &[u8], symbol: &[u8]) -> DispatchResult {
+			ensure!(
+				blockchain.len() <= T::MaxBlockchainLength::get() as usize,
+				Error::<T>::BlockchainTooLong
+			);
+			ensure!(symbol.len() <= T::MaxSymbolLength::get() as usize, Error::<T>::SymbolTooLong);
+			Ok(())
+		}
+
+		/// The `size` coin infos following the asset the previous batch ended with in the order of
+		/// their assets, wrapping around, so all assets are submitted in turn if they don't fit
+		/// into one batch
+		pub(crate) fn next_batch(
+			mut prices: Vec<((Vec<u8>, Vec<u8>), CoinInfo)>,
+			size: usize,
+		) -> Vec<((Vec<u8>, Vec<u8>), CoinInfo)> {
+			prices.sort_by(|(a, _), (b, _)| a.cmp(b));
+			let cursor = sp_io::offchain::local_storage_get(
+				offchain::StorageKind::PERSISTENT,
+				BATCH_CURSOR_STORAGE_KEY,
+			)
+			.and_then(|cursor| <(Vec<u8>, Vec<u8>)>::decode(&mut &cursor[..]).ok());
+			let start = cursor.map_or(0, |cursor| {
+				prices.iter().position(|(asset, _)| *asset > cursor).unwrap_or(0)
+			});
+			prices.rotate_left(start);
+			prices.truncate(size);
+			if let Some((asset, _)) = prices.last() {
+				sp_io::offchain::local_storage_set(
+					offchain::StorageKind::PERSISTENT,
+					BATCH_CURSOR_STORAGE_KEY,
+					&asset.encode(),
+				);
+			}
+			prices
+		}
+
+		pub(crate) fn update_prices() -> Result<(), Error<T>> {
 			// Expected contract for the API with the server is supported currencies in URL path and
 			// json encoded Vec<CoinInfo> as a result from the server
-			let supported_currencies = <SupportedCurrencies<T>>::iter_keys()
+			let supported_asset_ids: Vec<AssetId> = <SupportedCurrencies<T>>::iter_keys().collect();
+			let supported_currencies = supported_asset_ids
+				.iter()
 				.map(|AssetId { blockchain, symbol }| {
 					[
 						&b"{\"blockchain\":\""[..],
@@ -247,23 +814,86 @@ pub mod pallet {
 				.ok_or(<Error<T>>::NoBatchingApiEndPoint) // Error Redundant but Explains Error Reason
 				.unwrap_or(BATCHING_ENDPOINT_FALLBACK.to_vec());
 
-			let api = sp_std::str::from_utf8(&api).map_err(|_| <Error<T>>::DeserializeStrError)?;
-			let request = offchain::http::Request::post(api, vec![supported_currencies])
-				.add_header("content-type", "application/json");
-
-			let pending = request.send().map_err(|_| <Error<T>>::HttpRequestSendFailed)?;
-			let response = pending.wait().map_err(|_| <Error<T>>::HttpRequestFailed)?;
-			let body = response.body().collect::<Vec<u8>>();
-
-			let prices: Vec<CoinInfo> =
-				serde_json::from_slice(&body).map_err(|_| <Error<T>>::DeserializeError)?;
+			// The fallbacks are tried in order if the batching api doesn't serve the coin infos
+			let mut prices = Err(<Error<T>>::NoBatchingApiEndPoint);
+			for api in sp_std::iter::once(api).chain(<FallbackBatchingApis<T>>::get()) {
+				prices = Self::fetch_coin_infos(&api, &supported_currencies);
+				match &prices {
+					Ok(_) => {
+						log::info!("Coin infos served by {:?}", sp_std::str::from_utf8(&api));
+						sp_io::offchain::local_storage_set(
+							offchain::StorageKind::PERSISTENT,
+							LAST_BATCHING_API_STORAGE_KEY,
+							&api,
+						);
+						break
+					},
+					Err(e) => log::warn!(
+						"Failed to fetch coin infos from {:?}: {:?}",
+						sp_std::str::from_utf8(&api),
+						e
+					),
+				}
+			}
+			let prices = prices?;
 
-			let prices: Vec<((Vec<u8>, Vec<u8>), CoinInfo)> = prices
+			// Coin infos are stored under the casing of the supported currency, which is the key
+			// consumers query, whatever casing the batching server responds with
+			let now = sp_io::offchain::timestamp().unix_millis() / 1000;
+			let mut prices: Vec<((Vec<u8>, Vec<u8>), CoinInfo)> = prices
 				.into_iter()
-				.map(|p| ((p.blockchain.clone(), p.symbol.clone()), p))
+				.filter_map(|mut p| {
+					if now.saturating_sub(p.last_update_timestamp) > T::MaxPriceAge::get() {
+						log::warn!(
+							"Skipping stale coin info of {:?} updated at {}",
+							sp_std::str::from_utf8(&p.symbol),
+							p.last_update_timestamp
+						);
+						return None
+					}
+					let asset_id = supported_asset_ids
+						.iter()
+						.find(|id| id.matches(&p.blockchain, &p.symbol))?;
+					if p.price == 0 && !Self::allows_zero_price(asset_id) {
+						log::warn!(
+							"Skipping zero price of {:?}",
+							sp_std::str::from_utf8(&asset_id.symbol)
+						);
+						return None
+					}
+					p.blockchain = asset_id.blockchain.clone();
+					p.symbol = asset_id.symbol.clone();
+					if !BoundedCoinInfo::<T::StringLimit>::fits(&p) {
+						log::warn!(
+							"Skipping coin info of {:?} longer than the string limit",
+							sp_std::str::from_utf8(&asset_id.symbol)
+						);
+						return None
+					}
+					// Resubmitting what is already on chain only costs fees
+					if <CoinInfosMap<T>>::get(asset_id).map(CoinInfo::from).as_ref() == Some(&p) {
+						return None
+					}
+					Some(((p.blockchain.clone(), p.symbol.clone()), p))
+				})
 				.collect();
+			// Once an update is due for one asset, the other served coin infos are submitted along
+			if !prices.iter().any(|(_, p)| Self::is_update_due(p, now)) {
+				return Ok(())
+			}
 
-			let signer = Signer::<T, T::AuthorityId>::any_account();
+			let max_coins_per_batch = T::MaxCoinsPerBatch::get() as usize;
+			if prices.len() > max_coins_per_batch {
+				log::warn!(
+					"Submitting {} of {} coin infos, the rest in the next runs",
+					max_coins_per_batch,
+					prices.len()
+				);
+				prices = Self::next_batch(prices, max_coins_per_batch);
+			}
+
+			let feeder = Self::feeder_key().ok_or(<Error<T>>::NoAuthorizedFeederKey)?;
+			let signer = Signer::<T, T::AuthorityId>::any_account().with_filter(vec![feeder]);
 
 			log::info!("Signers, {:?}", signer.can_sign());
 
@@ -283,14 +913,6 @@ pub mod pallet {
 
 			Ok(())
 		}
-
-		fn check_origin_rights(origin_account_id: &T::AccountId) -> DispatchResult {
-			ensure!(
-				<AuthorizedAccounts<T>>::contains_key(origin_account_id),
-				Error::<T>::ThisAccountIdIsNotAuthorized
-			);
-			Ok(())
-		}
 	}
 
 	#[pallet::call]
@@ -302,8 +924,8 @@ pub mod pallet {
 			blockchain: Vec<u8>,
 			symbol: Vec<u8>,
 		) -> DispatchResult {
-			let origin_account_id = ensure_signed(origin)?;
-			Pallet::<T>::check_origin_rights(&origin_account_id)?;
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
+			Self::ensure_valid_asset_id(&blockchain, &symbol)?;
 
 			let asset_id = AssetId { blockchain: blockchain.clone(), symbol: symbol.clone() };
 			if !<SupportedCurrencies<T>>::contains_key(&asset_id) {
@@ -321,8 +943,7 @@ pub mod pallet {
 			blockchain: Vec<u8>,
 			symbol: Vec<u8>,
 		) -> DispatchResult {
-			let origin_account_id = ensure_signed(origin)?;
-			Pallet::<T>::check_origin_rights(&origin_account_id)?;
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
 
 			let asset_id = AssetId { blockchain: blockchain.clone(), symbol: symbol.clone() };
 			if <SupportedCurrencies<T>>::contains_key(&asset_id) {
@@ -336,12 +957,10 @@ pub mod pallet {
 		#[pallet::call_index(2)]
 		#[pallet::weight(<T as Config>::WeightInfo::authorize_account())]
 		pub fn authorize_account(origin: OriginFor<T>, account_id: T::AccountId) -> DispatchResult {
-			if let Ok(origin_account_id) = ensure_signed(origin.clone()) {
-				Pallet::<T>::check_origin_rights(&origin_account_id)?;
-			} else {
-				ensure_root(origin)?;
-			}
+			T::ManageFeedersOrigin::ensure_origin(origin)?;
 
+			// Authorizing an account again lifts its expiry
+			<AccountExpiries<T>>::remove(&account_id);
 			if !<AuthorizedAccounts<T>>::contains_key(&account_id) {
 				Self::deposit_event(Event::<T>::AccountIdAuthorized(account_id.clone()));
 				<AuthorizedAccounts<T>>::insert(account_id, ());
@@ -357,46 +976,247 @@ pub mod pallet {
 			account_id: T::AccountId,
 		) -> DispatchResult {
 			if let Ok(origin_account_id) = ensure_signed(origin.clone()) {
-				Pallet::<T>::check_origin_rights(&origin_account_id)?;
 				ensure!(
 					account_id != origin_account_id,
 					Error::<T>::UserUnableToDeauthorizeThemself
 				);
-			} else {
-				ensure_root(origin)?;
 			}
+			T::ManageFeedersOrigin::ensure_origin(origin)?;
 
 			if <AuthorizedAccounts<T>>::contains_key(&account_id) {
 				Self::deposit_event(Event::<T>::AccountIdDeauthorized(account_id.clone()));
-				<AuthorizedAccounts<T>>::remove(account_id);
+				<AuthorizedAccounts<T>>::remove(&account_id);
+				<AccountExpiries<T>>::remove(account_id);
 			}
 
 			Ok(())
 		}
 
+		/// Sets the coin infos of the given currencies, at most `MaxCoinsPerBatch` at once. The
+		/// weight is charged for the number of coin infos, capped at `MaxCoinsPerBatch`, with an
+		/// `OnPriceSet` call and the aggregation of the pending submissions under a `FeederQuorum`
+		/// each, and refunded down to the `OnPriceSet` calls of the changed coin infos, or to the
+		/// checks if the batch is too large.
 		#[pallet::call_index(4)]
-		#[pallet::weight(<T as Config>::WeightInfo::set_updated_coin_infos())]
+		#[pallet::weight({
+			let n = (coin_infos.len() as u32).min(T::MaxCoinsPerBatch::get());
+			Pallet::<T>::set_updated_coin_infos_weight(n, n)
+		})]
 		pub fn set_updated_coin_infos(
 			origin: OriginFor<T>,
 			coin_infos: Vec<((Vec<u8>, Vec<u8>), CoinInfo)>,
 		) -> DispatchResultWithPostInfo {
-			let origin_account_id = ensure_signed(origin)?;
-			Pallet::<T>::check_origin_rights(&origin_account_id)?;
+			T::SubmitPricesOrigin::ensure_origin(origin.clone())?;
+			let batch_size = coin_infos.len() as u32;
+			if batch_size > T::MaxCoinsPerBatch::get() {
+				return Err(DispatchErrorWithPostInfo {
+					post_info: PostDispatchInfo {
+						actual_weight: Some(<T as Config>::WeightInfo::set_updated_coin_infos(0)),
+						pays_fee: Pays::No,
+					},
+					error: Error::<T>::TooManyCoinInfos.into(),
+				})
+			}
+			for ((blockchain, symbol), _) in &coin_infos {
+				Self::ensure_valid_asset_id(blockchain, symbol)?;
+			}
+			for ((blockchain, symbol), c) in &coin_infos {
+				let asset_id = AssetId { blockchain: blockchain.clone(), symbol: symbol.clone() };
+				ensure!(
+					c.price != 0 || Self::allows_zero_price(&asset_id),
+					Error::<T>::ZeroPriceNotAllowed
+				);
+				ensure!(BoundedCoinInfo::<T::StringLimit>::fits(c), Error::<T>::CoinInfoTooLong);
+			}
+			let coin_infos = if T::FeederQuorum::get() > 1 {
+				let feeder =
+					ensure_signed(origin).map_err(|_| Error::<T>::QuorumNeedsSignedOrigin)?;
+				let coin_infos = Self::aggregate_submissions(feeder, coin_infos);
+				if coin_infos.is_empty() {
+					return Ok(PostDispatchInfo {
+						actual_weight: Some(Self::set_updated_coin_infos_weight(batch_size, 0)),
+						pays_fee: Pays::No,
+					})
+				}
+				coin_infos
+			} else {
+				coin_infos
+			};
 			Self::deposit_event(Event::<T>::UpdatedPrices(coin_infos.clone()));
+			let publish_price_root = T::PublishPriceRoot::get();
+			let mut leaves = if publish_price_root {
+				<UpdatedCoinInfoLeaves<T>>::get()
+			} else {
+				BTreeMap::new()
+			};
+			let mut prices_set = 0;
 			for ((blockchain, symbol), c) in coin_infos {
-				<CoinInfosMap<T>>::insert(AssetId { blockchain, symbol }, c);
+				let asset_id = AssetId { blockchain, symbol };
+				if publish_price_root {
+					leaves.insert(asset_id.encode(), merkle::leaf_hash(&asset_id, &c));
+				}
+				let changed = <CoinInfosMap<T>>::get(&asset_id).map_or(true, |previous| {
+					(previous.price, previous.last_update_timestamp) !=
+						(c.price, c.last_update_timestamp)
+				});
+				if changed {
+					T::OnPriceSet::on_price_set(&asset_id, &c);
+					prices_set += 1;
+					Self::deposit_event(Event::<T>::UpdatedPrice {
+						blockchain: asset_id.blockchain.clone(),
+						symbol: asset_id.symbol.clone(),
+						price: c.price,
+						timestamp: c.last_update_timestamp,
+					});
+				}
+				<StaleCoinInfos<T>>::remove(&asset_id);
+				<CoinInfosMap<T>>::insert(asset_id, BoundedCoinInfo::truncate_from(c));
 			}
-			Ok(Pays::No.into())
+			if publish_price_root {
+				<UpdatedCoinInfoLeaves<T>>::put(leaves);
+			}
+			Ok(PostDispatchInfo {
+				actual_weight: Some(Self::set_updated_coin_infos_weight(batch_size, prices_set)),
+				pays_fee: Pays::No,
+			})
 		}
 
 		#[pallet::call_index(5)]
 		#[pallet::weight(<T as Config>::WeightInfo::set_batching_api())]
 		pub fn set_batching_api(origin: OriginFor<T>, api: Vec<u8>) -> DispatchResult {
-			let origin_account_id = ensure_signed(origin)?;
-			Pallet::<T>::check_origin_rights(&origin_account_id)?;
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
 			<BatchingApi<T>>::put(api.clone());
 			Self::deposit_event(Event::<T>::BatchingApiRouteSet(api));
 			Ok(())
 		}
+
+		/// Sets the block number from which `account_id` is no longer authorized, or lifts its
+		/// expiry with `None`. Rotate feeder keys by authorizing the new key before the old one
+		/// expires, the offchain worker switches to the new key right away.
+		#[pallet::call_index(6)]
+		#[pallet::weight(<T as Config>::WeightInfo::set_account_expiry())]
+		pub fn set_account_expiry(
+			origin: OriginFor<T>,
+			account_id: T::AccountId,
+			expires_at: Option<T::BlockNumber>,
+		) -> DispatchResult {
+			T::ManageFeedersOrigin::ensure_origin(origin)?;
+			ensure!(
+				<AuthorizedAccounts<T>>::contains_key(&account_id),
+				Error::<T>::ThisAccountIdIsNotAuthorized
+			);
+
+			match expires_at {
+				Some(expiry) => <AccountExpiries<T>>::insert(&account_id, expiry),
+				None => <AccountExpiries<T>>::remove(&account_id),
+			}
+			Self::deposit_event(Event::<T>::AccountExpirySet(account_id, expires_at));
+			Ok(())
+		}
+
+		/// Allows `set_updated_coin_infos` to set a zero price for a currency whose price may
+		/// legitimately be zero, or disallows it again
+		#[pallet::call_index(7)]
+		#[pallet::weight(<T as Config>::WeightInfo::set_zero_price_allowed())]
+		pub fn set_zero_price_allowed(
+			origin: OriginFor<T>,
+			blockchain: Vec<u8>,
+			symbol: Vec<u8>,
+			allowed: bool,
+		) -> DispatchResult {
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
+			Self::ensure_valid_asset_id(&blockchain, &symbol)?;
+
+			let asset_id = AssetId { blockchain: blockchain.clone(), symbol: symbol.clone() };
+			if allowed {
+				<ZeroPriceAllowed<T>>::insert(asset_id, ());
+			} else {
+				<ZeroPriceAllowed<T>>::remove(asset_id);
+			}
+			Self::deposit_event(Event::<T>::ZeroPriceAllowedSet(blockchain, symbol, allowed));
+			Ok(())
+		}
+
+		/// Removes the coin infos last updated before the unix timestamp `cutoff`, e.g. of delisted
+		/// currencies. Looks at `limit` coin infos per call, at most `MAX_COIN_INFOS_PER_REMOVAL`,
+		/// and continues where the previous call stopped until all coin infos were looked at.
+		#[pallet::call_index(8)]
+		#[pallet::weight(<T as Config>::WeightInfo::remove_stale_coin_infos(
+			(*limit).min(MAX_COIN_INFOS_PER_REMOVAL)
+		))]
+		pub fn remove_stale_coin_infos(
+			origin: OriginFor<T>,
+			cutoff: u64,
+			limit: u32,
+		) -> DispatchResultWithPostInfo {
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
+			let limit = limit.min(MAX_COIN_INFOS_PER_REMOVAL);
+
+			let coin_infos: Vec<(AssetId, _)> = match <RemovalCursor<T>>::get() {
+				Some(cursor) => <CoinInfosMap<T>>::iter_from(cursor).take(limit as usize).collect(),
+				None => <CoinInfosMap<T>>::iter().take(limit as usize).collect(),
+			};
+			let looked_at = coin_infos.len() as u32;
+			let complete = looked_at < limit;
+			if complete {
+				<RemovalCursor<T>>::kill();
+			} else if let Some((asset_id, _)) = coin_infos.last() {
+				<RemovalCursor<T>>::put(<CoinInfosMap<T>>::hashed_key_for(asset_id));
+			}
+
+			let mut removed = 0;
+			for (asset_id, coin_info) in coin_infos {
+				if coin_info.last_update_timestamp < cutoff {
+					<StaleCoinInfos<T>>::remove(&asset_id);
+					<CoinInfosMap<T>>::remove(asset_id);
+					removed += 1;
+				}
+			}
+			Self::deposit_event(Event::<T>::StaleCoinInfosRemoved(removed, complete));
+			Ok(Some(<T as Config>::WeightInfo::remove_stale_coin_infos(looked_at)).into())
+		}
+
+		/// Sets the batching apis the offchain worker falls back to, in order, if the
+		/// `BatchingApi` doesn't serve the coin infos
+		#[pallet::call_index(9)]
+		#[pallet::weight(<T as Config>::WeightInfo::set_fallback_batching_apis())]
+		pub fn set_fallback_batching_apis(
+			origin: OriginFor<T>,
+			apis: Vec<Vec<u8>>,
+		) -> DispatchResult {
+			T::ManageCurrenciesOrigin::ensure_origin(origin)?;
+			ensure!(
+				apis.len() as u32 <= MAX_FALLBACK_BATCHING_APIS,
+				Error::<T>::TooManyBatchingApis
+			);
+			<FallbackBatchingApis<T>>::put(apis.clone());
+			Self::deposit_event(Event::<T>::FallbackBatchingApisSet(apis));
+			Ok(())
+		}
+	}
+}
+
+/// Origin check that succeeds for signed origins of accounts in `AuthorizedAccounts` which haven't
+/// expired, yielding the account id. Combine it with e.g. `EnsureRoot` through `EitherOfDiverse` to
+/// also accept other origins.
+pub struct EnsureAuthorizedAccount<T>(sp_std::marker::PhantomData<T>);
+
+impl<T: Config> frame_support::traits::EnsureOrigin<T::RuntimeOrigin>
+	for EnsureAuthorizedAccount<T>
+{
+	type Success = T::AccountId;
+
+	fn try_origin(o: T::RuntimeOrigin) -> Result<Self::Success, T::RuntimeOrigin> {
+		o.into().and_then(|o| match o {
+			frame_system::RawOrigin::Signed(who) if Pallet::<T>::is_authorized(&who) => Ok(who),
+			r => Err(T::RuntimeOrigin::from(r)),
+		})
+	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	fn try_successful_origin() -> Result<T::RuntimeOrigin, ()> {
+		let who: T::AccountId = frame_benchmarking::whitelisted_caller();
+		<AuthorizedAccounts<T>>::insert(&who, ());
+		Ok(frame_system::RawOrigin::Signed(who).into())
 	}
 }
@@ -0,0 +1,53 @@
+//! Binary merkle tree over the coin infos updated in a block.
+//!
+//! Leaves are `blake2_256(0x00 ++ SCALE((AssetId, CoinInfo)))`, ordered by the SCALE encoded
+//! `AssetId`. Inner nodes are `blake2_256(0x01 ++ left ++ right)`, a node without sibling is
+//! carried up unchanged. The prefixes keep an inner node from passing as a leaf in a proof. The
+//! root of an empty tree is all zeros.
+use crate::{AssetId, CoinInfo};
+use codec::Encode;
+use frame_support::sp_std::vec::Vec;
+use sp_core::hashing::blake2_256;
+
+/// Prefix of the `DigestItem::Other` the price root of a block is deposited as
+pub const PRICE_ROOT_DIGEST_PREFIX: [u8; 4] = *b"diap";
+
+/// Byte the preimage of a leaf hash starts with
+pub const LEAF_PREFIX: u8 = 0x00;
+
+/// Byte the preimage of an inner node hash starts with
+pub const NODE_PREFIX: u8 = 0x01;
+
+pub type Hash = [u8; 32];
+
+pub fn leaf_hash(asset_id: &AssetId, coin_info: &CoinInfo) -> Hash {
+	blake2_256(&[&[LEAF_PREFIX][..], &(asset_id, coin_info).encode()].concat())
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+	blake2_256(&[&[NODE_PREFIX][..], &left[..], &right[..]].concat())
+}
+
+/// Root of the tree over `leaves`, which have to be in leaf order already
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+	if leaves.is_empty() {
+		return [0u8; 32]
+	}
+	let mut layer = leaves.to_vec();
+	while layer.len() > 1 {
+		layer = layer
+			.chunks(2)
+			.map(|pair| match pair {
+				[left, right] => hash_pair(left, right),
+				[single] => *single,
+				_ => unreachable!("chunks of two"),
+			})
+			.collect::<Vec<_>>();
+	}
+	layer[0]
+}
+
+/// Content of the digest item announcing `root`
+pub fn price_root_digest(root: &Hash) -> Vec<u8> {
+	[&PRICE_ROOT_DIGEST_PREFIX[..], &root[..]].concat()
+}
@@ -0,0 +1,91 @@
+//! Storage migrations of the dia-oracle pallet, to be added to the `Executive` of the runtime
+
+pub mod v1 {
+	use crate::{AssetId, BoundedCoinInfo, CoinInfo, Config, Pallet};
+	use frame_support::{
+		pallet_prelude::*,
+		sp_std::{marker::PhantomData, vec::Vec},
+		storage_alias,
+		traits::{GetStorageVersion, OnRuntimeUpgrade},
+	};
+
+	/// `CoinInfosMap` as it was stored before version 1, with unbounded coin infos
+	#[storage_alias]
+	type CoinInfosMap<T: Config> = StorageMap<Pallet<T>, Blake2_128Concat, AssetId, CoinInfo>;
+
+	/// Bounds the symbol, name and blockchain of every stored coin info by the `StringLimit`.
+	/// Bounded coin infos encode like unbounded ones, so only the coin infos longer than the limit
+	/// are written: a too long name is cut off at the limit, while coin infos with a too long
+	/// symbol or blockchain are removed, as a cut off one would no longer match its key.
+	pub struct MigrateToBoundedCoinInfos<T>(PhantomData<T>);
+
+	/// Coin infos that don't fit into the `StringLimit`, with whether they are removed rather
+	/// than cut off
+	fn too_long_coin_infos<T: Config>() -> Vec<(AssetId, CoinInfo, bool)> {
+		let limit = T::StringLimit::get() as usize;
+		<CoinInfosMap<T>>::iter()
+			.filter(|(_, coin_info)| !BoundedCoinInfo::<T::StringLimit>::fits(coin_info))
+			.map(|(asset_id, coin_info)| {
+				let remove = coin_info.symbol.len() > limit || coin_info.blockchain.len() > limit;
+				(asset_id, coin_info, remove)
+			})
+			.collect()
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToBoundedCoinInfos<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() >= 1 {
+				log::info!("dia-oracle coin infos are already bounded");
+				return T::DbWeight::get().reads(1)
+			}
+
+			let read = <CoinInfosMap<T>>::iter_keys().count() as u64;
+			let too_long = too_long_coin_infos::<T>();
+			for (asset_id, coin_info, remove) in &too_long {
+				let symbol = frame_support::sp_std::str::from_utf8(&coin_info.symbol);
+				if *remove {
+					log::warn!("Removing the coin info of {:?} exceeding the string limit", symbol);
+					<CoinInfosMap<T>>::remove(asset_id);
+				} else {
+					log::warn!("Cutting off the name of {:?} at the string limit", symbol);
+					<crate::CoinInfosMap<T>>::insert(
+						asset_id,
+						BoundedCoinInfo::truncate_from(coin_info.clone()),
+					);
+				}
+			}
+			StorageVersion::new(1).put::<Pallet<T>>();
+			log::info!("Bounded {} of {} dia-oracle coin infos", too_long.len(), read);
+
+			let written = too_long.len() as u64;
+			T::DbWeight::get().reads_writes(2 * read + 1, written + 1)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			let count = <CoinInfosMap<T>>::iter_keys().count() as u32;
+			let removed =
+				too_long_coin_infos::<T>().iter().filter(|(_, _, remove)| *remove).count();
+			Ok((count, removed as u32).encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), &'static str> {
+			let (count, removed) = <(u32, u32)>::decode(&mut &state[..])
+				.map_err(|_| "dia-oracle pre_upgrade state doesn't decode")?;
+			ensure!(
+				Pallet::<T>::on_chain_storage_version() >= 1,
+				"dia-oracle storage version wasn't bumped"
+			);
+			// Iterating skips values that don't decode, so every coin info is bounded if none is
+			// skipped
+			let bounded = <crate::CoinInfosMap<T>>::iter_values().count() as u32;
+			ensure!(
+				bounded == <crate::CoinInfosMap<T>>::iter_keys().count() as u32,
+				"dia-oracle coin infos that don't fit into the string limit are left"
+			);
+			ensure!(bounded == count - removed, "dia-oracle coin infos were lost");
+			Ok(())
+		}
+	}
+}
@@ -1,10 +1,17 @@
 use crate as dia_oracle;
-use frame_support::parameter_types;
+use dia_oracle::{AssetId, CoinInfo};
+use frame_support::{
+	parameter_types,
+	traits::{ConstBool, ConstU32, ConstU64, ConstU8, EitherOfDiverse, GenesisBuild, UnixTime},
+	weights::Weight,
+};
 use frame_system as system;
+use frame_system::EnsureRoot;
 use sp_core::{sr25519::Signature, H256};
 use sp_runtime::{
 	testing::{Header, TestXt},
 	traits::{BlakeTwo256, Extrinsic as ExtrinsicT, IdentifyAccount, IdentityLookup, Verify},
+	Permill,
 };
 use sp_std::convert::{TryFrom, TryInto};
 
@@ -26,6 +33,34 @@ frame_support::construct_runtime!(
 parameter_types! {
 	pub const BlockHashCount: u64 = 250;
 	pub const SS58Prefix: u8 = 42;
+	pub static Now: u64 = 0;
+	pub static PricesSet: Vec<(AssetId, u128)> = vec![];
+	pub static UpdateDeviationThreshold: Permill = Permill::zero();
+	pub static UpdateHeartbeat: u64 = 0;
+	pub static StalenessThreshold: u64 = 0;
+	pub static FeederQuorum: u32 = 0;
+}
+
+/// Unix time in seconds set by tests through `Now`
+pub struct MockUnixTime;
+
+impl UnixTime for MockUnixTime {
+	fn now() -> core::time::Duration {
+		core::time::Duration::from_secs(Now::get())
+	}
+}
+
+/// Records the prices set on chain in `PricesSet`
+pub struct RecordPricesSet;
+
+impl dia_oracle::OnPriceSet for RecordPricesSet {
+	fn on_price_set(asset_id: &AssetId, coin_info: &CoinInfo) {
+		PricesSet::mutate(|prices| prices.push((asset_id.clone(), coin_info.price)));
+	}
+
+	fn on_price_set_weight() -> Weight {
+		Weight::from_ref_time(1_000_000)
+	}
 }
 
 impl system::Config for Test {
@@ -89,6 +124,24 @@ impl dia_oracle::Config for Test {
 	type AuthorityId = super::crypto::DiaAuthId;
 	type RuntimeCall = RuntimeCall;
 	type WeightInfo = ();
+	type ManageFeedersOrigin =
+		EitherOfDiverse<EnsureRoot<AccountId>, dia_oracle::EnsureAuthorizedAccount<Test>>;
+	type ManageCurrenciesOrigin = dia_oracle::EnsureAuthorizedAccount<Test>;
+	type SubmitPricesOrigin = dia_oracle::EnsureAuthorizedAccount<Test>;
+	type PublishPriceRoot = ConstBool<true>;
+	type MaxBlockchainLength = ConstU32<32>;
+	type MaxSymbolLength = ConstU32<32>;
+	type Decimals = ConstU8<10>;
+	type StringLimit = ConstU32<64>;
+	type MaxCoinsPerBatch = ConstU32<16>;
+	type FeederQuorum = FeederQuorum;
+	type QuorumWindow = ConstU64<10>;
+	type UnixTime = MockUnixTime;
+	type MaxPriceAge = ConstU64<3600>;
+	type UpdateDeviationThreshold = UpdateDeviationThreshold;
+	type UpdateHeartbeat = UpdateHeartbeat;
+	type StalenessThreshold = StalenessThreshold;
+	type OnPriceSet = RecordPricesSet;
 }
 
 // Build genesis storage according to the mock runtime.
@@ -96,6 +149,13 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
 }
 
+/// Like `new_test_ext` with the oracle starting from `genesis`
+pub fn new_test_ext_with(genesis: dia_oracle::GenesisConfig<Test>) -> sp_io::TestExternalities {
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	genesis.assimilate_storage(&mut storage).unwrap();
+	storage.into()
+}
+
 pub fn get_account_id(id: u8) -> AccountId {
 	AccountId::from(sp_core::sr25519::Public::from_raw([id; 32]))
 }
@@ -0,0 +1,64 @@
+//! Optional authentication of the offchain worker towards the batching server.
+//!
+//! If the offchain local storage (`PERSISTENT`) holds a key under [`HMAC_KEY_STORAGE_KEY`], every
+//! request carries the current unix timestamp in seconds and the hex encoded HMAC-SHA256 of
+//! `"{timestamp}\n{path}"` in the [`TIMESTAMP_HEADER`] and [`SIGNATURE_HEADER`] headers.
+use frame_support::sp_std::vec::Vec;
+use sp_core::hashing::sha2_256;
+
+/// Offchain local storage key of the shared secret, stored as raw bytes
+pub const HMAC_KEY_STORAGE_KEY: &[u8] = b"dia-oracle::hmac-key";
+pub const TIMESTAMP_HEADER: &str = "X-Dia-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Dia-Signature";
+
+const BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256 as specified in RFC 2104
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+	let mut key_block = [0u8; BLOCK_SIZE];
+	if key.len() > BLOCK_SIZE {
+		key_block[..32].copy_from_slice(&sha2_256(key));
+	} else {
+		key_block[..key.len()].copy_from_slice(key);
+	}
+
+	let inner: Vec<u8> =
+		key_block.iter().map(|b| b ^ 0x36).chain(message.iter().copied()).collect();
+	let outer: Vec<u8> =
+		key_block.iter().map(|b| b ^ 0x5c).chain(sha2_256(&inner).iter().copied()).collect();
+	sha2_256(&outer)
+}
+
+/// Returns the path (including the query) of an `http(s)://host[:port]/path` url
+pub fn url_path(url: &str) -> &str {
+	let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+	without_scheme.find('/').map_or("/", |index| &without_scheme[index..])
+}
+
+/// Returns the decimal timestamp and the hex encoded signature for a request to `path`
+pub fn sign(key: &[u8], timestamp: u64, path: &str) -> (Vec<u8>, Vec<u8>) {
+	let timestamp = to_decimal(timestamp);
+	let message = [&timestamp[..], &b"\n"[..], path.as_bytes()].concat();
+	(timestamp, to_hex(&hmac_sha256(key, &message)))
+}
+
+fn to_decimal(mut value: u64) -> Vec<u8> {
+	let mut digits = Vec::new();
+	loop {
+		digits.push(b'0' + (value % 10) as u8);
+		value /= 10;
+		if value == 0 {
+			break
+		}
+	}
+	digits.reverse();
+	digits
+}
+
+fn to_hex(bytes: &[u8]) -> Vec<u8> {
+	const HEX: &[u8; 16] = b"0123456789abcdef";
+	bytes
+		.iter()
+		.flat_map(|b| [HEX[(b >> 4) as usize], HEX[(b & 0x0f) as usize]])
+		.collect()
+}
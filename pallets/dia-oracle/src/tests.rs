@@ -1,8 +1,28 @@
 use crate::mock::*;
 use crate::*;
 
-use frame_support::assert_err;
-use sp_core::sr25519::Public;
+use codec::{Decode, Encode};
+use frame_support::{
+	assert_err, assert_noop, assert_ok,
+	dispatch::Pays,
+	traits::{Get, Hooks},
+};
+use parking_lot::RwLock;
+use sp_core::{
+	offchain::{
+		testing::{
+			OffchainState, PendingRequest, PoolState, TestOffchainExt, TestTransactionPoolExt,
+		},
+		OffchainDbExt, OffchainWorkerExt, StorageKind, Timestamp, TransactionPoolExt,
+	},
+	sr25519::Public,
+};
+use sp_keystore::{testing::MemoryKeystore, Keystore, KeystoreExt};
+use sp_runtime::{
+	testing::TestXt, traits::Dispatchable, ArithmeticError, DigestItem, DispatchError, Permill,
+	RuntimeAppPublic,
+};
+use std::sync::Arc;
 
 pub const ALICE: Public = Public([1u8; 32]);
 
@@ -112,6 +132,37 @@ fn deauthorize_account_should_not_work_by_deauthorizing_themself() {
 	})
 }
 
+#[test]
+fn root_should_be_able_to_authorize_account() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(DOracle::authorize_account(RuntimeOrigin::root(), ALICE));
+
+		assert_eq!(<AuthorizedAccounts<Test>>::contains_key(ALICE), true);
+	})
+}
+
+#[test]
+fn unauthorized_account_should_be_rejected_by_configured_origins() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![1], vec![1]),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			DOracle::authorize_account(RuntimeOrigin::signed(ALICE), get_account_id(2)),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), vec![]),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			DOracle::add_currency(RuntimeOrigin::root(), vec![1], vec![1]),
+			DispatchError::BadOrigin
+		);
+	})
+}
+
 #[test]
 fn set_updated_coin_infos_should_work() {
 	new_test_ext().execute_with(|| {
@@ -129,6 +180,7 @@ fn set_updated_coin_infos_should_work() {
 			((vec![1, 2, 3], vec![1, 2, 3]), CoinInfo::default()),
 			((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone()),
 		];
+		<ZeroPriceAllowed<Test>>::insert(AssetId::new(vec![1, 2, 3], vec![1, 2, 3]), ());
 		let _test1 = DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos);
 
 		assert_eq!(
@@ -140,13 +192,328 @@ fn set_updated_coin_infos_should_work() {
 			true
 		);
 		assert_eq!(
-			<CoinInfosMap<Test>>::get(AssetId::new(vec![2, 2, 2], vec![2, 2, 2])),
-			example_info
+			<CoinInfosMap<Test>>::get(AssetId::new(vec![2, 2, 2], vec![2, 2, 2]))
+				.map(CoinInfo::from),
+			Some(example_info)
 		);
 		assert_eq!(
 			<CoinInfosMap<Test>>::get(AssetId::new(vec![1, 2, 3], vec![1, 2, 3])),
-			CoinInfo::default()
+			Some(BoundedCoinInfo::default())
+		);
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_reject_zero_prices_unless_allowed() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		let coin_infos = vec![
+			((vec![1], vec![1]), CoinInfo { price: 1, ..Default::default() }),
+			((vec![2], vec![2]), CoinInfo { price: 0, ..Default::default() }),
+		];
+
+		assert_noop!(
+			DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos.clone()),
+			Error::<Test>::ZeroPriceNotAllowed
+		);
+		assert!(!DOracle::is_available(vec![1], vec![1]));
+
+		assert_noop!(
+			DOracle::set_zero_price_allowed(RuntimeOrigin::root(), vec![2], vec![2], true),
+			DispatchError::BadOrigin
+		);
+		assert_ok!(DOracle::set_zero_price_allowed(
+			RuntimeOrigin::signed(ALICE),
+			vec![2],
+			vec![2],
+			true
+		));
+		assert_ok!(DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos));
+		assert!(DOracle::is_available(vec![1], vec![1]));
+		assert!(DOracle::is_available(vec![2], vec![2]));
+		assert_eq!(DOracle::get_value(vec![2], vec![2]), Ok(PriceInfo { value: 0 }));
+		assert!(!DOracle::is_available(vec![3], vec![3]));
+
+		// A zero price stored while it was allowed means there is no price once it no longer is
+		assert_ok!(DOracle::set_zero_price_allowed(
+			RuntimeOrigin::signed(ALICE),
+			vec![2],
+			vec![2],
+			false
+		));
+		assert!(!DOracle::is_available(vec![2], vec![2]));
+		assert_err!(DOracle::get_value(vec![2], vec![2]), Error::<Test>::NoCoinInfoAvailable);
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_notify_on_price_set() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+
+		let coin_infos = vec![
+			((vec![1], vec![1]), CoinInfo { price: 1, ..Default::default() }),
+			((vec![2], vec![2]), CoinInfo { price: 2, ..Default::default() }),
+		];
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			coin_infos.clone()
+		));
+		assert_eq!(
+			PricesSet::get(),
+			vec![(AssetId::new(vec![1], vec![1]), 1), (AssetId::new(vec![2], vec![2]), 2)]
+		);
+
+		// Only the coin info whose price changed is passed on and charged for
+		let mut coin_infos = coin_infos;
+		coin_infos[1].1.price = 3;
+		let post_info =
+			DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos).unwrap();
+		assert_eq!(
+			PricesSet::get(),
+			vec![
+				(AssetId::new(vec![1], vec![1]), 1),
+				(AssetId::new(vec![2], vec![2]), 2),
+				(AssetId::new(vec![2], vec![2]), 3)
+			]
 		);
+		assert_eq!(
+			post_info.actual_weight,
+			Some(
+				<() as WeightInfo>::set_updated_coin_infos(2)
+					.saturating_add(RecordPricesSet::on_price_set_weight())
+			)
+		);
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_emit_an_event_per_changed_price() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		let coin_info = |price, last_update_timestamp| CoinInfo {
+			price,
+			last_update_timestamp,
+			..Default::default()
+		};
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			vec![((vec![1], vec![1]), coin_info(1, 10)), ((vec![2], vec![2]), coin_info(2, 10))],
+		));
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			vec![((vec![1], vec![1]), coin_info(1, 10)), ((vec![2], vec![2]), coin_info(3, 20))],
+		));
+
+		let updates: Vec<(Vec<u8>, u128, u64)> = System::events()
+			.into_iter()
+			.filter_map(|record| match record.event {
+				RuntimeEvent::DOracle(Event::UpdatedPrice { symbol, price, timestamp, .. }) =>
+					Some((symbol, price, timestamp)),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(updates, vec![(vec![1], 1, 10), (vec![2], 2, 10), (vec![2], 3, 20)]);
+	})
+}
+
+#[test]
+fn genesis_config_should_seed_feeders_currencies_and_coin_infos() {
+	let btc_info = CoinInfo { price: 9, last_update_timestamp: 9, ..Default::default() };
+	let genesis = crate::GenesisConfig::<Test>::default()
+		.with_authorized_account(ALICE)
+		.with_supported_currency(b"Bitcoin".to_vec(), b"BTC".to_vec())
+		.with_batching_api(BATCHING_API.as_bytes().to_vec())
+		.with_coin_info(b"Bitcoin".to_vec(), b"BTC".to_vec(), btc_info.clone());
+
+	new_test_ext_with(genesis).execute_with(|| {
+		assert!(DOracle::is_authorized(&ALICE));
+		assert!(<SupportedCurrencies<Test>>::contains_key(AssetId::new(
+			b"Bitcoin".to_vec(),
+			b"BTC".to_vec()
+		)));
+		assert_eq!(DOracle::batching_api(), Some(BATCHING_API.as_bytes().to_vec()));
+		assert_eq!(DOracle::get_coin_info(b"Bitcoin".to_vec(), b"BTC".to_vec()), Ok(btc_info));
+	})
+}
+
+#[test]
+fn genesis_config_should_round_trip_through_a_chain_spec() {
+	let btc_info = CoinInfo {
+		symbol: b"BTC".to_vec(),
+		name: b"Bitcoin".to_vec(),
+		blockchain: b"Bitcoin".to_vec(),
+		price: 9,
+		..Default::default()
+	};
+	let genesis = crate::GenesisConfig::<Test>::default()
+		.with_authorized_account(ALICE)
+		.with_coin_info(b"Bitcoin".to_vec(), b"BTC".to_vec(), btc_info);
+
+	let json = serde_json::to_string(&genesis).unwrap();
+	assert!(json.contains(r#""symbol":"BTC""#));
+	let decoded: crate::GenesisConfig<Test> = serde_json::from_str(&json).unwrap();
+	assert_eq!(decoded.authorized_accounts, genesis.authorized_accounts);
+	assert_eq!(decoded.coin_infos_map, genesis.coin_infos_map);
+}
+
+#[test]
+fn add_currency_should_reject_too_long_keys() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+
+		assert_noop!(
+			DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![1; 33], vec![1]),
+			Error::<Test>::BlockchainTooLong
+		);
+		assert_noop!(
+			DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![1], vec![1; 33]),
+			Error::<Test>::SymbolTooLong
+		);
+		assert_ok!(DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![1; 32], vec![1; 32]));
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_reject_too_long_keys() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+
+		assert_noop!(
+			DOracle::set_updated_coin_infos(
+				RuntimeOrigin::signed(ALICE),
+				vec![
+					((vec![1], vec![1]), CoinInfo::default()),
+					((vec![1], vec![1; 33]), CoinInfo::default())
+				]
+			),
+			Error::<Test>::SymbolTooLong
+		);
+		assert_eq!(<CoinInfosMap<Test>>::contains_key(AssetId::new(vec![1], vec![1])), false);
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_reject_too_large_batches() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		let coin_infos = |n: u8| {
+			let coin_info = CoinInfo { price: 9, ..Default::default() };
+			(0..n).map(|i| ((vec![1], vec![i]), coin_info.clone())).collect::<Vec<_>>()
+		};
+
+		let err = DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos(17))
+			.unwrap_err();
+		assert_eq!(err.error, Error::<Test>::TooManyCoinInfos.into());
+		assert_eq!(
+			err.post_info.actual_weight,
+			Some(<() as WeightInfo>::set_updated_coin_infos(0))
+		);
+		assert_eq!(<CoinInfosMap<Test>>::iter().count(), 0);
+
+		let post_info =
+			DOracle::set_updated_coin_infos(RuntimeOrigin::signed(ALICE), coin_infos(2)).unwrap();
+		assert_eq!(
+			post_info.actual_weight,
+			Some(
+				<() as WeightInfo>::set_updated_coin_infos(2)
+					.saturating_add(RecordPricesSet::on_price_set_weight().saturating_mul(2))
+			)
+		);
+		assert_eq!(post_info.pays_fee, Pays::No);
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_set_the_median_once_the_quorum_is_reached() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		FeederQuorum::set(3);
+		for id in 1..=4 {
+			<AuthorizedAccounts<Test>>::insert(get_account_id(id), ());
+		}
+		let submit = |id: u8, price: u128| {
+			let coin_info = CoinInfo { price, ..Default::default() };
+			assert_ok!(DOracle::set_updated_coin_infos(
+				RuntimeOrigin::signed(get_account_id(id)),
+				vec![((vec![1], vec![1]), coin_info)],
+			));
+		};
+		let price = || <CoinInfosMap<Test>>::get(AssetId::new(vec![1], vec![1])).map(|c| c.price);
+
+		submit(1, 100);
+		// A feeder submitting again replaces its submission
+		submit(1, 130);
+		submit(2, 120);
+		assert_eq!(price(), None);
+
+		submit(3, 9000);
+		assert_eq!(price(), Some(130));
+		assert_eq!(
+			<PendingSubmissions<Test>>::iter_prefix(AssetId::new(vec![1], vec![1])).count(),
+			0
+		);
+
+		// Submissions older than the window don't count
+		submit(1, 200);
+		submit(2, 210);
+		System::set_block_number(11);
+		submit(3, 220);
+		assert_eq!(price(), Some(130));
+		System::set_block_number(12);
+		submit(1, 230);
+		submit(4, 240);
+		assert_eq!(price(), Some(230));
+	})
+}
+
+#[test]
+fn set_updated_coin_infos_should_reject_too_long_strings() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		let coin_info = CoinInfo { name: vec![1; 65], price: 9, ..Default::default() };
+
+		assert_noop!(
+			DOracle::set_updated_coin_infos(
+				RuntimeOrigin::signed(ALICE),
+				vec![((vec![1], vec![1]), coin_info)],
+			),
+			Error::<Test>::CoinInfoTooLong
+		);
+	})
+}
+
+#[test]
+fn migration_should_bound_stored_coin_infos() {
+	use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(0).put::<DOracle>();
+		let put = |asset_id: &AssetId, coin_info: CoinInfo| {
+			frame_support::storage::unhashed::put(
+				&<CoinInfosMap<Test>>::hashed_key_for(asset_id),
+				&coin_info,
+			)
+		};
+		let (long_name, long_symbol, short) = (
+			AssetId::new(vec![1], vec![1]),
+			AssetId::new(vec![2], vec![2; 100]),
+			AssetId::new(vec![3], vec![3]),
+		);
+		put(&long_name, CoinInfo { name: vec![1; 100], price: 9, ..Default::default() });
+		put(&long_symbol, CoinInfo { symbol: vec![2; 100], price: 9, ..Default::default() });
+		put(&short, CoinInfo { symbol: vec![3], price: 9, ..Default::default() });
+
+		migrations::v1::MigrateToBoundedCoinInfos::<Test>::on_runtime_upgrade();
+
+		assert_eq!(DOracle::on_chain_storage_version(), 1);
+		let migrated = <CoinInfosMap<Test>>::get(&long_name).unwrap();
+		assert_eq!(migrated.name.into_inner(), vec![1; 64]);
+		assert_eq!(migrated.price, 9);
+		// A cut off symbol would no longer match the key
+		assert!(!<CoinInfosMap<Test>>::contains_key(&long_symbol));
+		assert_eq!(<CoinInfosMap<Test>>::get(&short).map(|c| c.symbol.into_inner()), Some(vec![3]));
 	})
 }
 
@@ -183,6 +550,7 @@ fn get_coin_info_should_work() {
 			((vec![1, 2, 3], vec![1, 2, 3]), CoinInfo::default()),
 			((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone()),
 		];
+		<ZeroPriceAllowed<Test>>::insert(AssetId::new(vec![1, 2, 3], vec![1, 2, 3]), ());
 
 		let _test1 = DOracle::set_updated_coin_infos(
 			RuntimeOrigin::signed(get_account_id(1)),
@@ -213,6 +581,7 @@ fn get_coin_info_should_return_error() {
 			((vec![1, 2, 3], vec![1, 2, 3]), CoinInfo::default()),
 			((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone()),
 		];
+		<ZeroPriceAllowed<Test>>::insert(AssetId::new(vec![1, 2, 3], vec![1, 2, 3]), ());
 
 		let _test1 = DOracle::set_updated_coin_infos(
 			RuntimeOrigin::signed(get_account_id(1)),
@@ -242,6 +611,7 @@ fn get_value_in_coin_info_should_work() {
 			((vec![1, 2, 3], vec![1, 2, 3]), CoinInfo::default()),
 			((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone()),
 		];
+		<ZeroPriceAllowed<Test>>::insert(AssetId::new(vec![1, 2, 3], vec![1, 2, 3]), ());
 
 		let _test1 = DOracle::set_updated_coin_infos(
 			RuntimeOrigin::signed(get_account_id(1)),
@@ -273,3 +643,730 @@ fn get_value_in_coin_info_should_return_error() {
 		assert_err!(fail_coin_info, Error::<Test>::NoCoinInfoAvailable);
 	})
 }
+
+#[test]
+fn ensure_price_fresh_should_reject_old_coin_infos() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(get_account_id(1), ());
+		let example_info = CoinInfo { last_update_timestamp: 1000, price: 9, ..Default::default() };
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(get_account_id(1)),
+			vec![((vec![2, 2, 2], vec![2, 2, 2]), example_info.clone())],
+		));
+		fn price(max_age: u64) -> Result<u128, DispatchError> {
+			let coin_info = ensure_price_fresh!(DOracle, vec![2, 2, 2], vec![2, 2, 2], max_age);
+			Ok(coin_info.price)
+		}
+
+		Now::set(1060);
+		assert_eq!(price(60), Ok(9));
+		assert_err!(price(59), Error::<Test>::PriceTooOld);
+		assert_err!(
+			DOracle::get_fresh_coin_info(vec![1, 2, 3], vec![1, 2, 3], 60),
+			Error::<Test>::NoCoinInfoAvailable
+		);
+
+		Now::set(900);
+		assert_eq!(price(0), Ok(9));
+	})
+}
+
+#[test]
+fn on_finalize_should_emit_price_stale_once() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		StalenessThreshold::set(60);
+		<AuthorizedAccounts<Test>>::insert(get_account_id(1), ());
+		let set_price = |last_update_timestamp| {
+			let coin_info = CoinInfo { last_update_timestamp, price: 9, ..Default::default() };
+			assert_ok!(DOracle::set_updated_coin_infos(
+				RuntimeOrigin::signed(get_account_id(1)),
+				vec![((vec![2, 2, 2], vec![2, 2, 2]), coin_info)],
+			));
+		};
+		let stale_events = || {
+			System::events()
+				.into_iter()
+				.filter(|record| {
+					matches!(record.event, RuntimeEvent::DOracle(Event::PriceStale(..)))
+				})
+				.count()
+		};
+		set_price(1000);
+
+		Now::set(1060);
+		DOracle::on_finalize(1);
+		assert_eq!(stale_events(), 0);
+		assert!(!DOracle::is_price_stale(vec![2, 2, 2], vec![2, 2, 2]));
+
+		Now::set(1061);
+		DOracle::on_finalize(2);
+		DOracle::on_finalize(3);
+		assert_eq!(stale_events(), 1);
+		System::assert_has_event(RuntimeEvent::DOracle(Event::PriceStale(
+			vec![2, 2, 2],
+			vec![2, 2, 2],
+			1000,
+		)));
+		assert!(DOracle::is_price_stale(vec![2, 2, 2], vec![2, 2, 2]));
+		assert!(DOracle::is_price_stale(vec![1, 2, 3], vec![1, 2, 3]));
+
+		// Setting the price again rearms the event
+		set_price(1061);
+		assert!(!<StaleCoinInfos<Test>>::contains_key(AssetId::new(vec![2, 2, 2], vec![2, 2, 2])));
+		Now::set(1122);
+		DOracle::on_finalize(4);
+		assert_eq!(stale_events(), 2);
+	})
+}
+
+#[test]
+fn on_finalize_should_check_a_bounded_number_of_coin_infos() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		StalenessThreshold::set(60);
+		Now::set(1061);
+		for symbol in 0..=MAX_COIN_INFOS_PER_STALENESS_CHECK {
+			let coin_info = BoundedCoinInfo { last_update_timestamp: 1000, ..Default::default() };
+			<CoinInfosMap<Test>>::insert(AssetId::new(vec![2], symbol.encode()), coin_info);
+		}
+		let flagged = || <StaleCoinInfos<Test>>::iter().count() as u32;
+
+		assert!(DOracle::on_initialize(1).ref_time() > 0);
+		DOracle::on_finalize(1);
+		assert_eq!(flagged(), MAX_COIN_INFOS_PER_STALENESS_CHECK);
+		assert!(<StalenessCursor<Test>>::get().is_some());
+
+		DOracle::on_finalize(2);
+		assert!(<StalenessCursor<Test>>::get().is_none());
+		assert_eq!(flagged(), MAX_COIN_INFOS_PER_STALENESS_CHECK + 1);
+	})
+}
+
+#[test]
+fn remove_stale_coin_infos_should_continue_where_it_stopped() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		<AuthorizedAccounts<Test>>::insert(get_account_id(1), ());
+		for (symbol, last_update_timestamp) in [(1, 900), (2, 1000), (3, 900)] {
+			let coin_info = BoundedCoinInfo { last_update_timestamp, ..Default::default() };
+			<CoinInfosMap<Test>>::insert(AssetId::new(vec![2], vec![symbol]), coin_info);
+		}
+		let remove =
+			|| DOracle::remove_stale_coin_infos(RuntimeOrigin::signed(get_account_id(1)), 1000, 2);
+
+		assert_noop!(
+			DOracle::remove_stale_coin_infos(RuntimeOrigin::signed(get_account_id(2)), 1000, 2),
+			DispatchError::BadOrigin
+		);
+		assert_ok!(remove());
+		assert!(<RemovalCursor<Test>>::get().is_some());
+		assert_ok!(remove());
+		assert!(<RemovalCursor<Test>>::get().is_none());
+		let removals: Vec<(u32, bool)> = System::events()
+			.into_iter()
+			.filter_map(|record| match record.event {
+				RuntimeEvent::DOracle(Event::StaleCoinInfosRemoved(removed, complete)) =>
+					Some((removed, complete)),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(removals.iter().map(|(removed, _)| removed).sum::<u32>(), 2);
+		assert_eq!(
+			removals.iter().map(|(_, complete)| *complete).collect::<Vec<_>>(),
+			[false, true]
+		);
+
+		let remaining: Vec<AssetId> = <CoinInfosMap<Test>>::iter_keys().collect();
+		assert_eq!(remaining, vec![AssetId::new(vec![2], vec![2])]);
+	})
+}
+
+#[test]
+fn get_value_should_convert_to_other_decimals() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		let coin_info = CoinInfo { price: 1_234_567_890_123, ..Default::default() };
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			vec![((vec![1], vec![1]), coin_info)],
+		));
+		let value_with_decimals =
+			|decimals| DOracle::get_value_with_decimals(vec![1], vec![1], decimals);
+
+		assert_eq!(
+			DOracle::get_native_value(vec![1], vec![1]),
+			Ok(PriceInfo { value: 12_345_678_901 })
+		);
+		assert_eq!(value_with_decimals(18), Ok(PriceInfo { value: 1_234_567_890_123_000_000 }));
+		assert_eq!(value_with_decimals(0), Ok(PriceInfo { value: 1 }));
+		assert_eq!(
+			value_with_decimals(60),
+			Err(DispatchError::Arithmetic(ArithmeticError::Overflow))
+		);
+	})
+}
+
+#[test]
+fn rescale_should_round_down_and_detect_overflows() {
+	assert_eq!(rescale(1_999, 3, 0), Some(1));
+	assert_eq!(rescale(1, 0, 38), Some(10u128.pow(38)));
+	assert_eq!(rescale(u128::MAX, 60, 0), Some(0));
+	assert_eq!(rescale(4, 0, 38), None);
+}
+
+#[test]
+fn get_exchange_rate_should_derive_cross_rate_of_fresh_prices() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(get_account_id(1), ());
+		let coin_info = |price, last_update_timestamp| CoinInfo {
+			price,
+			last_update_timestamp,
+			..Default::default()
+		};
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(get_account_id(1)),
+			vec![
+				((b"Polkadot".to_vec(), b"DOT".to_vec()), coin_info(5 * PRICE_UNIT, 1000)),
+				((b"FIAT".to_vec(), b"BRL-USD".to_vec()), coin_info(PRICE_UNIT / 5, 1000)),
+				((b"Kusama".to_vec(), b"KSM".to_vec()), coin_info(20 * PRICE_UNIT, 900)),
+				((b"Stellar".to_vec(), b"XLM".to_vec()), coin_info(0, 1000)),
+				((b"Big".to_vec(), b"BIG".to_vec()), coin_info(u128::MAX, 1000)),
+			],
+		));
+		let dot = || (b"Polkadot".to_vec(), b"DOT".to_vec());
+
+		Now::set(1060);
+		assert_eq!(
+			DOracle::get_exchange_rate(dot(), (b"FIAT".to_vec(), b"BRL-USD".to_vec()), 60),
+			Ok(PriceInfo { value: 25 * PRICE_UNIT })
+		);
+		assert_err!(
+			DOracle::get_exchange_rate(dot(), (b"Kusama".to_vec(), b"KSM".to_vec()), 60),
+			Error::<Test>::PriceTooOld
+		);
+		assert_eq!(
+			DOracle::get_exchange_rate(dot(), (b"Kusama".to_vec(), b"KSM".to_vec()), 160),
+			Ok(PriceInfo { value: PRICE_UNIT / 4 })
+		);
+		assert_err!(
+			DOracle::get_exchange_rate(dot(), (b"Stellar".to_vec(), b"XLM".to_vec()), 60),
+			Error::<Test>::ZeroPrice
+		);
+		assert_err!(
+			DOracle::get_exchange_rate(
+				(b"Big".to_vec(), b"BIG".to_vec()),
+				(b"FIAT".to_vec(), b"BRL-USD".to_vec()),
+				60
+			),
+			Error::<Test>::ExchangeRateOverflow
+		);
+		assert_err!(
+			DOracle::get_exchange_rate(dot(), (b"Polkadot".to_vec(), b"XYZ".to_vec()), 60),
+			Error::<Test>::NoCoinInfoAvailable
+		);
+	})
+}
+
+/// Oracle implementing only the required methods, with a price of two for every asset
+struct FixedOracle;
+
+impl DiaOracle for FixedOracle {
+	fn get_coin_info(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<CoinInfo, DispatchError> {
+		Ok(CoinInfo { blockchain, symbol, price: 2 * PRICE_UNIT, ..Default::default() })
+	}
+
+	fn get_value(blockchain: Vec<u8>, symbol: Vec<u8>) -> Result<PriceInfo, DispatchError> {
+		FixedOracle::get_coin_info(blockchain, symbol).map(|info| PriceInfo { value: info.price })
+	}
+}
+
+#[test]
+fn dia_oracle_should_provide_the_other_methods() {
+	assert!(FixedOracle::is_available(vec![1], vec![1]));
+	assert!(!FixedOracle::is_price_stale(vec![1], vec![1]));
+	assert_eq!(
+		FixedOracle::get_native_value(vec![1], vec![1]),
+		Ok(PriceInfo { value: 2 * PRICE_UNIT })
+	);
+	// Without a clock no price is fresh
+	assert_err!(
+		FixedOracle::get_fresh_coin_info(vec![1], vec![1], 60),
+		DispatchError::Other("PriceTooOld")
+	);
+	assert_err!(
+		FixedOracle::get_exchange_rate((vec![1], vec![1]), (vec![2], vec![2]), 60),
+		DispatchError::Other("PriceTooOld")
+	);
+	assert_eq!(exchange_rate(2 * PRICE_UNIT, 4 * PRICE_UNIT), Some(PRICE_UNIT / 2));
+	assert_eq!(exchange_rate(1, 0), None);
+	assert_eq!(exchange_rate(u128::MAX, 1), None);
+}
+
+#[test]
+fn hmac_sha256_should_match_rfc_4231() {
+	let mac = signing::hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+
+	assert_eq!(
+		mac,
+		[
+			0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+			0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+			0x64, 0xec, 0x38, 0x43,
+		]
+	);
+}
+
+#[test]
+fn sign_should_sign_timestamp_and_path() {
+	assert_eq!(signing::url_path("http://0.0.0.0:8070/currencies/"), "/currencies/");
+	assert_eq!(signing::url_path("https://example.com"), "/");
+
+	let (timestamp, signature) = signing::sign(b"secret", 1700000000, "/currencies/");
+
+	assert_eq!(timestamp, b"1700000000".to_vec());
+	// Same vector is checked on the batching server side
+	assert_eq!(
+		signature,
+		b"12cdbc46add9bc26f32f3ddf831f673da6960abc2cbdbceccf6ec59e0d830c21".to_vec()
+	);
+}
+
+#[test]
+fn merkle_root_should_carry_up_unpaired_nodes() {
+	let leaves = [[1u8; 32], [2u8; 32], [3u8; 32]];
+	let node = |left: [u8; 32], right: [u8; 32]| {
+		sp_core::hashing::blake2_256(&[&[merkle::NODE_PREFIX][..], &left, &right].concat())
+	};
+
+	assert_eq!(merkle::merkle_root(&[]), [0u8; 32]);
+	assert_eq!(merkle::merkle_root(&leaves[..1]), [1u8; 32]);
+	assert_eq!(merkle::merkle_root(&leaves), node(node([1u8; 32], [2u8; 32]), [3u8; 32]));
+}
+
+#[test]
+fn set_updated_coin_infos_should_publish_price_root() {
+	new_test_ext().execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		System::set_block_number(1);
+
+		let btc_info: CoinInfo = CoinInfo {
+			symbol: b"BTC".to_vec(),
+			name: b"Bitcoin".to_vec(),
+			blockchain: b"Bitcoin".to_vec(),
+			supply: 9,
+			last_update_timestamp: 9,
+			price: 9,
+		};
+		let eth_info: CoinInfo = CoinInfo {
+			symbol: b"ETH".to_vec(),
+			blockchain: b"Ethereum".to_vec(),
+			..Default::default()
+		};
+		let mxn_info: CoinInfo = CoinInfo {
+			symbol: b"MXN-USD".to_vec(),
+			name: b"MXNUSD=X".to_vec(),
+			blockchain: b"FIAT".to_vec(),
+			supply: 0,
+			last_update_timestamp: 1700000000,
+			price: 53712327000,
+		};
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			vec![
+				((b"FIAT".to_vec(), b"MXN-USD".to_vec()), mxn_info),
+				((b"Bitcoin".to_vec(), b"BTC".to_vec()), btc_info),
+			]
+		));
+		assert_ok!(DOracle::set_updated_coin_infos(
+			RuntimeOrigin::signed(ALICE),
+			vec![((b"Ethereum".to_vec(), b"ETH".to_vec()), eth_info)]
+		));
+
+		DOracle::on_finalize(1);
+
+		// Same vector is checked on the batching server side
+		let root = [
+			0x30, 0xe0, 0xde, 0x58, 0x49, 0x26, 0x19, 0x60, 0x5a, 0x24, 0xaf, 0x7f, 0x8a, 0xb8,
+			0xd3, 0x01, 0xad, 0x2d, 0xb7, 0x56, 0x31, 0x27, 0x74, 0xb2, 0x8e, 0xbd, 0x15, 0x57,
+			0x5c, 0x7a, 0xe0, 0x34,
+		];
+		assert_eq!(DOracle::price_root(), Some((1, root)));
+		assert!(System::digest()
+			.logs
+			.contains(&DigestItem::Other(merkle::price_root_digest(&root))));
+		assert!(<UpdatedCoinInfoLeaves<Test>>::get().is_empty());
+	})
+}
+
+const BATCHING_API: &str = "http://localhost:8070/currencies";
+const BTC_RESPONSE: &[u8] = br#"[{"symbol":"BTC","name":"Bitcoin","blockchain":"Bitcoin","supply":9,"lastUpdateTimestamp":9,"price":9}]"#;
+
+/// Test externalities with the offchain worker extensions and a feeder key in the keystore
+fn new_offchain_test_ext(
+) -> (sp_io::TestExternalities, Public, Arc<RwLock<OffchainState>>, Arc<RwLock<PoolState>>) {
+	let (offchain, offchain_state) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let keystore = MemoryKeystore::new();
+	let feeder = keystore.sr25519_generate_new(crypto::KEY_TYPE, Some("//Feeder")).unwrap();
+
+	let mut ext = new_test_ext();
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+	ext.register_extension(KeystoreExt::new(keystore));
+	ext.execute_with(|| {
+		<SupportedCurrencies<Test>>::insert(AssetId::new(b"Bitcoin".to_vec(), b"BTC".to_vec()), ());
+		<BatchingApi<Test>>::put(BATCHING_API.as_bytes().to_vec());
+	});
+	(ext, feeder, offchain_state, pool_state)
+}
+
+fn expect_batching_request(state: &RwLock<OffchainState>, response: Vec<u8>) {
+	expect_batching_request_to(state, BATCHING_API, response)
+}
+
+fn expect_batching_request_to(state: &RwLock<OffchainState>, api: &str, response: Vec<u8>) {
+	state.write().expect_request(PendingRequest {
+		method: "POST".into(),
+		uri: api.into(),
+		headers: vec![("content-type".into(), "application/json".into())],
+		body: br#"[{"blockchain":"Bitcoin","symbol":"BTC"}]"#.to_vec(),
+		response: Some(response),
+		sent: true,
+		..Default::default()
+	});
+}
+
+#[test]
+fn offchain_worker_should_submit_and_apply_prices() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, BTC_RESPONSE.to_vec());
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		assert_ok!(DOracle::update_prices());
+
+		let tx = pool_state.write().transactions.pop().expect("a transaction was submitted");
+		assert!(pool_state.read().transactions.is_empty());
+		let tx = TestXt::<RuntimeCall, ()>::decode(&mut &tx[..]).unwrap();
+		let btc_info = CoinInfo {
+			symbol: b"BTC".to_vec(),
+			name: b"Bitcoin".to_vec(),
+			blockchain: b"Bitcoin".to_vec(),
+			supply: 9,
+			last_update_timestamp: 9,
+			price: 9,
+		};
+		assert_eq!(tx.signature, Some((0, ())));
+		assert_eq!(
+			tx.call,
+			RuntimeCall::DOracle(crate::Call::set_updated_coin_infos {
+				coin_infos: vec![((b"Bitcoin".to_vec(), b"BTC".to_vec()), btc_info.clone())],
+			})
+		);
+
+		assert_ok!(tx.call.dispatch(RuntimeOrigin::signed(feeder)));
+		assert_eq!(DOracle::get_coin_info(b"Bitcoin".to_vec(), b"BTC".to_vec()), Ok(btc_info));
+	})
+}
+
+#[test]
+fn offchain_worker_should_keep_the_casing_of_supported_currencies() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(
+		&offchain_state,
+		br#"[{"symbol":"btc","name":"Bitcoin","blockchain":"BITCOIN","supply":9,"lastUpdateTimestamp":9,"price":9},{"symbol":"ETH","name":"Ethereum","blockchain":"Ethereum","supply":9,"lastUpdateTimestamp":9,"price":9}]"#.to_vec(),
+	);
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		assert_ok!(DOracle::update_prices());
+
+		let tx = pool_state.write().transactions.pop().expect("a transaction was submitted");
+		let tx = TestXt::<RuntimeCall, ()>::decode(&mut &tx[..]).unwrap();
+		let btc_info = CoinInfo {
+			symbol: b"BTC".to_vec(),
+			name: b"Bitcoin".to_vec(),
+			blockchain: b"Bitcoin".to_vec(),
+			supply: 9,
+			last_update_timestamp: 9,
+			price: 9,
+		};
+		// Unrequested coin infos are dropped
+		assert_eq!(
+			tx.call,
+			RuntimeCall::DOracle(crate::Call::set_updated_coin_infos {
+				coin_infos: vec![((b"Bitcoin".to_vec(), b"BTC".to_vec()), btc_info)],
+			})
+		);
+	})
+}
+
+#[test]
+fn offchain_worker_should_skip_stale_coin_infos() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, BTC_RESPONSE.to_vec());
+	expect_batching_request(&offchain_state, BTC_RESPONSE.to_vec());
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+
+		offchain_state.write().timestamp = Timestamp::from_unix_millis((9 + 3601) * 1000);
+		assert_ok!(DOracle::update_prices());
+		assert!(pool_state.read().transactions.is_empty());
+
+		offchain_state.write().timestamp = Timestamp::from_unix_millis((9 + 3600) * 1000);
+		assert_ok!(DOracle::update_prices());
+		assert_eq!(pool_state.read().transactions.len(), 1);
+	})
+}
+
+#[test]
+fn offchain_worker_should_skip_coin_infos_already_on_chain() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, BTC_RESPONSE.to_vec());
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		<CoinInfosMap<Test>>::insert(
+			AssetId::new(b"Bitcoin".to_vec(), b"BTC".to_vec()),
+			BoundedCoinInfo::truncate_from(CoinInfo {
+				symbol: b"BTC".to_vec(),
+				name: b"Bitcoin".to_vec(),
+				blockchain: b"Bitcoin".to_vec(),
+				supply: 9,
+				last_update_timestamp: 9,
+				price: 9,
+			}),
+		);
+		assert_ok!(DOracle::update_prices());
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_submit_deviating_or_outdated_prices() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	let btc_response = |price: u128, timestamp: u64| {
+		format!(
+			r#"[{{"symbol":"BTC","name":"Bitcoin","blockchain":"Bitcoin","supply":9,"lastUpdateTimestamp":{},"price":{}}}]"#,
+			timestamp, price
+		)
+		.into_bytes()
+	};
+	expect_batching_request(&offchain_state, btc_response(1_005, 9));
+	expect_batching_request(&offchain_state, btc_response(1_020, 9));
+	expect_batching_request(&offchain_state, btc_response(1_005, 3_605));
+
+	ext.execute_with(|| {
+		UpdateDeviationThreshold::set(Permill::from_percent(1));
+		UpdateHeartbeat::set(3_600);
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		<CoinInfosMap<Test>>::insert(
+			AssetId::new(b"Bitcoin".to_vec(), b"BTC".to_vec()),
+			BoundedCoinInfo::truncate_from(CoinInfo {
+				symbol: b"BTC".to_vec(),
+				name: b"Bitcoin".to_vec(),
+				blockchain: b"Bitcoin".to_vec(),
+				supply: 9,
+				last_update_timestamp: 5,
+				price: 1_000,
+			}),
+		);
+
+		// Moved by half a percent within the heartbeat
+		offchain_state.write().timestamp = Timestamp::from_unix_millis(10 * 1000);
+		assert_ok!(DOracle::update_prices());
+		assert!(pool_state.read().transactions.is_empty());
+
+		// Moved by two percent
+		assert_ok!(DOracle::update_prices());
+		assert_eq!(pool_state.read().transactions.len(), 1);
+
+		// The price on chain is older than the heartbeat
+		offchain_state.write().timestamp = Timestamp::from_unix_millis(3_605 * 1000);
+		assert_ok!(DOracle::update_prices());
+		assert_eq!(pool_state.read().transactions.len(), 2);
+	})
+}
+
+#[test]
+fn offchain_worker_should_rotate_batches_over_all_coin_infos() {
+	let (mut ext, _, _, _) = new_offchain_test_ext();
+
+	ext.execute_with(|| {
+		let batch_size = <Test as Config>::MaxCoinsPerBatch::get() as u8;
+		let prices: Vec<_> = (0..batch_size + 4)
+			.rev()
+			.map(|symbol| ((b"FIAT".to_vec(), vec![symbol]), CoinInfo::default()))
+			.collect();
+		let next_batch = || {
+			DOracle::next_batch(prices.clone(), batch_size as usize)
+				.into_iter()
+				.map(|((_, symbol), _)| symbol[0])
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(next_batch(), (0..batch_size).collect::<Vec<_>>());
+		// The assets left out are submitted by the next run, followed by the first ones again
+		assert_eq!(
+			next_batch(),
+			(batch_size..batch_size + 4).chain(0..batch_size - 4).collect::<Vec<_>>()
+		);
+		assert_eq!(
+			next_batch(),
+			(batch_size - 4..batch_size + 4).chain(0..batch_size - 8).collect::<Vec<_>>()
+		);
+	})
+}
+
+#[test]
+fn offchain_worker_should_reject_malformed_json() {
+	let (mut ext, _, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, br#"[{"symbol":"BTC","price":"nine"}]"#.to_vec());
+
+	ext.execute_with(|| {
+		assert_err!(DOracle::update_prices(), Error::<Test>::DeserializeError);
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_handle_empty_responses() {
+	let (mut ext, _, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, vec![]);
+	expect_batching_request(&offchain_state, b"[]".to_vec());
+
+	ext.execute_with(|| {
+		assert_err!(DOracle::update_prices(), Error::<Test>::DeserializeError);
+		assert_ok!(DOracle::update_prices());
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_reject_oversized_responses() {
+	let (mut ext, _, offchain_state, pool_state) = new_offchain_test_ext();
+	let mut response = BTC_RESPONSE.to_vec();
+	response.resize(MAX_RESPONSE_BODY_SIZE + 1, b' ');
+	expect_batching_request(&offchain_state, response);
+
+	ext.execute_with(|| {
+		assert_err!(DOracle::update_prices(), Error::<Test>::ResponseTooLarge);
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_fall_back_to_the_next_batching_api() {
+	const FALLBACK_API: &str = "http://localhost:8071/currencies";
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, b"502 Bad Gateway".to_vec());
+	expect_batching_request_to(&offchain_state, FALLBACK_API, BTC_RESPONSE.to_vec());
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(ALICE, ());
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		assert_noop!(
+			DOracle::set_fallback_batching_apis(
+				RuntimeOrigin::signed(ALICE),
+				vec![FALLBACK_API.as_bytes().to_vec(); MAX_FALLBACK_BATCHING_APIS as usize + 1]
+			),
+			Error::<Test>::TooManyBatchingApis
+		);
+		assert_ok!(DOracle::set_fallback_batching_apis(
+			RuntimeOrigin::signed(ALICE),
+			vec![FALLBACK_API.as_bytes().to_vec()]
+		));
+
+		assert_ok!(DOracle::update_prices());
+		assert_eq!(pool_state.read().transactions.len(), 1);
+		assert_eq!(
+			sp_io::offchain::local_storage_get(
+				StorageKind::PERSISTENT,
+				LAST_BATCHING_API_STORAGE_KEY
+			),
+			Some(FALLBACK_API.as_bytes().to_vec())
+		);
+	})
+}
+
+#[test]
+fn offchain_worker_should_not_request_without_supported_currencies() {
+	let (mut ext, _, _, pool_state) = new_offchain_test_ext();
+
+	ext.execute_with(|| {
+		<SupportedCurrencies<Test>>::remove(AssetId::new(b"Bitcoin".to_vec(), b"BTC".to_vec()));
+
+		// No request is expected, an unexpected one would panic
+		assert_ok!(DOracle::update_prices());
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_not_submit_without_authorized_key() {
+	let (mut ext, feeder, offchain_state, pool_state) = new_offchain_test_ext();
+	expect_batching_request(&offchain_state, BTC_RESPONSE.to_vec());
+
+	ext.execute_with(|| {
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		<AccountExpiries<Test>>::insert(feeder, 1);
+
+		assert_err!(DOracle::update_prices(), Error::<Test>::NoAuthorizedFeederKey);
+		assert!(pool_state.read().transactions.is_empty());
+	})
+}
+
+#[test]
+fn offchain_worker_should_pick_the_key_expiring_last() {
+	let (mut ext, feeder, _, _) = new_offchain_test_ext();
+
+	ext.execute_with(|| {
+		let next_feeder: Public =
+			crypto::Public::generate_pair(Some(b"//NextFeeder".to_vec())).into();
+		<AuthorizedAccounts<Test>>::insert(feeder, ());
+		assert_eq!(DOracle::feeder_key(), Some(feeder));
+
+		// Rotation: the new key is authorized while the old one is still valid
+		assert_ok!(DOracle::authorize_account(RuntimeOrigin::root(), next_feeder));
+		assert_ok!(DOracle::set_account_expiry(RuntimeOrigin::root(), feeder, Some(10)));
+		assert_eq!(DOracle::feeder_key(), Some(next_feeder));
+
+		assert_ok!(DOracle::set_account_expiry(RuntimeOrigin::root(), next_feeder, Some(5)));
+		assert_eq!(DOracle::feeder_key(), Some(feeder));
+	})
+}
+
+#[test]
+fn expired_accounts_should_not_be_authorized() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			DOracle::set_account_expiry(RuntimeOrigin::root(), ALICE, Some(3)),
+			Error::<Test>::ThisAccountIdIsNotAuthorized
+		);
+		assert_ok!(DOracle::authorize_account(RuntimeOrigin::root(), ALICE));
+		assert_ok!(DOracle::set_account_expiry(RuntimeOrigin::root(), ALICE, Some(3)));
+		assert_eq!(DOracle::account_expiry(ALICE), Some(3));
+
+		System::set_block_number(2);
+		assert_ok!(DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![1], vec![1]));
+
+		System::set_block_number(3);
+		assert!(!DOracle::is_authorized(&ALICE));
+		assert_noop!(
+			DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![2], vec![2]),
+			DispatchError::BadOrigin
+		);
+
+		// Authorizing again lifts the expiry
+		assert_ok!(DOracle::authorize_account(RuntimeOrigin::root(), ALICE));
+		assert_eq!(DOracle::account_expiry(ALICE), None);
+		assert_ok!(DOracle::add_currency(RuntimeOrigin::signed(ALICE), vec![2], vec![2]));
+
+		assert_ok!(DOracle::set_account_expiry(RuntimeOrigin::root(), ALICE, Some(3)));
+		assert_ok!(DOracle::deauthorize_account(RuntimeOrigin::root(), ALICE));
+		assert_eq!(DOracle::account_expiry(ALICE), None);
+	})
+}
@@ -1,29 +1,14 @@
-//! Autogenerated weights for `dia_oracle`
+//! Weights for `dia_oracle`
 //!
-//! THIS FILE WAS AUTO-GENERATED USING THE SUBSTRATE BENCHMARK CLI VERSION 4.0.0-dev
-//! DATE: 2021-11-03, STEPS: `20`, REPEAT: 10, LOW RANGE: `[]`, HIGH RANGE: `[]`
-//! EXECUTION: Some(Wasm), WASM-EXECUTION: Compiled, CHAIN: Some("dev"), DB CACHE: 128
-
-// Executed Command:
-// target/debug/node-template
-// benchmark
-// --chain
-// dev
-// --execution
-// wasm
-// --wasm-execution
-// compiled
-// --pallet
-// dia-oracle
-// --extrinsic
-// *
-// --steps
-// 20
-// --repeat
-// 10
-// --raw
-// --output
-// ./
+//! Only `add_currency`, `remove_currency`, `authorize_account`, `deauthorize_account`, their
+//! `_signed` variants, `set_batching_api` and the base weight of `set_updated_coin_infos` come
+//! from a run of the Substrate benchmark CLI 4.0.0-dev (DATE: 2021-11-03, STEPS: `20`,
+//! REPEAT: 10, CHAIN: Some("dev"), DB CACHE: 128). The weights marked as estimates were added
+//! without running the benchmarks of `benchmarking.rs` and are to be replaced by the output of
+//!
+//! target/release/node-template benchmark pallet --chain dev --execution wasm
+//! --wasm-execution compiled --pallet dia-oracle --extrinsic '*' --steps 20 --repeat 10
+//! --output pallets/dia-oracle/src/weights.rs
 
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
@@ -43,23 +28,29 @@ pub trait WeightInfo{
 	fn authorize_account_signed() -> Weight ;
 	fn deauthorize_account() -> Weight ;
 	fn deauthorize_account_signed() -> Weight ;
-	fn set_updated_coin_infos() -> Weight;
+	fn set_updated_coin_infos(n: u32, ) -> Weight;
 	fn set_batching_api() -> Weight;
+	fn set_fallback_batching_apis() -> Weight;
+	fn set_account_expiry() -> Weight;
+	fn set_zero_price_allowed() -> Weight;
+	fn remove_stale_coin_infos(n: u32) -> Weight;
 }
 pub struct DiaWeightInfo<T>(PhantomData<T>);
 impl<T: frame_system::Config> WeightInfo for DiaWeightInfo<T> {
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	// Storage: DiaOracle SupportedCurrencies (r:1 w:1)
 	fn add_currency() -> Weight {
 		Weight::from_ref_time(1_494_649_000)
-			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().reads(3))
 			.saturating_add(T::DbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	// Storage: DiaOracle SupportedCurrencies (r:1 w:0)
 	fn remove_currency() -> Weight {
 		Weight::from_ref_time(542_550_000)
-			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().reads(3))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:1)
 	fn authorize_account() -> Weight {
@@ -68,9 +59,10 @@ impl<T: frame_system::Config> WeightInfo for DiaWeightInfo<T> {
 			.saturating_add(T::DbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:2 w:1)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	fn authorize_account_signed() -> Weight {
 		Weight::from_ref_time(1_525_600_000)
-			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().reads(3))
 			.saturating_add(T::DbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
@@ -79,40 +71,94 @@ impl<T: frame_system::Config> WeightInfo for DiaWeightInfo<T> {
 			.saturating_add(T::DbWeight::get().reads(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:2 w:1)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	fn deauthorize_account_signed() -> Weight {
 		Weight::from_ref_time(1_513_398_000)
-			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().reads(3))
 			.saturating_add(T::DbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
-	// Storage: DiaOracle CoinInfosMap (r:0 w:1)
-	fn set_updated_coin_infos() -> Weight {
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle UpdatedCoinInfoLeaves (r:1 w:1)
+	// Storage: DiaOracle StaleCoinInfos (r:0 w:n)
+	// Storage: DiaOracle CoinInfosMap (r:n w:n)
+	// Storage: System Events (r:0 w:n)
+	fn set_updated_coin_infos(n: u32, ) -> Weight {
 		Weight::from_ref_time(1_241_248_000)
-			.saturating_add(T::DbWeight::get().reads(1))
+			// Estimate, not benchmarked
+			.saturating_add(Weight::from_ref_time(40_000_000).saturating_mul(n as u64))
+			.saturating_add(T::DbWeight::get().reads(3))
+			.saturating_add(T::DbWeight::get().reads((1 as u64).saturating_mul(n as u64)))
 			.saturating_add(T::DbWeight::get().writes(1))
+			.saturating_add(T::DbWeight::get().writes((3 as u64).saturating_mul(n as u64)))
 	}
 
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle BatchingApi (r:0 w:1)
 	fn set_batching_api() -> Weight {
 		Weight::from_ref_time(1_241_248_000)
-			.saturating_add(T::DbWeight::get().reads(1))
+			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle FallbackBatchingApis (r:0 w:1)
+	fn set_fallback_batching_apis() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:1)
+	fn set_account_expiry() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle ZeroPriceAllowed (r:0 w:1)
+	fn set_zero_price_allowed() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(T::DbWeight::get().reads(2))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle RemovalCursor (r:1 w:1)
+	// Storage: DiaOracle CoinInfosMap (r:n w:n)
+	// Storage: DiaOracle StaleCoinInfos (r:0 w:n)
+	fn remove_stale_coin_infos(n: u32, ) -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(Weight::from_ref_time(25_000_000).saturating_mul(n as u64))
+			.saturating_add(T::DbWeight::get().reads(3))
+			.saturating_add(T::DbWeight::get().reads((1 as u64).saturating_mul(n as u64)))
 			.saturating_add(T::DbWeight::get().writes(1))
+			.saturating_add(T::DbWeight::get().writes((2 as u64).saturating_mul(n as u64)))
 	}
 }
 
 
 impl WeightInfo for () {
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	// Storage: DiaOracle SupportedCurrencies (r:1 w:1)
 	fn add_currency() -> Weight {
 		Weight::from_ref_time(1_494_649_000)
-			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().reads(3))
 			.saturating_add(RocksDbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	// Storage: DiaOracle SupportedCurrencies (r:1 w:0)
 	fn remove_currency() -> Weight {
 		Weight::from_ref_time(542_550_000)
-			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().reads(3))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:1)
 	fn authorize_account() -> Weight {
@@ -121,9 +167,10 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:2 w:1)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	fn authorize_account_signed() -> Weight {
 		Weight::from_ref_time(1_525_600_000)
-			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().reads(3))
 			.saturating_add(RocksDbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
@@ -132,22 +179,74 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:2 w:1)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
 	fn deauthorize_account_signed() -> Weight {
 		Weight::from_ref_time(1_513_398_000)
-			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().reads(3))
 			.saturating_add(RocksDbWeight::get().writes(1))
 	}
 	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
-	// Storage: DiaOracle CoinInfosMap (r:0 w:1)
-	fn set_updated_coin_infos() -> Weight {
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle UpdatedCoinInfoLeaves (r:1 w:1)
+	// Storage: DiaOracle StaleCoinInfos (r:0 w:n)
+	// Storage: DiaOracle CoinInfosMap (r:n w:n)
+	// Storage: System Events (r:0 w:n)
+	fn set_updated_coin_infos(n: u32, ) -> Weight {
 		Weight::from_ref_time(1_241_248_000)
-			.saturating_add(RocksDbWeight::get().reads(1))
+			// Estimate, not benchmarked
+			.saturating_add(Weight::from_ref_time(40_000_000).saturating_mul(n as u64))
+			.saturating_add(RocksDbWeight::get().reads(3))
+			.saturating_add(RocksDbWeight::get().reads((1 as u64).saturating_mul(n as u64)))
 			.saturating_add(RocksDbWeight::get().writes(1))
+			.saturating_add(RocksDbWeight::get().writes((3 as u64).saturating_mul(n as u64)))
 	}
 
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle BatchingApi (r:0 w:1)
 	fn set_batching_api() -> Weight {
 		Weight::from_ref_time(1_241_248_000)
-			.saturating_add(RocksDbWeight::get().reads(1))
+			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle FallbackBatchingApis (r:0 w:1)
+	fn set_fallback_batching_apis() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:1)
+	fn set_account_expiry() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle ZeroPriceAllowed (r:0 w:1)
+	fn set_zero_price_allowed() -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(RocksDbWeight::get().reads(2))
+			.saturating_add(RocksDbWeight::get().writes(1))
+	}
+	// Estimate, not benchmarked
+	// Storage: DiaOracle AuthorizedAccounts (r:1 w:0)
+	// Storage: DiaOracle AccountExpiries (r:1 w:0)
+	// Storage: DiaOracle RemovalCursor (r:1 w:1)
+	// Storage: DiaOracle CoinInfosMap (r:n w:n)
+	// Storage: DiaOracle StaleCoinInfos (r:0 w:n)
+	fn remove_stale_coin_infos(n: u32, ) -> Weight {
+		Weight::from_ref_time(1_241_248_000)
+			.saturating_add(Weight::from_ref_time(25_000_000).saturating_mul(n as u64))
+			.saturating_add(RocksDbWeight::get().reads(3))
+			.saturating_add(RocksDbWeight::get().reads((1 as u64).saturating_mul(n as u64)))
 			.saturating_add(RocksDbWeight::get().writes(1))
+			.saturating_add(RocksDbWeight::get().writes((2 as u64).saturating_mul(n as u64)))
 	}
 }
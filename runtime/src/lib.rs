@@ -45,7 +45,8 @@ pub use frame_support::{
 	},
 	StorageValue,
 };
-use frame_support::traits::{ConstU64, ConstU32};
+use frame_support::traits::{ConstBool, ConstU64, ConstU32, EitherOfDiverse};
+use frame_system::EnsureRoot;
 pub use frame_system::Call as SystemCall;
 pub use pallet_balances::Call as BalancesCall;
 pub use pallet_timestamp::Call as TimestampCall;
@@ -287,11 +288,34 @@ impl pallet_sudo::Config for Runtime {
 	type RuntimeCall = RuntimeCall;
 }
 
+parameter_types! {
+	/// Price moves of more than half a percent are submitted right away
+	pub const PriceUpdateDeviationThreshold: Permill = Permill::from_parts(5_000);
+}
+
 impl dia_oracle::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type RuntimeCall = RuntimeCall;
 	type AuthorityId = dia_oracle::crypto::DiaAuthId;
 	type WeightInfo = ();
+	type ManageFeedersOrigin =
+		EitherOfDiverse<EnsureRoot<AccountId>, dia_oracle::EnsureAuthorizedAccount<Runtime>>;
+	type ManageCurrenciesOrigin = dia_oracle::EnsureAuthorizedAccount<Runtime>;
+	type SubmitPricesOrigin = dia_oracle::EnsureAuthorizedAccount<Runtime>;
+	type PublishPriceRoot = ConstBool<true>;
+	type MaxBlockchainLength = ConstU32<32>;
+	type MaxSymbolLength = ConstU32<32>;
+	type Decimals = ConstU8<12>;
+	type StringLimit = ConstU32<64>;
+	type MaxCoinsPerBatch = ConstU32<200>;
+	type FeederQuorum = ConstU32<1>;
+	type QuorumWindow = ConstU32<10>;
+	type UnixTime = Timestamp;
+	type MaxPriceAge = ConstU64<3600>;
+	type UpdateDeviationThreshold = PriceUpdateDeviationThreshold;
+	type UpdateHeartbeat = ConstU64<1800>;
+	type StalenessThreshold = ConstU64<3600>;
+	type OnPriceSet = ();
 }
 
 impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for Runtime
@@ -391,8 +415,12 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	Migrations,
 >;
 
+/// Storage migrations run on runtime upgrades
+type Migrations = (dia_oracle::migrations::v1::MigrateToBoundedCoinInfos<Runtime>,);
+
 impl_runtime_apis! {
 	impl sp_api::Core<Block> for Runtime {
 		fn version() -> RuntimeVersion {